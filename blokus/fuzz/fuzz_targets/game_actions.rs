@@ -0,0 +1,61 @@
+/*
+Fuzz target feeding Game whatever a client could send it
+Any sequence of placements, single tiles, and passes has to be either played or
+turned away with an error, and a turned away action must leave the game as it was.
+Run with `cargo fuzz run game_actions` from the blokus directory.
+*/
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+use blokus::game::Game;
+
+#[derive(Arbitrary, Debug)]
+enum Action {
+    Place {
+        piece: usize,
+        variant: usize,
+        offset: usize,
+    },
+    Apply {
+        tile: usize,
+        piece: Option<usize>,
+    },
+    Pass,
+}
+
+/// What has to survive a failed action unchanged
+fn snapshot(game: &Game) -> (Vec<u8>, Vec<(i32, i32)>, usize, Vec<usize>) {
+    let mut legal = game.get_legal_tiles();
+    legal.sort();
+    (
+        game.get_board().to_vec(),
+        game.history.clone(),
+        game.current_player(),
+        legal,
+    )
+}
+
+fuzz_target!(|actions: Vec<Action>| {
+    let mut game = Game::reset();
+    for action in actions {
+        let before = snapshot(&game);
+        let result = match action {
+            Action::Place {
+                piece,
+                variant,
+                offset,
+            } => game
+                .place_piece(piece, variant, offset)
+                .map(|next| game = next),
+            Action::Apply { tile, piece } => game.apply(tile, piece),
+            Action::Pass => game.pass().map(|next| game = next),
+        };
+        if result.is_err() {
+            assert_eq!(snapshot(&game), before);
+        }
+        assert!(game.get_score().iter().all(|score| *score <= 20));
+    }
+});
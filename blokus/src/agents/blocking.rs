@@ -0,0 +1,28 @@
+use super::Agent;
+use crate::game::{Game, Move};
+use crate::move_ordering::MoveOrdering;
+
+/// Plays the move the move ordering likes best
+/// Likes moves that open anchors for itself, cover opponents' anchors, and
+/// head toward the middle of the board where most of the fighting happens.
+#[derive(Clone, Debug, Default)]
+pub struct BlockingAgent {
+    pub ordering: MoveOrdering,
+}
+
+impl BlockingAgent {
+    /// Weighted sum of the move's features, higher is better
+    pub fn score(&self, game: &Game, m: Move) -> f32 {
+        self.ordering.score(game, m)
+    }
+}
+
+impl Agent for BlockingAgent {
+    fn choose_move(&mut self, game: &Game) -> Result<Move, String> {
+        self.ordering
+            .order(game)
+            .first()
+            .map(|(m, _)| *m)
+            .ok_or("No legal moves".to_string())
+    }
+}
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{sorted_moves, Agent};
+use crate::cancel::CancellationToken;
+use crate::evaluator::Evaluator;
+use crate::game::{Game, Move};
+use crate::search::SearchAnalysis;
+
+const NUM_PLAYERS: usize = 4;
+
+/// How much to hold back a searching agent for casual players
+/// The same evaluator, heuristic or model, can be played at any level.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Difficulty {
+    pub max_sims: usize,       // Cap on simulations per tile
+    pub policy_noise: f32,     // Fraction of the policy replaced by random noise
+    pub random_move_rate: f32, // Chance of playing a uniformly random move instead of searching
+    pub value_softening: f32,  // Fraction of each value pulled toward an even game
+}
+
+impl Difficulty {
+    pub const LEVELS: [&'static str; 3] = ["easy", "medium", "hard"];
+
+    pub fn from_name(name: &str) -> Result<Difficulty, String> {
+        match name {
+            "easy" => Ok(Difficulty {
+                max_sims: 10,
+                policy_noise: 0.5,
+                random_move_rate: 0.2,
+                value_softening: 0.5,
+            }),
+            "medium" => Ok(Difficulty {
+                max_sims: 50,
+                policy_noise: 0.2,
+                random_move_rate: 0.05,
+                value_softening: 0.2,
+            }),
+            "hard" => Ok(Difficulty {
+                max_sims: 200,
+                policy_noise: 0.0,
+                random_move_rate: 0.0,
+                value_softening: 0.0,
+            }),
+            _ => Err(format!("Unknown difficulty: {}", name)),
+        }
+    }
+}
+
+/// Wraps an evaluator with a difficulty's policy noise and value softening
+pub struct HandicappedEvaluator<E: Evaluator> {
+    evaluator: E,
+    policy_noise: f32,
+    value_softening: f32,
+    rng: StdRng,
+}
+
+impl<E: Evaluator> HandicappedEvaluator<E> {
+    pub fn new(evaluator: E, difficulty: &Difficulty, seed: Option<u64>) -> Self {
+        HandicappedEvaluator {
+            evaluator,
+            policy_noise: difficulty.policy_noise,
+            value_softening: difficulty.value_softening,
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+        }
+    }
+}
+
+impl<E: Evaluator> Evaluator for HandicappedEvaluator<E> {
+    fn evaluate(&mut self, game: &Game) -> Result<(Vec<f32>, Vec<f32>), String> {
+        let (mut policy, mut values) = self.evaluator.evaluate(game)?;
+
+        // Mix in normalized random weights over the tiles the policy allows
+        if self.policy_noise > 0.0 {
+            let noise: Vec<f32> = policy
+                .iter()
+                .map(|p| if *p > 0.0 { self.rng.gen::<f32>() } else { 0.0 })
+                .collect();
+            let total: f32 = noise.iter().sum();
+            if total > 0.0 {
+                for (p, n) in policy.iter_mut().zip(noise) {
+                    *p = (1.0 - self.policy_noise) * *p + self.policy_noise * n / total;
+                }
+            }
+        }
+
+        for value in values.iter_mut() {
+            *value =
+                (1.0 - self.value_softening) * *value + self.value_softening / NUM_PLAYERS as f32;
+        }
+        Ok((policy, values))
+    }
+}
+
+/// Sometimes plays a random move instead of asking the wrapped agent
+pub struct HandicappedAgent {
+    agent: Box<dyn Agent>,
+    random_move_rate: f32,
+    rng: StdRng,
+    last_was_random: bool,
+}
+
+impl HandicappedAgent {
+    pub fn new(agent: Box<dyn Agent>, difficulty: &Difficulty, seed: Option<u64>) -> Self {
+        HandicappedAgent {
+            agent,
+            random_move_rate: difficulty.random_move_rate,
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            last_was_random: false,
+        }
+    }
+}
+
+impl Agent for HandicappedAgent {
+    fn choose_move(&mut self, game: &Game) -> Result<Move, String> {
+        self.last_was_random = self.rng.gen::<f32>() < self.random_move_rate;
+        if !self.last_was_random {
+            return self.agent.choose_move(game);
+        }
+        let moves = sorted_moves(game);
+        if moves.is_empty() {
+            return Err("No legal moves".to_string());
+        }
+        Ok(moves[self.rng.gen_range(0..moves.len())])
+    }
+
+    fn set_time_budget(&mut self, budget: Option<Duration>) {
+        self.agent.set_time_budget(budget);
+    }
+
+    fn set_cancellation(&mut self, cancel: CancellationToken) {
+        self.agent.set_cancellation(cancel);
+    }
+
+    /// Random moves have no analysis behind them
+    fn analysis(&self) -> Option<SearchAnalysis> {
+        if self.last_was_random {
+            None
+        } else {
+            self.agent.analysis()
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::from_spec;
+    use crate::evaluator::HeuristicEvaluator;
+
+    #[test]
+    fn test_handicapped_evaluator() {
+        let game = Game::reset();
+        let easy = Difficulty::from_name("easy").unwrap();
+        let mut evaluator =
+            HandicappedEvaluator::new(HeuristicEvaluator::default(), &easy, Some(0));
+        let (policy, values) = evaluator.evaluate(&game).unwrap();
+        assert!((policy.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        assert!(policy.iter().all(|p| *p >= 0.0));
+        assert!(values.iter().all(|v| *v >= easy.value_softening / 4.0));
+    }
+
+    #[test]
+    fn test_difficulty_specs() {
+        let game = Game::reset();
+        for level in Difficulty::LEVELS {
+            let mut agent = from_spec(&format!("mcts:5@{}", level), Some(1)).unwrap();
+            let chosen = agent.choose_move(&game).unwrap();
+            assert!(game.get_legal_moves().contains(&chosen));
+        }
+        assert!(from_spec("greedy@easy", None).is_err());
+        assert!(from_spec("mcts@impossible", None).is_err());
+    }
+}
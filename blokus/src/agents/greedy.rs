@@ -0,0 +1,36 @@
+use super::{sorted_moves, Agent};
+use crate::game::{Game, Move};
+
+/// Plays the biggest piece it can, preferring placements that open the most new anchors
+/// The usual sanity opponent, a trained model should beat it comfortably
+#[derive(Clone, Debug, Default)]
+pub struct GreedyAgent;
+
+/// Count the anchors a placement creates for the player making it
+/// Places the tiles on a copy of the board, without validating the move
+pub fn new_anchors(game: &Game, (piece, variant, offset): Move) -> usize {
+    let player = game.current_player();
+    let mut board = game.board.clone();
+    for tile in &game.get_piece(player, piece, variant).offsets {
+        board.place_tile(offset + tile, player);
+    }
+    board
+        .get_anchors(player)
+        .difference(&game.board.get_anchors(player))
+        .count()
+}
+
+impl Agent for GreedyAgent {
+    fn choose_move(&mut self, game: &Game) -> Result<Move, String> {
+        let player = game.current_player();
+        let mut best = None;
+        for m in sorted_moves(game) {
+            let points = game.get_piece(player, m.0, m.1).offsets.len();
+            let score = (points, new_anchors(game, m));
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((m, score));
+            }
+        }
+        best.map(|(m, _)| m).ok_or("No legal moves".to_string())
+    }
+}
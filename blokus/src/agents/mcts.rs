@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use super::Agent;
+use crate::cancel::CancellationToken;
+use crate::evaluator::Evaluator;
+use crate::game::{Game, Move};
+use crate::search::{self, SearchAnalysis, SearchConfig};
+
+/// Plays the most visited tile from MCTS until its piece is finished
+/// Without a time budget each tile gets `sims_per_move` simulations, with one
+/// each tile search gets half of the time left for the move. Once cancelled the
+/// remaining tiles of the piece are picked from what has been searched so far.
+pub struct MctsAgent<E: Evaluator> {
+    evaluator: E,
+    config: SearchConfig,
+    time_budget: Option<Duration>,
+    cancel: CancellationToken,
+    last_analysis: Option<SearchAnalysis>,
+}
+
+impl<E: Evaluator> MctsAgent<E> {
+    pub fn new(evaluator: E, config: SearchConfig) -> MctsAgent<E> {
+        MctsAgent {
+            evaluator,
+            config,
+            time_budget: None,
+            cancel: CancellationToken::new(),
+            last_analysis: None,
+        }
+    }
+}
+
+impl<E: Evaluator> Agent for MctsAgent<E> {
+    fn choose_move(&mut self, game: &Game) -> Result<Move, String> {
+        let deadline = self.time_budget.map(|budget| Instant::now() + budget);
+        let mut scratch = game.clone();
+        let mut tiles = Vec::new();
+        self.last_analysis = None;
+        loop {
+            let root = match deadline {
+                Some(d) => {
+                    let remaining = d.saturating_duration_since(Instant::now());
+                    search::search_until(
+                        &scratch,
+                        &self.config,
+                        &mut self.evaluator,
+                        Some(Instant::now() + remaining / 2),
+                        &self.cancel,
+                    )?
+                }
+                None => search::search_cancellable(
+                    &scratch,
+                    &self.config,
+                    &mut self.evaluator,
+                    &self.cancel,
+                )?,
+            };
+
+            // The first tile decides where the piece goes, so report that search
+            if self.last_analysis.is_none() {
+                self.last_analysis = Some(SearchAnalysis::from_root(&root));
+            }
+
+            let tile = search::best_action(&root).ok_or("No legal moves")?;
+            scratch.apply(tile, None)?;
+            tiles.push(tile);
+            if scratch.is_turn_start() {
+                break;
+            }
+        }
+
+        game.find_move(&tiles).ok_or(format!(
+            "Search placed tiles {:?} that are not a move",
+            tiles
+        ))
+    }
+
+    fn set_time_budget(&mut self, budget: Option<Duration>) {
+        self.time_budget = budget;
+    }
+
+    fn set_cancellation(&mut self, cancel: CancellationToken) {
+        self.cancel = cancel;
+    }
+
+    fn analysis(&self) -> Option<SearchAnalysis> {
+        self.last_analysis.clone()
+    }
+}
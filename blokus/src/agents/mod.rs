@@ -0,0 +1,252 @@
+/*
+Players that choose whole piece placements, used for baselines and arena games
+*/
+
+use std::time::Duration;
+
+use crate::cancel::CancellationToken;
+use crate::evaluator::{Evaluator, HeuristicEvaluator, RolloutEvaluator};
+use crate::game::{Game, Move};
+use crate::search::{SearchAnalysis, SearchConfig};
+
+mod blocking;
+mod difficulty;
+mod greedy;
+mod mcts;
+mod random;
+
+pub use blocking::BlockingAgent;
+pub use difficulty::{Difficulty, HandicappedAgent, HandicappedEvaluator};
+pub use greedy::{new_anchors, GreedyAgent};
+pub use mcts::MctsAgent;
+pub use random::RandomAgent;
+
+/// Anything that can pick a move for the player to move
+/// Front-ends drive every kind of player through this, moves are asked for at the start of a turn
+pub trait Agent {
+    fn choose_move(&mut self, game: &Game) -> Result<Move, String>;
+
+    /// Limit the thinking time for following moves, agents that do not search ignore it
+    fn set_time_budget(&mut self, _budget: Option<Duration>) {}
+
+    /// Stop searching and play the best move found so far once the token is cancelled
+    /// Agents that do not search ignore it
+    fn set_cancellation(&mut self, _cancel: CancellationToken) {}
+
+    /// Search statistics behind the last chosen move, if the agent keeps any
+    fn analysis(&self) -> Option<SearchAnalysis> {
+        None
+    }
+}
+
+/// Playouts per position for the `rollout` agents
+const ROLLOUT_PLAYOUTS: usize = 4;
+
+/// MCTS with exploration noise turned off, held back by the difficulty if one is given
+/// Searches stop early once the best tile is decided, since only the move is needed
+fn searching_agent<E: Evaluator + 'static>(
+    evaluator: E,
+    sims: usize,
+    difficulty: Option<&Difficulty>,
+    seed: Option<u64>,
+) -> Box<dyn Agent> {
+    let config = |sims: usize| SearchConfig {
+        sims_per_move: sims,
+        exploration_fraction: 0.0,
+        early_stop: true,
+        ..SearchConfig::default()
+    };
+    match difficulty {
+        Some(difficulty) => {
+            let evaluator = HandicappedEvaluator::new(evaluator, difficulty, seed);
+            let agent = MctsAgent::new(evaluator, config(sims.min(difficulty.max_sims)));
+            Box::new(HandicappedAgent::new(Box::new(agent), difficulty, seed))
+        }
+        None => Box::new(MctsAgent::new(evaluator, config(sims))),
+    }
+}
+
+/// Build an agent from a short description
+/// `mcts[:sims]` searches with the heuristic evaluator (200 simulations per tile by default),
+/// `rollout[:sims]` searches with heuristic playouts instead (50 simulations by default),
+/// `model:<path>` searches with a checkpoint from the Rust trainer (needs the `train` feature),
+/// `onnx:<path>` searches with an exported network (needs the `onnx` feature),
+/// and `blocking`, `greedy`, and `random` are the baselines. Searching agents can be held back
+/// with a difficulty suffix like `mcts@easy` or `model:<path>@medium`, see `Difficulty`.
+/// The seed only affects agents that make random choices.
+pub fn from_spec(spec: &str, seed: Option<u64>) -> Result<Box<dyn Agent>, String> {
+    let (base, difficulty) = match spec.split_once('@') {
+        Some((base, level)) => (base, Some(Difficulty::from_name(level)?)),
+        None => (spec, None),
+    };
+    let (name, argument) = match base.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (base, None),
+    };
+
+    let agent: Box<dyn Agent> = match (name, argument) {
+        ("mcts", _) => {
+            let sims = match argument {
+                Some(sims) => sims
+                    .parse()
+                    .map_err(|_| format!("Invalid simulation count in {}", spec))?,
+                None => 200,
+            };
+            searching_agent(
+                HeuristicEvaluator::default(),
+                sims,
+                difficulty.as_ref(),
+                seed,
+            )
+        }
+        ("rollout", _) => {
+            let sims = match argument {
+                Some(sims) => sims
+                    .parse()
+                    .map_err(|_| format!("Invalid simulation count in {}", spec))?,
+                None => 50,
+            };
+            searching_agent(
+                RolloutEvaluator::new(ROLLOUT_PLAYOUTS, seed),
+                sims,
+                difficulty.as_ref(),
+                seed,
+            )
+        }
+        #[cfg(feature = "train")]
+        ("model", Some(path)) => searching_agent(
+            crate::train::ModelEvaluator::load(path)?,
+            200,
+            difficulty.as_ref(),
+            seed,
+        ),
+        #[cfg(feature = "onnx")]
+        ("onnx", Some(path)) => searching_agent(
+            crate::onnx::OnnxEvaluator::load(path)?,
+            200,
+            difficulty.as_ref(),
+            seed,
+        ),
+        _ if difficulty.is_some() => {
+            return Err(format!("Only searching agents have difficulties: {}", spec))
+        }
+        ("blocking", None) => Box::new(BlockingAgent::default()),
+        ("greedy", None) => Box::new(GreedyAgent),
+        ("random", None) => match seed {
+            Some(seed) => Box::new(RandomAgent::seeded(seed)),
+            None => Box::new(RandomAgent::new()),
+        },
+        _ => return Err(format!("Unknown agent: {}", spec)),
+    };
+    Ok(agent)
+}
+
+/// Get the legal moves in a fixed order so seeded agents are reproducible
+pub fn sorted_moves(game: &Game) -> Vec<Move> {
+    let mut moves: Vec<Move> = game.get_legal_moves().into_iter().collect();
+    moves.sort();
+    moves
+}
+
+/// Whether to take the opening side under the swap rule, when the evaluator rates it ahead
+pub fn should_swap<E: Evaluator>(game: &Game, evaluator: &mut E) -> Result<bool, String> {
+    if !game.can_swap() {
+        return Ok(false);
+    }
+    let (_, values) = evaluator.evaluate(game)?;
+    let sides = game.variant().team_values(values);
+    Ok(sides[0] > sides[1])
+}
+
+/// Play a game to the end with one agent per seat
+pub fn play_game(agents: &mut [&mut dyn Agent]) -> Result<Game, String> {
+    let mut game = Game::reset();
+    while !game.is_terminal() {
+        let player = game.current_player();
+        let (piece, variant, offset) = agents[player].choose_move(&game)?;
+        game = game.place_piece(piece, variant, offset)?;
+    }
+    Ok(game)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::HeuristicEvaluator;
+    use crate::game::Variant;
+    use crate::move_ordering::MoveOrdering;
+    use crate::search::SearchConfig;
+
+    #[test]
+    fn test_random_game() {
+        let mut agents: Vec<RandomAgent> = (0..4).map(RandomAgent::seeded).collect();
+        let mut seats: Vec<&mut dyn Agent> =
+            agents.iter_mut().map(|a| a as &mut dyn Agent).collect();
+        let game = play_game(&mut seats).unwrap();
+        assert!(game.is_terminal());
+
+        // Same seeds, same game
+        let mut agents: Vec<RandomAgent> = (0..4).map(RandomAgent::seeded).collect();
+        let mut seats: Vec<&mut dyn Agent> =
+            agents.iter_mut().map(|a| a as &mut dyn Agent).collect();
+        assert_eq!(play_game(&mut seats).unwrap().history, game.history);
+    }
+
+    #[test]
+    fn test_should_swap() {
+        let game = Game::new(Variant::TwoPlayer).with_swap_rule().unwrap();
+        let mut evaluator = HeuristicEvaluator::default();
+        assert_eq!(should_swap(&game, &mut evaluator), Ok(false));
+
+        // Five in a row is a strong opening to take over
+        let game = game.place_piece(9, 0, 0).unwrap();
+        assert_eq!(should_swap(&game, &mut evaluator), Ok(true));
+    }
+
+    #[test]
+    fn test_greedy_plays_biggest_piece() {
+        let game = Game::reset();
+        let (piece, variant, _) = GreedyAgent.choose_move(&game).unwrap();
+        assert_eq!(game.get_piece(0, piece, variant).offsets.len(), 5);
+    }
+
+    #[test]
+    fn test_blocking_covers_opponent_anchor() {
+        // Give player 1 a tile diagonal to player 0's corner, so the corner is an anchor for both
+        let mut game = Game::reset();
+        game.board.place_tile(21, 1);
+        assert!(game.board.get_anchors(1).contains(&0));
+
+        let pieces = game.get_current_player_pieces();
+        let monomino = pieces.iter().position(|p| p.points == 1).unwrap();
+        let agent = BlockingAgent::default();
+        let without_blocking = BlockingAgent {
+            ordering: MoveOrdering {
+                block_weight: 0.0,
+                ..agent.ordering.clone()
+            },
+        };
+        let gain =
+            agent.score(&game, (monomino, 0, 0)) - without_blocking.score(&game, (monomino, 0, 0));
+        assert_eq!(gain, agent.ordering.block_weight);
+    }
+
+    #[test]
+    fn test_mcts_agent() {
+        let config = SearchConfig {
+            sims_per_move: 10,
+            ..SearchConfig::default()
+        };
+        let mut agent = MctsAgent::new(HeuristicEvaluator::default(), config);
+        let game = Game::reset();
+        let chosen = agent.choose_move(&game).unwrap();
+        assert!(game.get_legal_moves().contains(&chosen));
+        assert_eq!(agent.analysis().unwrap().visits, 10);
+
+        agent.set_time_budget(Some(Duration::from_millis(20)));
+        let chosen = agent.choose_move(&game).unwrap();
+        assert!(game.get_legal_moves().contains(&chosen));
+        assert!(RandomAgent::seeded(0).analysis().is_none());
+    }
+}
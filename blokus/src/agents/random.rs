@@ -0,0 +1,41 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use super::{sorted_moves, Agent};
+use crate::game::{Game, Move};
+
+/// Picks uniformly among the legal moves
+pub struct RandomAgent {
+    rng: StdRng,
+}
+
+impl RandomAgent {
+    pub fn new() -> RandomAgent {
+        RandomAgent {
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Create an agent that always makes the same choices
+    pub fn seeded(seed: u64) -> RandomAgent {
+        RandomAgent {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for RandomAgent {
+    fn default() -> Self {
+        RandomAgent::new()
+    }
+}
+
+impl Agent for RandomAgent {
+    fn choose_move(&mut self, game: &Game) -> Result<Move, String> {
+        let moves = sorted_moves(game);
+        if moves.is_empty() {
+            return Err("No legal moves".to_string());
+        }
+        Ok(moves[self.rng.gen_range(0..moves.len())])
+    }
+}
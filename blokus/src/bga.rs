@@ -0,0 +1,249 @@
+/*
+Importer for Blokus games exported from Board Game Arena, a source of strong human games
+to warm start training on
+A log is the JSON BGA gives for a table's replay, {"data": {"logs": [packet, ...]}}, where each
+packet holds a "data" list of notifications. Two kinds of notification are read:
+- "playPiece", args "player_id" and "squares", the cells of the piece as {"x": .., "y": ..}
+- "pass", args "player_id", a player giving up the rest of the game
+Chat, scores and everything else are skipped. Numbers may be given as strings, as BGA does.
+Each color is keyed by the notification's "color" arg when it has one, since one player holds
+two colors in a two player game, and by "player_id" otherwise, and numbered in turn order.
+BGA's cells are not in the engine's coordinates, so the board is turned and mirrored until
+the first color opens in red's corner and the second in blue's. Cells may count from 0 or 1.
+*/
+
+use serde_json::Value;
+
+use crate::board::BOARD_SIZE as D;
+use crate::game::Game;
+use crate::records::{GameRecord, RecordedMove};
+
+/// Files ending in this are read as BGA logs, see records::load
+pub const EXTENSION: &str = ".bga.json";
+
+const NUM_PLAYERS: usize = 4;
+const PLAY: &str = "playPiece";
+const PASS: &str = "pass";
+
+/// A move as logged, no cells for a pass
+struct LoggedMove {
+    color: String,
+    cells: Vec<(i64, i64)>, // (x, y)
+}
+
+/// Parse one BGA log, or a JSON array of them, into game records
+pub fn parse(text: &str) -> Result<Vec<GameRecord>, String> {
+    let value: Value = serde_json::from_str(text).map_err(|e| format!("Invalid BGA log: {}", e))?;
+    let logs = match value {
+        Value::Array(logs) => logs,
+        log => vec![log],
+    };
+    logs.iter()
+        .enumerate()
+        .map(|(i, log)| to_record(log).map_err(|e| format!("BGA log {}: {}", i + 1, e)))
+        .collect()
+}
+
+/// A number BGA may have written as a string
+fn number(value: &Value) -> Option<i64> {
+    value
+        .as_i64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+/// The piece placements and passes of a log in order
+fn logged_moves(log: &Value) -> Result<Vec<LoggedMove>, String> {
+    let packets = log
+        .pointer("/data/logs")
+        .and_then(Value::as_array)
+        .ok_or("No logs in the export")?;
+    let notifications = packets
+        .iter()
+        .filter_map(|packet| packet.get("data").and_then(Value::as_array))
+        .flatten();
+
+    let mut moves = Vec::new();
+    for notification in notifications {
+        let kind = notification.get("type").and_then(Value::as_str);
+        if kind != Some(PLAY) && kind != Some(PASS) {
+            continue;
+        }
+        let args = notification.get("args").ok_or("A move without args")?;
+        let color = match args.get("color").or(args.get("player_id")) {
+            Some(Value::String(id)) => id.clone(),
+            Some(Value::Number(id)) => id.to_string(),
+            _ => return Err("A move without a player".to_string()),
+        };
+        let mut cells = Vec::new();
+        if kind == Some(PLAY) {
+            let squares = args
+                .get("squares")
+                .and_then(Value::as_array)
+                .ok_or("A piece without squares")?;
+            for square in squares {
+                let x = square.get("x").and_then(number);
+                let y = square.get("y").and_then(number);
+                let (Some(x), Some(y)) = (x, y) else {
+                    return Err(format!("Invalid square: {}", square));
+                };
+                cells.push((x, y));
+            }
+        }
+        moves.push(LoggedMove { color, cells });
+    }
+    Ok(moves)
+}
+
+/// Turn a (row, col) quarter turns to the left, then mirror it left to right if asked
+fn transform((row, col): (usize, usize), turns: usize, mirror: bool) -> (usize, usize) {
+    let (mut row, mut col) = (row, col);
+    for _ in 0..turns {
+        (row, col) = (D - 1 - col, row);
+    }
+    if mirror {
+        col = D - 1 - col;
+    }
+    (row, col)
+}
+
+/// The board corner among a piece's cells
+fn corner(cells: &[(usize, usize)]) -> Option<(usize, usize)> {
+    cells
+        .iter()
+        .copied()
+        .find(|(row, col)| (*row == 0 || *row == D - 1) && (*col == 0 || *col == D - 1))
+}
+
+fn to_record(log: &Value) -> Result<GameRecord, String> {
+    let moves = logged_moves(log)?;
+    let mut colors: Vec<&str> = Vec::new();
+    for m in &moves {
+        if !colors.contains(&m.color.as_str()) {
+            colors.push(&m.color);
+        }
+    }
+    if colors.len() != NUM_PLAYERS {
+        return Err(format!(
+            "{} colors moved, not {}",
+            colors.len(),
+            NUM_PLAYERS
+        ));
+    }
+
+    // Cells count from whichever of 0 or 1 the openings in the corners touch
+    let coords = || {
+        moves
+            .iter()
+            .flat_map(|m| &m.cells)
+            .flat_map(|(x, y)| [*x, *y])
+    };
+    let base = coords().min().unwrap_or(0);
+    if !(0..=1).contains(&base) || coords().any(|c| c - base >= D as i64) {
+        return Err("Squares are off the board".to_string());
+    }
+    let cells: Vec<Vec<(usize, usize)>> = moves
+        .iter()
+        .map(|m| {
+            m.cells
+                .iter()
+                .map(|(x, y)| ((y - base) as usize, (x - base) as usize))
+                .collect()
+        })
+        .collect();
+
+    // Find the turn and mirror that put the first two openings in red's and blue's corners
+    let opening = |color: &str| {
+        moves
+            .iter()
+            .zip(&cells)
+            .find(|(m, cells)| m.color == color && !cells.is_empty())
+            .and_then(|(_, cells)| corner(cells))
+            .ok_or(format!("{} did not open in a corner", color))
+    };
+    let (first, second) = (opening(colors[0])?, opening(colors[1])?);
+    let (turns, mirror) = (0..8)
+        .map(|t| (t % 4, t >= 4))
+        .find(|(turns, mirror)| {
+            transform(first, *turns, *mirror) == (0, 0)
+                && transform(second, *turns, *mirror) == (0, D - 1)
+        })
+        .ok_or("The first two colors did not open in neighbouring corners")?;
+
+    // Replay as the moves are read, BGA may log passes for players the engine already skips
+    let mut game = Game::reset();
+    let mut record = GameRecord::default();
+    for (i, (m, cells)) in moves.iter().zip(&cells).enumerate() {
+        let player = colors.iter().position(|c| *c == m.color).unwrap();
+        if cells.is_empty() && !game.is_player_active(player) {
+            continue;
+        }
+        if player != game.current_player() {
+            return Err(format!("Move {}: {} moved out of turn", i + 1, m.color));
+        }
+        if cells.is_empty() {
+            game = game.pass().map_err(|e| format!("Move {}: {}", i + 1, e))?;
+            record.moves.push(RecordedMove {
+                player,
+                tiles: Vec::new(),
+            });
+            continue;
+        }
+
+        let mut tiles: Vec<usize> = cells
+            .iter()
+            .map(|cell| transform(*cell, turns, mirror))
+            .map(|(row, col)| row * D + col)
+            .collect();
+        tiles.sort();
+        let (piece, variant, offset) = game.find_move(&tiles).ok_or(format!(
+            "Move {}: {} cannot cover {:?}",
+            i + 1,
+            m.color,
+            tiles
+        ))?;
+        game = game.place_piece(piece, variant, offset)?;
+        record.moves.push(RecordedMove { player, tiles });
+    }
+    Ok(record)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::{from_history, replay};
+    use serde_json::json;
+
+    #[test]
+    fn test_bga_import() {
+        // Play a few rounds, then log them as BGA would with the board mirrored and cells from 1
+        let mut game = Game::reset();
+        for _ in 0..12 {
+            let m = *game.get_legal_moves().iter().min().unwrap();
+            game = game.place_piece(m.0, m.1, m.2).unwrap();
+        }
+        let record = from_history(&game.history).unwrap();
+        let mut notifications = vec![json!({"type": "message", "args": {"text": "gl hf"}})];
+        for m in &record.moves {
+            let squares: Vec<Value> = m
+                .tiles
+                .iter()
+                .map(|t| json!({"x": (D - t % D).to_string(), "y": t / D + 1}))
+                .collect();
+            let args = json!({"player_id": format!("84{}", m.player), "squares": squares});
+            notifications.push(json!({"type": PLAY, "args": args}));
+        }
+        let log = json!({"data": {"logs": [{"data": notifications}]}});
+
+        let imported = parse(&log.to_string()).unwrap();
+        assert_eq!(imported, vec![record.clone()]);
+        assert_eq!(replay(&imported[0]).unwrap().game.history, game.history);
+
+        // Several logs at once, and errors name the log
+        let logs = json!([log, {"data": {"logs": []}}]);
+        assert!(parse(&logs.to_string())
+            .unwrap_err()
+            .starts_with("BGA log 2"));
+        assert!(parse("{").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+// Mine puzzles out of blunders in self-play or recorded games, and check puzzle files
+// Usage: puzzles mine [--margin m] [--variant classic|two-player|team] (--self-play n [--agent spec] [--seed s] | <games.json|games.csv>)
+//        puzzles check <puzzles.txt>
+// Mined puzzles are printed one per line in the format of blokus::puzzles, ready to be
+// picked through and opened in the GUI's puzzle mode
+use std::env;
+use std::process;
+
+use blokus::agents::from_spec;
+use blokus::evaluator::HeuristicEvaluator;
+use blokus::game::{Game, Variant};
+use blokus::puzzles::{load, mine};
+use blokus::records::{from_history_at, load_played, GameRecord};
+
+const USAGE: &str = "Usage: puzzles mine [--margin m] [--variant classic|two-player|team] (--self-play n [--agent spec] [--seed s] | <games.json|games.csv>)
+       puzzles check <puzzles.txt>";
+const MARGIN: f32 = 0.1; // Default drop in win rate that counts as a blunder
+
+/// Play games with the same agent in every seat, the blunders it makes are the puzzles
+fn self_play(
+    variant: Variant,
+    games: usize,
+    spec: &str,
+    seed: u64,
+) -> Result<Vec<GameRecord>, String> {
+    let mut records = Vec::new();
+    for i in 0..games {
+        let mut agent = from_spec(spec, Some(seed + i as u64))?;
+        let start = Game::new(variant);
+        let mut game = start.clone();
+        while !game.is_terminal() {
+            let (piece, variant, offset) = agent.choose_move(&game)?;
+            game = game.place_piece(piece, variant, offset)?;
+        }
+        records.push(from_history_at(&start, &game.history)?);
+    }
+    Ok(records)
+}
+
+fn run_mine(args: &[String]) -> Result<(), String> {
+    let mut margin = MARGIN;
+    let mut variant = Variant::Classic;
+    let mut games = None;
+    let mut spec = "mcts:50".to_string();
+    let mut seed = 0;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--margin" => margin = value()?.parse().map_err(|_| "Invalid margin")?,
+            "--variant" => variant = Variant::from_name(value()?)?,
+            "--self-play" => games = Some(value()?.parse().map_err(|_| "Invalid game count")?),
+            "--agent" => spec = value()?.clone(),
+            "--seed" => seed = value()?.parse().map_err(|_| "Invalid seed")?,
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let records = match (games, path) {
+        (Some(games), None) => self_play(variant, games, &spec, seed)?,
+        (None, Some(path)) => load_played(&path)?.into_iter().map(|g| g.record).collect(),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let mut evaluator = HeuristicEvaluator::default();
+    let mut found = 0;
+    for (i, record) in records.iter().enumerate() {
+        let puzzles = mine(Game::new(variant), record, &mut evaluator, margin)
+            .map_err(|e| format!("Game {}: {}", i + 1, e))?;
+        eprintln!("game {}: {} puzzles", i + 1, puzzles.len());
+        for mut puzzle in puzzles {
+            puzzle.title = format!("Game {}, {}", i + 1, puzzle.title.to_lowercase());
+            println!("{}", puzzle.to_line());
+            found += 1;
+        }
+    }
+    eprintln!("{} puzzles from {} games", found, records.len());
+    Ok(())
+}
+
+/// Print how many moves solve each puzzle, the file failing to load if any has none
+fn run_check(args: &[String]) -> Result<(), String> {
+    let [path] = args else {
+        return Err(USAGE.to_string());
+    };
+    let puzzles = load(path)?;
+    for (i, puzzle) in puzzles.iter().enumerate() {
+        let solutions = puzzle.solutions()?.len();
+        let unique = if solutions == 1 { "" } else { ", not unique" };
+        println!("puzzle {}: {} solutions{}", i + 1, solutions, unique);
+    }
+    println!("{} puzzles", puzzles.len());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("mine") => run_mine(&args[1..]),
+        Some("check") => run_check(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
@@ -0,0 +1,83 @@
+// Round-robin tournament between agents
+// Usage: tournament [--games n] [--seed n] [--csv path] [--json path] [--db path] <agent> <agent> [agent...]
+// Agents are specs like mcts:400, blocking, greedy, random, or model:<path> with the train feature
+// --db stores every game and updates agent ratings in a SQLite database (needs the storage feature)
+use std::env;
+use std::fs;
+use std::process;
+
+#[cfg(feature = "storage")]
+use blokus::storage::Database;
+use blokus::tournament::{round_robin, TournamentResult};
+
+const USAGE: &str = "Usage: tournament [--games n] [--seed n] [--csv path] [--json path] [--db path] <agent> <agent> [agent...]";
+
+/// Store every game of the tournament and print the updated ratings
+#[cfg(feature = "storage")]
+fn record(result: &TournamentResult, path: &str) -> Result<(), String> {
+    let mut db = Database::open(path)?;
+    for pairing in &result.pairings {
+        for played in &pairing.played {
+            db.record_game("tournament", played)?;
+        }
+    }
+    println!("agent,rating,games");
+    for standing in &result.standings {
+        if let Some(player) = db.player(&standing.agent)? {
+            println!("{},{:.0},{}", player.name, player.rating, player.games);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "storage"))]
+fn record(_result: &TournamentResult, _path: &str) -> Result<(), String> {
+    Err("--db needs the storage feature".to_string())
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let mut games = 2;
+    let mut seed = 0;
+    let mut csv = None;
+    let mut json = None;
+    let mut db = None;
+    let mut specs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--games" => games = value()?.parse().map_err(|_| "Invalid --games")?,
+            "--seed" => seed = value()?.parse().map_err(|_| "Invalid --seed")?,
+            "--csv" => csv = Some(value()?.clone()),
+            "--json" => json = Some(value()?.clone()),
+            "--db" => db = Some(value()?.clone()),
+            _ => specs.push(arg.clone()),
+        }
+    }
+    if specs.len() < 2 {
+        return Err(USAGE.to_string());
+    }
+
+    let result = round_robin(&specs, games, seed)?;
+    print!("{}", result.standings_csv());
+    if let Some(path) = csv {
+        let output = format!("{}\n{}", result.pairings_csv(), result.standings_csv());
+        fs::write(&path, output).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+    if let Some(path) = json {
+        fs::write(&path, result.to_json())
+            .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+    if let Some(path) = db {
+        record(&result, &path)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,115 @@
+// Run self-play and training end to end without Python
+// Usage: cargo run --release --features train --bin train -- <checkpoint.safetensors> [rounds] [--team] [--pieces] [--history n] [--absolute] [--payoff spec] [--baseline-games n] [--fpu-reduction r] [--prior-temperature t]
+// With --team the games are played in teams and the network sees team-relative planes
+// With --pieces the network also sees which pieces each color still holds
+// With --history n it also sees where each of the last n pieces went
+// With --absolute the board is not turned to the player to move, planes mark who it is instead
+// The payoff scores finished games as value targets, like rank or margin, see blokus::payoff
+// With --baseline-games n each checkpoint plays n games against every scripted baseline, 0 to skip
+// --fpu-reduction r and --prior-temperature t tune the search, see blokus::search::SearchConfig
+use std::env;
+use std::process;
+
+use blokus::game::{StateOptions, Variant};
+use blokus::logging::{self, Format};
+use blokus::payoff::Payoff;
+use blokus::train::{TrainConfig, Trainer};
+
+const USAGE: &str = "Usage: train <checkpoint.safetensors> [rounds] [--team] [--pieces] \
+                     [--history n] [--absolute] [--payoff spec] [--baseline-games n] \
+                     [--fpu-reduction r] [--prior-temperature t]";
+
+fn main() {
+    logging::init(Format::Text);
+    let mut team = false;
+    let mut pieces = false;
+    let mut history = 0;
+    let mut absolute = false;
+    let mut payoff = Payoff::default();
+    let mut baseline_games = None;
+    let mut fpu_reduction = None;
+    let mut prior_temperature = None;
+    let mut args = Vec::new();
+    let mut all = env::args();
+    while let Some(arg) = all.next() {
+        match arg.as_str() {
+            "--team" => team = true,
+            "--pieces" => pieces = true,
+            "--absolute" => absolute = true,
+            "--history" => {
+                let n = all.next().unwrap_or_default();
+                history = n.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid number of history planes: {}", n);
+                    process::exit(1);
+                });
+            }
+            "--payoff" => {
+                let spec = all.next().unwrap_or_default();
+                payoff = Payoff::from_spec(&spec).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                });
+            }
+            "--baseline-games" => {
+                let n = all.next().unwrap_or_default();
+                baseline_games = Some(n.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid number of baseline games: {}", n);
+                    process::exit(1);
+                }));
+            }
+            "--fpu-reduction" => {
+                let r = all.next().unwrap_or_default();
+                fpu_reduction = Some(r.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid first play urgency reduction: {}", r);
+                    process::exit(1);
+                }));
+            }
+            "--prior-temperature" => {
+                let t = all.next().unwrap_or_default();
+                prior_temperature = Some(t.parse().unwrap_or_else(|_| {
+                    eprintln!("Invalid prior temperature: {}", t);
+                    process::exit(1);
+                }));
+            }
+            _ => args.push(arg),
+        }
+    }
+    if args.len() < 2 {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+
+    let mut config = TrainConfig::default();
+    if team {
+        config.variant = Variant::Team;
+    }
+    config.state = StateOptions {
+        team_relative: team,
+        pieces,
+        history,
+        absolute,
+    };
+    config.search.payoff = payoff;
+    config.search.fpu_reduction = fpu_reduction;
+    if let Some(temperature) = prior_temperature {
+        config.search.prior_temperature = temperature;
+    }
+    if let Some(games) = baseline_games {
+        config.baseline_games = games;
+    }
+    if let Some(rounds) = args.get(2) {
+        config.training_rounds = match rounds.parse() {
+            Ok(r) => r,
+            Err(_) => {
+                eprintln!("Invalid number of rounds: {}", rounds);
+                process::exit(1);
+            }
+        };
+    }
+
+    let result = Trainer::new(config).and_then(|mut trainer| trainer.run(&args[1]));
+    if let Err(e) = result {
+        eprintln!("Training failed: {}", e);
+        process::exit(1);
+    }
+}
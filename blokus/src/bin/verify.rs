@@ -0,0 +1,63 @@
+// Replay recorded games through the engine to check they still follow its rules
+// Usage: verify [--variant classic|two-player|team] <games.json|games.csv>
+// Every move must be legal, and played games with final scores must end with those scores,
+// which catches rule changes between engine versions and bad imports of outside games
+use std::env;
+use std::process;
+
+use blokus::game::{Game, Variant};
+use blokus::records::{load_played, verify};
+
+const USAGE: &str = "Usage: verify [--variant classic|two-player|team] <games.json|games.csv>";
+
+/// Check every game in the file, returning how many failed
+fn run(args: &[String]) -> Result<usize, String> {
+    let mut variant = Variant::Classic;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--variant" => {
+                let name = args.next().ok_or("Missing value for --variant")?;
+                variant = Variant::from_name(name)?;
+            }
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let path = path.ok_or(USAGE)?;
+
+    let games = load_played(&path)?;
+    let mut failed = 0;
+    for (i, played) in games.iter().enumerate() {
+        match verify(Game::new(variant), played) {
+            Ok(game) => {
+                let scores: Vec<String> = game.get_score().iter().map(|s| s.to_string()).collect();
+                let checked = if played.is_finished() {
+                    ""
+                } else {
+                    ", not scored"
+                };
+                println!("game {}: ok, scores {}{}", i, scores.join(","), checked);
+            }
+            Err(e) => {
+                failed += 1;
+                println!("game {}: {}", i, e);
+            }
+        }
+    }
+    println!("{} of {} games verified", games.len() - failed, games.len());
+    Ok(failed)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(0) => {}
+        Ok(_) => process::exit(1),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    }
+}
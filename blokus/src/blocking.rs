@@ -0,0 +1,107 @@
+/*
+Blocking analysis, what a candidate move takes away from the opponents
+An anchor counts as live while some legal placement of its player covers it, so a move
+blocks an anchor by covering it or by filling the squares every placement through it needs.
+Partners in the team games are not blocked, only opponents are reported.
+*/
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::game::{get_moves, Game, Move};
+
+const NUM_PLAYERS: usize = 4;
+
+/// What a move costs one opponent
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Blocked {
+    pub player: usize,
+    pub anchors: Vec<usize>, // Live anchors the move kills, in square order
+    pub anchors_left: usize, // Live anchors the player keeps
+    pub placements: usize,   // Legal placements the player loses
+}
+
+impl Blocked {
+    /// Whether the move takes the player's last live anchor, shutting them out of the game
+    pub fn is_shut_out(&self) -> bool {
+        !self.anchors.is_empty() && self.anchors_left == 0
+    }
+}
+
+/// Anchors covered by at least one legal placement, and the number of placements
+fn live_anchors(board: &Board, player: usize) -> (HashSet<usize>, usize) {
+    let anchors = board.get_anchors(player);
+    let (moves, tile_groups) = get_moves(board, player);
+    let live = tile_groups
+        .iter()
+        .flatten()
+        .filter(|t| anchors.contains(t))
+        .copied()
+        .collect();
+    (live, moves.len())
+}
+
+/// Opponents the move takes anchors or placements from, in player order
+/// The move is for the player to move and is checked against the legal moves.
+pub fn blocked_by(game: &Game, (piece, variant, offset): Move) -> Result<Vec<Blocked>, String> {
+    if !game.get_legal_moves().contains(&(piece, variant, offset)) {
+        return Err(format!("Illegal move: {:?}", (piece, variant, offset)));
+    }
+
+    let player = game.current_player();
+    let mut board = game.board.clone();
+    for tile in &game.get_piece(player, piece, variant).offsets {
+        board.place_tile(offset + tile, player);
+    }
+
+    let partner = game.variant().partner(player);
+    let mut blocked = Vec::new();
+    for opponent in
+        (0..NUM_PLAYERS).filter(|p| *p != player && *p != partner && game.is_player_active(*p))
+    {
+        let (live_before, moves_before) = live_anchors(&game.board, opponent);
+        let (live_after, moves_after) = live_anchors(&board, opponent);
+        let mut anchors: Vec<usize> = live_before.difference(&live_after).copied().collect();
+        anchors.sort();
+        let placements = moves_before - moves_after;
+        if anchors.is_empty() && placements == 0 {
+            continue;
+        }
+        blocked.push(Blocked {
+            player: opponent,
+            anchors,
+            anchors_left: live_after.len(),
+            placements,
+        });
+    }
+    Ok(blocked)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocked_by() {
+        // Nothing to block from the opening corner
+        let game = Game::reset();
+        let opening = *game.get_legal_moves().iter().next().unwrap();
+        assert_eq!(blocked_by(&game, opening).unwrap(), vec![]);
+        assert!(blocked_by(&game, (0, 0, 210)).is_err());
+
+        // Give player 1 a single tile diagonal to player 0's corner, then take its corner
+        let mut game = Game::reset();
+        game.board.place_tile(21, 1);
+        let pieces = game.get_current_player_pieces();
+        let monomino = pieces.iter().position(|p| p.points == 1).unwrap();
+        let blocked = blocked_by(&game, (monomino, 0, 0)).unwrap();
+        assert_eq!(blocked.len(), 1);
+        assert_eq!(blocked[0].player, 1);
+        assert_eq!(blocked[0].anchors, vec![0]);
+        assert!(blocked[0].placements > 0);
+        assert!(!blocked[0].is_shut_out());
+    }
+}
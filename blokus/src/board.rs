@@ -8,6 +8,9 @@ use crate::pieces::{Piece, PieceVariant, PIECE_TYPES};
 
 pub const BOARD_SIZE: usize = 20;
 const TOTAL_TILES: i32 = 89;
+/// Blokus Junior is played on the middle squares of the board with the smallest pieces
+pub const JUNIOR_SIZE: usize = 14;
+pub const JUNIOR_PIECES: usize = 9; // The first piece types, those of one to four squares
 const CORNERS_OFFSETS: [i32; 4] = [
     1 + BOARD_SIZE as i32,
     -1 - BOARD_SIZE as i32,
@@ -20,6 +23,14 @@ pub struct Board {
     pub board: [u8; BOARD_SIZE * BOARD_SIZE], // 20x20 board
     pieces: [Vec<Piece>; 4],
     anchors: [HashSet<usize>; 4],
+    total_tiles: i32, // Squares in a full set, which pieces left in hand are counted against
+}
+
+/// Whether a square is on Blokus Junior's smaller board
+pub fn in_junior_area(tile: usize) -> bool {
+    let start = (BOARD_SIZE - JUNIOR_SIZE) / 2;
+    let area = start..start + JUNIOR_SIZE;
+    area.contains(&(tile / BOARD_SIZE)) && area.contains(&(tile % BOARD_SIZE))
 }
 
 impl Board {
@@ -56,7 +67,34 @@ impl Board {
             board: [0; BOARD_SIZE * BOARD_SIZE],
             pieces: player_pieces,
             anchors: anchors,
+            total_tiles: TOTAL_TILES,
+        }
+    }
+
+    /// Blokus Junior's board: the squares off its smaller board are blocked for everyone and
+    /// each color has only the smallest pieces, red and yellow start from opposite corners
+    /// while blue and green have nowhere to start
+    pub fn junior() -> Board {
+        let mut board = Board::new();
+        for tile in 0..BOARD_SIZE * BOARD_SIZE {
+            if !in_junior_area(tile) {
+                board.board[tile] = 0b1111_0000;
+            }
+        }
+        for pieces in board.pieces.iter_mut() {
+            pieces.truncate(JUNIOR_PIECES);
         }
+        board.total_tiles = board.pieces[0].iter().map(|p| p.points as i32).sum();
+
+        let start = (BOARD_SIZE - JUNIOR_SIZE) / 2;
+        let end = start + JUNIOR_SIZE - 1;
+        board.anchors = [
+            HashSet::from([start * BOARD_SIZE + start]),
+            HashSet::new(),
+            HashSet::from([end * BOARD_SIZE + end]),
+            HashSet::new(),
+        ];
+        board
     }
 
     pub fn is_valid_move(
@@ -68,9 +106,9 @@ impl Board {
         // Check piece is within bounds and does not go over edge of board
         let variant = &piece_variant.variant;
         let piece_squares = &piece_variant.offsets;
-        if offset + variant.len() > self.board.len() {
-            return false;
-        } else if offset % BOARD_SIZE + piece_variant.width > BOARD_SIZE {
+        if offset.saturating_add(variant.len()) > self.board.len()
+            || offset % BOARD_SIZE + piece_variant.width > BOARD_SIZE
+        {
             return false;
         }
 
@@ -148,6 +186,16 @@ impl Board {
         self.pieces[player].clone()
     }
 
+    /// The player's remaining pieces without copying them
+    pub(crate) fn pieces(&self, player: usize) -> &[Piece] {
+        &self.pieces[player]
+    }
+
+    /// The player's anchors without copying them
+    pub(crate) fn anchors(&self, player: usize) -> &HashSet<usize> {
+        &self.anchors[player]
+    }
+
     pub fn use_piece(&mut self, player: usize, piece: usize) {
         self.pieces[player].remove(piece);
     }
@@ -165,7 +213,7 @@ impl Board {
         // 15 bonus points for playing all pieces
         for (i, pieces) in self.pieces.iter().enumerate() {
             // Subtract to get the number of pieces remaining
-            scores[i] = scores[i] - TOTAL_TILES;
+            scores[i] -= self.total_tiles;
 
             if pieces.len() == 0 {
                 scores[i] += 15;
@@ -180,6 +228,31 @@ impl Board {
         scores
     }
 
+    /// Render the board with ANSI colors and the square coordinates used in move notation
+    pub fn to_ansi(&self) -> String {
+        let colors = ["41", "44", "43", "42"]; // Red, blue, yellow, green backgrounds
+        let mut output = String::from("   ");
+        for col in 0..BOARD_SIZE {
+            output.push_str(&format!(" {}", (b'a' + col as u8) as char));
+        }
+        output.push('\n');
+        for row in 0..BOARD_SIZE {
+            output.push_str(&format!("{:>2} ", row + 1));
+            for col in 0..BOARD_SIZE {
+                let cell_value = self.board[row * BOARD_SIZE + col] & 0b0000_1111;
+                match cell_value {
+                    1..=4 => output.push_str(&format!(
+                        "\x1b[{}m  \x1b[0m",
+                        colors[cell_value as usize - 1]
+                    )),
+                    _ => output.push_str(" ."),
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+
     pub fn print_board(&self) {
         let player1_emoji = "🟥";
         let player2_emoji = "🟦";
@@ -223,4 +296,21 @@ mod tests {
         assert_eq!(board.is_valid_move(0, &piece, 0), true);
         assert!(board.is_valid_move(0, &piece, 19) == false);
     }
+
+    #[test]
+    fn test_junior_board() {
+        let board = Board::junior();
+        let piece = PieceVariant::new(vec![vec![true, true]]);
+        assert!(board.is_valid_move(0, &piece, 63)); // c4, the corner of the smaller board
+        assert!(!board.is_valid_move(0, &piece, 0));
+        assert!(board.is_valid_move(2, &piece, 335));
+        assert!(board.get_anchors(1).is_empty());
+
+        // Squares off the smaller board are never open, even once a piece is beside them
+        let mut board = board;
+        board.place_tile(63, 0);
+        assert!(!board.get_anchors(0).contains(&42));
+        assert_eq!(board.get_pieces(0).len(), JUNIOR_PIECES);
+        assert_eq!(board.get_scores([0; 4])[0], 1 - 29);
+    }
 }
@@ -0,0 +1,74 @@
+/*
+Cancelling searches from other threads
+A token is shared between whoever runs a search and whoever may need to stop it, like a
+server room when a player resigns, disconnects, or takes back a move. Searches check the
+flag before every simulation and return what they have found so far, and other threads
+can wait on the token to learn when it is cancelled.
+*/
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct Shared {
+    cancelled: AtomicBool,
+    lock: Mutex<()>,
+    notify: Condvar,
+}
+
+/// A cancellation flag with wake-ups, clones share the same flag
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    shared: Arc<Shared>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancel every search using the token and wake anyone waiting on it
+    pub fn cancel(&self) {
+        let _guard = self.shared.lock.lock().unwrap();
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+        self.shared.notify.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Block until the token is cancelled or the timeout passes, returns whether it was cancelled
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let guard = self.shared.lock.lock().unwrap();
+        let (_guard, _) = self
+            .shared
+            .notify
+            .wait_timeout_while(guard, timeout, |_| !self.is_cancelled())
+            .unwrap();
+        self.is_cancelled()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_cancel_wakes_waiters() {
+        let token = CancellationToken::new();
+        assert!(!token.is_cancelled());
+        assert!(!token.wait_timeout(Duration::from_millis(1)));
+
+        let waiter = {
+            let token = token.clone();
+            thread::spawn(move || token.wait_timeout(Duration::from_secs(60)))
+        };
+        token.cancel();
+        assert!(waiter.join().unwrap());
+        assert!(token.is_cancelled());
+    }
+}
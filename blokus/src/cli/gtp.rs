@@ -0,0 +1,26 @@
+/*
+Engine protocol over stdin and stdout, see blokus::protocol for the commands
+
+Options:
+  --engine <agent>   mcts, blocking, greedy, or random (default mcts)
+  --sims <n>         Simulations per tile for mcts (default 200)
+  --time <ms>        Time per move for mcts instead of a simulation count
+*/
+
+use std::io::{self, BufReader};
+
+use blokus::protocol::Engine;
+
+use super::{make_agent, number_option, option};
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let sims = number_option(args, "--sims", 200)?;
+    let time = match option(args, "--time") {
+        Some(_) => Some(number_option(args, "--time", 0)?),
+        None => None,
+    };
+    let agent = make_agent(option(args, "--engine").unwrap_or("mcts"), sims, time)?;
+
+    let mut engine = Engine::new(agent);
+    engine.run(BufReader::new(io::stdin()), io::stdout())
+}
@@ -0,0 +1,45 @@
+/*
+Shared helpers for the command line front-end
+*/
+
+use std::time::Duration;
+
+use blokus::agents::{self, Agent};
+
+pub mod gtp;
+pub mod play;
+pub mod render;
+
+/// Get the value following a `--name` option
+pub fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
+}
+
+/// Get a numeric option, or the default when it is not given
+pub fn number_option<T: std::str::FromStr>(
+    args: &[String],
+    name: &str,
+    default: T,
+) -> Result<T, String> {
+    match option(args, name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("Invalid value for {}: {}", name, value)),
+        None => Ok(default),
+    }
+}
+
+/// Build an engine player by name, see `agents::from_spec`
+/// MCTS searches for `sims` simulations per tile, or for `time_ms` per move when that is given
+pub fn make_agent(name: &str, sims: usize, time_ms: Option<u64>) -> Result<Box<dyn Agent>, String> {
+    let spec = match name {
+        "mcts" => format!("mcts:{}", sims),
+        _ => name.to_string(),
+    };
+    let mut agent = agents::from_spec(&spec, None)?;
+    agent.set_time_budget(time_ms.map(Duration::from_millis));
+    Ok(agent)
+}
@@ -0,0 +1,111 @@
+/*
+Human against the engine in the terminal
+
+Options:
+  --seat <0-3>         Seat the human plays, 0 moves first (default 0)
+  --opponent <agent>   mcts, blocking, greedy, or random (default mcts)
+  --sims <n>           Simulations per tile for mcts (default 200)
+  --time <ms>          Time per move for mcts instead of a simulation count
+*/
+
+use std::io::{self, BufRead, Write};
+
+use blokus::agents::sorted_moves;
+use blokus::game::{Game, Move};
+use blokus::notation::{format_move, parse_move};
+
+use super::{make_agent, number_option, option};
+
+const PLAYER_NAMES: [&str; 4] = ["Red", "Blue", "Yellow", "Green"];
+
+fn print_position(game: &Game) {
+    println!("{}", game.board.to_ansi());
+    let scores = game.get_score();
+    for (player, name) in PLAYER_NAMES.iter().enumerate() {
+        let pieces = game.board.get_pieces(player).len();
+        println!(
+            "{:<7} score {:>4}, {:>2} pieces left",
+            name, scores[player], pieces
+        );
+    }
+    println!();
+}
+
+/// Ask until the human enters a legal move, returns None if they quit
+fn read_move(game: &Game, input: &mut impl BufRead) -> Result<Option<Move>, String> {
+    let moves = sorted_moves(game);
+    loop {
+        print!("Your move (squares like a1-a2-b2, a number from 'list', or 'quit'): ");
+        io::stdout().flush().map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        if input.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim();
+        match line {
+            "quit" => return Ok(None),
+            "list" => {
+                for (i, m) in moves.iter().enumerate() {
+                    println!("{:>4}: {}", i, format_move(game, *m));
+                }
+            }
+            _ => {
+                if let Ok(i) = line.parse::<usize>() {
+                    match moves.get(i) {
+                        Some(m) => return Ok(Some(*m)),
+                        None => println!("There are only {} moves", moves.len()),
+                    }
+                    continue;
+                }
+                match parse_move(game, line) {
+                    Ok(m) => return Ok(Some(m)),
+                    Err(e) => println!("{}", e),
+                }
+            }
+        }
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let seat: usize = number_option(args, "--seat", 0)?;
+    if seat > 3 {
+        return Err("Seat must be between 0 and 3".to_string());
+    }
+    let sims = number_option(args, "--sims", 200)?;
+    let time = match option(args, "--time") {
+        Some(_) => Some(number_option(args, "--time", 0)?),
+        None => None,
+    };
+    let mut engine = make_agent(option(args, "--opponent").unwrap_or("mcts"), sims, time)?;
+
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut game = Game::reset();
+    println!("You are {}", PLAYER_NAMES[seat]);
+    while !game.is_terminal() {
+        print_position(&game);
+        let player = game.current_player();
+        let chosen = if player == seat {
+            match read_move(&game, &mut input)? {
+                Some(m) => m,
+                None => return Ok(()),
+            }
+        } else {
+            let m = engine.choose_move(&game)?;
+            println!("{} plays {}", PLAYER_NAMES[player], format_move(&game, m));
+            m
+        };
+        game = game.place_piece(chosen.0, chosen.1, chosen.2)?;
+    }
+
+    print_position(&game);
+    let scores = game.get_score();
+    let best = scores.iter().max().unwrap();
+    let winners: Vec<&str> = (0..4)
+        .filter(|p| scores[*p] == *best)
+        .map(|p| PLAYER_NAMES[p])
+        .collect();
+    println!("Game over, winner: {}", winners.join(", "));
+    Ok(())
+}
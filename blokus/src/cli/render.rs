@@ -0,0 +1,49 @@
+/*
+Render a recorded game as an SVG of the final board or an animated GIF
+
+Usage: blokus render <records.json|records.csv> [options]
+  --game <n>     Which game in the file to draw, from 0 (default 0)
+  --svg <path>   Write the final board as SVG
+  --gif <path>   Write the whole game as an animated GIF
+  --delay <cs>   Hundredths of a second per GIF frame (default 50)
+*/
+
+use std::fs;
+
+use blokus::records;
+use blokus::render::{board_svg, game_gif};
+
+use super::{number_option, option};
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let path = match args.first() {
+        Some(path) if !path.starts_with("--") => path,
+        _ => {
+            return Err(
+                "Usage: blokus render <records> [--game n] [--svg path] [--gif path]".to_string(),
+            )
+        }
+    };
+    let index: usize = number_option(args, "--game", 0)?;
+    let delay = number_option(args, "--delay", 50)?;
+    let svg = option(args, "--svg");
+    let gif = option(args, "--gif");
+    if svg.is_none() && gif.is_none() {
+        return Err("Give --svg, --gif, or both".to_string());
+    }
+
+    let games = records::load(path)?;
+    let record = games
+        .get(index)
+        .ok_or(format!("{} has {} games", path, games.len()))?;
+    let write = |path: &str, contents: &[u8]| {
+        fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path, e))
+    };
+    if let Some(path) = svg {
+        write(path, board_svg(record)?.as_bytes())?;
+    }
+    if let Some(path) = gif {
+        write(path, &game_gif(record, delay)?)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+/*
+Dead pieces and dead regions, what a color can never play again
+The squares a color may still cover are the empty ones not beside its own tiles. Pockets of
+them too small for the color's smallest piece are dropped, then so are squares no remaining
+piece fits over, and the rest is flood filled from the color's anchors, through corners too
+since later pieces only need to touch diagonally. This overestimates what the color can reach,
+so anything it rules out is certain: a dead square stays out of the color's reach and a dead
+piece stays in hand for the rest of the game.
+*/
+
+use crate::board::BOARD_SIZE;
+use crate::game::Game;
+use crate::pieces::{Piece, PieceVariant};
+
+const BOARD_SPACES: usize = BOARD_SIZE * BOARD_SIZE;
+const NUM_PLAYERS: usize = 4;
+
+/// Whether the placement stays on the board and only covers the given squares
+fn fits(variant: &PieceVariant, offset: usize, squares: &[bool]) -> bool {
+    offset + variant.variant.len() <= BOARD_SPACES
+        && offset % BOARD_SIZE + variant.width <= BOARD_SIZE
+        && variant.offsets.iter().all(|t| squares[offset + t])
+}
+
+/// Squares covered by some placement of the pieces that only uses the given squares
+fn covered(pieces: &[Piece], squares: &[bool]) -> Vec<bool> {
+    let mut covered = vec![false; BOARD_SPACES];
+    for variant in pieces.iter().flat_map(|p| &p.variants) {
+        for offset in 0..BOARD_SPACES {
+            if fits(variant, offset, squares) {
+                variant
+                    .offsets
+                    .iter()
+                    .for_each(|t| covered[offset + t] = true);
+            }
+        }
+    }
+    covered
+}
+
+/// Neighbors of a square, along the edges and also across the corners if asked
+fn neighbors(square: usize, corners: bool) -> Vec<usize> {
+    let (row, col) = (
+        (square / BOARD_SIZE) as isize,
+        (square % BOARD_SIZE) as isize,
+    );
+    let mut steps = vec![(-1, 0), (1, 0), (0, -1), (0, 1)];
+    if corners {
+        steps.extend([(-1, -1), (-1, 1), (1, -1), (1, 1)]);
+    }
+    steps
+        .into_iter()
+        .map(|(dr, dc)| (row + dr, col + dc))
+        .filter(|(r, c)| {
+            (0..BOARD_SIZE as isize).contains(r) && (0..BOARD_SIZE as isize).contains(c)
+        })
+        .map(|(r, c)| r as usize * BOARD_SIZE + c as usize)
+        .collect()
+}
+
+/// Groups of the given squares joined along edges, or also across corners
+fn regions(squares: &[bool], corners: bool) -> Vec<Vec<usize>> {
+    let mut seen = vec![false; BOARD_SPACES];
+    let mut regions = Vec::new();
+    for start in 0..BOARD_SPACES {
+        if !squares[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut region = vec![start];
+        let mut i = 0;
+        while i < region.len() {
+            for next in neighbors(region[i], corners) {
+                if squares[next] && !seen[next] {
+                    seen[next] = true;
+                    region.push(next);
+                }
+            }
+            i += 1;
+        }
+        regions.push(region);
+    }
+    regions
+}
+
+/// Squares the color might still cover with its remaining pieces, indexed by square
+pub fn reachable(game: &Game, player: usize) -> Vec<bool> {
+    let pieces = game.get_player_pieces(player);
+    let Some(smallest) = pieces.iter().map(|p| p.points as usize).min() else {
+        return vec![false; BOARD_SPACES];
+    };
+    if !game.is_player_active(player) {
+        return vec![false; BOARD_SPACES];
+    }
+
+    // Empty squares not beside the color's tiles, in pockets big enough for a piece
+    let restricted = 1 << (player + 4);
+    let open: Vec<bool> = game
+        .get_board()
+        .iter()
+        .map(|c| c & restricted == 0)
+        .collect();
+    let mut roomy = vec![false; BOARD_SPACES];
+    for region in regions(&open, false) {
+        if region.len() >= smallest {
+            region.iter().for_each(|s| roomy[*s] = true);
+        }
+    }
+
+    // Squares some piece fits over, joined to an anchor
+    let usable = covered(&pieces, &roomy);
+    let anchors = game.board.get_anchors(player);
+    let mut reachable = vec![false; BOARD_SPACES];
+    for region in regions(&usable, true) {
+        if region.iter().any(|s| anchors.contains(s)) {
+            region.iter().for_each(|s| reachable[*s] = true);
+        }
+    }
+    reachable
+}
+
+/// Empty squares the color can never cover, in square order
+pub fn dead_squares(game: &Game, player: usize) -> Vec<usize> {
+    let reachable = reachable(game, player);
+    let ownership = game.get_ownership();
+    (0..BOARD_SPACES)
+        .filter(|s| ownership[*s] < 0 && !reachable[*s])
+        .collect()
+}
+
+/// Pieces the color can never place, as indices into its remaining pieces
+pub fn dead_pieces(game: &Game, player: usize) -> Vec<usize> {
+    let reachable = reachable(game, player);
+    game.get_player_pieces(player)
+        .iter()
+        .enumerate()
+        .filter(|(_, piece)| {
+            !piece
+                .variants
+                .iter()
+                .any(|v| (0..BOARD_SPACES).any(|o| fits(v, o, &reachable)))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Empty squares no color can ever cover, they stay empty until the end of the game
+pub fn sealed_squares(game: &Game) -> Vec<usize> {
+    let reachable: Vec<Vec<bool>> = (0..NUM_PLAYERS).map(|p| reachable(game, p)).collect();
+    let ownership = game.get_ownership();
+    (0..BOARD_SPACES)
+        .filter(|s| ownership[*s] < 0 && reachable.iter().all(|r| !r[*s]))
+        .collect()
+}
+
+/// Final scores of the colors that can no longer place anything, indexed by player
+/// A color whose remaining pieces are all dead keeps its current score to the end.
+pub fn settled_scores(game: &Game) -> Vec<Option<i32>> {
+    let scores = game.get_score();
+    (0..NUM_PLAYERS)
+        .map(|p| {
+            let settled = dead_pieces(game, p).len() == game.get_player_pieces(p).len();
+            settled.then_some(scores[p])
+        })
+        .collect()
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nothing_dead_at_the_start() {
+        let game = Game::reset();
+        assert!(reachable(&game, 0)[0]);
+        assert!(reachable(&game, 0)[210]);
+        assert_eq!(dead_pieces(&game, 0), Vec::<usize>::new());
+        assert_eq!(sealed_squares(&game), Vec::<usize>::new());
+        assert_eq!(settled_scores(&game), vec![None; NUM_PLAYERS]);
+    }
+
+    #[test]
+    fn test_walled_off_corner() {
+        // Player 1 walls player 0's corner into a two by two pocket
+        let mut game = Game::reset();
+        for tile in [2, 22, 40, 41, 42] {
+            game.board.place_tile(tile, 1);
+        }
+        assert_eq!(dead_squares(&game, 0).len(), 400 - 5 - 4);
+
+        // Only pieces that fit in the pocket are left alive
+        let pieces = game.get_player_pieces(0);
+        let dead = dead_pieces(&game, 0);
+        let alive: Vec<u32> = (0..pieces.len())
+            .filter(|i| !dead.contains(i))
+            .map(|i| pieces[i].points)
+            .collect();
+        assert_eq!(alive, vec![1, 2, 3, 4]); // Single square, domino, bent three, and square
+        assert_eq!(settled_scores(&game)[0], None);
+    }
+}
@@ -0,0 +1,153 @@
+/*
+Exact solver for endgames with few legal moves left
+*/
+
+use crate::game::{Game, Move};
+
+const NUM_PLAYERS: usize = 4;
+
+/// Result of solving a position exactly
+/// Every player is assumed to maximize their own final score (max^n search), or their
+/// side's combined score in the two player and team games
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    pub scores: Vec<i32>,
+    pub best_move: Option<Move>, // For the player to move
+    pub nodes: usize,
+}
+
+/// Total number of legal moves across all players still in the game
+pub fn total_moves(game: &Game) -> usize {
+    (0..NUM_PLAYERS)
+        .map(|player| game.count_player_moves(player))
+        .sum()
+}
+
+/// Check if a position is small enough that the solver should take over
+pub fn is_solvable(game: &Game, threshold: usize) -> bool {
+    game.is_turn_start() && !game.is_terminal() && total_moves(game) <= threshold
+}
+
+/// Solve a position exactly, giving up once more than `node_limit` positions are visited
+/// Only works between turns, since whole pieces are placed at a time
+pub fn solve(game: &Game, node_limit: usize) -> Option<Solution> {
+    if !game.is_turn_start() {
+        return None;
+    }
+
+    let mut nodes = 0;
+    let (scores, best_move) = maxn(game, node_limit, &mut nodes)?;
+    Some(Solution {
+        scores,
+        best_move,
+        nodes,
+    })
+}
+
+fn maxn(game: &Game, node_limit: usize, nodes: &mut usize) -> Option<(Vec<i32>, Option<Move>)> {
+    *nodes += 1;
+    if *nodes > node_limit {
+        return None;
+    }
+    if game.is_terminal() {
+        return Some((game.get_score(), None));
+    }
+
+    // Sort so ties are always broken the same way
+    let player = game.current_player();
+    let variant = game.variant();
+    let mut moves: Vec<Move> = game.get_legal_moves().into_iter().collect();
+    moves.sort();
+
+    let mut best: Option<(Vec<i32>, Move)> = None;
+    for (p, v, o) in moves {
+        let next = game.place_piece(p, v, o).ok()?;
+        let (scores, _) = maxn(&next, node_limit, nodes)?;
+        let side = |s: &[i32]| variant.team_scores(s)[player];
+        if best.as_ref().is_none_or(|(b, _)| side(&scores) > side(b)) {
+            best = Some((scores, (p, v, o)));
+        }
+    }
+
+    let (scores, best_move) = best?;
+    Some((scores, Some(best_move)))
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Variant;
+
+    /// Play the first legal move for every player until few moves are left
+    fn late_position(max_moves: usize) -> Game {
+        let mut game = Game::reset();
+        while !game.is_terminal() && total_moves(&game) > max_moves {
+            let mut moves: Vec<_> = game.get_legal_moves().into_iter().collect();
+            moves.sort();
+            let (p, v, o) = moves[moves.len() / 2];
+            game = game.place_piece(p, v, o).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn test_terminal_position() {
+        let game = late_position(0);
+        assert!(game.is_terminal());
+        let solution = solve(&game, 10).unwrap();
+        assert_eq!(solution.scores, game.get_score());
+        assert_eq!(solution.best_move, None);
+    }
+
+    #[test]
+    fn test_solution_is_reachable() {
+        let game = late_position(15);
+        let solution = solve(&game, 1_000_000).unwrap();
+        let best_move = solution.best_move.unwrap();
+        assert!(game.get_legal_moves().contains(&best_move));
+
+        // The best move can't be worse for the mover than any other move
+        let player = game.current_player();
+        for (p, v, o) in game.get_legal_moves() {
+            let next = game.place_piece(p, v, o).unwrap();
+            let other = solve(&next, 1_000_000).unwrap();
+            assert!(other.scores[player] <= solution.scores[player]);
+        }
+    }
+
+    #[test]
+    fn test_team_endgame() {
+        // A two player position where the move best for the mover's own color costs its side
+        let mut game = Game::new(Variant::TwoPlayer);
+        while total_moves(&game) > 23 {
+            let mut moves: Vec<_> = game.get_legal_moves().into_iter().collect();
+            moves.sort();
+            let (p, v, o) = moves[(moves.len() / 2 + 3) % moves.len()];
+            game = game.place_piece(p, v, o).unwrap();
+        }
+        let player = game.current_player();
+        let side = |scores: &[i32]| Variant::TwoPlayer.team_scores(scores)[player];
+        let solution = solve(&game, 1_000_000).unwrap();
+
+        let mut selfish: Option<Vec<i32>> = None;
+        for (p, v, o) in game.get_legal_moves() {
+            let next = game.place_piece(p, v, o).unwrap();
+            let other = solve(&next, 1_000_000).unwrap();
+            assert!(side(&other.scores) <= side(&solution.scores));
+            if selfish
+                .as_ref()
+                .is_none_or(|s| other.scores[player] > s[player])
+            {
+                selfish = Some(other.scores);
+            }
+        }
+        assert!(side(&selfish.unwrap()) < side(&solution.scores));
+    }
+
+    #[test]
+    fn test_node_limit() {
+        let game = late_position(15);
+        assert_eq!(solve(&game, 1), None);
+    }
+}
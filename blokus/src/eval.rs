@@ -0,0 +1,128 @@
+/*
+Static evaluation, a single number for how well a color stands without searching
+Each color is measured by:
+- remaining: points of the pieces still in hand, each one costs a point at the end
+- mobility: legal placements, on a log scale so a crowded corner is not worth hundreds of them
+- anchors: corners the color can still play from
+- contested: anchors an opponent can also play from, and so may lose before the next turn
+The heuristic evaluator squashes the scores into values for MCTS, and a search without a
+network can compare positions by `Eval::relative`.
+*/
+
+use crate::game::Game;
+
+const NUM_PLAYERS: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Eval {
+    pub remaining_weight: f32, // Per point left in hand
+    pub mobility_weight: f32,  // Per unit of log mobility
+    pub anchor_weight: f32,    // Per live anchor
+    pub contested_weight: f32, // Per anchor shared with an opponent, on top of the anchor itself
+}
+
+impl Default for Eval {
+    fn default() -> Self {
+        Eval {
+            remaining_weight: 0.25,
+            mobility_weight: 1.0,
+            anchor_weight: 0.5,
+            contested_weight: 0.25,
+        }
+    }
+}
+
+/// The raw measures behind a color's evaluation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Features {
+    pub remaining: u32,
+    pub mobility: usize,
+    pub anchors: usize,
+    pub contested: usize,
+}
+
+impl Features {
+    /// Measure a color, eliminated colors have no anchors or moves left
+    pub fn of(game: &Game, player: usize) -> Features {
+        let remaining = game
+            .get_player_pieces(player)
+            .iter()
+            .map(|p| p.points)
+            .sum();
+        if !game.is_player_active(player) {
+            return Features {
+                remaining,
+                ..Features::default()
+            };
+        }
+
+        let anchors = game.board.get_anchors(player);
+        let partner = game.variant().partner(player);
+        let contested = anchors
+            .iter()
+            .filter(|a| {
+                (0..NUM_PLAYERS)
+                    .filter(|p| *p != player && *p != partner && game.is_player_active(*p))
+                    .any(|p| game.board.get_anchors(p).contains(a))
+            })
+            .count();
+        Features {
+            remaining,
+            mobility: game.count_player_moves(player),
+            anchors: anchors.len(),
+            contested,
+        }
+    }
+}
+
+impl Eval {
+    /// Weighted sum of a color's features, higher is better
+    pub fn score(&self, game: &Game, player: usize) -> f32 {
+        let features = Features::of(game, player);
+        self.mobility_weight * (1.0 + features.mobility as f32).ln()
+            + self.anchor_weight * features.anchors as f32
+            - self.contested_weight * features.contested as f32
+            - self.remaining_weight * features.remaining as f32
+    }
+
+    /// Scores of every color, indexed by player
+    pub fn scores(&self, game: &Game) -> Vec<f32> {
+        (0..NUM_PLAYERS).map(|p| self.score(game, p)).collect()
+    }
+
+    /// A color's score minus the best score among its opponents, partners are not opponents
+    pub fn relative(&self, game: &Game, player: usize) -> f32 {
+        let scores = self.scores(game);
+        let partner = game.variant().partner(player);
+        let best_opponent = (0..NUM_PLAYERS)
+            .filter(|p| *p != player && *p != partner)
+            .map(|p| scores[p])
+            .fold(f32::MIN, f32::max);
+        scores[player] - best_opponent
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval() {
+        // Everyone starts even with all 89 points in hand and a single anchor
+        let game = Game::reset();
+        let eval = Eval::default();
+        let features = Features::of(&game, 0);
+        assert_eq!(features.remaining, 89);
+        assert_eq!(features.anchors, 1);
+        assert_eq!(features.contested, 0);
+        assert!(eval.scores(&game).windows(2).all(|pair| pair[0] == pair[1]));
+        assert_eq!(eval.relative(&game, 0), 0.0);
+
+        // Playing five in a row puts player 0 ahead
+        let game = game.place_piece(9, 0, 0).unwrap();
+        assert_eq!(Features::of(&game, 0).remaining, 84);
+        assert!(eval.relative(&game, 0) > 0.0);
+        assert!(eval.relative(&game, 1) < 0.0);
+    }
+}
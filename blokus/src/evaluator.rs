@@ -0,0 +1,271 @@
+/*
+Position evaluators used to guide search
+*/
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::agents::sorted_moves;
+use crate::board::BOARD_SIZE;
+use crate::eval::Eval;
+use crate::game::{Game, Move};
+use crate::move_ordering::MoveOrdering;
+use crate::payoff::Payoff;
+
+const NUM_PLAYERS: usize = 4;
+
+/// Anything that can score a position for MCTS
+/// The policy is indexed by tile in board coordinates and should sum to one over
+/// the legal tiles. Values are indexed by player, with higher meaning more likely to win.
+pub trait Evaluator {
+    fn evaluate(&mut self, game: &Game) -> Result<(Vec<f32>, Vec<f32>), String>;
+}
+
+/// Evaluator built from simple features of the position, needs no model
+/// Players are compared by the static evaluation, see `Eval`, and tiles are preferred
+/// when they are part of many placements of large pieces, or by the move ordering's
+/// scores when one is set.
+#[derive(Clone, Debug)]
+pub struct HeuristicEvaluator {
+    pub eval: Eval,
+    pub temperature: f32, // Softens the differences between players' values
+    pub ordering: Option<(MoveOrdering, f32)>, // Ordering and its temperature for the policy
+}
+
+impl Default for HeuristicEvaluator {
+    fn default() -> Self {
+        HeuristicEvaluator {
+            eval: Eval::default(),
+            temperature: 10.0,
+            ordering: None,
+        }
+    }
+}
+
+impl HeuristicEvaluator {
+    /// Weight each legal tile by the points of the placements that cover it
+    fn policy(&self, game: &Game) -> Vec<f32> {
+        if let Some((ordering, temperature)) = &self.ordering {
+            return ordering.tile_priors(game, *temperature);
+        }
+        let mut policy = vec![0.0; BOARD_SIZE * BOARD_SIZE];
+        let player = game.current_player();
+        for (p, v, o) in game.get_legal_moves() {
+            let piece = game.get_piece(player, p, v);
+            let points = piece.offsets.len() as f32;
+            for offset in &piece.offsets {
+                policy[o + offset] += points;
+            }
+        }
+
+        // Only keep tiles that can still be placed this turn
+        let legal_tiles = game.get_legal_tiles();
+        let total: f32 = legal_tiles.iter().map(|t| policy[*t]).sum();
+        let mut normalized = vec![0.0; BOARD_SIZE * BOARD_SIZE];
+        for tile in legal_tiles {
+            normalized[tile] = policy[tile] / total;
+        }
+        normalized
+    }
+
+    /// Score each player's position then squash the scores into win estimates
+    fn values(&self, game: &Game) -> Vec<f32> {
+        let scores = self.eval.scores(game);
+        let max = scores.iter().cloned().fold(f32::MIN, f32::max);
+        let exp: Vec<f32> = scores
+            .iter()
+            .map(|f| ((f - max) / self.temperature).exp())
+            .collect();
+        let total: f32 = exp.iter().sum();
+        exp.iter().map(|e| e / total).collect()
+    }
+}
+
+impl Evaluator for HeuristicEvaluator {
+    fn evaluate(&mut self, game: &Game) -> Result<(Vec<f32>, Vec<f32>), String> {
+        if game.is_terminal() {
+            let values = game.variant().color_values(game.get_payoff());
+            return Ok((vec![0.0; BOARD_SIZE * BOARD_SIZE], values));
+        }
+        Ok((self.policy(game), self.values(game)))
+    }
+}
+
+/// Evaluator that plays games out to the end, needs no model
+/// Values are each color's average payoff over the playouts, by default winner takes all, and
+/// the policy is uniform over the legal tiles. Like every evaluator the values are per color,
+/// partners split their side's payoff and search adds them back up, see `Variant::team_values`.
+/// Heuristic playouts only pick among the placements of the largest pieces that fit, which
+/// ends games closer to how real players would.
+pub struct RolloutEvaluator {
+    pub playouts: usize,
+    pub heuristic: bool,
+    pub payoff: Payoff, // Should match the search's, see SearchConfig
+    rng: StdRng,
+}
+
+impl RolloutEvaluator {
+    /// Heuristic playouts, the seed makes them reproducible
+    pub fn new(playouts: usize, seed: Option<u64>) -> RolloutEvaluator {
+        RolloutEvaluator {
+            playouts,
+            heuristic: true,
+            payoff: Payoff::default(),
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+        }
+    }
+
+    /// Play whole pieces until the game is over and score the result for each color
+    fn playout(&mut self, game: &Game) -> Result<Vec<f32>, String> {
+        let mut game = game.clone();
+        while !game.is_terminal() {
+            let player = game.current_player();
+            let mut moves = sorted_moves(&game);
+            if self.heuristic {
+                let size = |(p, v, _): &Move| game.get_piece(player, *p, *v).offsets.len();
+                let largest = moves.iter().map(size).max().unwrap_or(0);
+                moves.retain(|m| size(m) == largest);
+            }
+            if moves.is_empty() {
+                return Err(format!("No legal moves for player {} in a playout", player));
+            }
+            let (piece, variant, offset) = moves[self.rng.gen_range(0..moves.len())];
+            game.play_piece(piece, variant, offset)?;
+        }
+        Ok(game.variant().color_values(self.payoff.of(&game)))
+    }
+}
+
+impl Evaluator for RolloutEvaluator {
+    fn evaluate(&mut self, game: &Game) -> Result<(Vec<f32>, Vec<f32>), String> {
+        if game.is_terminal() {
+            let values = game.variant().color_values(self.payoff.of(game));
+            return Ok((vec![0.0; BOARD_SIZE * BOARD_SIZE], values));
+        }
+
+        let legal_tiles = game.get_legal_tiles();
+        let mut policy = vec![0.0; BOARD_SIZE * BOARD_SIZE];
+        for tile in &legal_tiles {
+            policy[*tile] = 1.0 / legal_tiles.len() as f32;
+        }
+
+        let mut values = vec![0.0; NUM_PLAYERS];
+        for _ in 0..self.playouts {
+            for (value, payoff) in values.iter_mut().zip(self.playout(game)?) {
+                *value += payoff / self.playouts as f32;
+            }
+        }
+        Ok((policy, values))
+    }
+}
+
+/// Each color's chance of winning at every position, for plotting over the course of a game
+/// Values are clamped at zero and scaled to sum to one, so evaluators whose values are not
+/// probabilities still give shares. Finished games get their winner-takes-all payoff.
+pub fn win_probabilities<E: Evaluator>(
+    positions: &[Game],
+    evaluator: &mut E,
+) -> Result<Vec<Vec<f32>>, String> {
+    let mut probabilities = Vec::new();
+    for game in positions {
+        if game.is_terminal() {
+            probabilities.push(game.get_payoff());
+            continue;
+        }
+        let (_, values) = evaluator.evaluate(game)?;
+        let values: Vec<f32> = values.iter().map(|v| v.max(0.0)).collect();
+        let total: f32 = values.iter().sum();
+        probabilities.push(if total > 0.0 {
+            values.iter().map(|v| v / total).collect()
+        } else {
+            vec![1.0 / NUM_PLAYERS as f32; NUM_PLAYERS]
+        });
+    }
+    Ok(probabilities)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Variant;
+
+    #[test]
+    fn test_policy_covers_legal_tiles() {
+        let game = Game::reset();
+        let (policy, values) = HeuristicEvaluator::default().evaluate(&game).unwrap();
+        assert_eq!(policy.len(), BOARD_SIZE * BOARD_SIZE);
+        assert!((policy.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        for tile in game.get_legal_tiles() {
+            assert!(policy[tile] > 0.0);
+        }
+        assert_eq!(values.len(), NUM_PLAYERS);
+        assert!((values.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_values_favor_placed_squares() {
+        let game = Game::reset();
+        let game = game.place_piece(9, 0, 0).unwrap(); // Five in a row for player 0
+        let (_, values) = HeuristicEvaluator::default().evaluate(&game).unwrap();
+        assert!(values[0] > values[1]);
+    }
+
+    #[test]
+    fn test_policy_from_ordering() {
+        let game = Game::reset();
+        let mut evaluator = HeuristicEvaluator {
+            ordering: Some((MoveOrdering::default(), 1.0)),
+            ..HeuristicEvaluator::default()
+        };
+        let (policy, _) = evaluator.evaluate(&game).unwrap();
+        assert_eq!(policy, MoveOrdering::default().tile_priors(&game, 1.0));
+    }
+
+    #[test]
+    fn test_rollouts() {
+        let game = Game::reset();
+        let mut evaluator = RolloutEvaluator::new(2, Some(0));
+        let (policy, values) = evaluator.evaluate(&game).unwrap();
+        assert!((policy.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        assert_eq!(
+            policy.iter().filter(|p| **p > 0.0).count(),
+            game.get_legal_tiles().len()
+        );
+        assert_eq!(values.len(), NUM_PLAYERS);
+        assert!((values.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+
+        // Seeded playouts repeat
+        let (_, again) = RolloutEvaluator::new(2, Some(0)).evaluate(&game).unwrap();
+        assert_eq!(values, again);
+
+        // Partners split their side's result, scored by the chosen payoff
+        let mut evaluator = RolloutEvaluator {
+            payoff: Payoff::Margin,
+            ..RolloutEvaluator::new(2, Some(0))
+        };
+        let (_, values) = evaluator.evaluate(&Game::new(Variant::Team)).unwrap();
+        assert_eq!((values[0], values[1]), (values[2], values[3]));
+        assert!((values.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_win_probabilities() {
+        let start = Game::reset();
+        let (piece, variant, offset) = *start.get_legal_moves().iter().next().unwrap();
+        let positions = vec![
+            start.clone(),
+            start.place_piece(piece, variant, offset).unwrap(),
+        ];
+        let probabilities =
+            win_probabilities(&positions, &mut RolloutEvaluator::new(2, Some(0))).unwrap();
+        assert_eq!(probabilities.len(), 2);
+        for shares in probabilities {
+            assert_eq!(shares.len(), NUM_PLAYERS);
+            assert!((shares.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        }
+    }
+}
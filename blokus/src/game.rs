@@ -1,19 +1,25 @@
 use std::collections::{HashMap, HashSet};
-use std::iter::zip;
 
-use crate::board::Board;
-use crate::pieces::{Piece, PieceVariant};
+use serde::{Deserialize, Serialize};
+
+use crate::board::{in_junior_area, Board};
+use crate::movegen;
+use crate::pieces::{Piece, PieceVariant, PIECE_TYPES};
+use crate::position::color;
 
 const D: usize = 20;
 const BOARD_SPACES: usize = 400;
 const NUM_PLAYERS: usize = 4;
 
+/// A whole piece placement: (piece index, variant index, board offset)
+/// The piece index is into the player's remaining pieces, as in `place_piece`
+pub type Move = (usize, usize, usize);
+
+/// Legal moves and the tiles each one covers, in the same order
+pub(crate) type MovesAndTiles = (Vec<Move>, Vec<Vec<usize>>);
+
 /// Get the legal moves for a piece
-fn get_piece_moves(
-    piece_i: usize,
-    board: &Board,
-    player: usize,
-) -> (Vec<(usize, usize, usize)>, Vec<Vec<usize>>) {
+fn get_piece_moves(piece_i: usize, board: &Board, player: usize) -> MovesAndTiles {
     let mut moves = Vec::new();
     let mut tile_groups = Vec::new();
     let piece = &board.get_pieces(player)[piece_i];
@@ -44,7 +50,7 @@ fn get_piece_moves(
 }
 
 /// Get the legal moves for a player, tile placements grouped by move
-fn get_moves(board: &Board, player: usize) -> (Vec<(usize, usize, usize)>, Vec<Vec<usize>>) {
+pub(crate) fn get_moves(board: &Board, player: usize) -> MovesAndTiles {
     let mut moves = Vec::new();
     let mut tile_groups = Vec::new();
     for piece in 0..board.get_pieces(player).len() {
@@ -59,14 +65,13 @@ fn get_moves(board: &Board, player: usize) -> (Vec<(usize, usize, usize)>, Vec<V
 /// Get the tile based representation for legal moves
 fn get_tile_moves(board: &Board, player: usize) -> HashMap<usize, HashSet<(usize, usize, usize)>> {
     let mut tile_rep = HashMap::new();
-    let (moves, tile_groups) = get_moves(board, player);
-
-    for (id, tiles) in zip(moves, tile_groups) {
-        for tile in tiles {
-            if !tile_rep.contains_key(&tile) {
-                tile_rep.insert(tile, HashSet::new());
-            }
-            tile_rep.get_mut(&tile).unwrap().insert(id);
+    let pieces = board.get_pieces(player);
+    for (p, v, o) in movegen::GENERATOR(board, player) {
+        for square in &pieces[p].variants[v].offsets {
+            tile_rep
+                .entry(o + square)
+                .or_insert_with(HashSet::new)
+                .insert((p, v, o));
         }
     }
 
@@ -75,63 +80,428 @@ fn get_tile_moves(board: &Board, player: usize) -> HashMap<usize, HashSet<(usize
 
 /// Rotates the tensor of boards 90 degrees to the left
 fn rotate_state(state: [[[bool; D]; D]; NUM_PLAYERS + 1]) -> [[[bool; D]; D]; NUM_PLAYERS + 1] {
-    let mut new_state = state.clone();
-    for i in 0..NUM_PLAYERS + 1 {
-        // Row
-        for j in 0..D {
-            for k in 0..D {
-                new_state[i][j][k] = state[i][k][D - j - 1];
+    state.map(rotate_plane)
+}
+
+/// Rotates one board 90 degrees to the left
+fn rotate_plane(plane: [[bool; D]; D]) -> [[bool; D]; D] {
+    let mut new_plane = plane;
+    for j in 0..D {
+        for k in 0..D {
+            new_plane[j][k] = plane[k][D - j - 1];
+        }
+    }
+    new_plane
+}
+
+/// Pack bools eight to a byte with the first in the high bit, as numpy's packbits does
+pub fn pack_bits(bits: impl IntoIterator<Item = bool>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (i, bit) in bits.into_iter().enumerate() {
+        if i % 8 == 0 {
+            bytes.push(0);
+        }
+        if bit {
+            *bytes.last_mut().unwrap() |= 0x80 >> (i % 8);
+        }
+    }
+    bytes
+}
+
+/// The first `len` bools packed by `pack_bits`
+pub fn unpack_bits(bytes: &[u8], len: usize) -> Result<Vec<bool>, String> {
+    if bytes.len() != len.div_ceil(8) {
+        return Err(format!("{} bits do not fit in {} bytes", len, bytes.len()));
+    }
+    Ok((0..len)
+        .map(|i| bytes[i / 8] & (0x80 >> (i % 8)) != 0)
+        .collect())
+}
+
+/// Winner takes all, split evenly among tied players
+pub fn scores_to_payoff(scores: &[i32]) -> Vec<f32> {
+    let mut payoff = vec![0.0; scores.len()];
+    let mut indices = Vec::new();
+    let mut highest_score = scores[0];
+    for (i, score) in scores.iter().enumerate() {
+        if *score == highest_score {
+            indices.push(i);
+        } else if *score > highest_score {
+            indices.clear();
+            indices.push(i);
+            highest_score = *score;
+        }
+    }
+
+    for i in &indices {
+        payoff[*i] = 1.0 / indices.len() as f32;
+    }
+
+    payoff
+}
+
+/// Rules a game is played under, all but Junior on the standard board with all four colors
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Variant {
+    #[default]
+    Classic, // Four players with a color each
+    TwoPlayer, // Two players, one with red and yellow and the other with blue and green
+    Team,      // Four players in two teams, partners across the table share their score
+    Junior,    // Two players, red and yellow, with the smallest pieces on a smaller board
+}
+
+impl Variant {
+    pub const ALL: [Variant; 4] = [
+        Variant::Classic,
+        Variant::TwoPlayer,
+        Variant::Team,
+        Variant::Junior,
+    ];
+
+    pub fn from_name(name: &str) -> Result<Variant, String> {
+        match name {
+            "classic" => Ok(Variant::Classic),
+            "two-player" => Ok(Variant::TwoPlayer),
+            "team" => Ok(Variant::Team),
+            "junior" => Ok(Variant::Junior),
+            _ => Err(format!("Unknown variant: {}", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Classic => "classic",
+            Variant::TwoPlayer => "two-player",
+            Variant::Team => "team",
+            Variant::Junior => "junior",
+        }
+    }
+
+    /// The board and piece sets a game starts with, see `Board::junior`
+    pub fn board(&self) -> Board {
+        match self {
+            Variant::Junior => Board::junior(),
+            Variant::Classic | Variant::TwoPlayer | Variant::Team => Board::new(),
+        }
+    }
+
+    /// Whether a square can be played on at all
+    pub fn on_board(&self, square: usize) -> bool {
+        match self {
+            Variant::Junior => in_junior_area(square),
+            Variant::Classic | Variant::TwoPlayer | Variant::Team => square < BOARD_SPACES,
+        }
+    }
+
+    /// Whether a color takes part, Junior leaves out blue and green
+    pub fn has_color(&self, player: usize) -> bool {
+        match self {
+            Variant::Junior => player.is_multiple_of(2),
+            Variant::Classic | Variant::TwoPlayer | Variant::Team => player < NUM_PLAYERS,
+        }
+    }
+
+    /// The colors out of the game from the start, the ones it leaves out
+    pub fn absent(&self) -> [bool; NUM_PLAYERS] {
+        std::array::from_fn(|player| !self.has_color(player))
+    }
+
+    /// People at the table
+    pub fn seats(&self) -> usize {
+        match self {
+            Variant::TwoPlayer | Variant::Junior => 2,
+            Variant::Classic | Variant::Team => NUM_PLAYERS,
+        }
+    }
+
+    /// The seat playing a color before any swap of sides, `Game::seat_of` follows swaps
+    pub fn seat(&self, player: usize) -> usize {
+        match self {
+            Variant::TwoPlayer => player % 2,
+            Variant::Junior => player / 2,
+            Variant::Classic | Variant::Team => player,
+        }
+    }
+
+    /// The colors a seat plays, the first one names it
+    pub fn colors(&self, seat: usize) -> Vec<usize> {
+        match self {
+            Variant::TwoPlayer => vec![seat, seat + 2],
+            Variant::Junior => vec![seat * 2],
+            Variant::Classic | Variant::Team => vec![seat],
+        }
+    }
+
+    /// The color on the same side, across the table, a color is its own partner in the classic game
+    pub fn partner(&self, player: usize) -> usize {
+        match self {
+            Variant::Classic | Variant::Junior => player,
+            Variant::TwoPlayer | Variant::Team => (player + 2) % NUM_PLAYERS,
+        }
+    }
+
+    /// The other color played by the same person, only the two player game has one
+    pub fn second_color(&self, player: usize) -> usize {
+        match self {
+            Variant::TwoPlayer => self.partner(player),
+            Variant::Classic | Variant::Team | Variant::Junior => player,
+        }
+    }
+
+    /// Add each color's score to its partner's
+    pub fn team_scores(&self, scores: &[i32]) -> Vec<i32> {
+        match self {
+            Variant::Classic | Variant::Junior => scores.to_vec(),
+            Variant::TwoPlayer | Variant::Team => (0..scores.len())
+                .map(|p| scores[p] + scores[self.partner(p)])
+                .collect(),
+        }
+    }
+
+    /// Turn per color win chances into the chance each color's side wins
+    pub fn team_values(&self, values: Vec<f32>) -> Vec<f32> {
+        match self {
+            Variant::Classic | Variant::Junior => values,
+            Variant::TwoPlayer | Variant::Team => (0..values.len())
+                .map(|p| values[p] + values[self.partner(p)])
+                .collect(),
+        }
+    }
+
+    /// Split each side's value evenly between its colors, undoing `team_values`
+    pub fn color_values(&self, values: Vec<f32>) -> Vec<f32> {
+        match self {
+            Variant::Classic | Variant::Junior => values,
+            Variant::TwoPlayer | Variant::Team => values.iter().map(|v| v / 2.0).collect(),
+        }
+    }
+}
+
+/// How one player's score is made up, see `Game::get_score_breakdown`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoreBreakdown {
+    pub placed: i32,        // Squares on the board
+    pub in_hand: i32,       // Squares left in unplayed pieces, a point off each
+    pub all_played: i32,    // 15 for playing every piece
+    pub monomino_last: i32, // 5 more when the last piece was the single square
+    pub total: i32,
+}
+
+/// Tile recorded in the history when a player passes
+pub const PASS: i32 = -1;
+
+/// Tile recorded in the history when the second player swaps sides, see `Game::swap`
+pub const SWAP: i32 = -2;
+
+/// What each plane of `Game::get_board_state` holds, seen from the player to move
+pub const STATE_PLANES: [&str; 5] = ["player", "next", "opposite", "previous", "legal"];
+
+/// Planes of the team-relative network input, see `StateOptions`, as many as `STATE_PLANES`
+/// so the same networks take either. The sides come first, then the player's and partner's squares
+pub const TEAM_STATE_PLANES: [&str; 5] = ["team", "opponents", "player", "partner", "legal"];
+
+/// Pieces in a full set, see `Game::get_piece_state`
+pub const NUM_PIECES: usize = PIECE_TYPES.len();
+
+/// How positions are laid out for the network
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateOptions {
+    pub team_relative: bool, // TEAM_STATE_PLANES instead of STATE_PLANES, for cooperative agents
+    pub pieces: bool,        // A plane per color and piece after the board, set while it is held
+    pub history: usize,      // Planes of the tiles of each of the last N pieces, most recent first
+    pub absolute: bool,      // Board coordinates instead of turned to the player, see `turns`
+}
+
+impl StateOptions {
+    /// What each plane holds, the piece planes are named like "next-piece-20" and the history
+    /// planes "recent-1" for the last piece placed, then "recent-2" and on
+    /// Absolute states end with a constant plane per color, "to-move-r" and on, set for the player
+    /// to move, since nothing else shows whose corner is whose
+    pub fn planes(&self) -> Vec<String> {
+        let board = if self.team_relative {
+            TEAM_STATE_PLANES
+        } else {
+            STATE_PLANES
+        };
+        let mut planes: Vec<String> = board.iter().map(|p| p.to_string()).collect();
+        if self.pieces {
+            for seat in &STATE_PLANES[..NUM_PLAYERS] {
+                planes.extend((0..NUM_PIECES).map(|id| format!("{}-piece-{}", seat, id)));
             }
         }
+        planes.extend((1..=self.history).map(|n| format!("recent-{}", n)));
+        if self.absolute {
+            planes.extend((0..NUM_PLAYERS).map(|p| format!("to-move-{}", color(p))));
+        }
+        planes
+    }
+
+    /// Quarter turns to the left the planes, the legal tiles among them, and the policy get for
+    /// the player to move, see `opening_book::to_perspective` and `from_perspective`
+    /// Planes stay in order from the player to move either way
+    pub fn turns(&self, player: usize) -> usize {
+        if self.absolute {
+            0
+        } else {
+            player
+        }
     }
+}
+
+/// Policies are over the board's tiles row by row, turned to the player to move like the planes
+pub const POLICY_ENCODING: &str = "tiles-rotated";
 
-    new_state
+/// Who was passed over when play moved on, see Game::advance_player
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TurnOutcome {
+    pub skipped: Vec<usize>,    // Players already out of the game
+    pub eliminated: Vec<usize>, // Players found to have no legal moves, now out
+    pub next: Option<usize>,    // The player to move, None once the game is over
 }
 
 #[derive(Clone)]
 pub struct Game {
     pub board: Board,
-    pub history: Vec<(i32, i32)>, // Stack of (player, tile)
+    pub history: Vec<(i32, i32)>, // Stack of (player, tile), the tile is PASS for a pass
     eliminated: [bool; NUM_PLAYERS],
     current_player: usize, // Zero indexed!
     legal_tiles: HashMap<usize, HashSet<(usize, usize, usize)>>, // Map tile to index of the overall move
     last_piece_lens: [u32; NUM_PLAYERS], // Size of the last piece placed by each player
+    turn_tiles: Vec<usize>,              // Tiles placed so far by the current player this turn
+    piece_ends: Vec<usize>,              // Length of the history after each piece was finished
+    variant: Variant,
+    extra_moves: [u32; NUM_PLAYERS], // Pieces each player places right after their next one, see handicap
+    swap_rule: bool,                 // The second player may swap sides after the opening move
+    swapped: bool,
 }
 
 impl Game {
+    /// A classic four player game
     pub fn reset() -> Self {
-        let board = Board::new();
+        Game::new(Variant::Classic)
+    }
+
+    pub fn new(variant: Variant) -> Self {
+        let board = variant.board();
         let legal_tiles = get_tile_moves(&board, 0);
 
-        Game {
+        let game = Game {
             board: board,
             history: Vec::new(),
-            eliminated: [false; NUM_PLAYERS],
+            eliminated: variant.absent(),
             current_player: 0,
             legal_tiles: legal_tiles,
             last_piece_lens: [0; NUM_PLAYERS],
+            turn_tiles: Vec::new(),
+            piece_ends: Vec::new(),
+            variant,
+            extra_moves: [0; NUM_PLAYERS],
+            swap_rule: false,
+            swapped: false,
+        };
+        #[cfg(feature = "check-movegen")]
+        movegen::check(&game);
+        game
+    }
+
+    /// A game at a position set up directly, see position::from_position
+    /// Play moves on past a player to move who is out or has no legal moves
+    pub(crate) fn from_parts(
+        board: Board,
+        variant: Variant,
+        current_player: usize,
+        eliminated: [bool; NUM_PLAYERS],
+        last_piece_lens: [u32; NUM_PLAYERS],
+    ) -> Result<Game, String> {
+        if current_player >= NUM_PLAYERS {
+            return Err(format!("No player {}", current_player));
+        }
+        let mut game = Game {
+            legal_tiles: get_tile_moves(&board, current_player),
+            board,
+            history: Vec::new(),
+            eliminated,
+            current_player,
+            last_piece_lens,
+            turn_tiles: Vec::new(),
+            piece_ends: Vec::new(),
+            variant,
+            extra_moves: [0; NUM_PLAYERS],
+            swap_rule: false,
+            swapped: false,
+        };
+        if game.eliminated[current_player] || game.legal_tiles.is_empty() {
+            game.eliminated[current_player] |= game.legal_tiles.is_empty();
+            game.advance_player();
         }
+        #[cfg(feature = "check-movegen")]
+        movegen::check(&game);
+        Ok(game)
+    }
+
+    /// Let players place more pieces right after their next one before play passes on
+    pub(crate) fn grant_extra_moves(&mut self, extra_moves: [u32; NUM_PLAYERS]) {
+        self.extra_moves = extra_moves;
+    }
+
+    /// Size of the last piece the player placed, 0 before their first
+    pub fn last_piece_len(&self, player: usize) -> u32 {
+        self.last_piece_lens[player]
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Each color's score plus its partner's, the same as `get_score` in the classic game
+    pub fn get_team_scores(&self) -> Vec<i32> {
+        self.variant.team_scores(&self.get_score())
     }
 
     pub fn place_piece(&self, p: usize, v: usize, o: usize) -> Result<Game, String> {
         let mut new_state = self.clone();
+        new_state.play_piece(p, v, o)?;
+        Ok(new_state)
+    }
+
+    /// Place a whole piece on this game rather than a copy, for playing many moves in a row
+    /// A failed move can leave the game part way through it, so only play legal moves.
+    pub fn play_piece(&mut self, p: usize, v: usize, o: usize) -> Result<(), String> {
         let player = self.current_player;
-        let piece = self.get_piece(player, p, v);
+        let piece = match self.board.get_pieces(player).get(p) {
+            Some(piece) if v < piece.variants.len() => piece.variants[v].clone(),
+            _ => return Err(format!("No piece {} variant {}", p, v)),
+        };
 
-        // Check if move is valid
-        if !new_state.board.is_valid_move(player, &piece, o) {
+        // Check if move is valid, part way through a turn it has to cover the tiles placed so far
+        let valid = if self.turn_tiles.is_empty() {
+            self.board.is_valid_move(player, &piece, o)
+        } else {
+            self.get_legal_moves().contains(&(p, v, o))
+        };
+        if !valid {
             return Err("Invalid move".to_string());
         }
 
-        // Break move into tiles and apply individually
-        let offsets = piece.offsets.iter().collect::<Vec<_>>();
+        // Break move into tiles and apply individually, skipping tiles already placed
+        let offsets = piece
+            .offsets
+            .iter()
+            .filter(|offset| !self.turn_tiles.contains(&(o + **offset)))
+            .collect::<Vec<_>>();
+        if offsets.is_empty() {
+            self.finish_piece(p);
+            return Ok(());
+        }
         let last_index = offsets.len().saturating_sub(1);
         for (i, tile_offset) in offsets.iter().enumerate() {
             let tile = o + *tile_offset;
             let result = if i == last_index {
-                new_state.apply(tile, Some(p))
+                self.apply(tile, Some(p))
             } else {
-                new_state.apply(tile, None)
+                self.apply(tile, None)
             };
 
             match result {
@@ -140,7 +510,7 @@ impl Game {
             }
         }
 
-        Ok(new_state)
+        Ok(())
     }
 
     // Plays a tile on the board
@@ -148,13 +518,9 @@ impl Game {
     // Right now it forces you to place as many tiles as is legal or you can pass a piece you
     // want to finish playing. This is really only used by the GUI rn
     pub fn apply(&mut self, tile: usize, piece_to_finish: Option<usize>) -> Result<(), String> {
-        // Place piece on board
-        self.board.place_tile(tile, self.current_player);
-        self.history.push((self.current_player as i32, tile as i32));
-
-        // Update legal tiles
-        let valid_moves = match self.legal_tiles.remove(&tile) {
-            Some(moves) => moves,
+        // Check the tile, and the piece being finished, before changing anything
+        let valid_moves = match self.legal_tiles.get(&tile) {
+            Some(moves) => moves.clone(),
             None => {
                 return Err(format!(
                     "Invalid move - Player {}, Tile {}",
@@ -162,6 +528,24 @@ impl Game {
                 ))
             }
         };
+        if let Some(piece) = piece_to_finish {
+            let mut placed = self.turn_tiles.clone();
+            placed.push(tile);
+            if !valid_moves
+                .iter()
+                .any(|m| m.0 == piece && self.covers(*m, &placed))
+            {
+                return Err(format!("Piece {} is not complete at tile {}", piece, tile));
+            }
+        }
+
+        // Place piece on board
+        self.board.place_tile(tile, self.current_player);
+        self.history.push((self.current_player as i32, tile as i32));
+        self.turn_tiles.push(tile);
+
+        // Update legal tiles
+        self.legal_tiles.remove(&tile);
         for (tile, move_set) in self.legal_tiles.clone() {
             self.legal_tiles.insert(
                 tile,
@@ -179,47 +563,154 @@ impl Game {
                 Some(p) => p,
                 None => valid_moves.iter().next().unwrap().0,
             };
-            self.last_piece_lens[self.current_player] = self
-                .board
-                .get_pieces(self.current_player)
-                .remove(piece)
-                .points;
-            self.board.use_piece(self.current_player, piece);
-
-            // Advance to next player
-            self.advance_player();
+            self.finish_piece(piece);
         }
 
         Ok(())
     }
 
+    /// Whether the move's squares are exactly the tiles
+    fn covers(&self, (piece, variant, offset): Move, tiles: &[usize]) -> bool {
+        let squares = &self.get_piece(self.current_player, piece, variant).offsets;
+        squares.len() == tiles.len() && squares.iter().all(|s| tiles.contains(&(offset + s)))
+    }
+
+    /// Play a two player game under the swap rule, where after the first piece the second
+    /// player may take over red and yellow and leave blue and green to the first player
+    pub fn with_swap_rule(mut self) -> Result<Game, String> {
+        if self.variant != Variant::TwoPlayer {
+            return Err("The swap rule is only for two player games".to_string());
+        }
+        if !self.history.is_empty() {
+            return Err("The swap rule is set before the game starts".to_string());
+        }
+        self.swap_rule = true;
+        Ok(self)
+    }
+
+    pub fn has_swap_rule(&self) -> bool {
+        self.swap_rule
+    }
+
+    /// Whether the second player took the first player's side
+    pub fn is_swapped(&self) -> bool {
+        self.swapped
+    }
+
+    /// The seat playing a color, as `Variant::seat` until the sides are swapped, when the two
+    /// seats trade colors
+    pub fn seat_of(&self, player: usize) -> usize {
+        let seat = self.variant.seat(player);
+        if self.swapped {
+            seat ^ 1
+        } else {
+            seat
+        }
+    }
+
+    /// The colors a seat plays, the other way around from `seat_of`
+    pub fn colors_of(&self, seat: usize) -> Vec<usize> {
+        let seat = if self.swapped { seat ^ 1 } else { seat };
+        self.variant.colors(seat)
+    }
+
+    /// Whether the player to move can swap sides instead of moving, right after the opening piece
+    pub fn can_swap(&self) -> bool {
+        self.swap_rule
+            && !self.swapped
+            && self.current_player == 1
+            && self.turn_tiles.is_empty()
+            && !self.history.is_empty()
+            && self.history.iter().all(|(p, t)| *p == 0 && *t != PASS)
+    }
+
+    /// Swap sides under the swap rule, the colors stay as they are and blue is still to move,
+    /// now played by the person who opened with red
+    pub fn swap(&self) -> Result<Game, String> {
+        if !self.can_swap() {
+            return Err("Sides can only be swapped right after the opening move".to_string());
+        }
+        let mut next = self.clone();
+        next.history.push((self.current_player as i32, SWAP));
+        next.swapped = true;
+        Ok(next)
+    }
+
+    /// Drop out for the rest of the game, as players with no moves left do
+    pub fn pass(&self) -> Result<Game, String> {
+        if self.is_terminal() {
+            return Err("The game is over".to_string());
+        }
+        if !self.turn_tiles.is_empty() {
+            return Err("Finish placing the piece before passing".to_string());
+        }
+        let mut next = self.clone();
+        next.history.push((self.current_player as i32, PASS));
+        next.eliminated[self.current_player] = true;
+        next.advance_player();
+        Ok(next)
+    }
+
+    /// Remove the placed piece from the player's set and move on to the next player
+    fn finish_piece(&mut self, piece: usize) {
+        self.last_piece_lens[self.current_player] = self
+            .board
+            .get_pieces(self.current_player)
+            .remove(piece)
+            .points;
+        self.board.use_piece(self.current_player, piece);
+
+        // Advance to next player, unless this player has another placement and a move for it
+        self.turn_tiles.clear();
+        self.piece_ends.push(self.history.len());
+        let player = self.current_player;
+        if self.extra_moves[player] > 0 {
+            self.extra_moves[player] -= 1;
+            self.legal_tiles = get_tile_moves(&self.board, player);
+            if !self.legal_tiles.is_empty() {
+                #[cfg(feature = "check-movegen")]
+                movegen::check(self);
+                return;
+            }
+        }
+        self.advance_player();
+    }
+
     pub fn get_board(&self) -> &[u8; BOARD_SPACES] {
         &self.board.board
     }
 
     /// Cycle to the next player
     /// Eliminates any players that have no legal moves
-    /// Returns index of the current player
-    pub fn advance_player(&mut self) -> usize {
-        // Return if the game is over
-        if self.is_terminal() {
-            return self.current_player;
-        }
+    /// Every seat is looked at once at most, so a full round of passes ends the game
+    pub fn advance_player(&mut self) -> TurnOutcome {
+        let mut outcome = TurnOutcome::default();
+        let mut passes = 0;
+        while passes < NUM_PLAYERS && !self.is_terminal() {
+            self.current_player = (self.current_player + 1) % NUM_PLAYERS;
+            passes += 1;
 
-        // Cycle to the next player
-        self.current_player = (self.current_player + 1) % NUM_PLAYERS;
-        self.legal_tiles = get_tile_moves(&self.board, self.current_player);
-
-        // If the player is already out of the game, cycle to the next player
-        // If they have no legal moves, eliminate them and advance
-        if self.eliminated[self.current_player] {
-            self.advance_player();
-        } else if self.legal_tiles.len() == 0 {
-            self.eliminated[self.current_player] = true;
-            self.advance_player();
+            // Players already out are passed over, and players with no legal moves drop out
+            if self.eliminated[self.current_player] {
+                outcome.skipped.push(self.current_player);
+                continue;
+            }
+            self.legal_tiles = get_tile_moves(&self.board, self.current_player);
+            if self.legal_tiles.is_empty() {
+                self.eliminated[self.current_player] = true;
+                outcome.eliminated.push(self.current_player);
+                continue;
+            }
+            outcome.next = Some(self.current_player);
+            #[cfg(feature = "check-movegen")]
+            movegen::check(self);
+            break;
         }
 
-        self.current_player
+        if self.is_terminal() {
+            self.legal_tiles.clear();
+        }
+        outcome
     }
 
     pub fn current_player(&self) -> usize {
@@ -231,6 +722,10 @@ impl Game {
         self.board.get_pieces(self.current_player)
     }
 
+    pub fn get_player_pieces(&self, player: usize) -> Vec<Piece> {
+        self.board.get_pieces(player)
+    }
+
     pub fn get_piece(&self, player: usize, piece: usize, variant: usize) -> PieceVariant {
         self.board.get_pieces(player)[piece].variants[variant].clone()
     }
@@ -239,36 +734,170 @@ impl Game {
         self.board.get_anchors(self.current_player)
     }
 
+    /// Check whether the current player has not started placing a piece yet
+    pub fn is_turn_start(&self) -> bool {
+        self.turn_tiles.is_empty()
+    }
+
+    /// Tiles placed so far by the current player this turn, in order
+    pub fn turn_tiles(&self) -> &[usize] {
+        &self.turn_tiles
+    }
+
     pub fn get_legal_tiles(&self) -> Vec<usize> {
         self.legal_tiles.keys().map(|k| *k).collect()
     }
 
+    /// Get the (piece, variant, offset) moves still consistent with the tiles placed this turn
+    pub fn get_legal_moves(&self) -> HashSet<Move> {
+        self.legal_tiles.values().flatten().copied().collect()
+    }
+
+    /// Find the legal move covering exactly the given tiles, in any order
+    pub fn find_move(&self, tiles: &[usize]) -> Option<Move> {
+        let mut tiles = tiles.to_vec();
+        tiles.sort();
+        let player = self.current_player;
+        self.get_legal_moves().into_iter().find(|(p, v, o)| {
+            let mut covered: Vec<usize> = self
+                .get_piece(player, *p, *v)
+                .offsets
+                .iter()
+                .map(|t| o + t)
+                .collect();
+            covered.sort();
+            covered == tiles
+        })
+    }
+
+    /// Count the legal moves a player would have from the current board
+    pub fn count_player_moves(&self, player: usize) -> usize {
+        if self.eliminated[player] {
+            return 0;
+        }
+        get_moves(&self.board, player).0.len()
+    }
+
+    /// Count the legal placements of each piece a player has left, in get_player_pieces order
+    pub fn count_piece_moves(&self, player: usize) -> Vec<usize> {
+        let pieces = self.board.get_pieces(player).len();
+        if self.eliminated[player] {
+            return vec![0; pieces];
+        }
+        (0..pieces)
+            .map(|piece| get_piece_moves(piece, &self.board, player).0.len())
+            .collect()
+    }
+
+    /// Count the legal placements of each piece the player to move has left
+    /// Unlike `count_piece_moves` this respects the tiles already placed this turn
+    pub fn moves_per_piece(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.board.get_pieces(self.current_player).len()];
+        for (piece, _, _) in self.get_legal_moves() {
+            counts[piece] += 1;
+        }
+        counts
+    }
+
     /// Get the scores for the end of the game
     pub fn get_score(&self) -> Vec<i32> {
         self.board.get_scores(self.last_piece_lens)
     }
 
-    /// Player fewest tiles remaining wins, payoff is between 0 and 1
-    pub fn get_payoff(&self) -> Vec<f32> {
-        let scores = self.board.get_scores(self.last_piece_lens);
-        let mut payoff = vec![0.0; 4];
-        let mut indices = Vec::new();
-        let mut highest_score = scores[0];
-        for (i, score) in scores.iter().enumerate() {
-            if *score == highest_score {
-                indices.push(i);
-            } else if *score > highest_score {
-                indices.clear();
-                indices.push(i);
-                highest_score = *score;
+    /// Split each player's score into squares left in hand and bonuses
+    pub fn get_score_breakdown(&self) -> Vec<ScoreBreakdown> {
+        let ownership = self.get_ownership();
+        let scores = self.get_score();
+        (0..NUM_PLAYERS)
+            .map(|player| {
+                let placed = ownership.iter().filter(|o| **o == player as i32).count() as i32;
+                let in_hand = self
+                    .get_player_pieces(player)
+                    .iter()
+                    .map(|p| p.variants[0].offsets.len() as i32)
+                    .sum();
+                let all_played = if in_hand == 0 { 15 } else { 0 };
+                let monomino_last = if in_hand == 0 && self.last_piece_lens[player] == 1 {
+                    5
+                } else {
+                    0
+                };
+                ScoreBreakdown {
+                    placed,
+                    in_hand,
+                    all_played,
+                    monomino_last,
+                    total: scores[player],
+                }
+            })
+            .collect()
+    }
+
+    /// Get the player occupying each square, or -1 if it is empty
+    pub fn get_ownership(&self) -> Vec<i32> {
+        self.board
+            .board
+            .iter()
+            .map(|cell| (*cell & 0b1111) as i32 - 1)
+            .collect()
+    }
+
+    /// Estimate each player's share of every square, indexed by square then player
+    /// Occupied squares belong to their owner. An empty square is split between the active
+    /// players by how many of their legal placements, with the pieces they have left, cover it,
+    /// and a square nobody can reach from an anchor is left at zero for everyone.
+    pub fn influence_map(&self) -> Vec<[f32; NUM_PLAYERS]> {
+        let mut influence = vec![[0.0; NUM_PLAYERS]; BOARD_SPACES];
+        for player in (0..NUM_PLAYERS).filter(|p| !self.eliminated[*p]) {
+            for tiles in get_moves(&self.board, player).1 {
+                for tile in tiles {
+                    influence[tile][player] += 1.0;
+                }
             }
         }
 
-        for i in &indices {
-            payoff[*i] = 1.0 / indices.len() as f32;
+        for (square, owner) in self.get_ownership().into_iter().enumerate() {
+            let shares = &mut influence[square];
+            if owner >= 0 {
+                *shares = [0.0; NUM_PLAYERS];
+                shares[owner as usize] = 1.0;
+                continue;
+            }
+            let total: f32 = shares.iter().sum();
+            if total > 0.0 {
+                shares.iter_mut().for_each(|s| *s /= total);
+            }
         }
+        influence
+    }
 
-        payoff
+    /// Get each player's score minus the best score among their opponents
+    pub fn get_score_margins(&self) -> Vec<i32> {
+        let scores = self.get_score();
+        (0..NUM_PLAYERS)
+            .map(|player| {
+                let best_opponent = (0..NUM_PLAYERS)
+                    .filter(|p| *p != player)
+                    .map(|p| scores[p])
+                    .max()
+                    .unwrap();
+                scores[player] - best_opponent
+            })
+            .collect()
+    }
+
+    /// Colors on the side with the highest score, both partners when a team wins
+    pub fn get_winners(&self) -> Vec<usize> {
+        let scores = self.get_team_scores();
+        let best = *scores.iter().max().unwrap();
+        (0..NUM_PLAYERS).filter(|p| scores[*p] == best).collect()
+    }
+
+    /// Player fewest tiles remaining wins, payoff is between 0 and 1
+    /// In team games both colors of the winning side get the side's payoff
+    pub fn get_payoff(&self) -> Vec<f32> {
+        self.variant
+            .team_values(scores_to_payoff(&self.get_team_scores()))
     }
 
     /// Check if all players have been eliminated
@@ -280,8 +909,14 @@ impl Game {
         !self.eliminated[player]
     }
 
-    pub fn get_board_state(&self) -> [[[bool; D]; D]; 5] {
-        let mut board_state = [[[false; D]; D]; 5];
+    /// Network input with the planes in `STATE_PLANES`, turned to the player to move
+    pub fn get_board_state(&self) -> [[[bool; D]; D]; STATE_PLANES.len()] {
+        self.board_planes(self.current_player)
+    }
+
+    /// The planes in `STATE_PLANES` turned the given number of quarter turns to the left
+    fn board_planes(&self, turns: usize) -> [[[bool; D]; D]; STATE_PLANES.len()] {
+        let mut board_state = [[[false; D]; D]; STATE_PLANES.len()];
         let board = self.board.board;
         for i in 0..BOARD_SPACES {
             let player = (board[i] & 0b1111) as usize; // check if there is a player piece
@@ -303,10 +938,670 @@ impl Game {
         }
 
         // Rotate the board to the current player perspective
-        for _ in 0..self.current_player {
+        for _ in 0..turns {
             board_state = rotate_state(board_state);
         }
 
         board_state
     }
+
+    /// Network input laid out as the options pick, the planes of `StateOptions::planes`
+    pub fn get_state(&self, options: &StateOptions) -> Vec<[[bool; D]; D]> {
+        let turns = options.turns(self.current_player);
+        let planes = self.board_planes(turns);
+        let mut state = if options.team_relative {
+            self.team_relative_state(&planes).to_vec()
+        } else {
+            planes.to_vec()
+        };
+        if options.pieces {
+            for held in self.get_piece_state() {
+                state.extend(held.map(|has| [[has; D]; D]));
+            }
+        }
+        state.extend(self.get_history_state(options.history, turns));
+        if options.absolute {
+            state.extend((0..NUM_PLAYERS).map(|p| [[p == self.current_player; D]; D]));
+        }
+        state
+    }
+
+    /// A plane for each of the last n pieces placed, turned like the board, see `StateOptions`
+    /// Recency shows where fights over shared corners are going on, planes past the start are empty
+    pub fn get_history_state(&self, n: usize, turns: usize) -> Vec<[[bool; D]; D]> {
+        let mut recent = self.get_recent_pieces(n);
+        recent.resize(n, Vec::new());
+        recent
+            .into_iter()
+            .map(|tiles| {
+                let mut plane = [[false; D]; D];
+                for tile in tiles {
+                    plane[tile / D][tile % D] = true;
+                }
+                for _ in 0..turns {
+                    plane = rotate_plane(plane);
+                }
+                plane
+            })
+            .collect()
+    }
+
+    /// Tiles of up to the last n pieces placed by anyone, most recent first
+    /// A piece part way through being placed counts as the most recent
+    pub fn get_recent_pieces(&self, n: usize) -> Vec<Vec<usize>> {
+        let mut ends = self.piece_ends.clone();
+        if !self.turn_tiles.is_empty() {
+            ends.push(self.history.len());
+        }
+        (0..ends.len())
+            .rev()
+            .take(n)
+            .map(|i| {
+                // Passes and swaps before a piece fall in its range, they place no tiles
+                let start = if i == 0 { 0 } else { ends[i - 1] };
+                self.history[start..ends[i]]
+                    .iter()
+                    .filter(|(_, tile)| *tile >= 0)
+                    .map(|(_, tile)| *tile as usize)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Which pieces each color holds by piece id, from the player to move like `STATE_PLANES`
+    /// The board does not show what material is left, which matters a lot late in the game
+    pub fn get_piece_state(&self) -> [[bool; NUM_PIECES]; NUM_PLAYERS] {
+        let mut held = [[false; NUM_PIECES]; NUM_PLAYERS];
+        for (seat, pieces) in held.iter_mut().enumerate() {
+            let player = (self.current_player + seat) % NUM_PLAYERS;
+            for piece in self.board.get_pieces(player) {
+                pieces[piece.id] = true;
+            }
+        }
+        held
+    }
+
+    /// The board planes by side, see `TEAM_STATE_PLANES`
+    fn team_relative_state(
+        &self,
+        planes: &[[[bool; D]; D]; STATE_PLANES.len()],
+    ) -> [[[bool; D]; D]; TEAM_STATE_PLANES.len()] {
+        // Planes are already relative to the player to move, so the partner is at the same offset
+        let player = self.current_player;
+        let partner = (self.variant.partner(player) + NUM_PLAYERS - player) % NUM_PLAYERS;
+        let mut state = [[[false; D]; D]; TEAM_STATE_PLANES.len()];
+        for row in 0..D {
+            for col in 0..D {
+                let own = planes[0][row][col];
+                let ally = planes[partner][row][col];
+                state[0][row][col] = own || ally;
+                state[1][row][col] = (1..NUM_PLAYERS).any(|p| p != partner && planes[p][row][col]);
+                state[2][row][col] = own;
+                state[3][row][col] = ally;
+                state[4][row][col] = planes[4][row][col];
+            }
+        }
+        state
+    }
+
+    /// The network input laid out by `options` packed a bit per square, an eighth of the size
+    /// for sending to the model
+    pub fn get_packed_state(&self, options: &StateOptions) -> Vec<u8> {
+        pack_bits(self.get_state(options).into_iter().flatten().flatten())
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::board::JUNIOR_PIECES;
+    use crate::records;
+
+    /// Play a game from the choices, each picking a legal move or passing
+    fn play_out(choices: &[(usize, bool)], mut check: impl FnMut(&Game)) -> Game {
+        let mut game = Game::reset();
+        for &(choice, pass) in choices {
+            check(&game);
+            if game.is_terminal() {
+                break;
+            }
+            game = if pass {
+                game.pass().unwrap()
+            } else {
+                let mut moves: Vec<Move> = game.get_legal_moves().into_iter().collect();
+                moves.sort();
+                let (piece, variant, offset) = moves[choice % moves.len()];
+                game.place_piece(piece, variant, offset).unwrap()
+            };
+        }
+        check(&game);
+        game
+    }
+
+    fn choices(max_plies: usize) -> impl Strategy<Value = Vec<(usize, bool)>> {
+        prop::collection::vec((any::<usize>(), prop::bool::weighted(0.05)), 0..max_plies)
+    }
+
+    /// Every generated move is valid, and listed under the tiles it covers
+    fn check_generated_moves(choices: &[(usize, bool)]) {
+        play_out(choices, |game| {
+            let player = game.current_player();
+            for (piece, variant, offset) in game.get_legal_moves() {
+                let variant = game.get_piece(player, piece, variant);
+                assert!(game.board.is_valid_move(player, &variant, offset));
+            }
+
+            // Each move is listed under exactly the tiles its piece covers
+            let mut groups: HashMap<Move, HashSet<usize>> = HashMap::new();
+            for (tile, moves) in &game.legal_tiles {
+                for chosen in moves {
+                    groups.entry(*chosen).or_default().insert(*tile);
+                }
+            }
+            for ((piece, variant, offset), tiles) in groups {
+                let squares: HashSet<usize> = game
+                    .get_piece(game.current_player(), piece, variant)
+                    .offsets
+                    .iter()
+                    .map(|square| offset + square)
+                    .collect();
+                assert_eq!(tiles, squares);
+            }
+        });
+    }
+
+    /// Scores add up, and a move leaves the position it was played from untouched
+    fn check_placing(choices: &[(usize, bool)]) {
+        let game = play_out(choices, |game| {
+            for parts in game.get_score_breakdown() {
+                assert!(parts.placed <= 89);
+                assert_eq!(parts.placed + parts.in_hand, 89);
+                assert!(parts.total <= 20);
+            }
+        });
+
+        // The engine has no in-place make/unmake and no position hash: moves build a new
+        // game and leave the old one untouched, and callers undo by replaying the history
+        // without the last move. So this checks the position before a move survives it, and
+        // that the replay gives back exactly that board, anchors, pieces and legal moves.
+        if let Some(&(piece, variant, offset)) = game.get_legal_moves().iter().min() {
+            let before = game.clone();
+            let after = game.place_piece(piece, variant, offset).unwrap();
+            assert_eq!(before.board.board, game.board.board);
+            assert_eq!(before.history, game.history);
+            for player in 0..NUM_PLAYERS {
+                assert_eq!(
+                    before.board.get_anchors(player),
+                    game.board.get_anchors(player)
+                );
+            }
+            let squares = game
+                .get_piece(game.current_player(), piece, variant)
+                .offsets
+                .len();
+            assert_eq!(
+                after.history[..after.history.len() - squares],
+                game.history[..]
+            );
+            let undone = records::from_history(&after.history[..game.history.len()])
+                .and_then(|record| records::replay(&record))
+                .unwrap()
+                .game;
+            assert_eq!(undone.board.board, game.board.board);
+            assert_eq!(undone.current_player(), game.current_player());
+            assert_eq!(undone.get_legal_moves(), game.get_legal_moves());
+            for player in 0..NUM_PLAYERS {
+                assert_eq!(
+                    undone.board.get_anchors(player),
+                    game.board.get_anchors(player)
+                );
+                assert_eq!(
+                    undone.get_player_pieces(player).len(),
+                    game.get_player_pieces(player).len()
+                );
+            }
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_generated_moves_are_valid(choices in choices(16)) {
+            check_generated_moves(&choices);
+        }
+
+        #[test]
+        fn prop_placing_keeps_the_game_consistent(choices in choices(16)) {
+            check_placing(&choices);
+        }
+    }
+
+    // Whole games take minutes even in release, run them with cargo test --release -- --ignored
+    proptest! {
+        #[test]
+        #[ignore]
+        fn prop_generated_moves_are_valid_in_whole_games(choices in choices(100)) {
+            check_generated_moves(&choices);
+        }
+
+        #[test]
+        #[ignore]
+        fn prop_placing_keeps_whole_games_consistent(choices in choices(100)) {
+            check_placing(&choices);
+        }
+    }
+
+    #[test]
+    fn test_auxiliary_targets() {
+        let mut game = Game::reset();
+        game.apply(0, None).unwrap();
+        game.apply(1, None).unwrap();
+        let ownership = game.get_ownership();
+        assert_eq!(ownership.len(), BOARD_SPACES);
+        assert_eq!(&ownership[0..3], &[0, 0, -1]);
+
+        // One tile ahead of everybody else
+        assert_eq!(game.get_score_margins(), vec![2, -2, -2, -2]);
+    }
+
+    #[test]
+    fn test_influence_map() {
+        // Each corner belongs to the player starting there, the middle is out of reach
+        let game = Game::reset();
+        let influence = game.influence_map();
+        assert_eq!(influence.len(), BOARD_SPACES);
+        assert_eq!(influence[0], [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(influence[210], [0.0; NUM_PLAYERS]);
+
+        // Placed squares are owned outright, reachable squares split to one
+        let game = game.place_piece(9, 0, 0).unwrap();
+        let influence = game.influence_map();
+        assert_eq!(influence[0], [1.0, 0.0, 0.0, 0.0]);
+        for shares in influence {
+            let total: f32 = shares.iter().sum();
+            assert!(total == 0.0 || (total - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_score_breakdown() {
+        let game = Game::reset();
+        let opening = *game.get_legal_moves().iter().next().unwrap();
+        let game = game.place_piece(opening.0, opening.1, opening.2).unwrap();
+        let breakdown = game.get_score_breakdown();
+        let placed = breakdown[0].placed;
+        assert!(placed > 0);
+        assert_eq!(breakdown[0].in_hand, 89 - placed);
+        assert_eq!(breakdown[1].in_hand, 89);
+        for (parts, score) in breakdown.iter().zip(game.get_score()) {
+            assert_eq!(parts.total, score);
+            assert_eq!(
+                parts.total,
+                parts.all_played + parts.monomino_last - parts.in_hand
+            );
+        }
+    }
+
+    #[test]
+    fn test_two_player_variant() {
+        let game = Game::new(Variant::TwoPlayer);
+        assert_eq!(Game::reset().variant(), Variant::Classic);
+        assert_eq!(
+            Variant::from_name(game.variant().name()),
+            Ok(Variant::TwoPlayer)
+        );
+        assert_eq!(Variant::TwoPlayer.partner(1), 3);
+        assert_eq!(
+            Variant::TwoPlayer.team_values(vec![0.1, 0.2, 0.3, 0.4]),
+            vec![0.4, 0.6, 0.4, 0.6]
+        );
+
+        // Red's piece wins the game for red and yellow
+        let opening = *game.get_legal_moves().iter().next().unwrap();
+        let mut game = game.place_piece(opening.0, opening.1, opening.2).unwrap();
+        for _ in 0..4 {
+            game = game.pass().unwrap();
+        }
+        assert!(game.is_terminal());
+        assert_eq!(game.get_team_scores()[0], game.get_team_scores()[2]);
+        assert_eq!(game.get_payoff(), vec![1.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_team_variant() {
+        let game = Game::new(Variant::Team);
+        assert_eq!(Variant::from_name(game.variant().name()), Ok(Variant::Team));
+        assert_eq!(Variant::Team.partner(1), 3);
+        assert_eq!(Variant::Team.second_color(1), 1);
+        assert_eq!(Variant::TwoPlayer.second_color(1), 3);
+
+        // Red plays the single square and blue a domino, putting blue and yellow ahead
+        let mut moves: Vec<Move> = game.get_legal_moves().into_iter().collect();
+        moves.sort_by_key(|m| game.get_piece(0, m.0, m.1).offsets.len());
+        let mut game = game
+            .place_piece(moves[0].0, moves[0].1, moves[0].2)
+            .unwrap();
+        let big = *game
+            .get_legal_moves()
+            .iter()
+            .find(|m| game.get_piece(1, m.0, m.1).offsets.len() == 2)
+            .unwrap();
+        game = game.place_piece(big.0, big.1, big.2).unwrap();
+        game = game.pass().unwrap();
+        assert_eq!(game.get_winners(), vec![1, 3]);
+
+        // The team-relative planes merge each side and keep the player and partner apart
+        let team_relative = StateOptions {
+            team_relative: true,
+            ..StateOptions::default()
+        };
+        let state = game.get_state(&team_relative);
+        let count = |plane: usize| state[plane].iter().flatten().filter(|s| **s).count();
+        assert_eq!(game.current_player(), 3);
+        assert_eq!((count(0), count(1), count(2), count(3)), (2, 1, 0, 2));
+        assert_eq!(state[4], game.get_board_state()[4]);
+        assert_eq!(
+            game.get_state(&StateOptions::default()),
+            game.get_board_state().to_vec()
+        );
+
+        // Green passes and red's domino puts red and yellow ahead
+        game = game.pass().unwrap();
+        let domino = *game
+            .get_legal_moves()
+            .iter()
+            .find(|m| game.get_piece(0, m.0, m.1).offsets.len() == 2)
+            .unwrap();
+        game = game.place_piece(domino.0, domino.1, domino.2).unwrap();
+        while !game.is_terminal() {
+            game = game.pass().unwrap();
+        }
+        assert_eq!(game.get_team_scores()[0], game.get_team_scores()[2]);
+        assert_eq!(game.get_winners(), vec![0, 2]);
+        assert_eq!(game.get_payoff(), vec![1.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_junior_variant() {
+        let game = Game::new(Variant::Junior);
+        assert_eq!(
+            Variant::from_name(game.variant().name()),
+            Ok(Variant::Junior)
+        );
+        assert_eq!(Variant::Junior.colors(1), vec![2]);
+        assert_eq!(Variant::Junior.seat(2), 1);
+        assert!(!game.is_player_active(1) && !game.is_player_active(3));
+        assert_eq!(game.get_player_pieces(0).len(), JUNIOR_PIECES);
+
+        // Red and yellow take turns until neither can move, always on the smaller board
+        let mut game = game;
+        let mut players = Vec::new();
+        while !game.is_terminal() {
+            players.push(game.current_player());
+            let mut moves: Vec<Move> = game.get_legal_moves().into_iter().collect();
+            moves.sort();
+            game = match moves.last() {
+                Some(&(piece, variant, offset)) => {
+                    game.place_piece(piece, variant, offset).unwrap()
+                }
+                None => game.pass().unwrap(),
+            };
+        }
+        assert_eq!(&players[..4], &[0, 2, 0, 2]);
+        let ownership = game.get_ownership();
+        assert!((0..BOARD_SPACES).all(|s| ownership[s] < 0 || Variant::Junior.on_board(s)));
+
+        // Pieces left in hand count against the smaller set
+        let scores = game.get_score();
+        for player in [0, 2] {
+            let breakdown = &game.get_score_breakdown()[player];
+            assert_eq!(breakdown.total, scores[player]);
+            if breakdown.in_hand > 0 {
+                assert_eq!(scores[player], -breakdown.in_hand);
+            }
+        }
+        let winners = game.get_winners();
+        assert!(!winners.is_empty());
+        assert!(winners.iter().all(|p| Variant::Junior.has_color(*p)));
+    }
+
+    #[test]
+    fn test_piece_state() {
+        // Red plays the single square, id 0, and blue moves next
+        let game = Game::reset();
+        let single = game.find_move(&[0]).unwrap();
+        let game = game.place_piece(single.0, single.1, single.2).unwrap();
+        let held = game.get_piece_state();
+        assert!(held[..3].iter().all(|pieces| pieces.iter().all(|has| *has)));
+        assert!(!held[3][0] && held[3][1..].iter().all(|has| *has)); // Red is previous to blue
+
+        // The piece planes come after the board planes, in the same order
+        let options = StateOptions {
+            pieces: true,
+            ..StateOptions::default()
+        };
+        let state = game.get_state(&options);
+        let planes = options.planes();
+        assert_eq!(state.len(), planes.len());
+        assert_eq!(state.len(), STATE_PLANES.len() + NUM_PLAYERS * NUM_PIECES);
+        let red_single = planes.iter().position(|p| p == "previous-piece-0").unwrap();
+        assert_eq!(state[red_single], [[false; D]; D]);
+        assert_eq!(state[red_single + 1], [[true; D]; D]);
+        assert_eq!(state[..STATE_PLANES.len()], game.get_board_state());
+    }
+
+    #[test]
+    fn test_history_planes() {
+        // Red, blue and yellow each place a piece, green is to move
+        let mut game = Game::reset();
+        for _ in 0..3 {
+            let m = *game.get_legal_moves().iter().max().unwrap();
+            game = game.place_piece(m.0, m.1, m.2).unwrap();
+        }
+        let recent = game.get_recent_pieces(5);
+        assert_eq!(recent.len(), 3);
+        assert_eq!(recent[0].len(), 5);
+        assert!(recent[0].iter().all(|t| game.board.board[*t] & 0b1111 == 3));
+
+        // The history planes turn with the board, each piece on its owner's plane
+        let options = StateOptions {
+            history: 5,
+            ..StateOptions::default()
+        };
+        let state = game.get_state(&options);
+        assert_eq!(state.len(), options.planes().len());
+        assert_eq!(options.planes()[5], "recent-1");
+        let board = game.get_board_state();
+        let on = |plane: &[[bool; D]; D]| plane.iter().flatten().filter(|s| **s).count();
+        for (n, owner) in [(5, 3), (6, 2), (7, 1)] {
+            let inside = (0..D * D)
+                .filter(|i| state[n][i / D][i % D] && board[owner][i / D][i % D])
+                .count();
+            assert_eq!(inside, on(&state[n]));
+            assert_eq!(on(&state[n]), recent[n - 5].len());
+        }
+        assert_eq!(state[8], [[false; D]; D]);
+        assert_eq!(state[9], [[false; D]; D]);
+    }
+
+    #[test]
+    fn test_absolute_state() {
+        // Blue to move, whose planes are turned once to the left by default
+        let game = Game::reset();
+        let m = *game.get_legal_moves().iter().min().unwrap();
+        let game = game.place_piece(m.0, m.1, m.2).unwrap();
+        let options = StateOptions {
+            absolute: true,
+            history: 1,
+            ..StateOptions::default()
+        };
+        assert_eq!(StateOptions::default().turns(1), 1);
+        assert_eq!(options.turns(1), 0);
+
+        // Planes keep their order but not their turn, and end with who is to move
+        let state = game.get_state(&options);
+        let planes = options.planes();
+        assert_eq!(state.len(), planes.len());
+        let to_move = ["to-move-r", "to-move-b", "to-move-y", "to-move-g"];
+        assert_eq!(planes[planes.len() - 4..], to_move);
+        let rotated = game.get_board_state();
+        let turned: Vec<_> = state[..5].iter().copied().map(rotate_plane).collect();
+        assert_eq!(turned, rotated);
+        assert_eq!(state[5], state[3]); // Red's piece is the last one placed
+        assert_eq!(state[7], [[true; D]; D]);
+        assert_eq!(state[6], [[false; D]; D]);
+
+        // Legal tiles keep the board's coordinates, blue starts in the top right corner
+        assert!(state[4][0][19] && !rotated[4][0][19]);
+    }
+
+    #[test]
+    fn test_swap_rule() {
+        assert!(Game::reset().with_swap_rule().is_err());
+        let game = Game::new(Variant::TwoPlayer).with_swap_rule().unwrap();
+        assert!(game.has_swap_rule() && !game.can_swap());
+
+        // Blue may swap right after red's opening piece, and only then
+        let opening = *game.get_legal_moves().iter().min().unwrap();
+        let game = game.place_piece(opening.0, opening.1, opening.2).unwrap();
+        assert!(game.can_swap());
+        let without_rule = Game::new(Variant::TwoPlayer);
+        assert!(!without_rule
+            .place_piece(opening.0, opening.1, opening.2)
+            .unwrap()
+            .can_swap());
+        let swapped = game.swap().unwrap();
+        assert!(swapped.is_swapped() && !swapped.can_swap());
+        assert_eq!(swapped.current_player(), 1);
+        assert_eq!(swapped.history.last(), Some(&(1, SWAP)));
+        assert_eq!(
+            (swapped.seat_of(0), swapped.seat_of(1), swapped.seat_of(3)),
+            (1, 0, 0)
+        );
+        assert_eq!(swapped.colors_of(0), vec![1, 3]);
+        assert_eq!(game.colors_of(0), vec![0, 2]);
+        assert_eq!(swapped.get_legal_moves(), game.get_legal_moves());
+        assert!(swapped.swap().is_err());
+
+        // The swap is kept in records, and replays from a game with the rule
+        let reply = *swapped.get_legal_moves().iter().min().unwrap();
+        let after = swapped.place_piece(reply.0, reply.1, reply.2).unwrap();
+        assert!(!after.can_swap());
+        let start = Game::new(Variant::TwoPlayer).with_swap_rule().unwrap();
+        let record = records::from_history_at(&start, &after.history).unwrap();
+        assert!(record.swapped);
+        assert_eq!(
+            records::replay_from(start, &record).unwrap().game.history,
+            after.history
+        );
+        assert!(records::replay_from(Game::new(Variant::TwoPlayer), &record).is_err());
+    }
+
+    #[test]
+    fn test_count_piece_moves() {
+        let game = Game::reset();
+        let counts = game.count_piece_moves(0);
+        assert_eq!(counts.len(), 21);
+        assert_eq!(counts.iter().sum::<usize>(), game.count_player_moves(0));
+        assert_eq!(counts[0], 1); // The single square only fits in the corner
+
+        let passed = game.pass().unwrap();
+        assert_eq!(passed.count_piece_moves(0), vec![0; 21]);
+    }
+
+    #[test]
+    fn test_moves_per_piece() {
+        let mut game = Game::reset();
+        assert_eq!(game.moves_per_piece(), game.count_piece_moves(0));
+
+        // Only pieces that can grow past the corner and its neighbor stay playable
+        game.apply(0, None).unwrap();
+        game.apply(1, None).unwrap();
+        let counts = game.moves_per_piece();
+        assert_eq!(counts[0], 0);
+        assert_eq!(counts[1], 0);
+        assert!(counts[3] > 0);
+        assert_eq!(counts.iter().sum::<usize>(), game.get_legal_moves().len());
+    }
+
+    #[test]
+    fn test_packed_state() {
+        let game = Game::reset().place_piece(0, 0, 0).unwrap();
+        let packed = game.get_packed_state(&StateOptions::default());
+        assert_eq!(packed.len(), STATE_PLANES.len() * BOARD_SPACES / 8);
+
+        let state = game.get_board_state();
+        let squares: Vec<bool> = state.into_iter().flatten().flatten().collect();
+        assert_eq!(unpack_bits(&packed, squares.len()), Ok(squares));
+
+        assert_eq!(pack_bits([true, false, true]), vec![0b1010_0000]);
+        assert_eq!(unpack_bits(&[0b1010_0000], 3), Ok(vec![true, false, true]));
+        assert!(unpack_bits(&[0, 0], 3).is_err());
+
+        // Extra planes are packed after the board in the order `planes` names them
+        let options = StateOptions {
+            pieces: true,
+            history: 2,
+            ..StateOptions::default()
+        };
+        let packed = game.get_packed_state(&options);
+        assert_eq!(packed.len(), options.planes().len() * BOARD_SPACES / 8);
+        let state = game.get_state(&options);
+        let squares: Vec<bool> = state.into_iter().flatten().flatten().collect();
+        assert_eq!(unpack_bits(&packed, squares.len()), Ok(squares));
+    }
+
+    #[test]
+    fn test_advance_player() {
+        let mut game = Game::reset();
+        game.eliminated[1] = true;
+        let outcome = game.advance_player();
+        assert_eq!(outcome.skipped, vec![1]);
+        assert_eq!(outcome.next, Some(2));
+        assert_eq!(game.current_player(), 2);
+
+        // Nobody left to move
+        game.eliminated = [true; NUM_PLAYERS];
+        let outcome = game.advance_player();
+        assert_eq!(outcome, TurnOutcome::default());
+        assert!(game.get_legal_tiles().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let game = Game::reset();
+        assert!(game.place_piece(21, 0, 0).is_err());
+        assert!(game.place_piece(0, 8, 0).is_err());
+        assert!(game.place_piece(0, 0, usize::MAX).is_err());
+
+        // Errors leave the game as it was
+        let mut placing = game.clone();
+        assert!(placing.apply(BOARD_SPACES, None).is_err());
+        assert!(placing.apply(0, Some(20)).is_err());
+        assert!(placing.history.is_empty());
+        assert_eq!(placing.board.board, game.board.board);
+        assert!(placing.apply(0, Some(0)).is_ok());
+        assert_eq!(placing.current_player(), 1);
+    }
+
+    #[test]
+    fn test_pass() {
+        let game = Game::reset().pass().unwrap();
+        assert!(!game.is_player_active(0));
+        assert_eq!(game.current_player(), 1);
+        assert_eq!(game.history, vec![(0, PASS)]);
+
+        // Not part-way through a piece
+        let mut placing = game.clone();
+        placing.apply(19, None).unwrap();
+        assert!(placing.pass().is_err());
+
+        let over = game.pass().unwrap().pass().unwrap().pass().unwrap();
+        assert!(over.is_terminal());
+        assert!(over.pass().is_err());
+    }
 }
@@ -0,0 +1,108 @@
+/*
+Handicaps to even out games between players of different strength, set when a game is created
+A stronger player can have pieces taken out of their set, which never reach the board and so
+still count against their score, while a weaker player can be given more time for each move or
+an extra opening placement, a second piece right after their first before play passes on.
+*/
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Game, Variant};
+use crate::position::color;
+
+const NUM_PLAYERS: usize = 4;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Handicap {
+    pub removed: [Vec<usize>; NUM_PLAYERS], // Ids of the pieces taken out of each player's set
+    pub extra_time: [u64; NUM_PLAYERS],     // Seconds added to each player's time per move
+    pub extra_opening: [bool; NUM_PLAYERS], // Players who place two pieces on their first turn
+}
+
+impl Handicap {
+    pub fn is_none(&self) -> bool {
+        *self == Handicap::default()
+    }
+
+    /// Seconds the player has for each move under a time control
+    pub fn move_time(&self, time_control: u64, player: usize) -> u64 {
+        time_control + self.extra_time[player]
+    }
+
+    /// A new game with the pieces taken out and the opening placements granted
+    pub fn start(&self, variant: Variant) -> Result<Game, String> {
+        let mut board = variant.board();
+        for (player, removed) in self.removed.iter().enumerate() {
+            for id in removed {
+                let index = board
+                    .get_pieces(player)
+                    .iter()
+                    .position(|piece| piece.id == *id)
+                    .ok_or(format!("No piece {} in {}'s set", id, color(player)))?;
+                board.use_piece(player, index);
+            }
+        }
+        let mut game = Game::from_parts(board, variant, 0, variant.absent(), [0; NUM_PLAYERS])?;
+        game.grant_extra_moves(self.extra_opening.map(u32::from));
+        Ok(game)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::{from_position, to_position};
+    use crate::records::{from_history_at, replay_from};
+
+    #[test]
+    fn test_handicap() {
+        assert_eq!(
+            Handicap::default()
+                .start(Variant::Classic)
+                .unwrap()
+                .get_legal_moves(),
+            Game::reset().get_legal_moves()
+        );
+
+        // Red plays without the five square pieces, blue opens twice with extra time
+        let mut handicap = Handicap::default();
+        handicap.removed[0] = (9..21).collect();
+        handicap.extra_time[1] = 30;
+        handicap.extra_opening[1] = true;
+        assert_eq!(handicap.move_time(10, 0), 10);
+        assert_eq!(handicap.move_time(10, 1), 40);
+        let start = handicap.start(Variant::Classic).unwrap();
+        assert_eq!(start.get_player_pieces(0).len(), 9);
+        assert_eq!(start.get_player_pieces(1).len(), 21);
+
+        // Blue moves twice in the first round and red never can play a five square piece
+        let mut game = start.clone();
+        let mut players = Vec::new();
+        for _ in 0..6 {
+            let m = *game.get_legal_moves().iter().min().unwrap();
+            players.push(game.current_player());
+            assert!(game
+                .get_player_pieces(0)
+                .iter()
+                .all(|piece| piece.points < 5));
+            game = game.place_piece(m.0, m.1, m.2).unwrap();
+        }
+        assert_eq!(players, vec![0, 1, 1, 2, 3, 0]);
+
+        // The history splits into moves from the handicapped start, and the position reads back
+        let record = from_history_at(&start, &game.history).unwrap();
+        let replayed = replay_from(start.clone(), &record).unwrap().game;
+        assert_eq!(replayed.board.board, game.board.board);
+        assert_eq!(replayed.current_player(), game.current_player());
+        let position = to_position(&game);
+        assert_eq!(to_position(&from_position(&position).unwrap()), position);
+
+        // Removed pieces count against the score like pieces left in hand
+        assert_eq!(start.get_score()[0], -89);
+
+        handicap.removed[2] = vec![0, 0];
+        assert!(handicap.start(Variant::Classic).is_err());
+    }
+}
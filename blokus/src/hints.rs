@@ -0,0 +1,67 @@
+/*
+Ranked move suggestions for hint buttons and tutorials
+Every legal placement is played out one move and the resulting position is
+scored by an evaluator, so the heuristic evaluator gives instant hints and a
+model gives stronger ones. Ties keep the fixed legal move order.
+*/
+
+use serde::{Deserialize, Serialize};
+
+use crate::evaluator::Evaluator;
+use crate::game::Game;
+use crate::state::{legal_moves, LegalMove};
+
+/// A suggested move with how good the position looks after it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hint {
+    #[serde(flatten)]
+    pub placement: LegalMove,
+    pub win_rate: f32, // Evaluator's value for the player to move, after the move
+    pub score: i32,    // Player's game score after the move, minus the squares left in hand
+}
+
+/// The `k` best moves for the player to move, best first
+pub fn hints<E: Evaluator>(game: &Game, evaluator: &mut E, k: usize) -> Result<Vec<Hint>, String> {
+    let player = game.current_player();
+    let mut hints = Vec::new();
+    for placement in legal_moves(game) {
+        let next = game.place_piece(placement.piece, placement.variant, placement.offset)?;
+        let (_, values) = evaluator.evaluate(&next)?;
+        hints.push(Hint {
+            placement,
+            win_rate: values[player],
+            score: next.get_score()[player],
+        });
+    }
+
+    // Stable sort keeps the legal move order between equal hints
+    hints.sort_by(|a, b| {
+        b.win_rate
+            .total_cmp(&a.win_rate)
+            .then(b.score.cmp(&a.score))
+    });
+    hints.truncate(k);
+    Ok(hints)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::HeuristicEvaluator;
+
+    #[test]
+    fn test_hints_are_ranked() {
+        let game = Game::reset();
+        let mut evaluator = HeuristicEvaluator::default();
+        let top = hints(&game, &mut evaluator, 3).unwrap();
+        assert_eq!(top.len(), 3);
+        assert!(top.windows(2).all(|w| w[0].win_rate >= w[1].win_rate));
+
+        // Opening with a five square piece beats smaller ones
+        assert_eq!(top[0].score, -84);
+        let all = hints(&game, &mut evaluator, usize::MAX).unwrap();
+        assert_eq!(all.len(), game.get_legal_moves().len());
+        assert_eq!(all[..3], top[..]);
+    }
+}
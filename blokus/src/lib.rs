@@ -1,3 +1,42 @@
+pub mod agents;
+pub mod bga;
+pub mod blocking;
 pub mod board;
+pub mod cancel;
+pub mod dead;
+pub mod endgame;
+pub mod eval;
+pub mod evaluator;
 pub mod game;
+pub mod handicap;
+pub mod hints;
+#[cfg(feature = "logging")]
+pub mod logging;
+pub mod move_ordering;
+pub mod movegen;
+pub mod node;
+pub mod notation;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod opening_book;
+pub mod payoff;
 pub mod pieces;
+pub mod ponder;
+pub mod position;
+pub mod protocol;
+pub mod puzzles;
+pub mod records;
+pub mod render;
+pub mod replay_buffer;
+pub mod search;
+pub mod shaping;
+pub mod state;
+#[cfg(feature = "storage")]
+pub mod storage;
+pub mod symmetry;
+pub mod tournament;
+#[cfg(feature = "train")]
+pub mod train;
+pub mod tree_export;
+pub mod tutorial;
+pub mod verify;
@@ -0,0 +1,44 @@
+/*
+Where the tracing spans and events of the engine, workers, and server end up
+Searches open a span for each move searched, self-play workers one for each game, and the
+server one for each room, so every line is tagged with where it came from. Lines are
+filtered with RUST_LOG in the usual env-filter syntax, info and up by default, and
+BLOKUS_LOG_FORMAT picks text or JSON, one object per line for collecting the logs of
+many worker processes together.
+*/
+
+use tracing_subscriber::EnvFilter;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl Format {
+    /// The format BLOKUS_LOG_FORMAT asks for, or the given one when it is not set
+    fn from_env(default: Format) -> Format {
+        match std::env::var("BLOKUS_LOG_FORMAT").as_deref() {
+            Ok("json") => Format::Json,
+            Ok("text") => Format::Text,
+            _ => default,
+        }
+    }
+}
+
+/// Send log lines to stderr, only the first call in a process has an effect
+/// Workers can call it at the start of every game without checking.
+pub fn init(default: Format) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    let _ = match Format::from_env(default) {
+        Format::Text => builder.try_init(),
+        Format::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+    };
+}
@@ -0,0 +1,31 @@
+// Command line front-end for the engine
+// Usage: blokus <command> [options], run without a command for help
+use std::env;
+use std::process;
+
+mod cli;
+
+const USAGE: &str = "Usage: blokus <command> [options]
+
+Commands:
+  play    Play a game against the engine in the terminal
+  gtp     Talk a GTP-like text protocol over stdin and stdout
+  render  Draw a recorded game as an SVG or animated GIF";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(|a| a.as_str()) {
+        Some("play") => cli::play::run(&args[1..]),
+        Some("gtp") => cli::gtp::run(&args[1..]),
+        Some("render") => cli::render::run(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,151 @@
+/*
+Move ordering, ranking whole piece placements by simple features of the move itself
+An opening position has hundreds of legal moves and the middle game over a thousand, so a search
+with no network to suggest moves needs some order to try them in. Moves are liked for:
+- size: the squares of the piece, since every square left in hand costs a point
+- anchors: corners the player gains to play from later, net of the ones the piece uses up
+- centrality: closeness to the middle of the board, where most of the fighting happens
+- blocking: opponents' anchors the piece covers
+The blocking agent plays the best move, and the heuristic evaluator can turn the scores into
+tile priors for MCTS, see `HeuristicEvaluator::ordering`.
+*/
+
+use crate::agents::sorted_moves;
+use crate::board::BOARD_SIZE;
+use crate::game::{Game, Move};
+
+const NUM_PLAYERS: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveOrdering {
+    pub size_weight: f32,       // Per square of the piece
+    pub anchor_weight: f32,     // Per anchor gained
+    pub centrality_weight: f32, // Per square closer to the center, on average
+    pub block_weight: f32,      // Per opponent anchor covered
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        MoveOrdering {
+            size_weight: 1.0,
+            anchor_weight: 1.0,
+            centrality_weight: 0.5,
+            block_weight: 2.0,
+        }
+    }
+}
+
+impl MoveOrdering {
+    /// Weighted sum of the move's features, higher is better
+    pub fn score(&self, game: &Game, (piece, variant, offset): Move) -> f32 {
+        let player = game.current_player();
+        let tiles: Vec<usize> = game
+            .get_piece(player, piece, variant)
+            .offsets
+            .iter()
+            .map(|t| offset + t)
+            .collect();
+
+        // Anchors gained, net of the ones the piece uses up
+        let mut board = game.board.clone();
+        for tile in &tiles {
+            board.place_tile(*tile, player);
+        }
+        let anchors =
+            board.get_anchors(player).len() as f32 - game.board.get_anchors(player).len() as f32;
+
+        // Opponent anchors covered by the piece
+        let blocked = (0..NUM_PLAYERS)
+            .filter(|p| *p != player && game.is_player_active(*p))
+            .map(|p| {
+                let anchors = game.board.get_anchors(p);
+                tiles.iter().filter(|t| anchors.contains(t)).count()
+            })
+            .sum::<usize>() as f32;
+
+        // Average Manhattan distance to the center, negated so central is higher
+        let center = (BOARD_SIZE as f32 - 1.0) / 2.0;
+        let distance = tiles
+            .iter()
+            .map(|t| {
+                let (row, col) = ((t / BOARD_SIZE) as f32, (t % BOARD_SIZE) as f32);
+                (row - center).abs() + (col - center).abs()
+            })
+            .sum::<f32>()
+            / tiles.len() as f32;
+
+        self.size_weight * tiles.len() as f32
+            + self.anchor_weight * anchors
+            + self.block_weight * blocked
+            - self.centrality_weight * distance
+    }
+
+    /// Legal moves with their scores, best first and ties in the order of `sorted_moves`
+    pub fn order(&self, game: &Game) -> Vec<(Move, f32)> {
+        let mut scored: Vec<(Move, f32)> = sorted_moves(game)
+            .into_iter()
+            .map(|m| (m, self.score(game, m)))
+            .collect();
+        scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        scored
+    }
+
+    /// Policy over the board's tiles for the player to move, each move gets a share by the
+    /// softmax of its score, spread over the tiles it covers that can be placed this turn
+    /// The temperature is in score points, lower makes the best moves stand out more
+    pub fn tile_priors(&self, game: &Game, temperature: f32) -> Vec<f32> {
+        let mut priors = vec![0.0; BOARD_SIZE * BOARD_SIZE];
+        let order = self.order(game);
+        let Some((_, best)) = order.first() else {
+            return priors;
+        };
+        let legal = game.get_legal_tiles();
+        let player = game.current_player();
+        for ((piece, variant, offset), score) in &order {
+            let weight = ((score - best) / temperature).exp();
+            let tiles = &game.get_piece(player, *piece, *variant).offsets;
+            let placeable: Vec<usize> = tiles
+                .iter()
+                .map(|t| offset + t)
+                .filter(|t| legal.contains(t))
+                .collect();
+            for tile in &placeable {
+                priors[*tile] += weight / placeable.len() as f32;
+            }
+        }
+        let total: f32 = priors.iter().sum();
+        priors.iter().map(|p| p / total).collect()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_ordering() {
+        // Big pieces come first in the opening, each placement scored once
+        let game = Game::reset();
+        let ordering = MoveOrdering::default();
+        let order = ordering.order(&game);
+        assert_eq!(order.len(), game.get_legal_moves().len());
+        assert!(order.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        let ((piece, variant, _), _) = order[0];
+        assert_eq!(game.get_piece(0, piece, variant).offsets.len(), 5);
+
+        // Priors cover exactly the legal tiles and favor the best moves
+        let priors = ordering.tile_priors(&game, 1.0);
+        assert!((priors.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        let legal = game.get_legal_tiles();
+        assert!(legal.iter().all(|t| priors[*t] > 0.0));
+        assert_eq!(priors.iter().filter(|p| **p > 0.0).count(), legal.len());
+        let flat = MoveOrdering {
+            size_weight: 0.0,
+            anchor_weight: 0.0,
+            centrality_weight: 0.0,
+            block_weight: 0.0,
+        };
+        assert!(ordering.tile_priors(&game, 1.0)[0] < flat.tile_priors(&game, 1.0)[0]);
+    }
+}
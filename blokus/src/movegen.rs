@@ -0,0 +1,197 @@
+/*
+Move generators and a cross-check between them
+`reference_moves` is the simple generator the engine started with, lining every square
+of every orientation up with every anchor. Games are played with `bitboard_moves`, which
+keeps each row of the board as a bitmask of the squares the player may not cover and of its
+anchors, and slides each orientation's row masks over them, so a placement is a few shifts
+and ands instead of a walk over its squares with a hash lookup for each.
+With the check-movegen feature the game compares the generator it plays with against the
+reference at every position it reaches, and panics with the first position where they
+disagree, written so position::from_position sets it up again.
+*/
+
+use std::fmt;
+
+use crate::board::{Board, BOARD_SIZE};
+use crate::game::{self, Game, Move};
+use crate::pieces::PieceVariant;
+use crate::position::to_position;
+
+const MAX_PIECE_SIZE: usize = 5; // Rows or columns a piece can span
+
+/// Finds every legal move for a player, sorted
+pub type Generator = fn(&Board, usize) -> Vec<Move>;
+
+/// The generator games are played with
+pub const GENERATOR: Generator = bitboard_moves;
+
+/// Every legal move, found by trying each anchor against each square of each piece
+pub fn reference_moves(board: &Board, player: usize) -> Vec<Move> {
+    let mut moves = game::get_moves(board, player).0;
+    moves.sort();
+    moves.dedup();
+    moves
+}
+
+/// One bitmask per row of the board for a player, bit c for column c
+struct Rows {
+    blocked: [u32; BOARD_SIZE], // Taken squares and those beside the player's own pieces
+    anchors: [u32; BOARD_SIZE],
+}
+
+impl Rows {
+    fn new(board: &Board, player: usize) -> Rows {
+        let restricted: u8 = 1 << (player + 4);
+        let mut rows = Rows {
+            blocked: [0; BOARD_SIZE],
+            anchors: [0; BOARD_SIZE],
+        };
+        for (tile, square) in board.board.iter().enumerate() {
+            if square & restricted != 0 {
+                rows.blocked[tile / BOARD_SIZE] |= 1 << (tile % BOARD_SIZE);
+            }
+        }
+        for anchor in board.anchors(player) {
+            rows.anchors[anchor / BOARD_SIZE] |= 1 << (anchor % BOARD_SIZE);
+        }
+        rows
+    }
+}
+
+/// An orientation's squares as a bitmask per row from its top left corner, and its height
+fn variant_rows(variant: &PieceVariant) -> ([u32; MAX_PIECE_SIZE], usize) {
+    let mut rows = [0; MAX_PIECE_SIZE];
+    let mut height = 0;
+    for square in &variant.offsets {
+        rows[square / BOARD_SIZE] |= 1 << (square % BOARD_SIZE);
+        height = height.max(square / BOARD_SIZE + 1);
+    }
+    (rows, height)
+}
+
+/// Every legal move, sliding each orientation over the rows of the board as bitmasks
+/// Only rows with an anchor under the piece are tried, and moves come out already sorted.
+pub fn bitboard_moves(board: &Board, player: usize) -> Vec<Move> {
+    let rows = Rows::new(board, player);
+    let mut moves = Vec::new();
+    for (p, piece) in board.pieces(player).iter().enumerate() {
+        for (v, variant) in piece.variants.iter().enumerate() {
+            let (shape, height) = variant_rows(variant);
+            let shape = &shape[..height];
+            for top in 0..=BOARD_SIZE - height {
+                let anchors = &rows.anchors[top..top + height];
+                if anchors.iter().all(|row| *row == 0) {
+                    continue;
+                }
+                let blocked = &rows.blocked[top..top + height];
+                for left in 0..=BOARD_SIZE - variant.width {
+                    let on_anchor = shape
+                        .iter()
+                        .zip(anchors)
+                        .any(|(row, anchors)| (row << left) & anchors != 0);
+                    let on_blanks = shape
+                        .iter()
+                        .zip(blocked)
+                        .all(|(row, blocked)| (row << left) & blocked == 0);
+                    if on_anchor && on_blanks {
+                        moves.push((p, v, top * BOARD_SIZE + left));
+                    }
+                }
+            }
+        }
+    }
+    moves
+}
+
+/// Where a generator first disagreed with the reference
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub position: String,
+    pub missing: Vec<Move>, // Legal moves the generator did not find
+    pub extra: Vec<Move>,   // Moves it found that are not legal
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Move generators disagree at {}: missing {:?}, extra {:?}",
+            self.position, self.missing, self.extra
+        )
+    }
+}
+
+/// Compare a generator with the reference for the player to move
+pub fn cross_check(game: &Game, generator: Generator) -> Result<(), Divergence> {
+    if game.is_terminal() {
+        return Ok(());
+    }
+    let player = game.current_player();
+    let expected = reference_moves(&game.board, player);
+    let found = generator(&game.board, player);
+    if found == expected {
+        return Ok(());
+    }
+    Err(Divergence {
+        position: to_position(game),
+        missing: expected
+            .iter()
+            .filter(|m| !found.contains(m))
+            .copied()
+            .collect(),
+        extra: found
+            .iter()
+            .filter(|m| !expected.contains(m))
+            .copied()
+            .collect(),
+    })
+}
+
+/// Stop at the first position the game's generator gets wrong
+#[cfg(feature = "check-movegen")]
+pub(crate) fn check(game: &Game) {
+    if let Err(divergence) = cross_check(game, GENERATOR) {
+        panic!("{}", divergence);
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Variant;
+    use crate::position::from_position;
+
+    fn missing_one(board: &Board, player: usize) -> Vec<Move> {
+        let mut moves = bitboard_moves(board, player);
+        moves.pop();
+        moves
+    }
+
+    #[test]
+    fn test_generators_agree() {
+        // Whole games, with Junior's blocked border and two player's shared sides
+        for variant in Variant::ALL {
+            let mut game = Game::new(variant);
+            while !game.is_terminal() {
+                assert_eq!(cross_check(&game, bitboard_moves), Ok(()));
+                let (piece, variant, offset) = *game.get_legal_moves().iter().max().unwrap();
+                game = game.place_piece(piece, variant, offset).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_divergence() {
+        let game = Game::reset().place_piece(0, 0, 0).unwrap();
+        let divergence = cross_check(&game, missing_one).unwrap_err();
+        assert!(divergence.extra.is_empty());
+        assert_eq!(divergence.missing.len(), 1);
+
+        // The report sets the same position up again
+        let again = from_position(&divergence.position).unwrap();
+        assert_eq!(again.board.board, game.board.board);
+        assert_eq!(again.current_player(), 1);
+        assert!(divergence.to_string().contains(&divergence.position));
+    }
+}
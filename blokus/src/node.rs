@@ -11,6 +11,7 @@ pub struct Node {
     pub value_sum: f32,
     pub visits: u32,
     pub prior: f32,
+    pub held_back: Vec<(usize, f32)>, // Tiles and priors left out by progressive widening, best last
 }
 
 impl Node {
@@ -21,6 +22,7 @@ impl Node {
             value_sum: 0.0,
             visits: 0,
             prior: prior,
+            held_back: Vec::new(),
         }
     }   
 
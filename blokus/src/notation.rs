@@ -0,0 +1,72 @@
+/*
+Text notation for squares and moves
+Squares are a column letter and a row number counted from the top, so the
+top left corner is a1 and the bottom right is t20. A move lists the squares
+its piece covers joined by dashes, like a1-a2-b2.
+*/
+
+use crate::board::BOARD_SIZE as D;
+use crate::game::{Game, Move};
+
+pub fn format_square(tile: usize) -> String {
+    let column = (b'a' + (tile % D) as u8) as char;
+    format!("{}{}", column, tile / D + 1)
+}
+
+pub fn parse_square(text: &str) -> Result<usize, String> {
+    let invalid = || format!("Invalid square: {}", text);
+    let mut chars = text.trim().chars();
+    let column = chars.next().ok_or_else(invalid)?.to_ascii_lowercase();
+    let row: usize = chars.as_str().parse().map_err(|_| invalid())?;
+    if !('a'..='t').contains(&column) || row == 0 || row > D {
+        return Err(invalid());
+    }
+    Ok((row - 1) * D + (column as u8 - b'a') as usize)
+}
+
+/// Format a move for the player to move, squares in board order
+pub fn format_move(game: &Game, (piece, variant, offset): Move) -> String {
+    let mut tiles: Vec<usize> = game
+        .get_piece(game.current_player(), piece, variant)
+        .offsets
+        .iter()
+        .map(|t| offset + t)
+        .collect();
+    tiles.sort();
+    tiles
+        .into_iter()
+        .map(format_square)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Parse a move and check it is legal for the player to move
+pub fn parse_move(game: &Game, text: &str) -> Result<Move, String> {
+    let tiles = text
+        .split('-')
+        .map(parse_square)
+        .collect::<Result<Vec<_>, _>>()?;
+    game.find_move(&tiles)
+        .ok_or(format!("Not a legal move: {}", text))
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(format_square(0), "a1");
+        assert_eq!(format_square(399), "t20");
+        assert_eq!(parse_square("B3").unwrap(), 41);
+        assert!(parse_square("u1").is_err());
+        assert!(parse_square("a21").is_err());
+
+        let game = Game::reset();
+        for m in game.get_legal_moves() {
+            assert_eq!(parse_move(&game, &format_move(&game, m)).unwrap(), m);
+        }
+        assert!(parse_move(&game, "b2").is_err());
+    }
+}
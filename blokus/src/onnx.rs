@@ -0,0 +1,206 @@
+/*
+Evaluator for networks exported with model/export_onnx.py
+Runs on tract, which is pure Rust, so the same model can be searched with in the browser
+*/
+
+use std::io::Cursor;
+use std::sync::Arc;
+
+use tract_onnx::prelude::*;
+
+use crate::board::BOARD_SIZE;
+use crate::evaluator::Evaluator;
+use crate::game::{Game, StateOptions};
+use crate::opening_book::from_perspective;
+use crate::replay_buffer::encode_state;
+
+const NUM_PLAYERS: usize = 4;
+
+/// Runs an exported policy/value network on one position at a time
+/// The network takes `boards` [batch, 5, 20, 20] and gives `policy` [batch, 400] and
+/// `value` [batch, 4], both from the perspective of the player to move.
+/// Clones share the loaded network.
+#[derive(Clone)]
+pub struct OnnxEvaluator {
+    model: Arc<TypedRunnableModel<TypedModel>>,
+}
+
+impl OnnxEvaluator {
+    /// Load a model from the bytes of an .onnx file, for when there is no file system
+    pub fn from_bytes(bytes: &[u8]) -> Result<OnnxEvaluator, String> {
+        let model = tract_onnx::onnx()
+            .model_for_read(&mut Cursor::new(bytes))
+            .map_err(|e| format!("Failed to read ONNX model: {}", e))?;
+        OnnxEvaluator::from_model(model)
+    }
+
+    pub fn load(path: &str) -> Result<OnnxEvaluator, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        OnnxEvaluator::from_bytes(&bytes)
+    }
+
+    /// Fix the batch size to one and optimize the graph for it
+    fn from_model(model: InferenceModel) -> Result<OnnxEvaluator, String> {
+        let model = model
+            .with_input_fact(0, f32::fact([1, 5, BOARD_SIZE, BOARD_SIZE]).into())
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|e| format!("Failed to prepare ONNX model: {}", e))?;
+        Ok(OnnxEvaluator {
+            model: Arc::new(model),
+        })
+    }
+}
+
+impl Evaluator for OnnxEvaluator {
+    fn evaluate(&mut self, game: &Game) -> Result<(Vec<f32>, Vec<f32>), String> {
+        let input: Tensor = tract_ndarray::Array4::from_shape_vec(
+            (1, 5, BOARD_SIZE, BOARD_SIZE),
+            encode_state(game, &StateOptions::default()),
+        )
+        .map_err(|e| e.to_string())?
+        .into();
+        let outputs = self
+            .model
+            .run(tvec!(input.into()))
+            .map_err(|e| format!("Failed to run ONNX model: {}", e))?;
+        if outputs.len() < 2 {
+            return Err("ONNX model should output a policy and a value".to_string());
+        }
+        let policy = outputs[0].as_slice::<f32>().map_err(|e| e.to_string())?;
+        let mut value = outputs[1]
+            .as_slice::<f32>()
+            .map_err(|e| e.to_string())?
+            .to_vec();
+        if policy.len() != BOARD_SIZE * BOARD_SIZE || value.len() != NUM_PLAYERS {
+            return Err(format!(
+                "Unexpected ONNX output sizes: policy {}, value {}",
+                policy.len(),
+                value.len()
+            ));
+        }
+
+        // Rotate back from the player's perspective to board order
+        let player = game.current_player();
+        let mut board_policy = vec![0.0; policy.len()];
+        for (tile, prob) in policy.iter().enumerate() {
+            board_policy[from_perspective(tile, player)] = *prob;
+        }
+        value.rotate_right(player);
+        Ok((board_policy, value))
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tract_onnx::pb;
+    use tract_onnx::pb::tensor_shape_proto::{dimension, Dimension};
+
+    fn value_info(name: &str, dims: &[i64]) -> pb::ValueInfoProto {
+        let dim = dims
+            .iter()
+            .map(|d| Dimension {
+                value: Some(dimension::Value::DimValue(*d)),
+                ..Default::default()
+            })
+            .collect();
+        pb::ValueInfoProto {
+            name: name.to_string(),
+            r#type: Some(pb::TypeProto {
+                value: Some(pb::type_proto::Value::TensorType(pb::type_proto::Tensor {
+                    elem_type: pb::tensor_proto::DataType::Float as i32,
+                    shape: Some(pb::TensorShapeProto { dim }),
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn int_tensor(name: &str, values: &[i64]) -> pb::TensorProto {
+        pb::TensorProto {
+            name: name.to_string(),
+            dims: vec![values.len() as i64],
+            data_type: pb::tensor_proto::DataType::Int64 as i32,
+            int64_data: values.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    /// Stand-in network whose policy is the legal tile plane and whose value is fixed
+    fn stub_model() -> InferenceModel {
+        let node = |op: &str, inputs: &[&str], output: &str, attribute| pb::NodeProto {
+            op_type: op.to_string(),
+            input: inputs.iter().map(|s| s.to_string()).collect(),
+            output: vec![output.to_string()],
+            attribute,
+            ..Default::default()
+        };
+        let fixed_value = pb::AttributeProto {
+            name: "value".to_string(),
+            r#type: pb::attribute_proto::AttributeType::Tensor as i32,
+            t: Some(pb::TensorProto {
+                dims: vec![1, 4],
+                data_type: pb::tensor_proto::DataType::Float as i32,
+                float_data: vec![0.4, 0.3, 0.2, 0.1],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let graph = pb::GraphProto {
+            node: vec![
+                node("Flatten", &["boards"], "flat", vec![]),
+                node(
+                    "Slice",
+                    &["flat", "starts", "ends", "axes"],
+                    "policy",
+                    vec![],
+                ),
+                node("Constant", &[], "value", vec![fixed_value]),
+            ],
+            initializer: vec![
+                int_tensor("starts", &[1600]),
+                int_tensor("ends", &[2000]),
+                int_tensor("axes", &[1]),
+            ],
+            input: vec![value_info("boards", &[1, 5, 20, 20])],
+            output: vec![
+                value_info("policy", &[1, 400]),
+                value_info("value", &[1, 4]),
+            ],
+            ..Default::default()
+        };
+        let proto = pb::ModelProto {
+            ir_version: 8,
+            opset_import: vec![pb::OperatorSetIdProto {
+                domain: String::new(),
+                version: 13,
+            }],
+            graph: Some(graph),
+            ..Default::default()
+        };
+        tract_onnx::onnx().model_for_proto_model(&proto).unwrap()
+    }
+
+    #[test]
+    fn test_onnx_evaluator_orientation() {
+        let mut evaluator = OnnxEvaluator::from_model(stub_model()).unwrap();
+        let game = Game::reset().place_piece(0, 0, 0).unwrap();
+        assert_eq!(game.current_player(), 1);
+
+        // The legal plane comes back in board order and the first value is the mover's
+        let (policy, values) = evaluator.evaluate(&game).unwrap();
+        let legal_tiles = game.get_legal_tiles();
+        for (tile, prob) in policy.iter().enumerate() {
+            assert_eq!(*prob > 0.0, legal_tiles.contains(&tile), "tile {}", tile);
+        }
+        assert_eq!(values, vec![0.1, 0.4, 0.3, 0.2]);
+    }
+
+    #[test]
+    fn test_onnx_evaluator_rejects_garbage() {
+        assert!(OnnxEvaluator::from_bytes(b"not a model").is_err());
+    }
+}
@@ -0,0 +1,319 @@
+/*
+Opening book built from self-play statistics
+*/
+
+use std::collections::HashMap;
+use std::fs;
+
+use crate::board::BOARD_SIZE as D;
+use crate::game::{Game, PASS, SWAP};
+
+/// Statistics gathered for one canonical position
+#[derive(Clone, Debug, Default, PartialEq)]
+struct BookEntry {
+    games: u32,
+    visits: HashMap<usize, f32>, // Canonical tile -> accumulated visit fraction
+}
+
+/// Aggregates the search policies of early positions across self-play games
+/// Positions are stored from the perspective of the player to move, so the
+/// four seats share statistics, and are further folded along the main
+/// diagonal, which is the only reflection that keeps that player's corner fixed.
+/// Folding treats the two neighbouring opponents as interchangeable, which is
+/// a reasonable approximation while the board is still mostly empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpeningBook {
+    max_ply: usize,
+    min_games: u32,
+    positions: HashMap<String, BookEntry>,
+}
+
+/// Rotate an absolute tile into the current player's perspective
+/// Matches the rotation applied by `Game::get_board_state`
+pub fn to_perspective(tile: usize, player: usize) -> usize {
+    let (mut row, mut col) = (tile / D, tile % D);
+    for _ in 0..player {
+        (row, col) = (D - 1 - col, row);
+    }
+    row * D + col
+}
+
+/// Rotate a tile from the current player's perspective back to the board
+pub fn from_perspective(tile: usize, player: usize) -> usize {
+    let (mut row, mut col) = (tile / D, tile % D);
+    for _ in 0..player {
+        (row, col) = (col, D - 1 - row);
+    }
+    row * D + col
+}
+
+fn transpose(tile: usize) -> usize {
+    (tile % D) * D + tile / D
+}
+
+/// Get the canonical key for a position and whether it had to be transposed
+fn canonical_key(game: &Game) -> (String, bool) {
+    let state = game.get_board_state();
+
+    // Reflecting across the diagonal swaps the players on either side
+    let mut reflected = state;
+    for (plane, source) in [0, 3, 2, 1, 4].iter().enumerate() {
+        for row in 0..D {
+            for col in 0..D {
+                reflected[plane][row][col] = state[*source][col][row];
+            }
+        }
+    }
+
+    let encode = |planes: &[[[bool; D]; D]; 5]| -> String {
+        let bits: Vec<bool> = planes.iter().flatten().flatten().copied().collect();
+        bits.chunks(4)
+            .map(|nibble| {
+                let value = nibble.iter().fold(0, |acc, bit| (acc << 1) | *bit as u32);
+                char::from_digit(value, 16).unwrap()
+            })
+            .collect()
+    };
+
+    let key = encode(&state);
+    let reflected_key = encode(&reflected);
+    if reflected_key < key {
+        (reflected_key, true)
+    } else {
+        (key, false)
+    }
+}
+
+impl OpeningBook {
+    /// Create an empty book covering the first `max_ply` tile placements
+    /// Book moves are only played for positions seen in at least `min_games` games
+    pub fn new(max_ply: usize, min_games: u32) -> OpeningBook {
+        OpeningBook {
+            max_ply,
+            min_games,
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Add the early positions of a self-play game to the book
+    /// Takes the history and search policies in the format returned by self-play
+    /// Passes are replayed and swaps skipped, neither adds anything to the book
+    pub fn add_game(
+        &mut self,
+        history: &[(i32, i32)],
+        policies: &[Vec<(i32, f32)>],
+    ) -> Result<(), String> {
+        let mut game = Game::reset();
+        for (ply, ((_, tile), policy)) in history.iter().zip(policies).enumerate() {
+            // Games started from random positions have no policies for the random moves
+            if ply >= self.max_ply {
+                break;
+            }
+            match *tile {
+                PASS => {
+                    game = game.pass()?;
+                    continue;
+                }
+                SWAP => continue, // The board and the color to move stay as they are
+                _ => {}
+            }
+            if policy.is_empty() {
+                break;
+            }
+
+            let player = game.current_player();
+            let (key, transposed) = canonical_key(&game);
+            let entry = self.positions.entry(key).or_default();
+            entry.games += 1;
+            for (action, prob) in policy {
+                let mut action = to_perspective(*action as usize, player);
+                if transposed {
+                    action = transpose(action);
+                }
+                *entry.visits.entry(action).or_insert(0.0) += prob;
+            }
+
+            game.apply(*tile as usize, None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the book's policy for a position, with tiles in board coordinates
+    pub fn get_policy(&self, game: &Game) -> Option<Vec<(usize, f32)>> {
+        let (key, transposed) = canonical_key(game);
+        let entry = self.positions.get(&key)?;
+        if entry.games < self.min_games {
+            return None;
+        }
+
+        let player = game.current_player();
+        let total: f32 = entry.visits.values().sum();
+        let policy = entry
+            .visits
+            .iter()
+            .map(|(action, visits)| {
+                let action = if transposed {
+                    transpose(*action)
+                } else {
+                    *action
+                };
+                (from_perspective(action, player), visits / total)
+            })
+            .collect();
+        Some(policy)
+    }
+
+    /// Get the most visited book move for a position, if there is one
+    pub fn get_move(&self, game: &Game) -> Option<usize> {
+        self.get_policy(game)?
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map(|(tile, _)| tile)
+    }
+
+    /// Write the book as text: a header line, then one line per position
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut output = format!("{} {}\n", self.max_ply, self.min_games);
+        let mut keys: Vec<&String> = self.positions.keys().collect();
+        keys.sort();
+        for key in keys {
+            let entry = &self.positions[key];
+            let mut visits: Vec<(&usize, &f32)> = entry.visits.iter().collect();
+            visits.sort_by_key(|(tile, _)| **tile);
+            output.push_str(&format!("{} {}", key, entry.games));
+            for (tile, weight) in visits {
+                output.push_str(&format!(" {}:{}", tile, weight));
+            }
+            output.push('\n');
+        }
+
+        fs::write(path, output).map_err(|e| format!("Failed to write opening book: {}", e))
+    }
+
+    pub fn load(path: &str) -> Result<OpeningBook, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read opening book: {}", e))?;
+        let invalid = |line: &str| format!("Invalid opening book line: {}", line);
+
+        let mut lines = contents.lines();
+        let header = lines.next().ok_or("Empty opening book")?;
+        let mut fields = header.split_whitespace();
+        let max_ply = fields
+            .next()
+            .and_then(|f| f.parse().ok())
+            .ok_or_else(|| invalid(header))?;
+        let min_games = fields
+            .next()
+            .and_then(|f| f.parse().ok())
+            .ok_or_else(|| invalid(header))?;
+
+        let mut book = OpeningBook::new(max_ply, min_games);
+        for line in lines {
+            let mut fields = line.split_whitespace();
+            let key = fields.next().ok_or_else(|| invalid(line))?;
+            let games = fields
+                .next()
+                .and_then(|f| f.parse().ok())
+                .ok_or_else(|| invalid(line))?;
+
+            let mut visits = HashMap::new();
+            for field in fields {
+                let (tile, weight) = field.split_once(':').ok_or_else(|| invalid(line))?;
+                let tile = tile.parse().map_err(|_| invalid(line))?;
+                let weight = weight.parse().map_err(|_| invalid(line))?;
+                visits.insert(tile, weight);
+            }
+            book.positions
+                .insert(key.to_string(), BookEntry { games, visits });
+        }
+
+        Ok(book)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perspective_round_trip() {
+        for player in 0..4 {
+            for tile in [0, 19, 21, 380, 399] {
+                assert_eq!(from_perspective(to_perspective(tile, player), player), tile);
+            }
+        }
+
+        // Every player's starting corner is the top left from their perspective
+        assert_eq!(to_perspective(19, 1), 0);
+        assert_eq!(to_perspective(399, 2), 0);
+        assert_eq!(to_perspective(380, 3), 0);
+    }
+
+    #[test]
+    fn test_reflected_positions_share_entry() {
+        let mut horizontal = Game::reset();
+        horizontal.apply(0, None).unwrap();
+        horizontal.apply(1, None).unwrap();
+        let mut vertical = Game::reset();
+        vertical.apply(0, None).unwrap();
+        vertical.apply(20, None).unwrap();
+
+        let (horizontal_key, horizontal_transposed) = canonical_key(&horizontal);
+        let (vertical_key, vertical_transposed) = canonical_key(&vertical);
+        assert_eq!(horizontal_key, vertical_key);
+        assert_ne!(horizontal_transposed, vertical_transposed);
+
+        // Statistics from one orientation are mapped onto the other
+        let mut book = OpeningBook::new(10, 1);
+        let policies = [vec![(0, 1.0)], vec![(1, 1.0)], vec![(2, 1.0)]];
+        book.add_game(&[(0, 0), (0, 1), (0, 2)], &policies).unwrap();
+        assert_eq!(book.get_move(&horizontal), Some(2));
+        assert_eq!(book.get_move(&vertical), Some(40));
+    }
+
+    #[test]
+    fn test_min_games() {
+        let mut book = OpeningBook::new(10, 2);
+        book.add_game(&[(0, 0)], &[vec![(0, 1.0)]]).unwrap();
+        assert_eq!(book.get_move(&Game::reset()), None);
+        book.add_game(&[(0, 0)], &[vec![(0, 1.0)]]).unwrap();
+        assert_eq!(book.get_move(&Game::reset()), Some(0));
+    }
+
+    #[test]
+    fn test_book_move_is_legal() {
+        let mut book = OpeningBook::new(10, 1);
+        book.add_game(&[(0, 0), (0, 1)], &[vec![(0, 1.0)], vec![(1, 1.0)]])
+            .unwrap();
+
+        let game = Game::reset();
+        assert_eq!(book.get_move(&game), Some(0));
+        let mut game = game.clone();
+        game.apply(0, None).unwrap();
+        let tile = book.get_move(&game).unwrap();
+        assert!(game.get_legal_tiles().contains(&tile));
+    }
+
+    #[test]
+    fn test_game_with_pass() {
+        let mut book = OpeningBook::new(10, 1);
+        let history = [(0, PASS), (1, 19)];
+        let policies = [Vec::new(), vec![(19, 1.0)]];
+        book.add_game(&history, &policies).unwrap();
+
+        // Blue's position is reached with red out of the game, not with red on tile 4294967295
+        let game = Game::reset().pass().unwrap();
+        assert_eq!(book.len(), 1);
+        assert_eq!(book.get_move(&game), Some(19));
+    }
+}
@@ -0,0 +1,149 @@
+/*
+Payoffs, the value each color gets for how a game ended, used as search and training targets
+The reward definition shapes the style a network learns, so a training run can pick one:
+- winner: the side with the highest score gets 1, split evenly among tied sides
+- rank: 1, 0.66, 0.33, 0 by finishing place among the sides, tied sides share their places
+- margin: the score margin over the best opponent, scaled from 0 for the biggest possible loss
+  to 1 for the biggest possible win, so a close game is worth about 0.5 either way
+- targets:s0,s1,s2,s3: 1 for each color whose own score reaches its target, 0 otherwise
+Like `Game::get_payoff`, both colors of a side get the side's value in team games.
+*/
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::game::{scores_to_payoff, Game, Variant};
+
+const NUM_PLAYERS: usize = 4;
+const MAX_MARGIN: f32 = 109.0; // Best score, every piece played and both bonuses, minus the worst
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Payoff {
+    #[default]
+    Winner,
+    Rank,
+    Margin,
+    Targets([i32; NUM_PLAYERS]),
+}
+
+impl Payoff {
+    /// Read a payoff like "rank" or "targets:0,-10,-10,-10"
+    pub fn from_spec(spec: &str) -> Result<Payoff, String> {
+        match spec.split_once(':') {
+            None => match spec {
+                "winner" => Ok(Payoff::Winner),
+                "rank" => Ok(Payoff::Rank),
+                "margin" => Ok(Payoff::Margin),
+                _ => Err(format!("Unknown payoff: {}", spec)),
+            },
+            Some(("targets", targets)) => {
+                let targets: Vec<i32> = targets
+                    .split(',')
+                    .map(|t| t.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| format!("Invalid score targets: {}", targets))?;
+                let targets = targets
+                    .try_into()
+                    .map_err(|_| "Give one score target per color".to_string())?;
+                Ok(Payoff::Targets(targets))
+            }
+            Some(_) => Err(format!("Unknown payoff: {}", spec)),
+        }
+    }
+
+    /// Value of each color for a finished game
+    pub fn of(&self, game: &Game) -> Vec<f32> {
+        self.for_scores(game.variant(), &game.get_score())
+    }
+
+    /// Value of each color for final scores, which may come from a solved endgame
+    pub fn for_scores(&self, variant: Variant, scores: &[i32]) -> Vec<f32> {
+        let sides = variant.team_scores(scores);
+        // Opponents of a color are every other color not on its side
+        let opponents =
+            |p: usize| (0..NUM_PLAYERS).filter(move |q| *q != p && *q != variant.partner(p));
+        match self {
+            Payoff::Winner => variant.team_values(scores_to_payoff(&sides)),
+            Payoff::Rank => (0..NUM_PLAYERS)
+                .map(|p| {
+                    let beaten: f32 = opponents(p)
+                        .map(|q| match sides[p].cmp(&sides[q]) {
+                            Ordering::Greater => 1.0,
+                            Ordering::Equal => 0.5,
+                            Ordering::Less => 0.0,
+                        })
+                        .sum();
+                    beaten / opponents(p).count() as f32
+                })
+                .collect(),
+            Payoff::Margin => (0..NUM_PLAYERS)
+                .map(|p| {
+                    let best = opponents(p).map(|q| sides[q]).max().unwrap();
+                    let colors = if variant.partner(p) == p { 1.0 } else { 2.0 };
+                    let margin = (sides[p] - best) as f32 / (MAX_MARGIN * colors);
+                    (0.5 + margin / 2.0).clamp(0.0, 1.0)
+                })
+                .collect(),
+            Payoff::Targets(targets) => (0..NUM_PLAYERS)
+                .map(|p| if scores[p] >= targets[p] { 1.0 } else { 0.0 })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Payoff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Payoff::Winner => write!(f, "winner"),
+            Payoff::Rank => write!(f, "rank"),
+            Payoff::Margin => write!(f, "margin"),
+            Payoff::Targets(targets) => {
+                let targets: Vec<String> = targets.iter().map(|t| t.to_string()).collect();
+                write!(f, "targets:{}", targets.join(","))
+            }
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payoffs() {
+        let scores = [-10, -30, -10, -89];
+        let classic = |payoff: Payoff| payoff.for_scores(Variant::Classic, &scores);
+        assert_eq!(classic(Payoff::Winner), vec![0.5, 0.0, 0.5, 0.0]);
+        assert_eq!(
+            classic(Payoff::Rank),
+            vec![2.5 / 3.0, 1.0 / 3.0, 2.5 / 3.0, 0.0]
+        );
+        let margin = classic(Payoff::Margin);
+        assert_eq!(margin[0], 0.5);
+        assert!(margin[1] < 0.5 && margin[3] < margin[1] && margin[3] > 0.0);
+        assert_eq!(
+            classic(Payoff::Targets([-10, -20, 0, -89])),
+            vec![1.0, 0.0, 0.0, 1.0]
+        );
+
+        // In team games the sides are ranked and both partners share the value
+        let team = |payoff: Payoff| payoff.for_scores(Variant::TwoPlayer, &scores);
+        assert_eq!(team(Payoff::Winner), vec![1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(team(Payoff::Rank), vec![1.0, 0.0, 1.0, 0.0]);
+        let margin = team(Payoff::Margin);
+        assert_eq!(margin[0], margin[2]);
+        assert!((margin[0] + margin[1] - 1.0).abs() < 1e-6);
+
+        // The default matches the game's own payoff
+        let game = Game::reset();
+        assert_eq!(Payoff::default().of(&game), game.get_payoff());
+
+        for spec in ["winner", "rank", "margin", "targets:0,-10,-10,-5"] {
+            assert_eq!(Payoff::from_spec(spec).unwrap().to_string(), spec);
+        }
+        assert!(Payoff::from_spec("targets:0,0").is_err());
+        assert!(Payoff::from_spec("targets:a,b,c,d").is_err());
+        assert!(Payoff::from_spec("score").is_err());
+    }
+}
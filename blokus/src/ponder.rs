@@ -0,0 +1,107 @@
+/*
+Searching on the opponents' time
+*/
+
+use std::thread::{self, JoinHandle};
+
+use crate::cancel::CancellationToken;
+use crate::evaluator::Evaluator;
+use crate::game::Game;
+use crate::node::Node;
+use crate::search::{self, SearchConfig};
+
+/// A background search of the position left after our move
+/// The tree covers the opponents' likely replies, so once they have moved the
+/// matching subtree can seed our next search instead of starting from scratch.
+pub struct Ponder<E: Evaluator + Send + 'static> {
+    game: Game,
+    cancel: CancellationToken,
+    handle: JoinHandle<(Result<Node, String>, E)>,
+}
+
+impl<E: Evaluator + Send + 'static> Ponder<E> {
+    /// Start searching the given position until `finish` is called
+    pub fn start(game: Game, config: SearchConfig, mut evaluator: E) -> Ponder<E> {
+        let cancel = CancellationToken::new();
+        let handle = {
+            let game = game.clone();
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                let mut root = Node::new(0.0);
+                let result =
+                    search::extend_search(&mut root, &game, &config, &mut evaluator, None, &cancel)
+                        .map(|_| root);
+                (result, evaluator)
+            })
+        };
+
+        Ponder {
+            game,
+            cancel,
+            handle,
+        }
+    }
+
+    /// Stop pondering and keep the part of the tree matching the actual position
+    /// Gives back the evaluator along with the subtree, which is None when the
+    /// opponents played something the search never explored.
+    pub fn finish(self, actual: &Game) -> (Option<Node>, E) {
+        self.cancel.cancel();
+        let (result, evaluator) = self.handle.join().expect("Ponder thread panicked");
+
+        let pondered = &self.game.history;
+        let reusable = actual.history.len() >= pondered.len()
+            && actual.history[..pondered.len()] == pondered[..]
+            && actual.history[pondered.len()..]
+                .iter()
+                .all(|(_, tile)| *tile >= 0); // Passes and swaps are not in the tree
+        let root = match result {
+            Ok(root) if reusable => {
+                let actions: Vec<usize> = actual.history[pondered.len()..]
+                    .iter()
+                    .map(|(_, tile)| *tile as usize)
+                    .collect();
+                search::subtree(root, &actions)
+            }
+            _ => None,
+        };
+
+        (root, evaluator)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::HeuristicEvaluator;
+    use std::time::Duration;
+
+    #[test]
+    fn test_reuse_explored_reply() {
+        let game = Game::reset().place_piece(0, 0, 0).unwrap();
+        let config = SearchConfig::default();
+        let ponder = Ponder::start(game.clone(), config.clone(), HeuristicEvaluator::default());
+        thread::sleep(Duration::from_millis(100));
+        let (root, _) = ponder.finish(&game);
+        assert!(root.unwrap().visits > 0);
+
+        // The opponent's first tile was a child of the pondered root
+        let ponder = Ponder::start(game.clone(), config, HeuristicEvaluator::default());
+        thread::sleep(Duration::from_millis(100));
+        let mut actual = game.clone();
+        let tile = actual.get_legal_tiles()[0];
+        actual.apply(tile, None).unwrap();
+        let (root, _) = ponder.finish(&actual);
+        assert!(root.is_some());
+    }
+
+    #[test]
+    fn test_discard_unrelated_position() {
+        let game = Game::reset().place_piece(0, 0, 0).unwrap();
+        let ponder = Ponder::start(game, SearchConfig::default(), HeuristicEvaluator::default());
+        let other = Game::reset().place_piece(1, 0, 0).unwrap();
+        let (root, _) = ponder.finish(&other);
+        assert!(root.is_none());
+    }
+}
@@ -0,0 +1,395 @@
+/*
+One line of text for a position between turns, like FEN in chess
+`<rows> <to move> <pieces> <out> <variant>`, so the opening is
+`20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20 r 1fffff,1fffff,1fffff,1fffff - classic`
+- rows: the board's rows from the top split by '/', with r, b, y, and g for each player's
+  squares and a number for each run of empty squares
+- to move: the color of the player to move
+- pieces: each player's pieces left as a hex mask of piece ids, with a '*' once the single
+  square was the last piece they played
+- out: the colors of the players out of the game, or '-'
+- variant: classic, two-player, team, or junior
+Moves made to reach the position are not kept, so a game set up from one has no history.
+Editor sets up a position by hand instead, for composing puzzles or reproducing a reported game.
+*/
+
+use crate::board::{Board, BOARD_SIZE as D};
+use crate::game::{Game, Variant};
+use crate::pieces::PIECE_TYPES;
+
+const COLORS: [char; 4] = ['r', 'b', 'y', 'g'];
+/// Squares in each player's full set of pieces
+pub const TOTAL_TILES: u32 = 89;
+const ALL_PIECES: u32 = (1 << PIECE_TYPES.len()) - 1;
+
+pub(crate) fn color(player: usize) -> char {
+    COLORS[player]
+}
+
+pub(crate) fn parse_color(c: char) -> Result<usize, String> {
+    COLORS
+        .iter()
+        .position(|color| *color == c)
+        .ok_or_else(|| format!("Unknown color: {}", c))
+}
+
+/// Write the position, any piece part way through being placed is left out
+pub fn to_position(game: &Game) -> String {
+    let board = game.get_board();
+    let rows: Vec<String> = board
+        .chunks(D)
+        .map(|row| {
+            let mut text = String::new();
+            let mut empty = 0;
+            for cell in row {
+                match (*cell & 0b1111) as usize {
+                    0 => empty += 1,
+                    owner => {
+                        if empty > 0 {
+                            text.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        text.push(color(owner - 1));
+                    }
+                }
+            }
+            if empty > 0 {
+                text.push_str(&empty.to_string());
+            }
+            text
+        })
+        .collect();
+
+    let pieces: Vec<String> = (0..4)
+        .map(|player| {
+            let mask: u32 = game
+                .get_player_pieces(player)
+                .iter()
+                .map(|piece| 1 << piece.id)
+                .sum();
+            let last = if game.last_piece_len(player) == 1 {
+                "*"
+            } else {
+                ""
+            };
+            format!("{:x}{}", mask, last)
+        })
+        .collect();
+
+    let out: String = (0..4)
+        .filter(|p| !game.is_player_active(*p))
+        .map(color)
+        .collect();
+    format!(
+        "{} {} {} {} {}",
+        rows.join("/"),
+        color(game.current_player()),
+        pieces.join(","),
+        if out.is_empty() { "-" } else { &out },
+        game.variant().name()
+    )
+}
+
+/// Set up a game at a position, checking each player's squares match the pieces they have left
+pub fn from_position(text: &str) -> Result<Game, String> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    let [rows, to_move, pieces, out, variant] = fields[..] else {
+        return Err(format!("A position has five fields: {}", text));
+    };
+    let mut editor = Editor::new(Variant::from_name(variant)?);
+
+    let rows: Vec<&str> = rows.split('/').collect();
+    if rows.len() != D {
+        return Err(format!("A position has {} rows, not {}", D, rows.len()));
+    }
+    for (r, row) in rows.iter().enumerate() {
+        let mut col = 0;
+        let mut digits = String::new();
+        for c in row.chars().chain(std::iter::once('/')) {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                continue;
+            }
+            if !digits.is_empty() {
+                col += digits
+                    .parse::<usize>()
+                    .map_err(|_| format!("Row {} is too long", r + 1))?;
+                digits.clear();
+            }
+            if c == '/' {
+                break;
+            }
+            if col >= D {
+                return Err(format!("Row {} is too long", r + 1));
+            }
+            editor.set_square(r * D + col, Some(parse_color(c)?));
+            col += 1;
+        }
+        if col != D {
+            return Err(format!("Row {} has {} squares, not {}", r + 1, col, D));
+        }
+    }
+
+    let masks: Vec<&str> = pieces.split(',').collect();
+    if masks.len() != 4 {
+        return Err(format!("Four piece sets are needed, not {}", masks.len()));
+    }
+    for (player, mask) in masks.iter().enumerate() {
+        let (mask, monomino_last) = match mask.strip_suffix('*') {
+            Some(mask) => (mask, true),
+            None => (*mask, false),
+        };
+        editor.pieces[player] = u32::from_str_radix(mask, 16)
+            .ok()
+            .filter(|mask| *mask <= ALL_PIECES)
+            .ok_or_else(|| format!("Invalid pieces for {}: {}", color(player), mask))?;
+        editor.monomino_last[player] = monomino_last;
+    }
+
+    if out != "-" {
+        for c in out.chars() {
+            editor.out[parse_color(c)?] = true;
+        }
+    }
+    editor.to_move = match to_move.chars().collect::<Vec<_>>()[..] {
+        [c] => parse_color(c)?,
+        _ => return Err(format!("Unknown color: {}", to_move)),
+    };
+    editor.to_game()
+}
+
+/// A position being set up by hand, square by square and piece by piece
+/// Any squares and pieces can be given, to_game checks they make a position that can be played
+#[derive(Clone, Debug, PartialEq)]
+pub struct Editor {
+    pub squares: [Option<usize>; D * D], // The player on each square
+    pub pieces: [u32; 4],                // Each player's pieces left as a mask of piece ids
+    pub monomino_last: [bool; 4],        // Whether the single square was the last piece played
+    pub out: [bool; 4],
+    pub to_move: usize,
+    pub variant: Variant,
+}
+
+impl Editor {
+    /// An empty board with every piece of the variant's set in hand
+    pub fn new(variant: Variant) -> Editor {
+        let board = variant.board();
+        Editor {
+            squares: [None; D * D],
+            pieces: std::array::from_fn(|player| {
+                board.get_pieces(player).iter().map(|p| 1 << p.id).sum()
+            }),
+            monomino_last: [false; 4],
+            out: variant.absent(),
+            to_move: 0,
+            variant,
+        }
+    }
+
+    /// Start from a game's position, any piece part way through being placed is left out
+    pub fn from_game(game: &Game) -> Editor {
+        let mut editor = Editor::new(game.variant());
+        for (square, cell) in game.get_board().iter().enumerate() {
+            editor.squares[square] = match (*cell & 0b1111) as usize {
+                0 => None,
+                owner => Some(owner - 1),
+            };
+        }
+        for player in 0..4 {
+            editor.pieces[player] = game
+                .get_player_pieces(player)
+                .iter()
+                .map(|piece| 1 << piece.id)
+                .sum();
+            editor.monomino_last[player] = game.last_piece_len(player) == 1;
+            editor.out[player] = !game.is_player_active(player);
+        }
+        editor.to_move = game.current_player();
+        editor
+    }
+
+    /// Give a square to a player, or clear it with None
+    pub fn set_square(&mut self, square: usize, player: Option<usize>) {
+        self.squares[square] = player;
+    }
+
+    /// Take a piece out of a player's hand, or put it back
+    pub fn toggle_piece(&mut self, player: usize, piece: usize) {
+        self.pieces[player] ^= 1 << piece;
+    }
+
+    /// Squares the player has on the board
+    pub fn placed(&self, player: usize) -> u32 {
+        self.squares.iter().filter(|s| **s == Some(player)).count() as u32
+    }
+
+    /// Squares in the pieces the player has left
+    pub fn in_hand(&self, player: usize) -> u32 {
+        Board::new()
+            .get_pieces(player)
+            .iter()
+            .filter(|piece| self.pieces[player] & 1 << piece.id != 0)
+            .map(|piece| piece.points)
+            .sum()
+    }
+
+    /// The game at this position, checking each player's squares match the pieces they have left
+    pub fn to_game(&self) -> Result<Game, String> {
+        // Squares go down in any order, the anchors come out the same
+        let mut board = self.variant.board();
+        for (square, player) in self.squares.iter().enumerate() {
+            if let Some(player) = *player {
+                if player >= 4 {
+                    return Err(format!("No player {}", player));
+                }
+                if !self.variant.on_board(square) {
+                    return Err(format!(
+                        "Square {} is off the {} board",
+                        square,
+                        self.variant.name()
+                    ));
+                }
+                board.place_tile(square, player);
+            }
+        }
+
+        for player in 0..4 {
+            if self.pieces[player] > ALL_PIECES {
+                return Err(format!(
+                    "Invalid pieces for {}: {:x}",
+                    color(player),
+                    self.pieces[player]
+                ));
+            }
+            for (i, piece) in board.get_pieces(player).iter().enumerate().rev() {
+                if self.pieces[player] & 1 << piece.id == 0 {
+                    board.use_piece(player, i);
+                }
+            }
+            // Fewer when pieces were taken out of the set as a handicap
+            let (placed, in_hand) = (self.placed(player), self.in_hand(player));
+            if placed + in_hand > TOTAL_TILES {
+                return Err(format!(
+                    "{} has {} squares on the board but {} in hand",
+                    color(player),
+                    placed,
+                    in_hand
+                ));
+            }
+        }
+
+        // Only a single square played last counts for scoring
+        let last_piece_lens = self.monomino_last.map(u32::from);
+        Game::from_parts(board, self.variant, self.to_move, self.out, last_piece_lens)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    use crate::movegen::{cross_check, GENERATOR};
+
+    const OPENING: &str = "20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20 r 1fffff,1fffff,1fffff,1fffff - classic";
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(to_position(&Game::reset()), OPENING);
+
+        for variant in [Variant::TwoPlayer, Variant::Junior] {
+            let mut game = Game::new(variant);
+            for _ in 0..6 {
+                let (piece, variant, offset) = *game.get_legal_moves().iter().min().unwrap();
+                game = game.place_piece(piece, variant, offset).unwrap();
+            }
+            game = game.pass().unwrap();
+            let text = to_position(&game);
+            let again = from_position(&text).unwrap();
+            assert_eq!(to_position(&again), text);
+            assert_eq!(again.board.board, game.board.board);
+            assert_eq!(again.get_legal_moves(), game.get_legal_moves());
+            assert_eq!(again.get_score(), game.get_score());
+            for player in 0..4 {
+                assert_eq!(
+                    again.board.get_anchors(player),
+                    game.board.get_anchors(player)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_known_positions() {
+        let known = include_str!("../testdata/positions.txt");
+        let lines = known
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in lines {
+            let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+            let [position, to_move, moves, scores, terminal] = fields[..] else {
+                panic!("A known position has five fields: {}", line);
+            };
+            let game = from_position(position).unwrap();
+            let moves: usize = moves.parse().unwrap();
+            let scores: Vec<i32> = scores.split(',').map(|s| s.parse().unwrap()).collect();
+            let terminal: bool = terminal.parse().unwrap();
+
+            assert_eq!(
+                color(game.current_player()).to_string(),
+                to_move,
+                "{}",
+                line
+            );
+            assert_eq!(game.get_legal_moves().len(), moves, "{}", line);
+            assert_eq!(game.get_score(), scores, "{}", line);
+            assert_eq!(game.is_terminal(), terminal, "{}", line);
+            assert_eq!(cross_check(&game, GENERATOR), Ok(()), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_editor() {
+        let mut editor = Editor::new(Variant::Classic);
+        assert_eq!(to_position(&editor.to_game().unwrap()), OPENING);
+
+        // Red's single square in the corner, then blue to move
+        editor.set_square(0, Some(0));
+        assert!(editor.to_game().is_err());
+        editor.toggle_piece(0, 0);
+        editor.monomino_last[0] = true;
+        editor.to_move = 1;
+        let game = editor.to_game().unwrap();
+        assert_eq!(game.current_player(), 1);
+        assert_eq!(game.board.get_anchors(0), HashSet::from([D + 1]));
+        assert_eq!(game.get_score()[0], -88);
+        assert_eq!(Editor::from_game(&game), editor);
+
+        // Taking the square back off leaves the piece out of the set, as in a handicap
+        editor.set_square(0, None);
+        assert_eq!(editor.placed(0), 0);
+        assert_eq!(editor.in_hand(0), 88);
+        assert_eq!(editor.to_game().unwrap().get_score()[0], -89);
+        editor.toggle_piece(0, 0);
+        assert!(editor.to_game().is_ok());
+
+        let text = "20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/19g r 1fffff,1fffff,1fffff,1ffffe - classic";
+        let game = from_position(text).unwrap();
+        assert_eq!(
+            to_position(&Editor::from_game(&game).to_game().unwrap()),
+            text
+        );
+    }
+
+    #[test]
+    fn test_invalid_positions() {
+        assert!(from_position("").is_err());
+        assert!(from_position(&OPENING.replace(" r ", " x ")).is_err());
+        assert!(from_position(&OPENING.replacen("20", "r19", 1)).is_err()); // A square with no piece for it
+        assert!(from_position(&OPENING.replacen("20", "21", 1)).is_err());
+        assert!(from_position(&OPENING.replacen("20", "99999999999999999999999", 1)).is_err());
+        assert!(from_position(&OPENING.replace("classic", "duo")).is_err());
+    }
+}
@@ -0,0 +1,411 @@
+/*
+Line based engine protocol modelled on GTP, so GUIs and match schedulers can drive the engine
+Each command is a line like `[id] name [args]`, and each response is `=[id] result` on success
+or `?[id] message` on failure, followed by a blank line. Colors are red, blue, yellow, and green
+(or their first letters) and moves use the square notation from `notation`, with `pass` for a
+player that has no moves left.
+
+`analyze <color> [interval]` searches the position until the next command arrives, printing a
+line of candidate tiles every interval (in centiseconds, default 100) like lz-analyze does.
+Each candidate is `info move <square> visits <n> winrate <0-10000> prior <0-10000> pv <squares>`.
+*/
+
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::agents::Agent;
+use crate::board::BOARD_SIZE;
+use crate::cancel::CancellationToken;
+use crate::evaluator::HeuristicEvaluator;
+use crate::game::Game;
+use crate::node::Node;
+use crate::notation::{format_move, format_square, parse_move};
+use crate::search::{self, SearchAnalysis, SearchConfig};
+
+const COLORS: [&str; 4] = ["red", "blue", "yellow", "green"];
+
+const COMMANDS: [&str; 14] = [
+    "protocol_version",
+    "name",
+    "version",
+    "known_command",
+    "list_commands",
+    "boardsize",
+    "clear_board",
+    "play",
+    "genmove",
+    "undo",
+    "showboard",
+    "final_score",
+    "analyze",
+    "quit",
+];
+
+fn parse_color(text: &str) -> Result<usize, String> {
+    let text = text.to_lowercase();
+    COLORS
+        .iter()
+        .position(|c| *c == text || (text.len() == 1 && c.starts_with(&text)))
+        .ok_or(format!("invalid color: {}", text))
+}
+
+/// Plain text board with one letter per player, for terminals without color
+fn board_text(game: &Game) -> String {
+    let mut output = String::from("   ");
+    for col in 0..BOARD_SIZE {
+        output.push_str(&format!(" {}", (b'a' + col as u8) as char));
+    }
+    for row in 0..BOARD_SIZE {
+        output.push_str(&format!("\n{:>2} ", row + 1));
+        for col in 0..BOARD_SIZE {
+            let square = match game.board.board[row * BOARD_SIZE + col] & 0b0000_1111 {
+                1 => 'R',
+                2 => 'B',
+                3 => 'Y',
+                4 => 'G',
+                _ => '.',
+            };
+            output.push_str(&format!(" {}", square));
+        }
+    }
+    output
+}
+
+/// One line of candidates, most visited first
+fn analysis_line(analysis: &SearchAnalysis) -> String {
+    analysis
+        .children
+        .iter()
+        .filter(|child| child.visits > 0)
+        .enumerate()
+        .map(|(i, child)| {
+            // Only the best candidate's line is known past its first tile
+            let pv: Vec<String> = if i == 0 {
+                analysis
+                    .principal_variation
+                    .iter()
+                    .map(|tile| format_square(*tile))
+                    .collect()
+            } else {
+                vec![format_square(child.tile)]
+            };
+            format!(
+                "info move {} visits {} winrate {} prior {} pv {}",
+                format_square(child.tile),
+                child.visits,
+                (child.value * 10000.0).round() as i32,
+                (child.prior * 10000.0).round() as i32,
+                pv.join(" ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Split a line into its optional numeric id and words
+/// Blank lines and # comments give None
+fn parse_line(line: &str) -> Option<(String, Vec<&str>)> {
+    let line = line.split('#').next().unwrap_or("").trim();
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    if words.is_empty() {
+        return None;
+    }
+    let id = match words[0].parse::<u32>() {
+        Ok(id) => {
+            words.remove(0);
+            id.to_string()
+        }
+        Err(_) => String::new(),
+    };
+    Some((id, words))
+}
+
+fn write_text(output: &mut impl Write, text: &str) -> Result<(), String> {
+    output
+        .write_all(text.as_bytes())
+        .and_then(|_| output.flush())
+        .map_err(|e| e.to_string())
+}
+
+/// Engine state for one protocol session
+pub struct Engine {
+    game: Game,
+    previous: Vec<Game>, // Positions before each move, for undo
+    agent: Box<dyn Agent>,
+    analyzer: HeuristicEvaluator, // Evaluates positions for analyze, whatever the agent is
+    quit: bool,
+}
+
+impl Engine {
+    pub fn new(agent: Box<dyn Agent>) -> Engine {
+        Engine {
+            game: Game::reset(),
+            previous: Vec::new(),
+            agent,
+            analyzer: HeuristicEvaluator::default(),
+            quit: false,
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Check the command is for the player to move, or is a pass by a player who is out
+    fn check_turn(&self, color: usize) -> Result<bool, String> {
+        if self.game.is_terminal() || !self.game.is_player_active(color) {
+            return Ok(false);
+        }
+        if color != self.game.current_player() {
+            return Err(format!(
+                "it is {}'s turn",
+                COLORS[self.game.current_player()]
+            ));
+        }
+        Ok(true)
+    }
+
+    fn play(&mut self, color: usize, text: &str) -> Result<String, String> {
+        if !self.check_turn(color)? {
+            return match text {
+                "pass" => Ok(String::new()),
+                _ => Err(format!("{} has no moves left", COLORS[color])),
+            };
+        }
+        if text == "pass" {
+            return Err(format!("{} still has legal moves", COLORS[color]));
+        }
+
+        let (piece, variant, offset) = parse_move(&self.game, text)?;
+        let next = self.game.place_piece(piece, variant, offset)?;
+        self.previous.push(std::mem::replace(&mut self.game, next));
+        Ok(String::new())
+    }
+
+    fn genmove(&mut self, color: usize) -> Result<String, String> {
+        if !self.check_turn(color)? {
+            return Ok("pass".to_string());
+        }
+
+        let chosen = self.agent.choose_move(&self.game)?;
+        let text = format_move(&self.game, chosen);
+        let next = self.game.place_piece(chosen.0, chosen.1, chosen.2)?;
+        self.previous.push(std::mem::replace(&mut self.game, next));
+        Ok(text)
+    }
+
+    fn final_score(&self) -> String {
+        self.game
+            .get_score()
+            .iter()
+            .enumerate()
+            .map(|(player, score)| format!("{} {}", COLORS[player], score))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Run one command, returning the response text or an error message
+    pub fn execute(&mut self, command: &str, args: &[&str]) -> Result<String, String> {
+        let color = || parse_color(args.first().ok_or("missing color")?);
+        match command {
+            "protocol_version" => Ok("2".to_string()),
+            "name" => Ok("blokus-engine".to_string()),
+            "version" => Ok(env!("CARGO_PKG_VERSION").to_string()),
+            "known_command" => Ok(args
+                .first()
+                .is_some_and(|c| COMMANDS.contains(c))
+                .to_string()),
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "boardsize" => match args.first().map(|s| s.parse()) {
+                Some(Ok(BOARD_SIZE)) => Ok(String::new()),
+                _ => Err("unacceptable size".to_string()),
+            },
+            "clear_board" => {
+                self.game = Game::reset();
+                self.previous.clear();
+                Ok(String::new())
+            }
+            "play" => {
+                let text = args.get(1).ok_or("missing move")?;
+                self.play(color()?, text)
+            }
+            "genmove" => self.genmove(color()?),
+            "undo" => match self.previous.pop() {
+                Some(game) => {
+                    self.game = game;
+                    Ok(String::new())
+                }
+                None => Err("cannot undo".to_string()),
+            },
+            "showboard" => Ok(format!("\n{}", board_text(&self.game))),
+            "final_score" => Ok(self.final_score()),
+            "quit" => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            _ => Err("unknown command".to_string()),
+        }
+    }
+
+    /// Handle one input line, returning the formatted response
+    /// Blank lines and # comments get no response
+    pub fn respond(&mut self, line: &str) -> Option<String> {
+        let (id, words) = parse_line(line)?;
+        let command = words.first().copied().unwrap_or("");
+        let response = match self.execute(command, words.get(1..).unwrap_or(&[])) {
+            Ok(text) if text.is_empty() => format!("={}", id),
+            Ok(text) => format!("={} {}", id, text),
+            Err(e) => format!("?{} {}", id, e),
+        };
+        Some(format!("{}\n\n", response))
+    }
+
+    /// Search the current position, writing candidates every interval until a line arrives
+    /// Returns the line that ended the analysis, or None at the end of the input
+    fn analyze(
+        &mut self,
+        id: &str,
+        args: &[&str],
+        lines: &Receiver<String>,
+        output: &mut impl Write,
+    ) -> Result<Option<String>, String> {
+        let started = parse_color(args.first().copied().unwrap_or(""))
+            .and_then(|color| self.check_turn(color));
+        let interval = match args.get(1).map(|s| s.parse::<u64>()) {
+            None => Ok(100),
+            Some(Ok(interval)) if interval > 0 => Ok(interval),
+            Some(_) => Err("invalid interval".to_string()),
+        };
+        let interval = match (started, interval) {
+            (Ok(true), Ok(interval)) => Duration::from_millis(interval * 10),
+            (Ok(false), _) => return Err("no moves to analyze".to_string()),
+            (Err(e), _) | (_, Err(e)) => return Err(e),
+        };
+        write_text(output, &format!("={}\n", id))?;
+
+        let config = SearchConfig {
+            exploration_fraction: 0.0,
+            ..SearchConfig::default()
+        };
+        let cancel = CancellationToken::new();
+        let mut root = Node::new(0.0);
+        loop {
+            let deadline = Some(Instant::now() + interval);
+            search::extend_search(
+                &mut root,
+                &self.game,
+                &config,
+                &mut self.analyzer,
+                deadline,
+                &cancel,
+            )?;
+            // Keep searching until a candidate has been visited, an empty line would end the response
+            let line = analysis_line(&SearchAnalysis::from_root(&root));
+            if line.is_empty() {
+                continue;
+            }
+            write_text(output, &format!("{}\n", line))?;
+
+            match lines.try_recv() {
+                Ok(line) => {
+                    write_text(output, "\n")?;
+                    return Ok(Some(line));
+                }
+                Err(TryRecvError::Disconnected) => {
+                    write_text(output, "\n")?;
+                    return Ok(None);
+                }
+                Err(TryRecvError::Empty) => (),
+            }
+        }
+    }
+
+    /// Answer commands until `quit` or the end of the input
+    /// Input is read on its own thread so a new command can interrupt `analyze`
+    pub fn run(
+        &mut self,
+        input: impl BufRead + Send + 'static,
+        mut output: impl Write,
+    ) -> Result<(), String> {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in input.lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut pending = None;
+        while !self.quit {
+            let line = match pending.take().or_else(|| lines.recv().ok()) {
+                Some(line) => line,
+                None => break,
+            };
+            let response = match parse_line(&line) {
+                Some((id, words)) if words[0] == "analyze" => {
+                    match self.analyze(&id, &words[1..], &lines, &mut output) {
+                        Ok(Some(next)) => {
+                            pending = Some(next);
+                            continue;
+                        }
+                        Ok(None) => break,
+                        Err(e) => Some(format!("?{} {}\n\n", id, e)),
+                    }
+                }
+                _ => self.respond(&line),
+            };
+            if let Some(response) = response {
+                write_text(&mut output, &response)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::RandomAgent;
+
+    #[test]
+    fn test_session() {
+        let mut engine = Engine::new(Box::new(RandomAgent::seeded(0)));
+        let input = "1 boardsize 20\nplay red a1-a2\n# comment\n\ngenmove b\nplay red t1\n\
+                     undo\nboardsize 14\nquit\nname\n";
+        let mut output = Vec::new();
+        engine.run(input.as_bytes(), &mut output).unwrap();
+        let responses: Vec<&str> = std::str::from_utf8(&output)
+            .unwrap()
+            .split("\n\n")
+            .collect();
+
+        assert_eq!(&responses[..2], ["=1", "="]);
+        assert!(responses[2].starts_with("= ") && responses[2].contains("t1"));
+        assert_eq!(responses[3], "? it is yellow's turn");
+        assert_eq!(&responses[4..7], ["=", "? unacceptable size", "="]);
+        assert_eq!(responses[7], ""); // Nothing after quit
+        assert_eq!(engine.game().history, vec![(0, 0), (0, 20)]);
+    }
+
+    #[test]
+    fn test_analyze_until_next_command() {
+        let mut engine = Engine::new(Box::new(RandomAgent::seeded(0)));
+        let mut output = Vec::new();
+        let input = "analyze blue\n2 analyze red 1\nname\n";
+        engine.run(input.as_bytes(), &mut output).unwrap();
+        let responses: Vec<&str> = std::str::from_utf8(&output)
+            .unwrap()
+            .split("\n\n")
+            .collect();
+
+        assert_eq!(responses[0], "? it is red's turn");
+        let mut lines = responses[1].lines();
+        assert_eq!(lines.next(), Some("=2"));
+        assert!(lines.next().unwrap().starts_with("info move "));
+        assert_eq!(responses[2], "= blokus-engine");
+    }
+}
@@ -0,0 +1,297 @@
+/*
+Puzzles: a position and an objective for the player to move, one per line of a puzzle file
+`<position> | <objective> | <title>`, with the position in the format of blokus::position,
+blank lines and lines starting with '#' skipped, and the title optional. Objectives are
+- play <move> [<move> ...]: play one of the listed moves, in square notation like a1-a2-b2
+- anchors <n>: keep at least n anchors that a piece can still be played from after the move
+- block <color>: leave the color with no legal moves
+Puzzles are mined from played games by looking for blunders, moves an evaluator rates well
+below the best one where the best one is also clearly ahead of the rest, see `mine`.
+*/
+
+use std::collections::HashSet;
+use std::fs;
+
+use crate::evaluator::Evaluator;
+use crate::game::{get_moves, Game, Move};
+use crate::hints::hints;
+use crate::notation::{format_square, parse_move};
+use crate::position::{color, from_position, parse_color, to_position};
+use crate::records::GameRecord;
+
+/// What the player to move has to do
+#[derive(Clone, Debug, PartialEq)]
+pub enum Objective {
+    Play(Vec<Vec<usize>>), // Squares of each move that solves it, in board order
+    Anchors(usize),
+    Block(usize), // The player to leave without a move
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Puzzle {
+    pub position: String,
+    pub objective: Objective,
+    pub title: String,
+}
+
+/// Anchors of the player that some legal placement covers
+pub fn live_anchors(game: &Game, player: usize) -> usize {
+    let (_, tiles) = get_moves(&game.board, player);
+    let covered: HashSet<usize> = tiles.into_iter().flatten().collect();
+    game.board
+        .get_anchors(player)
+        .iter()
+        .filter(|anchor| covered.contains(anchor))
+        .count()
+}
+
+fn squares(game: &Game, (piece, variant, offset): Move) -> Vec<usize> {
+    let player = game.current_player();
+    let mut tiles: Vec<usize> = game
+        .get_piece(player, piece, variant)
+        .offsets
+        .iter()
+        .map(|t| offset + t)
+        .collect();
+    tiles.sort();
+    tiles
+}
+
+impl Puzzle {
+    /// Read one line of a puzzle file, checking some move solves it
+    pub fn parse(line: &str) -> Result<Puzzle, String> {
+        let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+        let (position, objective, title) = match fields[..] {
+            [position, objective] => (position, objective, ""),
+            [position, objective, title] => (position, objective, title),
+            _ => return Err("A puzzle has a position, an objective, and a title".to_string()),
+        };
+        let game = from_position(position)?;
+        if game.is_terminal() {
+            return Err("The game is already over".to_string());
+        }
+
+        let words: Vec<&str> = objective.split_whitespace().collect();
+        let objective = match words[..] {
+            ["play", ref moves @ ..] if !moves.is_empty() => Objective::Play(
+                moves
+                    .iter()
+                    .map(|text| parse_move(&game, text).map(|m| squares(&game, m)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            ["anchors", n] => Objective::Anchors(
+                n.parse()
+                    .map_err(|_| format!("Invalid anchor count: {}", n))?,
+            ),
+            ["block", target] => match target.chars().collect::<Vec<_>>()[..] {
+                [c] => Objective::Block(parse_color(c)?),
+                _ => return Err(format!("Unknown color: {}", target)),
+            },
+            _ => return Err(format!("Unknown objective: {}", objective)),
+        };
+
+        let puzzle = Puzzle {
+            position: to_position(&game),
+            objective,
+            title: title.to_string(),
+        };
+        if puzzle.solutions()?.is_empty() {
+            return Err("No move meets the objective".to_string());
+        }
+        Ok(puzzle)
+    }
+
+    pub fn to_line(&self) -> String {
+        let objective = match &self.objective {
+            Objective::Play(moves) => {
+                let moves: Vec<String> = moves
+                    .iter()
+                    .map(|tiles| {
+                        let squares: Vec<String> =
+                            tiles.iter().map(|t| format_square(*t)).collect();
+                        squares.join("-")
+                    })
+                    .collect();
+                format!("play {}", moves.join(" "))
+            }
+            Objective::Anchors(n) => format!("anchors {}", n),
+            Objective::Block(player) => format!("block {}", color(*player)),
+        };
+        format!("{} | {} | {}", self.position, objective, self.title)
+    }
+
+    pub fn game(&self) -> Result<Game, String> {
+        from_position(&self.position)
+    }
+
+    /// Whether a move solves the puzzle, an error if it is not legal
+    pub fn solves(&self, m: Move) -> Result<bool, String> {
+        let game = self.game()?;
+        self.check(&game, m)
+    }
+
+    fn check(&self, game: &Game, (piece, variant, offset): Move) -> Result<bool, String> {
+        let player = game.current_player();
+        let next = game.place_piece(piece, variant, offset)?;
+        Ok(match &self.objective {
+            Objective::Play(moves) => moves.contains(&squares(game, (piece, variant, offset))),
+            Objective::Anchors(n) => live_anchors(&next, player) >= *n,
+            Objective::Block(target) => next.count_player_moves(*target) == 0,
+        })
+    }
+
+    /// Every move that solves the puzzle, in a fixed order
+    pub fn solutions(&self) -> Result<Vec<Move>, String> {
+        let game = self.game()?;
+        let mut moves: Vec<Move> = game.get_legal_moves().into_iter().collect();
+        moves.sort();
+        let mut solutions = Vec::new();
+        for m in moves {
+            if self.check(&game, m)? {
+                solutions.push(m);
+            }
+        }
+        Ok(solutions)
+    }
+}
+
+/// Read every puzzle in a puzzle file's text
+pub fn parse(text: &str) -> Result<Vec<Puzzle>, String> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| Puzzle::parse(line).map_err(|e| format!("Line {}: {}", i + 1, e)))
+        .collect()
+}
+
+pub fn load(path: &str) -> Result<Vec<Puzzle>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    parse(&text)
+}
+
+/// Find puzzles in a played game, each position where the move played was rated at least
+/// `margin` below the best one and the best was at least `margin` ahead of the next best,
+/// so there is one clear answer
+pub fn mine<E: Evaluator>(
+    start: Game,
+    record: &GameRecord,
+    evaluator: &mut E,
+    margin: f32,
+) -> Result<Vec<Puzzle>, String> {
+    let mut game = start;
+    let mut puzzles = Vec::new();
+    for (i, recorded) in record.moves.iter().enumerate() {
+        if recorded.tiles.is_empty() {
+            game = game.pass()?;
+            continue;
+        }
+        let m = game
+            .find_move(&recorded.tiles)
+            .ok_or(format!("Move {}: not a legal move", i + 1))?;
+
+        let ranked = hints(&game, evaluator, usize::MAX)?;
+        let played = squares(&game, m);
+        let played = ranked.iter().find(|hint| hint.placement.tiles == played);
+        if let (Some(played), [best, second, ..]) = (played, &ranked[..]) {
+            if best.win_rate - played.win_rate >= margin
+                && best.win_rate - second.win_rate >= margin
+            {
+                puzzles.push(Puzzle {
+                    position: to_position(&game),
+                    objective: Objective::Play(vec![best.placement.tiles.clone()]),
+                    title: format!("Move {}", i + 1),
+                });
+            }
+        }
+        game = game.place_piece(m.0, m.1, m.2)?;
+    }
+    Ok(puzzles)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::from_history;
+
+    const OPENING: &str = "20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20/20 r 1fffff,1fffff,1fffff,1fffff - classic";
+
+    #[test]
+    fn test_parse_and_check() {
+        let text = format!(
+            "# Opening puzzles\n\n{} | play a1 | Corner\n{} | anchors 3\n",
+            OPENING, OPENING
+        );
+        let puzzles = parse(&text).unwrap();
+        assert_eq!(puzzles.len(), 2);
+        assert_eq!(puzzles[0].title, "Corner");
+        assert_eq!(Puzzle::parse(&puzzles[0].to_line()).unwrap(), puzzles[0]);
+
+        // Only the single square in the corner solves the first
+        let game = puzzles[0].game().unwrap();
+        let solutions = puzzles[0].solutions().unwrap();
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(game.find_move(&[0]), Some(solutions[0]));
+        assert_eq!(puzzles[0].solves(solutions[0]), Ok(true));
+
+        // The single square leaves one anchor, so it cannot keep three
+        let solutions = puzzles[1].solutions().unwrap();
+        assert!(!solutions.is_empty() && solutions.len() < game.get_legal_moves().len());
+        assert_eq!(puzzles[1].solves(game.find_move(&[0]).unwrap()), Ok(false));
+        assert!(puzzles[1].solves((0, 0, 399)).is_err());
+
+        // Each example has one answer
+        let examples = parse(include_str!("../testdata/puzzles.txt")).unwrap();
+        assert_eq!(examples.len(), 3);
+        for puzzle in examples {
+            assert_eq!(puzzle.solutions().unwrap().len(), 1, "{}", puzzle.title);
+        }
+
+        assert!(parse(&format!("{} | play a2", OPENING)).is_err()); // Not a legal move
+        assert!(parse(&format!("{} | anchors 30", OPENING)).is_err()); // Nothing solves it
+        assert!(parse(&format!("{} | block x", OPENING)).is_err());
+        assert!(parse(&format!("{} | win", OPENING)).is_err());
+    }
+
+    /// Rates positions with fewer squares on the board higher, so the single square is best
+    struct FewestSquares;
+
+    impl Evaluator for FewestSquares {
+        fn evaluate(&mut self, game: &Game) -> Result<(Vec<f32>, Vec<f32>), String> {
+            let placed = game
+                .board
+                .board
+                .iter()
+                .filter(|c| **c & 0b1111 != 0)
+                .count();
+            Ok((Vec::new(), vec![-(placed as f32); 4]))
+        }
+    }
+
+    #[test]
+    fn test_mine() {
+        // Red opens with a five square piece and blue with the single square
+        let mut game = Game::reset();
+        let mut moves: Vec<Move> = game.get_legal_moves().into_iter().collect();
+        moves.sort_by_key(|m| (squares(&game, *m).len(), *m));
+        let red = *moves.last().unwrap();
+        game = game.place_piece(red.0, red.1, red.2).unwrap();
+        let blue = *game
+            .get_legal_moves()
+            .iter()
+            .find(|m| squares(&game, **m).len() == 1)
+            .unwrap();
+        game = game.place_piece(blue.0, blue.1, blue.2).unwrap();
+
+        let record = from_history(&game.history).unwrap();
+        let puzzles = mine(Game::reset(), &record, &mut FewestSquares, 0.5).unwrap();
+        assert_eq!(puzzles.len(), 1);
+        assert_eq!(puzzles[0].position, OPENING);
+        assert_eq!(puzzles[0].objective, Objective::Play(vec![vec![0]]));
+        assert_eq!(puzzles[0].title, "Move 1");
+        let again = Puzzle::parse(&puzzles[0].to_line()).unwrap();
+        assert_eq!(again.solutions().unwrap().len(), 1);
+        assert_eq!(again.solves(red), Ok(false));
+    }
+}
@@ -0,0 +1,441 @@
+/*
+Importer for externally recorded games, such as ones scraped from online play,
+and the record of a game played here, as kept by the tournament runner and the server
+Replaying a played game also checks it against the final scores it was recorded with.
+*/
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::bga;
+use crate::game::{Game, Move, PASS, SWAP};
+
+/// One recorded move: the player and the board tiles (row * 20 + col) their piece covers
+/// No tiles means the player passed
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RecordedMove {
+    pub player: usize,
+    pub tiles: Vec<usize>,
+}
+
+/// The moves of one game in order, players with no moves left are skipped
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct GameRecord {
+    pub moves: Vec<RecordedMove>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub swapped: bool, // The second player swapped sides after the opening move, see Game::swap
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// A validated game along with the legal tiles before each tile placement, none for a pass
+pub struct ImportedGame {
+    pub game: Game,
+    pub legal_tiles: Vec<Vec<usize>>,
+}
+
+/// A game played here along with who sat in each seat, built up move by move
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlayedGame {
+    #[serde(default)]
+    pub seats: Vec<String>, // Player or agent spec in each seat
+    pub record: GameRecord,
+    #[serde(default)]
+    pub scores: Vec<i32>, // Final scores, empty until the game is over
+}
+
+/// Either kind of game in a JSON file, a bare record has no seats or scores
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AnyGame {
+    Played(PlayedGame),
+    Record(GameRecord),
+}
+
+impl PlayedGame {
+    pub fn new(seats: Vec<String>) -> PlayedGame {
+        PlayedGame {
+            seats,
+            record: GameRecord::default(),
+            scores: Vec::new(),
+        }
+    }
+
+    /// Record a move about to be played in `game`
+    pub fn push(&mut self, game: &Game, (piece, variant, offset): Move) {
+        let player = game.current_player();
+        let mut tiles: Vec<usize> = game
+            .get_piece(player, piece, variant)
+            .offsets
+            .iter()
+            .map(|t| offset + t)
+            .collect();
+        tiles.sort();
+        self.record.moves.push(RecordedMove { player, tiles });
+    }
+
+    /// Record the second player swapping sides
+    pub fn swap(&mut self) {
+        self.record.swapped = true;
+    }
+
+    /// Record the final scores of the finished game
+    pub fn finish(&mut self, game: &Game) {
+        self.scores = game.get_score();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.scores.is_empty()
+    }
+}
+
+/// Parse a JSON array of games, each like {"moves": [{"player": 0, "tiles": [0, 1]}]}
+pub fn parse_json(text: &str) -> Result<Vec<GameRecord>, String> {
+    serde_json::from_str(text).map_err(|e| format!("Invalid game records: {}", e))
+}
+
+/// Parse CSV lines of `game,player,tiles` with the tiles separated by spaces
+/// Consecutive lines with the same game id make up one game, a header line is optional
+pub fn parse_csv(text: &str) -> Result<Vec<GameRecord>, String> {
+    let mut records = Vec::new();
+    let mut current_id = None;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (i == 0 && line.starts_with("game")) {
+            continue;
+        }
+
+        let invalid = || format!("Invalid game record line {}: {}", i + 1, line);
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() != 3 {
+            return Err(invalid());
+        }
+        let player = fields[1].parse().map_err(|_| invalid())?;
+        let tiles = fields[2]
+            .split_whitespace()
+            .map(|t| t.parse().map_err(|_| invalid()))
+            .collect::<Result<Vec<usize>, String>>()?;
+
+        if current_id != Some(fields[0]) {
+            current_id = Some(fields[0]);
+            records.push(GameRecord::default());
+        }
+        records
+            .last_mut()
+            .unwrap()
+            .moves
+            .push(RecordedMove { player, tiles });
+    }
+
+    Ok(records)
+}
+
+/// Read game records from a .json or .csv file, or a Board Game Arena log, see blokus::bga
+pub fn load(path: &str) -> Result<Vec<GameRecord>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    if path.ends_with(bga::EXTENSION) {
+        bga::parse(&text)
+    } else if path.ends_with(".json") {
+        parse_json(&text)
+    } else if path.ends_with(".csv") {
+        parse_csv(&text)
+    } else {
+        Err(format!("Unknown game record format: {}", path))
+    }
+}
+
+/// Read games from a .json file of played games or records, or a .csv file of records
+pub fn load_played(path: &str) -> Result<Vec<PlayedGame>, String> {
+    if !path.ends_with(".json") || path.ends_with(bga::EXTENSION) {
+        return Ok(load(path)?.into_iter().map(PlayedGame::from).collect());
+    }
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let games: Vec<AnyGame> =
+        serde_json::from_str(&text).map_err(|e| format!("Invalid game records: {}", e))?;
+    Ok(games
+        .into_iter()
+        .map(|game| match game {
+            AnyGame::Played(played) => played,
+            AnyGame::Record(record) => PlayedGame::from(record),
+        })
+        .collect())
+}
+
+impl From<GameRecord> for PlayedGame {
+    fn from(record: GameRecord) -> Self {
+        PlayedGame {
+            seats: Vec::new(),
+            record,
+            scores: Vec::new(),
+        }
+    }
+}
+
+/// Replay a played game, checking every move is legal and a finished game ends with
+/// the scores it was recorded with
+pub fn verify(start: Game, played: &PlayedGame) -> Result<Game, String> {
+    let game = replay_from(start, &played.record)?.game;
+    if !played.is_finished() {
+        return Ok(game);
+    }
+    if !game.is_terminal() {
+        return Err("The game was scored before it was over".to_string());
+    }
+    let scores = game.get_score();
+    if scores != played.scores {
+        return Err(format!(
+            "The game was scored {:?} but its moves score {:?}",
+            played.scores, scores
+        ));
+    }
+    Ok(game)
+}
+
+/// Replay a record through the engine, checking every move is legal
+pub fn replay(record: &GameRecord) -> Result<ImportedGame, String> {
+    replay_from(Game::reset(), record)
+}
+
+/// Replay a record from the start of a game that may be another variant
+pub fn replay_from(start: Game, record: &GameRecord) -> Result<ImportedGame, String> {
+    replay_visiting(start, record, |_| ())
+}
+
+/// Replay a record, showing `visit` the position before each tile placement and pass,
+/// the same positions `ImportedGame::legal_tiles` holds the legal tiles of
+pub fn replay_visiting(
+    start: Game,
+    record: &GameRecord,
+    mut visit: impl FnMut(&Game),
+) -> Result<ImportedGame, String> {
+    let mut game = start;
+    let mut legal_tiles = Vec::new();
+    for (i, recorded) in record.moves.iter().enumerate() {
+        if game.is_terminal() {
+            return Err(format!("Move {}: the game is already over", i + 1));
+        }
+        if record.swapped && game.can_swap() {
+            game = game.swap()?;
+        }
+        if recorded.player != game.current_player() {
+            return Err(format!(
+                "Move {}: player {} moved but it is player {}'s turn",
+                i + 1,
+                recorded.player,
+                game.current_player()
+            ));
+        }
+
+        if recorded.tiles.is_empty() {
+            visit(&game);
+            game = game.pass().map_err(|e| format!("Move {}: {}", i + 1, e))?;
+            legal_tiles.push(Vec::new());
+            continue;
+        }
+
+        // Find the placement covering exactly the recorded tiles
+        let mut tiles = recorded.tiles.clone();
+        tiles.sort();
+        let piece = match game.find_move(&tiles) {
+            Some((p, _, _)) => p,
+            None => {
+                return Err(format!(
+                    "Move {}: player {} cannot legally cover tiles {:?}",
+                    i + 1,
+                    recorded.player,
+                    recorded.tiles
+                ))
+            }
+        };
+
+        // Place tiles one at a time so the legal tiles are known before each one
+        for (j, tile) in tiles.iter().enumerate() {
+            visit(&game);
+            let mut legal = game.get_legal_tiles();
+            legal.sort();
+            legal_tiles.push(legal);
+            let finish = if j == tiles.len() - 1 {
+                Some(piece)
+            } else {
+                None
+            };
+            game.apply(*tile, finish)?;
+        }
+    }
+
+    if record.swapped && game.can_swap() {
+        game = game.swap()?;
+    }
+    if record.swapped && !game.is_swapped() {
+        return Err("The sides were swapped but the game has no swap rule".to_string());
+    }
+    Ok(ImportedGame { game, legal_tiles })
+}
+
+/// Recover the moves of a game from its history of (player, tile) placements
+/// A player can move several times running once the others are out, so a run of one
+/// player's tiles is split into pieces by trying the largest legal piece first.
+pub fn from_history(history: &[(i32, i32)]) -> Result<GameRecord, String> {
+    from_history_at(&Game::reset(), history)
+}
+
+/// Recover the moves of a game that started from a set up position, see position::Editor
+pub fn from_history_at(start: &Game, history: &[(i32, i32)]) -> Result<GameRecord, String> {
+    let mut moves = Vec::new();
+    if split_history(start, history, &mut moves) {
+        let swapped = history.iter().any(|(_, tile)| *tile == SWAP);
+        Ok(GameRecord { moves, swapped })
+    } else {
+        Err("The game's history does not split into legal moves".to_string())
+    }
+}
+
+fn split_history(game: &Game, history: &[(i32, i32)], moves: &mut Vec<RecordedMove>) -> bool {
+    let Some((player, _)) = history.first() else {
+        return true;
+    };
+    if history[0].1 == SWAP {
+        let Ok(next) = game.swap() else {
+            return false;
+        };
+        return split_history(&next, &history[1..], moves);
+    }
+    if history[0].1 == PASS {
+        let Ok(next) = game.pass() else {
+            return false;
+        };
+        moves.push(RecordedMove {
+            player: *player as usize,
+            tiles: Vec::new(),
+        });
+        if split_history(&next, &history[1..], moves) {
+            return true;
+        }
+        moves.pop();
+        return false;
+    }
+    let run = history
+        .iter()
+        .take_while(|(p, t)| p == player && *t >= 0)
+        .count();
+    for len in (1..=run.min(5)).rev() {
+        let mut tiles: Vec<usize> = history[..len].iter().map(|(_, t)| *t as usize).collect();
+        let Some((piece, variant, offset)) = game.find_move(&tiles) else {
+            continue;
+        };
+        let Ok(next) = game.place_piece(piece, variant, offset) else {
+            continue;
+        };
+        tiles.sort();
+        moves.push(RecordedMove {
+            player: *player as usize,
+            tiles,
+        });
+        if split_history(&next, &history[len..], moves) {
+            return true;
+        }
+        moves.pop();
+    }
+    false
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_played_game_replays() {
+        let mut game = Game::reset();
+        let mut played = PlayedGame::new(vec!["random".to_string(); 4]);
+        for _ in 0..4 {
+            let chosen = *game.get_legal_moves().iter().next().unwrap();
+            played.push(&game, chosen);
+            game = game.place_piece(chosen.0, chosen.1, chosen.2).unwrap();
+        }
+        assert!(!played.is_finished());
+        assert_eq!(replay(&played.record).unwrap().game.history, game.history);
+        assert_eq!(from_history(&game.history).unwrap(), played.record);
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut game = Game::reset();
+        let mut played = PlayedGame::new(vec!["random".to_string(); 4]);
+        while !game.is_terminal() {
+            let chosen = *game.get_legal_moves().iter().min().unwrap();
+            played.push(&game, chosen);
+            game = game.place_piece(chosen.0, chosen.1, chosen.2).unwrap();
+        }
+
+        // Unfinished games only have their moves checked
+        assert!(verify(Game::reset(), &played).is_ok());
+        played.finish(&game);
+        let text = serde_json::to_string(&played).unwrap();
+        assert_eq!(serde_json::from_str::<PlayedGame>(&text).unwrap(), played);
+        assert_eq!(
+            verify(Game::reset(), &played).unwrap().history,
+            game.history
+        );
+
+        let mut wrong_score = played.clone();
+        wrong_score.scores[0] += 1;
+        assert!(verify(Game::reset(), &wrong_score).is_err());
+
+        let mut cut_short = played.clone();
+        cut_short.record.moves.pop();
+        assert!(verify(Game::reset(), &cut_short).is_err());
+    }
+
+    #[test]
+    fn test_parse_and_replay() {
+        let csv = "game,player,tiles\n1,0,0 1\n1,1,19\n2,0,0\n";
+        let records = parse_csv(csv).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].moves[1],
+            RecordedMove {
+                player: 1,
+                tiles: vec![19]
+            }
+        );
+
+        let json = r#"[{"moves": [{"player": 0, "tiles": [0, 1]}, {"player": 1, "tiles": [19]}]}]"#;
+        assert_eq!(parse_json(json).unwrap()[0], records[0]);
+
+        let imported = replay(&records[0]).unwrap();
+        assert_eq!(imported.game.history, vec![(0, 0), (0, 1), (1, 19)]);
+        assert_eq!(imported.legal_tiles.len(), 3);
+        assert_eq!(imported.game.current_player(), 2);
+    }
+
+    #[test]
+    fn test_passes() {
+        let records = parse_csv("1,0,0\n1,1,\n1,2,399\n").unwrap();
+        let imported = replay(&records[0]).unwrap();
+        assert_eq!(imported.game.history, vec![(0, 0), (1, PASS), (2, 399)]);
+        assert_eq!(imported.legal_tiles[1], Vec::<usize>::new());
+        assert!(!imported.game.is_player_active(1));
+        assert_eq!(from_history(&imported.game.history).unwrap(), records[0]);
+
+        // A position for every entry of the history, passes included
+        let mut visited = Vec::new();
+        replay_visiting(Game::reset(), &records[0], |game| {
+            visited.push(game.history.len())
+        })
+        .unwrap();
+        assert_eq!(visited, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_replay_rejects_illegal_moves() {
+        let out_of_turn = parse_csv("1,1,19\n").unwrap();
+        assert!(replay(&out_of_turn[0]).is_err());
+
+        // Not touching the starting corner
+        let off_corner = parse_csv("1,0,1 2\n").unwrap();
+        assert!(replay(&off_corner[0]).is_err());
+    }
+}
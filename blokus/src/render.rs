@@ -0,0 +1,272 @@
+/*
+Pictures of recorded games for reports and web pages
+A static SVG shows the final board, and an animated GIF adds one move per frame.
+Both use the GUI's player colors and number every piece by the move that placed it,
+at its first tile in reading order.
+*/
+
+use gif::{Encoder, Frame, Repeat};
+
+use crate::board::BOARD_SIZE;
+use crate::records::{replay, GameRecord};
+
+const CELL: usize = 24; // Pixels per board square, including the gap around it
+const GAP: usize = 1;
+
+// Board, empty square, then red, blue, yellow, and green, as in the GUI's stylesheet
+const BOARD_COLOR: [u8; 3] = [0xd9, 0xd9, 0xd9];
+const EMPTY_COLOR: [u8; 3] = [0xff, 0xff, 0xff];
+const PLAYER_COLORS: [[u8; 3]; 4] = [
+    [0xff, 0x00, 0x00],
+    [0x16, 0x16, 0xff],
+    [0xfa, 0xea, 0x0e],
+    [0x06, 0xe9, 0x8f],
+];
+
+/// Numbers are drawn light on red and blue and dark on yellow and green
+const DARK_TEXT: [bool; 4] = [false, false, true, true];
+
+// Palette indices for GIF frames
+const BOARD_INDEX: u8 = 0;
+const EMPTY_INDEX: u8 = 1;
+const PLAYER_INDEX: u8 = 2; // Up to 5
+const DARK_INDEX: u8 = 6;
+const LIGHT_INDEX: u8 = 7;
+
+/// 3x5 pixel digits, one row per three bits
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+const DIGIT_SCALE: usize = 2;
+
+/// The player and move number covering a square, if any
+type Square = Option<(usize, usize)>;
+
+/// Who covers each square after every move, checked against the rules
+fn board_after_moves(record: &GameRecord) -> Result<Vec<Vec<Square>>, String> {
+    replay(record)?;
+    let mut board = vec![None; BOARD_SIZE * BOARD_SIZE];
+    let mut boards = Vec::new();
+    for (i, recorded) in record.moves.iter().enumerate() {
+        for tile in &recorded.tiles {
+            board[*tile] = Some((recorded.player, i + 1));
+        }
+        boards.push(board.clone());
+    }
+    Ok(boards)
+}
+
+/// The square each move's number is drawn on
+fn labels(record: &GameRecord) -> Vec<usize> {
+    record
+        .moves
+        .iter()
+        .map(|m| *m.tiles.iter().min().unwrap_or(&0))
+        .collect()
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// SVG of the board at the end of the recorded game
+pub fn board_svg(record: &GameRecord) -> Result<String, String> {
+    let boards = board_after_moves(record)?;
+    let empty = vec![None; BOARD_SIZE * BOARD_SIZE];
+    let board = boards.last().unwrap_or(&empty);
+    let size = BOARD_SIZE * CELL + GAP;
+    let square = CELL - GAP;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{size}\" height=\"{size}\" viewBox=\"0 0 {size} {size}\">\n\
+         <rect width=\"{size}\" height=\"{size}\" fill=\"{}\"/>\n",
+        hex(BOARD_COLOR)
+    );
+    for (tile, cell) in board.iter().enumerate() {
+        let color = match cell {
+            Some((player, _)) => PLAYER_COLORS[*player],
+            None => EMPTY_COLOR,
+        };
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{square}\" height=\"{square}\" fill=\"{}\"/>\n",
+            (tile % BOARD_SIZE) * CELL + GAP,
+            (tile / BOARD_SIZE) * CELL + GAP,
+            hex(color)
+        ));
+    }
+    for (i, tile) in labels(record).iter().enumerate() {
+        let player = record.moves[i].player;
+        let text = if DARK_TEXT[player] {
+            "#000000"
+        } else {
+            "#ffffff"
+        };
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"11\" text-anchor=\"middle\" \
+             dominant-baseline=\"central\" fill=\"{}\">{}</text>\n",
+            (tile % BOARD_SIZE) * CELL + GAP + square / 2,
+            (tile / BOARD_SIZE) * CELL + GAP + square / 2,
+            text,
+            i + 1
+        ));
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// Draw a move number centered in a square of a frame
+fn draw_number(pixels: &mut [u8], width: usize, tile: usize, number: usize, index: u8) {
+    let digits: Vec<usize> = number
+        .to_string()
+        .bytes()
+        .map(|b| (b - b'0') as usize)
+        .collect();
+    let text_width = digits.len() * 4 * DIGIT_SCALE - DIGIT_SCALE;
+    let square = CELL - GAP;
+    let left = (tile % BOARD_SIZE) * CELL + GAP + (square - text_width.min(square)) / 2;
+    let top = (tile / BOARD_SIZE) * CELL + GAP + (square - 5 * DIGIT_SCALE) / 2;
+    for (d, digit) in digits.iter().enumerate() {
+        for (row, bits) in DIGITS[*digit].iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..DIGIT_SCALE {
+                    for dx in 0..DIGIT_SCALE {
+                        let x = left + (d * 4 + col) * DIGIT_SCALE + dx;
+                        let y = top + row * DIGIT_SCALE + dy;
+                        if x < width {
+                            pixels[y * width + x] = index;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Palette indices of one frame
+fn frame_pixels(board: &[Square], labels: &[usize]) -> Vec<u8> {
+    let size = BOARD_SIZE * CELL + GAP;
+    let mut pixels = vec![BOARD_INDEX; size * size];
+    for (tile, cell) in board.iter().enumerate() {
+        let index = match cell {
+            Some((player, _)) => PLAYER_INDEX + *player as u8,
+            None => EMPTY_INDEX,
+        };
+        let left = (tile % BOARD_SIZE) * CELL + GAP;
+        let top = (tile / BOARD_SIZE) * CELL + GAP;
+        for y in top..top + CELL - GAP {
+            pixels[y * size + left..y * size + left + CELL - GAP].fill(index);
+        }
+    }
+    for (tile, cell) in board.iter().enumerate() {
+        if let Some((player, number)) = cell {
+            if labels[number - 1] == tile {
+                let index = if DARK_TEXT[*player] {
+                    DARK_INDEX
+                } else {
+                    LIGHT_INDEX
+                };
+                draw_number(&mut pixels, size, tile, *number, index);
+            }
+        }
+    }
+    pixels
+}
+
+/// Animated GIF of the recorded game, starting from the empty board
+/// Each frame is shown for `delay` hundredths of a second, and the final board three times as long
+pub fn game_gif(record: &GameRecord, delay: u16) -> Result<Vec<u8>, String> {
+    let mut boards = board_after_moves(record)?;
+    boards.insert(0, vec![None; BOARD_SIZE * BOARD_SIZE]);
+    let labels = labels(record);
+
+    let mut palette = Vec::new();
+    palette.extend(BOARD_COLOR);
+    palette.extend(EMPTY_COLOR);
+    for color in PLAYER_COLORS {
+        palette.extend(color);
+    }
+    palette.extend([0x00, 0x00, 0x00]);
+    palette.extend([0xff, 0xff, 0xff]);
+
+    let size = (BOARD_SIZE * CELL + GAP) as u16;
+    let gif_err = |e: gif::EncodingError| format!("Failed to encode GIF: {}", e);
+    let mut output = Vec::new();
+    {
+        let mut encoder = Encoder::new(&mut output, size, size, &palette).map_err(gif_err)?;
+        encoder.set_repeat(Repeat::Infinite).map_err(gif_err)?;
+        for (i, board) in boards.iter().enumerate() {
+            let mut frame =
+                Frame::from_indexed_pixels(size, size, frame_pixels(board, &labels), None);
+            frame.delay = if i == boards.len() - 1 {
+                delay.saturating_mul(3)
+            } else {
+                delay
+            };
+            encoder.write_frame(&frame).map_err(gif_err)?;
+        }
+    }
+    Ok(output)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::RecordedMove;
+
+    fn record() -> GameRecord {
+        GameRecord {
+            moves: vec![
+                RecordedMove {
+                    player: 0,
+                    tiles: vec![0, 1],
+                },
+                RecordedMove {
+                    player: 1,
+                    tiles: vec![19],
+                },
+            ],
+            swapped: false,
+        }
+    }
+
+    #[test]
+    fn test_board_svg() {
+        let svg = board_svg(&record()).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("fill=\"#ff0000\"").count(), 2);
+        assert_eq!(svg.matches("fill=\"#1616ff\"").count(), 1);
+        assert!(svg.contains(">2</text>"));
+
+        let mut illegal = record();
+        illegal.moves[1].tiles = vec![1];
+        assert!(board_svg(&illegal).is_err());
+    }
+
+    #[test]
+    fn test_game_gif() {
+        let bytes = game_gif(&record(), 50).unwrap();
+        assert!(bytes.starts_with(b"GIF89a"));
+
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(bytes.as_slice())
+            .unwrap();
+        let mut frames = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 3);
+    }
+}
@@ -0,0 +1,121 @@
+/*
+Replay buffer of self-play training examples, used by the Rust training loop
+*/
+
+use rand::Rng;
+use std::collections::VecDeque;
+
+use crate::board::BOARD_SIZE as D;
+use crate::game::{Game, StateOptions};
+use crate::opening_book::to_perspective;
+
+/// Flatten the board state planes into the network's input layout
+pub fn encode_state(game: &Game, options: &StateOptions) -> Vec<f32> {
+    game.get_state(options)
+        .iter()
+        .flatten()
+        .flatten()
+        .map(|square| if *square { 1.0 } else { 0.0 })
+        .collect()
+}
+
+/// One position from the perspective of the player to move
+#[derive(Clone, Debug)]
+pub struct Example {
+    pub state: Vec<f32>,
+    pub policy: Vec<f32>,
+    pub values: Vec<f32>, // Relative to the player to move, filled in when the game ends
+    player: usize,
+}
+
+impl Example {
+    /// Build an example from a position and its search policy over board tiles
+    pub fn new(game: &Game, policy: &[(i32, f32)], options: &StateOptions) -> Example {
+        let player = game.current_player();
+        let turns = options.turns(player);
+        let mut rotated = vec![0.0; D * D];
+        for (tile, prob) in policy {
+            rotated[to_perspective(*tile as usize, turns)] = *prob;
+        }
+
+        Example {
+            state: encode_state(game, options),
+            policy: rotated,
+            values: Vec::new(),
+            player,
+        }
+    }
+}
+
+/// Fixed capacity buffer, the oldest examples are dropped first
+pub struct ReplayBuffer {
+    capacity: usize,
+    examples: VecDeque<Example>,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> ReplayBuffer {
+        ReplayBuffer {
+            capacity,
+            examples: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.examples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.examples.is_empty()
+    }
+
+    /// Add a finished game's examples, labelling them with its payoff
+    pub fn add_game(&mut self, examples: Vec<Example>, payoff: &[f32]) {
+        for mut example in examples {
+            example.values = payoff.to_vec();
+            example.values.rotate_left(example.player);
+            if self.examples.len() == self.capacity {
+                self.examples.pop_front();
+            }
+            self.examples.push_back(example);
+        }
+    }
+
+    /// Sample a batch uniformly with replacement
+    pub fn sample<R: Rng>(&self, batch_size: usize, rng: &mut R) -> Vec<&Example> {
+        if self.examples.is_empty() {
+            return Vec::new();
+        }
+        (0..batch_size)
+            .map(|_| &self.examples[rng.gen_range(0..self.examples.len())])
+            .collect()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_examples_use_player_perspective() {
+        let mut game = Game::reset();
+        game.apply(0, None).unwrap();
+        while game.current_player() != 1 {
+            let tile = game.get_legal_tiles()[0];
+            game.apply(tile, None).unwrap();
+        }
+
+        // Player 1 starts in the top right, which is the top left from their side
+        let example = Example::new(&game, &[(19, 1.0)], &StateOptions::default());
+        assert_eq!(example.policy[0], 1.0);
+        assert_eq!(example.state.len(), 5 * D * D);
+
+        let mut buffer = ReplayBuffer::new(1);
+        buffer.add_game(vec![example.clone(), example], &[0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(buffer.len(), 1);
+        let batch = buffer.sample(4, &mut rand::thread_rng());
+        assert_eq!(batch.len(), 4);
+        assert_eq!(batch[0].values, vec![1.0, 0.0, 0.0, 0.0]);
+    }
+}
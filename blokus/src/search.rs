@@ -0,0 +1,948 @@
+// Monte Carlo Tree Search guided by an evaluator
+use rand::Rng;
+use rand_distr::{Dirichlet, Distribution};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancellationToken;
+use crate::endgame;
+use crate::evaluator::Evaluator;
+use crate::game::Game;
+use crate::node::Node;
+use crate::payoff::Payoff;
+use crate::symmetry;
+use crate::verify::{self, Verification};
+
+/// Parameters for MCTS, mirrors the training config
+#[derive(Clone, Debug)]
+pub struct SearchConfig {
+    pub sims_per_move: usize,
+    pub sample_moves: usize,
+    pub c_base: f32,
+    pub c_init: f32,
+    pub dirichlet_alpha: f32,
+    pub exploration_fraction: f32,
+    pub endgame_threshold: usize, // Solve exactly below this many total legal moves, 0 to disable
+    pub verify_top_k: usize,      // Check this many top moves with a shallow minimax, 0 to disable
+    pub payoff: Payoff,           // Value of finished games, see blokus::payoff
+    pub prune_symmetry: bool,     // Search one of each mirrored pair of tiles, see blokus::symmetry
+    pub early_stop: bool,         // Stop once the remaining simulations cannot change the best tile
+    pub fpu_reduction: Option<f32>, // Value of unvisited tiles, see first_play_urgency
+    pub prior_temperature: f32,   // Above 1 flattens the policy's priors, below 1 sharpens them
+    pub widening_base: usize,     // Tiles a node considers at first, see widen, 0 for all of them
+    pub widening_exponent: f32,   // How fast the number of tiles considered grows with visits
+}
+
+/// Positions the endgame solver may visit before falling back to the evaluator
+const ENDGAME_NODE_LIMIT: usize = 20_000;
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            sims_per_move: 50,
+            sample_moves: 30,
+            c_base: 19652.0,
+            c_init: 1.25,
+            dirichlet_alpha: 0.3,
+            exploration_fraction: 0.25,
+            endgame_threshold: 0,
+            verify_top_k: 0,
+            payoff: Payoff::default(),
+            prune_symmetry: false,
+            early_stop: false,
+            fpu_reduction: None,
+            prior_temperature: 1.0,
+            widening_base: 0,
+            widening_exponent: 0.5,
+        }
+    }
+}
+
+/// Evaluate and Expand the Node
+pub fn evaluate<E: Evaluator>(
+    node: &mut Node,
+    game: &Game,
+    config: &SearchConfig,
+    evaluator: &mut E,
+) -> Result<Vec<f32>, String> {
+    // If the game is over, return the payoff
+    if game.is_terminal() {
+        return Ok(config.payoff.of(game));
+    }
+
+    // Get the policy and value from the evaluator, or exactly in small endgames
+    let (policy, value) = match solve_endgame(game, config) {
+        Some(result) => result,
+        None => {
+            let (policy, value) = evaluator.evaluate(game)?;
+            (policy, game.variant().team_values(value))
+        }
+    };
+    expand(node, game, &policy, config);
+    Ok(value)
+}
+
+/// Add children to the node with priors from the policy
+/// In symmetric positions a tile and its mirror image share one child, see `visit_policy`
+fn expand(node: &mut Node, game: &Game, policy: &[f32], config: &SearchConfig) {
+    // Normalize policy for node priors, filter out illegal moves
+    let legal_moves = game.get_legal_tiles();
+    let mut exp_policy = vec![];
+    for tile in legal_moves {
+        if policy[tile] > 0.0 {
+            exp_policy.push((tile, (policy[tile] / config.prior_temperature).exp()));
+        }
+    }
+    let total: f32 = exp_policy.iter().map(|(_, p)| p).sum();
+
+    // Expand the node with the policy
+    let player = game.current_player();
+    let symmetric = config.prune_symmetry && symmetry::is_symmetric(game);
+    node.to_play = player;
+    for (tile, prob) in exp_policy {
+        let tile = if symmetric {
+            symmetry::canonical(tile, player)
+        } else {
+            tile
+        };
+        let child = node.children.entry(tile).or_insert_with(|| Node::new(0.0));
+        child.prior += prob / total;
+    }
+
+    // Hold back all but the tiles with the highest priors
+    if config.widening_base > 0 && node.children.len() > config.widening_base {
+        let mut ranked: Vec<(usize, f32)> = node
+            .children
+            .drain()
+            .map(|(tile, child)| (tile, child.prior))
+            .collect();
+        ranked.sort_by(|a, b| a.1.total_cmp(&b.1).then(b.0.cmp(&a.0)));
+        node.held_back = ranked;
+        widen(node, config);
+    }
+}
+
+/// Progressive widening, bring held back tiles in as the node is visited
+/// A node considers `widening_base * (visits + 1)^widening_exponent` of its best tiles by
+/// prior, as midgame positions can have hundreds of legal tiles that are rarely worth a visit.
+fn widen(node: &mut Node, config: &SearchConfig) {
+    let considered =
+        config.widening_base as f32 * (node.visits as f32 + 1.0).powf(config.widening_exponent);
+    while (node.children.len() as f32) < considered {
+        let Some((tile, prior)) = node.held_back.pop() else {
+            break;
+        };
+        node.children.insert(tile, Node::new(prior));
+    }
+}
+
+/// Solve the position exactly if it is small enough
+/// The policy only covers the tiles of the best move so the search follows it
+fn solve_endgame(game: &Game, config: &SearchConfig) -> Option<(Vec<f32>, Vec<f32>)> {
+    if config.endgame_threshold == 0 || !endgame::is_solvable(game, config.endgame_threshold) {
+        return None;
+    }
+
+    let solution = endgame::solve(game, ENDGAME_NODE_LIMIT)?;
+    let (p, v, o) = solution.best_move?;
+    let piece = game.get_piece(game.current_player(), p, v);
+    let mut policy = vec![0.0; game.get_board().len()];
+    for offset in &piece.offsets {
+        policy[o + offset] = 1.0 / piece.offsets.len() as f32;
+    }
+    Some((
+        policy,
+        config.payoff.for_scores(game.variant(), &solution.scores),
+    ))
+}
+
+/// Get UCB score for a child node
+/// Exploration constant is based on the number of visits to the parent node
+/// so that it will encourage exploration of nodes that have not been visited
+/// Unvisited children are valued at the first play urgency instead of their empty average
+fn ucb_score(parent: &Node, child: &Node, first_play: f32, config: &SearchConfig) -> f32 {
+    let c_base = config.c_base;
+    let c_init = config.c_init;
+    let parent_visits = parent.visits as f32;
+    let exploration_constant = (((parent_visits + c_base + 1.0) / c_base).ln() + c_init)
+        * parent_visits.sqrt()
+        / (1.0 + child.visits as f32);
+    let prior_score = exploration_constant * child.prior;
+    let value_score = if child.visits == 0 {
+        first_play
+    } else {
+        child.value()
+    };
+    prior_score + value_score
+}
+
+/// Value of a child that has not been visited yet
+/// Without a reduction it is 0, as in AlphaZero, which with Blokus's hundreds of sparse priors
+/// leaves the search trying each new tile. With one it is the average value of the visited
+/// children less the reduction, so the search keeps deepening good lines instead.
+fn first_play_urgency(node: &Node, config: &SearchConfig) -> f32 {
+    let Some(reduction) = config.fpu_reduction else {
+        return 0.0;
+    };
+    let (value_sum, visits) = node
+        .children
+        .values()
+        .fold((0.0, 0), |(sum, n), c| (sum + c.value_sum, n + c.visits));
+    if visits == 0 {
+        return 0.0;
+    }
+    value_sum / visits as f32 - reduction
+}
+
+/// Add noise to the root node to encourage exploration
+fn add_exploration_noise(root: &mut Node, config: &SearchConfig) {
+    let num_actions = root.children.len();
+    if num_actions <= 1 {
+        return;
+    }
+
+    let alpha_vec = vec![config.dirichlet_alpha; num_actions];
+    let dirichlet = Dirichlet::new(&alpha_vec).unwrap();
+    let noise = dirichlet.sample(&mut rand::thread_rng());
+    for (i, (_tile, node)) in root.children.iter_mut().enumerate() {
+        node.prior = node.prior * (1.0 - config.exploration_fraction)
+            + noise[i] * config.exploration_fraction;
+    }
+}
+
+/// Sample from a softmax distribution
+/// Used to select actions during the first few moves to encourage exploration
+fn softmax_sample(visit_dist: Vec<(usize, u32)>) -> usize {
+    let total_visits: u32 = visit_dist.iter().fold(0, |acc, (_, visits)| acc + visits);
+    let sample = rand::thread_rng().gen_range(0.0..1.0);
+    let mut sum = 0.0;
+
+    for (tile, visits) in &visit_dist {
+        sum += (*visits as f32) / (total_visits as f32);
+        if sum > sample {
+            return *tile;
+        }
+    }
+    visit_dist.last().unwrap().0
+}
+
+/// Select child node to explore
+/// Uses UCB formula to balance exploration and exploitation
+/// Returns the action and the child node's key
+fn select_child(node: &Node, config: &SearchConfig) -> usize {
+    let first_play = first_play_urgency(node, config);
+    let mut best_score = f32::NEG_INFINITY;
+    let mut best_action = 0;
+    for (action, child) in &node.children {
+        let score = ucb_score(node, child, first_play, config);
+        if score >= best_score {
+            best_score = score;
+            best_action = *action;
+        }
+    }
+    best_action
+}
+
+/// Select action from policy
+pub fn select_action(root: &Node, num_moves: usize, config: &SearchConfig) -> usize {
+    let visit_dist: Vec<(usize, u32)> = root
+        .children
+        .iter()
+        .map(|(tile, node)| (*tile, node.visits))
+        .collect();
+    if num_moves < config.sample_moves {
+        softmax_sample(visit_dist)
+    } else {
+        visit_dist.iter().max_by(|a, b| a.1.cmp(&b.1)).unwrap().0
+    }
+}
+
+/// Pick the action to play, optionally double checking the search's choice
+/// Verification only applies once moves are no longer sampled for exploration
+pub fn choose_action(
+    game: &Game,
+    root: &Node,
+    num_moves: usize,
+    config: &SearchConfig,
+) -> Result<usize, String> {
+    if config.verify_top_k > 0 && num_moves >= config.sample_moves {
+        let verification = Verification {
+            top_k: config.verify_top_k,
+            ..Verification::default()
+        };
+        if let Some(action) = verify::select_verified_action(game, root, &verification)? {
+            return Ok(action);
+        }
+    }
+    Ok(select_action(root, num_moves, config))
+}
+
+/// Update node when visitied during backpropagation
+fn backpropagate(search_path: Vec<usize>, root: &mut Node, values: Vec<f32>) {
+    let mut node = root;
+    for tile in search_path {
+        node = node.children.get_mut(&tile).unwrap();
+        node.visits += 1;
+        node.value_sum += values[node.to_play];
+    }
+}
+
+/// Create the root for a search, evaluate it, and add children
+fn new_root<E: Evaluator>(
+    game: &Game,
+    config: &SearchConfig,
+    evaluator: &mut E,
+) -> Result<Node, String> {
+    let mut root = Node::new(0.0);
+    if let Err(e) = evaluate(&mut root, game, config, evaluator) {
+        return Err(format!("Error evaluating root node: {:}", e));
+    }
+    add_exploration_noise(&mut root, config);
+    Ok(root)
+}
+
+/// Walk down the tree to a leaf, returning the path taken and the leaf position
+fn select_leaf(root: &mut Node, game: &Game, config: &SearchConfig) -> (Vec<usize>, Game) {
+    root.visits += 1;
+    let mut node = root;
+    let mut scratch_game = game.clone();
+    let mut search_path = Vec::new();
+    while node.is_expanded() {
+        widen(node, config);
+        let action = select_child(node, config);
+        node = node.children.get_mut(&action).unwrap();
+        let _ = scratch_game.apply(action, None);
+        search_path.push(action);
+    }
+    (search_path, scratch_game)
+}
+
+/// Follow a search path from the root
+fn node_at<'a>(root: &'a mut Node, search_path: &[usize]) -> &'a mut Node {
+    let mut node = root;
+    for tile in search_path {
+        node = node.children.get_mut(tile).unwrap();
+    }
+    node
+}
+
+/// Run a single simulation from the root
+fn simulate<E: Evaluator>(
+    root: &mut Node,
+    game: &Game,
+    config: &SearchConfig,
+    evaluator: &mut E,
+) -> Result<(), String> {
+    // Select a leaf node
+    let (search_path, scratch_game) = select_leaf(root, game, config);
+
+    // Expand and evaluate the leaf node
+    let node = node_at(root, &search_path);
+    let values = evaluate(node, &scratch_game, config, evaluator)?;
+
+    // Backpropagate the value
+    backpropagate(search_path, root, values);
+    Ok(())
+}
+
+/// Run MCTS simulations from a position and return the searched root
+pub fn search<E: Evaluator>(
+    game: &Game,
+    config: &SearchConfig,
+    evaluator: &mut E,
+) -> Result<Node, String> {
+    search_cancellable(game, config, evaluator, &CancellationToken::new())
+}
+
+/// Run MCTS simulations like `search`, stopping early once the token is cancelled
+/// The root is returned either way, so the best move found so far can still be played
+pub fn search_cancellable<E: Evaluator>(
+    game: &Game,
+    config: &SearchConfig,
+    evaluator: &mut E,
+    cancel: &CancellationToken,
+) -> Result<Node, String> {
+    let span = tracing::debug_span!(
+        "search",
+        ply = game.history.len(),
+        player = game.current_player()
+    );
+    let _search = span.enter();
+    let mut root = new_root(game, config, evaluator)?;
+    for sim in 0..config.sims_per_move {
+        let remaining = config.sims_per_move - sim;
+        if cancel.is_cancelled() || (config.early_stop && is_decided(&root, remaining)) {
+            break;
+        }
+        simulate(&mut root, game, config, evaluator)?;
+    }
+    tracing::debug!(
+        visits = root.visits,
+        cancelled = cancel.is_cancelled(),
+        "search done"
+    );
+
+    Ok(root)
+}
+
+/// Whether the most visited tile stays ahead however the remaining simulations are spent
+/// Even if all of them went to the runner up it would still have fewer visits.
+pub fn is_decided(root: &Node, remaining: usize) -> bool {
+    let mut visits: Vec<u32> = root.children.values().map(|c| c.visits).collect();
+    visits.sort_unstable_by(|a, b| b.cmp(a));
+    match visits[..] {
+        [best, second, ..] => (best - second) as usize > remaining,
+        _ => true, // Nothing to choose between
+    }
+}
+
+/// A search that hands leaf positions out for evaluation instead of calling an evaluator
+/// Lets one caller interleave many searches and evaluate their leaves together in a batch
+pub struct SteppedSearch {
+    game: Game,
+    config: SearchConfig,
+    root: Node,
+    root_evaluated: bool,
+    simulations: usize,
+    pending: Option<(Vec<usize>, Game)>, // Search path and position awaiting evaluation
+    span: tracing::Span,                 // Opened inside the caller's span, such as a game's
+}
+
+impl SteppedSearch {
+    pub fn new(game: &Game, config: &SearchConfig) -> SteppedSearch {
+        SteppedSearch {
+            game: game.clone(),
+            config: config.clone(),
+            root: Node::new(0.0),
+            root_evaluated: false,
+            simulations: 0,
+            pending: None,
+            span: tracing::debug_span!(
+                "search",
+                ply = game.history.len(),
+                player = game.current_player()
+            ),
+        }
+    }
+
+    /// Run the search until a position needs the evaluator
+    /// Returns None once all simulations are done
+    pub fn next_leaf(&mut self) -> Option<&Game> {
+        if self.pending.is_none() {
+            self.pending = self.advance();
+        }
+        self.pending.as_ref().map(|(_, game)| game)
+    }
+
+    /// Simulate until reaching a leaf that needs the evaluator
+    /// Terminal positions and solved endgames are backed up along the way
+    fn advance(&mut self) -> Option<(Vec<usize>, Game)> {
+        if !self.root_evaluated {
+            self.root_evaluated = true;
+            if self.game.is_terminal() {
+                return None;
+            }
+            match solve_endgame(&self.game, &self.config) {
+                Some((policy, _)) => {
+                    expand(&mut self.root, &self.game, &policy, &self.config);
+                    add_exploration_noise(&mut self.root, &self.config);
+                }
+                None => return Some((Vec::new(), self.game.clone())),
+            }
+        }
+
+        while self.simulations < self.config.sims_per_move {
+            self.simulations += 1;
+            let (search_path, leaf) = select_leaf(&mut self.root, &self.game, &self.config);
+            if leaf.is_terminal() {
+                backpropagate(search_path, &mut self.root, self.config.payoff.of(&leaf));
+                continue;
+            }
+            match solve_endgame(&leaf, &self.config) {
+                Some((policy, values)) => {
+                    expand(
+                        node_at(&mut self.root, &search_path),
+                        &leaf,
+                        &policy,
+                        &self.config,
+                    );
+                    backpropagate(search_path, &mut self.root, values);
+                }
+                None => return Some((search_path, leaf)),
+            }
+        }
+        None
+    }
+
+    /// Give the evaluator's output for the position returned by `next_leaf`
+    pub fn provide(&mut self, policy: &[f32], values: Vec<f32>) -> Result<(), String> {
+        let (search_path, leaf) = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Err("No position is waiting for evaluation".to_string()),
+        };
+        expand(
+            node_at(&mut self.root, &search_path),
+            &leaf,
+            policy,
+            &self.config,
+        );
+        if search_path.is_empty() {
+            add_exploration_noise(&mut self.root, &self.config);
+        } else {
+            backpropagate(
+                search_path,
+                &mut self.root,
+                leaf.variant().team_values(values),
+            );
+        }
+        Ok(())
+    }
+
+    /// Get the searched root, finished or not
+    pub fn into_root(self) -> Node {
+        tracing::debug!(parent: &self.span, visits = self.root.visits, "search done");
+        self.root
+    }
+}
+
+/// Run MCTS simulations until the deadline passes or the token is cancelled
+/// The token is checked before every simulation, so other threads can end the search
+pub fn search_until<E: Evaluator>(
+    game: &Game,
+    config: &SearchConfig,
+    evaluator: &mut E,
+    deadline: Option<Instant>,
+    cancel: &CancellationToken,
+) -> Result<Node, String> {
+    let mut root = new_root(game, config, evaluator)?;
+    extend_search(&mut root, game, config, evaluator, deadline, cancel)?;
+    Ok(root)
+}
+
+/// Keep searching an existing tree, such as one reused from pondering
+/// Runs until the deadline passes or the token is cancelled
+pub fn extend_search<E: Evaluator>(
+    root: &mut Node,
+    game: &Game,
+    config: &SearchConfig,
+    evaluator: &mut E,
+    deadline: Option<Instant>,
+    cancel: &CancellationToken,
+) -> Result<(), String> {
+    if !root.is_expanded() {
+        evaluate(root, game, config, evaluator)?;
+    }
+    while !cancel.is_cancelled() && deadline.is_none_or(|d| Instant::now() < d) {
+        simulate(root, game, config, evaluator)?;
+    }
+
+    Ok(())
+}
+
+/// Take the part of the tree reached by playing the given tiles
+/// Returns None if the search never explored that line
+pub fn subtree(root: Node, actions: &[usize]) -> Option<Node> {
+    let mut node = root;
+    for action in actions {
+        node = node.children.remove(action)?;
+    }
+    Some(node)
+}
+
+/// Run MCTS simulations for a fixed amount of wall-clock time
+pub fn search_for<E: Evaluator>(
+    game: &Game,
+    config: &SearchConfig,
+    evaluator: &mut E,
+    duration: Duration,
+) -> Result<Node, String> {
+    search_until(
+        game,
+        config,
+        evaluator,
+        Some(Instant::now() + duration),
+        &CancellationToken::new(),
+    )
+}
+
+/// Get the most visited action at the root, falling back to the highest prior
+/// Gives the best move found so far, even if the search was stopped early
+pub fn best_action(root: &Node) -> Option<usize> {
+    root.children
+        .iter()
+        .max_by(|(a, x), (b, y)| {
+            x.visits
+                .cmp(&y.visits)
+                .then(x.prior.total_cmp(&y.prior))
+                .then(b.cmp(a))
+        })
+        .map(|(action, _)| *action)
+}
+
+/// Search statistics for one child of the root
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChildStats {
+    pub tile: usize,
+    pub visits: u32,
+    pub prior: f32,
+    pub value: f32,
+}
+
+/// Summary of a finished search for display and logging
+/// Children are ordered from most to least visited
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SearchAnalysis {
+    pub to_play: usize,
+    pub visits: u32,
+    pub principal_variation: Vec<usize>,
+    pub children: Vec<ChildStats>,
+}
+
+impl SearchAnalysis {
+    pub fn from_root(root: &Node) -> SearchAnalysis {
+        let mut children: Vec<ChildStats> = root
+            .children
+            .iter()
+            .map(|(tile, child)| ChildStats {
+                tile: *tile,
+                visits: child.visits,
+                prior: child.prior,
+                value: child.value(),
+            })
+            .collect();
+        children.sort_by(|a, b| {
+            b.visits
+                .cmp(&a.visits)
+                .then(b.prior.total_cmp(&a.prior))
+                .then(a.tile.cmp(&b.tile))
+        });
+
+        // Follow the most visited children down the tree
+        let mut principal_variation = Vec::new();
+        let mut node = root;
+        while let Some(action) = best_action(node) {
+            let child = &node.children[&action];
+            if child.visits == 0 {
+                break;
+            }
+            principal_variation.push(action);
+            node = child;
+        }
+
+        SearchAnalysis {
+            to_play: root.to_play,
+            visits: root.visits,
+            principal_variation,
+            children,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+/// Get the visit distribution over the root's children, used as the policy target
+/// A child standing for a mirrored pair of tiles has its visits split between the two
+pub fn visit_policy(root: &Node, game: &Game, config: &SearchConfig) -> Vec<(i32, f32)> {
+    let total_visits: u32 = root.children.values().map(|child| child.visits).sum();
+    let player = game.current_player();
+    let symmetric = config.prune_symmetry && symmetry::is_symmetric(game);
+    let mut policy = Vec::new();
+    for (tile, child) in &root.children {
+        let p = (child.visits as f32) / (total_visits as f32);
+        let mirror = symmetry::mirror(*tile, player);
+        if symmetric && mirror != *tile && !root.children.contains_key(&mirror) {
+            policy.push((*tile as i32, p / 2.0));
+            policy.push((mirror as i32, p / 2.0));
+        } else {
+            policy.push((*tile as i32, p));
+        }
+    }
+    policy
+}
+
+/// Run MCTS simulations to get policy for root node
+pub fn mcts<E: Evaluator>(
+    game: &Game,
+    policies: &mut Vec<Vec<(i32, f32)>>,
+    config: &SearchConfig,
+    evaluator: &mut E,
+) -> Result<usize, String> {
+    let root = search(game, config, evaluator)?;
+
+    // Save policy for this state
+    policies.push(visit_policy(&root, game, config));
+
+    // Pick action to take
+    choose_action(game, &root, policies.len(), config)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::sorted_moves;
+    use crate::evaluator::{HeuristicEvaluator, RolloutEvaluator};
+    use crate::game::Variant;
+    use std::collections::HashMap;
+
+    /// Rollout leaf values and terminal values back up on the same scale
+    /// Each side's value is shared by its colors and at most one, like a finished game's
+    fn assert_values_on_one_scale(variant: Variant) {
+        let config = SearchConfig::default();
+        let mut evaluator = RolloutEvaluator::new(2, Some(0));
+        let mut game = Game::new(variant);
+        let mut leaf = game.clone();
+        while !game.is_terminal() {
+            leaf = game.clone();
+            let (piece, variant, offset) = sorted_moves(&game)[0];
+            game.play_piece(piece, variant, offset).unwrap();
+        }
+        let leaf = evaluate(&mut Node::new(0.0), &leaf, &config, &mut evaluator).unwrap();
+        let terminal = evaluate(&mut Node::new(0.0), &game, &config, &mut evaluator).unwrap();
+        assert_eq!(terminal.iter().sum::<f32>(), 2.0);
+        assert!((leaf.iter().sum::<f32>() - 2.0).abs() < 1e-4);
+        assert!(leaf.iter().all(|v| *v <= 1.0));
+        assert_eq!((leaf[0], leaf[1]), (leaf[2], leaf[3]));
+    }
+
+    #[test]
+    fn test_search_without_model() {
+        let game = Game::reset();
+        let config = SearchConfig {
+            sims_per_move: 20,
+            ..SearchConfig::default()
+        };
+        let root = search(&game, &config, &mut HeuristicEvaluator::default()).unwrap();
+        let visits: u32 = root.children.values().map(|c| c.visits).sum();
+        assert_eq!(visits, 20);
+
+        let mut policies = Vec::new();
+        let action = mcts(
+            &game,
+            &mut policies,
+            &config,
+            &mut HeuristicEvaluator::default(),
+        );
+        assert!(game.get_legal_tiles().contains(&action.unwrap()));
+        assert_eq!(policies.len(), 1);
+    }
+
+    #[test]
+    fn test_search_for_duration() {
+        let game = Game::reset();
+        let config = SearchConfig::default();
+        let mut evaluator = HeuristicEvaluator::default();
+        let root = search_for(&game, &config, &mut evaluator, Duration::from_millis(50)).unwrap();
+        let action = best_action(&root).unwrap();
+        assert!(game.get_legal_tiles().contains(&action));
+    }
+
+    #[test]
+    fn test_search_analysis() {
+        let game = Game::reset();
+        let config = SearchConfig {
+            sims_per_move: 30,
+            ..SearchConfig::default()
+        };
+        let root = search(&game, &config, &mut HeuristicEvaluator::default()).unwrap();
+        let analysis = SearchAnalysis::from_root(&root);
+        assert_eq!(analysis.visits, 30);
+        assert_eq!(analysis.children.len(), root.children.len());
+        assert_eq!(analysis.principal_variation[0], analysis.children[0].tile);
+        assert!(analysis
+            .children
+            .windows(2)
+            .all(|w| w[0].visits >= w[1].visits));
+
+        let parsed: SearchAnalysis = serde_json::from_str(&analysis.to_json()).unwrap();
+        assert_eq!(parsed, analysis);
+    }
+
+    #[test]
+    fn test_stepped_search() {
+        let game = Game::reset();
+        let config = SearchConfig {
+            sims_per_move: 20,
+            ..SearchConfig::default()
+        };
+        let mut evaluator = HeuristicEvaluator::default();
+        let mut stepped = SteppedSearch::new(&game, &config);
+        assert!(stepped.provide(&[], vec![]).is_err());
+
+        let mut evaluations = 0;
+        while let Some(leaf) = stepped.next_leaf() {
+            let (policy, values) = evaluator.evaluate(leaf).unwrap();
+            stepped.provide(&policy, values).unwrap();
+            evaluations += 1;
+        }
+        assert_eq!(evaluations, 21);
+
+        let root = stepped.into_root();
+        assert_eq!(root.visits, 20);
+        let visits: u32 = root.children.values().map(|c| c.visits).sum();
+        assert_eq!(visits, 20);
+    }
+
+    #[test]
+    fn test_stopped_search_uses_priors() {
+        let game = Game::reset();
+        let config = SearchConfig::default();
+        let mut evaluator = HeuristicEvaluator::default();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let root = search_until(&game, &config, &mut evaluator, None, &cancel).unwrap();
+        assert_eq!(root.visits, 0);
+        let root = search_cancellable(&game, &config, &mut evaluator, &cancel).unwrap();
+        assert_eq!(root.visits, 0);
+        assert!(game
+            .get_legal_tiles()
+            .contains(&best_action(&root).unwrap()));
+    }
+
+    #[test]
+    fn test_early_stop() {
+        let mut root = Node::new(0.0);
+        for (tile, visits) in [(1, 30), (2, 10), (3, 0)] {
+            let mut child = Node::new(0.1);
+            child.visits = visits;
+            root.children.insert(tile, child);
+        }
+        assert!(is_decided(&root, 19));
+        assert!(!is_decided(&root, 20)); // The runner up could still tie
+        root.children.retain(|tile, _| *tile == 1);
+        assert!(is_decided(&root, 100));
+
+        // With a single tile to play the search stops before spending anything
+        let mut game = Game::reset();
+        while game.get_legal_tiles().len() > 1 {
+            let tile = *game.get_legal_tiles().iter().min().unwrap();
+            game.apply(tile, None).unwrap();
+        }
+        assert_eq!(game.get_legal_tiles().len(), 1);
+        let config = SearchConfig {
+            sims_per_move: 20,
+            early_stop: true,
+            ..SearchConfig::default()
+        };
+        let root = search(&game, &config, &mut HeuristicEvaluator::default()).unwrap();
+        assert_eq!(root.visits, 0);
+        assert_eq!(best_action(&root), game.get_legal_tiles().first().copied());
+    }
+
+    #[test]
+    fn test_first_play_urgency() {
+        let mut node = Node::new(0.0);
+        let mut visited = Node::new(0.5);
+        visited.visits = 4;
+        visited.value_sum = 2.0;
+        node.children.insert(1, visited);
+        node.children.insert(2, Node::new(0.5));
+        assert_eq!(first_play_urgency(&node, &SearchConfig::default()), 0.0);
+
+        let config = SearchConfig {
+            fpu_reduction: Some(0.2),
+            ..SearchConfig::default()
+        };
+        assert!((first_play_urgency(&node, &config) - 0.3).abs() < 1e-6);
+        assert_eq!(select_child(&node, &config), 1); // The visited tile is still worth more
+    }
+
+    #[test]
+    fn test_prior_temperature() {
+        let game = Game::reset();
+        let mut policy = vec![0.0; 400];
+        policy[0] = 2.0;
+        policy[21] = 1.0;
+        let spread = |temperature: f32| {
+            let config = SearchConfig {
+                prior_temperature: temperature,
+                ..SearchConfig::default()
+            };
+            let mut node = Node::new(0.0);
+            expand(&mut node, &game, &policy, &config);
+            node.children[&0].prior - node.children[&21].prior
+        };
+        assert!(spread(2.0) < spread(1.0));
+        assert!(spread(0.5) > spread(1.0));
+    }
+
+    #[test]
+    fn test_progressive_widening() {
+        let game = Game::reset();
+        let policy: Vec<f32> = (0..400).map(|tile| 1.0 / (tile + 1) as f32).collect();
+        let config = SearchConfig {
+            widening_base: 2,
+            prune_symmetry: false,
+            ..SearchConfig::default()
+        };
+        let mut node = Node::new(0.0);
+        expand(&mut node, &game, &policy, &config);
+        let mut considered: Vec<usize> = node.children.keys().copied().collect();
+        considered.sort();
+        assert_eq!(considered, vec![0, 1]); // The two highest priors
+        let held_back = node.held_back.len();
+        assert_eq!(held_back + 2, game.get_legal_tiles().len());
+
+        // 2 * sqrt(4) tiles after three visits
+        node.visits = 3;
+        widen(&mut node, &config);
+        assert_eq!(node.children.len(), 4);
+        assert_eq!(node.held_back.len(), held_back - 2);
+
+        let config = SearchConfig {
+            sims_per_move: 20,
+            ..config
+        };
+        let root = search(&game, &config, &mut HeuristicEvaluator::default()).unwrap();
+        assert!(root.children.len() < game.get_legal_tiles().len());
+        assert_eq!(root.children.values().map(|c| c.visits).sum::<u32>(), 20);
+    }
+
+    #[test]
+    fn test_symmetry_pruning() {
+        // The opening searches one tile of each mirrored pair
+        let game = Game::reset();
+        let config = SearchConfig {
+            sims_per_move: 20,
+            prune_symmetry: true,
+            ..SearchConfig::default()
+        };
+        let root = search(&game, &config, &mut HeuristicEvaluator::default()).unwrap();
+        let legal = game.get_legal_tiles();
+        assert!(root.children.len() < legal.len());
+        assert!(root
+            .children
+            .keys()
+            .all(|t| symmetry::canonical(*t, 0) == *t));
+        let priors: f32 = root.children.values().map(|c| c.prior).sum();
+        assert!((priors - 1.0).abs() < 1e-4);
+
+        // The policy target spreads back over every legal tile, evenly across each pair
+        let policy: HashMap<i32, f32> = visit_policy(&root, &game, &config).into_iter().collect();
+        assert_eq!(policy.len(), legal.len());
+        assert!((policy.values().sum::<f32>() - 1.0).abs() < 1e-4);
+        assert_eq!(policy[&1], policy[&20]);
+
+        // Without pruning every legal tile gets its own child
+        let config = SearchConfig {
+            prune_symmetry: false,
+            ..config
+        };
+        let root = search(&game, &config, &mut HeuristicEvaluator::default()).unwrap();
+        assert_eq!(root.children.len(), legal.len());
+        let policy = visit_policy(&root, &game, &config);
+        assert_eq!(policy.len(), legal.len());
+    }
+
+    #[test]
+    fn test_rollout_values_in_team_games() {
+        assert_values_on_one_scale(Variant::Team);
+    }
+
+    #[test]
+    fn test_rollout_values_in_two_player_games() {
+        assert_values_on_one_scale(Variant::TwoPlayer);
+    }
+}
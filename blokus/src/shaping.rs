@@ -0,0 +1,127 @@
+/*
+Intermediate reward signals for shaping value targets, since credit for a win is hard to
+assign from the final payoff alone over a game of some 80 moves
+Each whole-piece move is measured from the mover's side:
+- squares: squares the piece covers
+- anchors: corners the mover can still play from after the move, less the ones before it
+- mobility: legal moves the mover's opponents lost, summed over them
+Training mixes them into the value targets only when asked to, see SHAPING_MIX in the README.
+*/
+
+use crate::game::Game;
+use crate::records::from_history_at;
+
+const NUM_PLAYERS: usize = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Shaping {
+    pub squares: i32,
+    pub anchors: i32,
+    pub mobility: i32,
+}
+
+/// Anchors and legal move counts of every color in a position
+struct Measures {
+    anchors: [i32; NUM_PLAYERS],
+    moves: [i32; NUM_PLAYERS],
+}
+
+impl Measures {
+    fn of(game: &Game) -> Measures {
+        Measures {
+            anchors: [0, 1, 2, 3].map(|p| game.board.get_anchors(p).len() as i32),
+            moves: [0, 1, 2, 3].map(|p| game.count_player_moves(p) as i32),
+        }
+    }
+}
+
+impl Shaping {
+    /// Signals of the move that led from one position to the other, for the player who made it
+    pub fn between(before: &Game, after: &Game) -> Shaping {
+        let squares = (after.history.len() - before.history.len()) as i32;
+        Shaping::measured(before, squares, &Measures::of(before), &Measures::of(after))
+    }
+
+    fn measured(before: &Game, squares: i32, from: &Measures, to: &Measures) -> Shaping {
+        let player = before.current_player();
+        let partner = before.variant().partner(player);
+        Shaping {
+            squares,
+            anchors: to.anchors[player] - from.anchors[player],
+            mobility: (0..NUM_PLAYERS)
+                .filter(|p| *p != player && *p != partner)
+                .map(|p| from.moves[p] - to.moves[p])
+                .sum(),
+        }
+    }
+
+    pub fn to_tuple(self) -> (i32, i32, i32) {
+        (self.squares, self.anchors, self.mobility)
+    }
+}
+
+/// Signals for each entry of a game's history, on the last tile of each piece
+/// Other tiles of a piece, passes, and swaps get no signal
+pub fn game_signals(start: &Game, history: &[(i32, i32)]) -> Result<Vec<Shaping>, String> {
+    let record = from_history_at(start, history)?;
+    let mut game = start.clone();
+    let mut measures = Measures::of(&game);
+    let mut signals = Vec::with_capacity(history.len());
+    for recorded in &record.moves {
+        if record.swapped && game.can_swap() {
+            game = game.swap()?;
+            signals.push(Shaping::default());
+        }
+        if recorded.tiles.is_empty() {
+            game = game.pass()?;
+            signals.push(Shaping::default());
+            continue;
+        }
+
+        let (piece, variant, offset) = game
+            .find_move(&recorded.tiles)
+            .ok_or("The game's history does not split into legal moves")?;
+        let next = game.place_piece(piece, variant, offset)?;
+        let after = Measures::of(&next);
+        let squares = recorded.tiles.len() as i32;
+        signals.extend((1..squares).map(|_| Shaping::default()));
+        signals.push(Shaping::measured(&game, squares, &measures, &after));
+        game = next;
+        measures = after;
+    }
+    if record.swapped && game.can_swap() {
+        signals.push(Shaping::default());
+    }
+    Ok(signals)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Variant;
+
+    #[test]
+    fn test_shaping() {
+        // Red opens in its corner with the five square line along the edge
+        let game = Game::reset();
+        let line = game.find_move(&[0, 1, 2, 3, 4]).unwrap();
+        let next = game.place_piece(line.0, line.1, line.2).unwrap();
+        let shaping = Shaping::between(&game, &next);
+        assert_eq!(shaping.squares, 5);
+        assert_eq!(shaping.anchors, 0); // The starting corner for the one past the end of the line
+        assert_eq!(shaping.mobility, 0); // Nobody else is near yet
+
+        // Every tile of the game gets an entry, with the signal on each piece's last tile
+        let mut game = Game::new(Variant::TwoPlayer);
+        for _ in 0..8 {
+            let m = *game.get_legal_moves().iter().min().unwrap();
+            game = game.place_piece(m.0, m.1, m.2).unwrap();
+        }
+        let signals = game_signals(&Game::new(Variant::TwoPlayer), &game.history).unwrap();
+        assert_eq!(signals.len(), game.history.len());
+        let squares: i32 = signals.iter().map(|s| s.squares).sum();
+        assert_eq!(squares as usize, game.history.len());
+        assert_eq!(signals.iter().filter(|s| s.squares > 0).count(), 8);
+    }
+}
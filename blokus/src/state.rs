@@ -0,0 +1,242 @@
+/*
+Serializable snapshots of a game, for front-ends that are not written in Rust
+This is the JSON the REST routes, room sockets, WebAssembly bindings and GUI exchange, so it
+only uses plain values and never the engine's internal encodings. Its layout is versioned:
+STATE_VERSION goes up whenever a field is renamed, removed, or changes meaning, while new
+optional fields can be added within a version. Readers should go through GameState::from_json,
+which refuses states of any other version.
+*/
+
+use serde::{Deserialize, Serialize};
+
+use crate::agents::sorted_moves;
+use crate::game::{Game, Variant, PASS, SWAP};
+use crate::notation::format_move;
+use crate::records::{from_history_at, replay_from};
+
+/// Version of the GameState layout, written into every state
+pub const STATE_VERSION: u32 = 1;
+
+/// Everything needed to draw a position, and to rebuild the game from
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameState {
+    pub version: u32,
+    pub variant: String, // Name of the rules, see Variant::from_name
+    pub board: Vec<u8>,  // Owner of each tile row by row, 0 when empty and 1-4 for the players
+    pub current_player: usize,
+    pub is_terminal: bool,
+    pub scores: Vec<i32>,
+    pub remaining_pieces: Vec<Vec<usize>>, // Ids of the pieces each player has left
+    pub history: Vec<HistoryEntry>,
+    #[serde(default)]
+    pub swap_rule: bool, // Whether the game is played under the swap rule, see Game::swap
+    #[serde(default)]
+    pub can_swap: bool, // Whether the player to move may swap sides instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_left: Option<u64>, // Milliseconds the player to move has left in a timed game
+}
+
+/// One step of a game in the order it was played, a whole piece takes a step per tile
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HistoryEntry {
+    Tile { player: usize, tile: usize }, // Tiles are numbered row by row like the board
+    Pass { player: usize },
+    Swap { player: usize },
+}
+
+impl HistoryEntry {
+    fn from_pair((player, tile): (i32, i32)) -> HistoryEntry {
+        let player = player as usize;
+        match tile {
+            PASS => HistoryEntry::Pass { player },
+            SWAP => HistoryEntry::Swap { player },
+            tile => HistoryEntry::Tile {
+                player,
+                tile: tile as usize,
+            },
+        }
+    }
+
+    fn to_pair(self) -> (i32, i32) {
+        match self {
+            HistoryEntry::Tile { player, tile } => (player as i32, tile as i32),
+            HistoryEntry::Pass { player } => (player as i32, PASS),
+            HistoryEntry::Swap { player } => (player as i32, SWAP),
+        }
+    }
+}
+
+/// A whole piece placement for the player to move
+/// `piece` indexes the player's remaining pieces, like the engine's moves
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LegalMove {
+    pub piece: usize,
+    pub variant: usize,
+    pub offset: usize,
+    pub tiles: Vec<usize>,
+    pub notation: String,
+}
+
+impl GameState {
+    pub fn new(game: &Game) -> GameState {
+        GameState {
+            version: STATE_VERSION,
+            variant: game.variant().name().to_string(),
+            board: game.get_board().iter().map(|t| t & 0b1111).collect(),
+            current_player: game.current_player(),
+            is_terminal: game.is_terminal(),
+            scores: game.get_score(),
+            remaining_pieces: (0..4)
+                .map(|p| {
+                    game.board
+                        .get_pieces(p)
+                        .iter()
+                        .map(|piece| piece.id)
+                        .collect()
+                })
+                .collect(),
+            history: game
+                .history
+                .iter()
+                .map(|entry| HistoryEntry::from_pair(*entry))
+                .collect(),
+            swap_rule: game.has_swap_rule(),
+            can_swap: game.can_swap(),
+            time_left: None,
+        }
+    }
+
+    /// The state with the clock of the player to move, which the game itself does not keep
+    pub fn with_time_left(mut self, time_left: Option<u64>) -> GameState {
+        self.time_left = time_left;
+        self
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Read a state, refusing ones written for another version of the layout
+    pub fn from_json(text: &str) -> Result<GameState, String> {
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u32,
+        }
+        let invalid = |e: serde_json::Error| format!("Invalid game state: {}", e);
+        let Versioned { version } = serde_json::from_str(text).map_err(invalid)?;
+        if version != STATE_VERSION {
+            return Err(format!(
+                "Game state version {} is not supported, expected version {}",
+                version, STATE_VERSION
+            ));
+        }
+        serde_json::from_str(text).map_err(invalid)
+    }
+
+    /// The engine's history pairs of (player, tile), see Game::history
+    pub fn history_pairs(&self) -> Vec<(i32, i32)> {
+        self.history.iter().map(|entry| entry.to_pair()).collect()
+    }
+
+    /// Replay the history into a game, checking every move is legal
+    pub fn to_game(&self) -> Result<Game, String> {
+        let mut start = Game::new(Variant::from_name(&self.variant)?);
+        if self.swap_rule {
+            start = start.with_swap_rule()?;
+        }
+        let record = from_history_at(&start, &self.history_pairs())?;
+        Ok(replay_from(start, &record)?.game)
+    }
+}
+
+/// Get the legal moves in a fixed order, with the tiles they cover
+pub fn legal_moves(game: &Game) -> Vec<LegalMove> {
+    let player = game.current_player();
+    sorted_moves(game)
+        .into_iter()
+        .map(|(piece, variant, offset)| {
+            let mut tiles: Vec<usize> = game
+                .get_piece(player, piece, variant)
+                .offsets
+                .iter()
+                .map(|t| offset + t)
+                .collect();
+            tiles.sort();
+            LegalMove {
+                piece,
+                variant,
+                offset,
+                tiles,
+                notation: format_move(game, (piece, variant, offset)),
+            }
+        })
+        .collect()
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_snapshot() {
+        let game = Game::reset().place_piece(0, 0, 0).unwrap();
+        let state = GameState::new(&game);
+        assert_eq!(state.board[0], 1);
+        assert_eq!(state.current_player, 1);
+        assert_eq!(state.remaining_pieces[0].len(), 20);
+        assert_eq!(state.remaining_pieces[1].len(), 21);
+
+        let json = state.to_json();
+        assert_eq!(GameState::from_json(&json).unwrap(), state);
+
+        // Blue starts in the top right corner
+        let moves = legal_moves(&game);
+        assert!(moves.iter().all(|m| m.tiles.contains(&19)));
+        assert_eq!(moves.len(), game.get_legal_moves().len());
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        // A two player game under the swap rule, with a swap and a pass in its history
+        let mut game = Game::new(Variant::TwoPlayer).with_swap_rule().unwrap();
+        let m = *game.get_legal_moves().iter().min().unwrap();
+        game = game.place_piece(m.0, m.1, m.2).unwrap().swap().unwrap();
+        let m = *game.get_legal_moves().iter().min().unwrap();
+        game = game.place_piece(m.0, m.1, m.2).unwrap().pass().unwrap();
+
+        let state = GameState::new(&game).with_time_left(Some(1500));
+        let json = state.to_json();
+        let read = GameState::from_json(&json).unwrap();
+        assert_eq!(read, state);
+        assert_eq!(read.history_pairs(), game.history);
+        let rebuilt = read.to_game().unwrap();
+        assert_eq!(rebuilt.board.board, game.board.board);
+        assert_eq!(rebuilt.current_player(), game.current_player());
+        assert!(rebuilt.is_swapped());
+
+        // The history is spelled out rather than using the engine's codes
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], STATE_VERSION);
+        assert_eq!(value["variant"], "two-player");
+        assert_eq!(value["time_left"], 1500);
+        assert_eq!(value["history"][0]["kind"], "tile");
+        let swap = game
+            .history
+            .iter()
+            .position(|(_, tile)| *tile == SWAP)
+            .unwrap();
+        assert_eq!(value["history"][swap]["kind"], "swap");
+        assert_eq!(value["history"][swap]["player"], 1);
+        assert_eq!(value["history"][game.history.len() - 1]["kind"], "pass");
+        assert!(!GameState::new(&game).to_json().contains("time_left"));
+
+        // Other versions and older states without one are refused
+        let mut value = value;
+        value["version"] = 2.into();
+        assert!(GameState::from_json(&value.to_string()).is_err());
+        value.as_object_mut().unwrap().remove("version");
+        assert!(GameState::from_json(&value.to_string()).is_err());
+    }
+}
@@ -0,0 +1,551 @@
+/*
+SQLite storage for played games, their moves and results, and player ratings
+Players are identified by name: agent specs like mcts:400, model:<path> checkpoints,
+or anything else for people. Every finished game updates the Elo ratings of the
+players in it, comparing each pair of players by their best seat's score.
+Series keep a running count of wins between the same group of players, like a room's
+rematches, whatever seats they took in each game.
+*/
+
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::records::{GameRecord, PlayedGame, RecordedMove};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS players (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    kind TEXT NOT NULL,
+    rating REAL NOT NULL,
+    games INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE IF NOT EXISTS games (
+    id INTEGER PRIMARY KEY,
+    source TEXT NOT NULL,
+    played_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS seats (
+    game_id INTEGER NOT NULL REFERENCES games(id),
+    seat INTEGER NOT NULL,
+    player_id INTEGER NOT NULL REFERENCES players(id),
+    score INTEGER NOT NULL,
+    PRIMARY KEY (game_id, seat)
+);
+CREATE TABLE IF NOT EXISTS moves (
+    game_id INTEGER NOT NULL REFERENCES games(id),
+    ply INTEGER NOT NULL,
+    player INTEGER NOT NULL,
+    tiles TEXT NOT NULL,
+    PRIMARY KEY (game_id, ply)
+);
+CREATE TABLE IF NOT EXISTS series (
+    id INTEGER PRIMARY KEY,
+    players TEXT NOT NULL UNIQUE,
+    games INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE IF NOT EXISTS series_wins (
+    series_id INTEGER NOT NULL REFERENCES series(id),
+    place INTEGER NOT NULL,
+    player TEXT NOT NULL,
+    wins REAL NOT NULL,
+    PRIMARY KEY (series_id, place)
+);
+";
+
+pub const INITIAL_RATING: f64 = 1500.0;
+const K_FACTOR: f64 = 32.0;
+
+/// A player or agent with its current rating
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PlayerRecord {
+    pub name: String,
+    pub kind: String, // agent, model, or human
+    pub rating: f64,
+    pub games: u32,
+}
+
+/// A stored game without its moves
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StoredGame {
+    pub id: i64,
+    pub source: String, // What played the game, like tournament or server
+    pub played_at: u64, // Seconds since the Unix epoch
+    pub seats: Vec<String>,
+    pub scores: Vec<i32>,
+}
+
+/// Wins between the same players over a run of games
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SeriesRecord {
+    pub players: Vec<String>, // Sorted by name
+    pub wins: Vec<f64>,       // A shared win is split between the winners
+    pub games: u32,
+}
+
+/// The series' key, the sorted names one per line, and the order that sorts them
+fn series_key(players: &[String]) -> (String, Vec<usize>) {
+    let mut order: Vec<usize> = (0..players.len()).collect();
+    order.sort_by(|a, b| players[*a].cmp(&players[*b]));
+    let names: Vec<&str> = order.iter().map(|i| players[*i].as_str()).collect();
+    (names.join("\n"), order)
+}
+
+/// Kind of player a name refers to
+pub fn player_kind(name: &str) -> &'static str {
+    let base = name.split([':', '@']).next().unwrap_or(name);
+    match base {
+        "model" => "model",
+        "mcts" | "blocking" | "greedy" | "random" => "agent",
+        _ => "human",
+    }
+}
+
+/// Rating changes for one game, given each player's rating and best score
+/// Each pair of players counts as a match worth K / (players - 1)
+fn rating_changes(results: &[(f64, i32)]) -> Vec<f64> {
+    let opponents = results.len().saturating_sub(1).max(1) as f64;
+    results
+        .iter()
+        .enumerate()
+        .map(|(i, (rating, score))| {
+            let total: f64 = results
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, (other_rating, other_score))| {
+                    let expected = 1.0 / (1.0 + 10f64.powf((other_rating - rating) / 400.0));
+                    let actual = match score.cmp(other_score) {
+                        std::cmp::Ordering::Greater => 1.0,
+                        std::cmp::Ordering::Equal => 0.5,
+                        std::cmp::Ordering::Less => 0.0,
+                    };
+                    actual - expected
+                })
+                .sum();
+            K_FACTOR * total / opponents
+        })
+        .collect()
+}
+
+fn db_err(e: rusqlite::Error) -> String {
+    format!("Database error: {}", e)
+}
+
+pub struct Database {
+    connection: Connection,
+}
+
+impl Database {
+    /// Open or create a database file
+    pub fn open(path: &str) -> Result<Database, String> {
+        let connection =
+            Connection::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        Database::init(connection)
+    }
+
+    /// A database that only lasts as long as it is open
+    pub fn in_memory() -> Result<Database, String> {
+        Database::init(Connection::open_in_memory().map_err(db_err)?)
+    }
+
+    fn init(connection: Connection) -> Result<Database, String> {
+        connection.execute_batch(SCHEMA).map_err(db_err)?;
+        Ok(Database { connection })
+    }
+
+    /// Store a finished game with its moves and update the ratings of its players
+    /// Returns the id of the stored game
+    pub fn record_game(&mut self, source: &str, played: &PlayedGame) -> Result<i64, String> {
+        if !played.is_finished() {
+            return Err("Only finished games can be recorded".to_string());
+        }
+        if played.seats.len() != played.scores.len() {
+            return Err("Every seat needs a player".to_string());
+        }
+
+        // Best score of each distinct player
+        let mut best: BTreeMap<&str, i32> = BTreeMap::new();
+        for (name, score) in played.seats.iter().zip(&played.scores) {
+            let entry = best.entry(name.as_str()).or_insert(*score);
+            *entry = (*entry).max(*score);
+        }
+
+        let played_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let transaction = self.connection.transaction().map_err(db_err)?;
+        transaction
+            .execute(
+                "INSERT INTO games (source, played_at) VALUES (?1, ?2)",
+                params![source, played_at as i64],
+            )
+            .map_err(db_err)?;
+        let game_id = transaction.last_insert_rowid();
+
+        let mut ids = BTreeMap::new();
+        for name in best.keys() {
+            transaction
+                .execute(
+                    "INSERT OR IGNORE INTO players (name, kind, rating) VALUES (?1, ?2, ?3)",
+                    params![name, player_kind(name), INITIAL_RATING],
+                )
+                .map_err(db_err)?;
+            let (id, rating): (i64, f64) = transaction
+                .query_row(
+                    "SELECT id, rating FROM players WHERE name = ?1",
+                    params![name],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .map_err(db_err)?;
+            ids.insert(*name, (id, rating));
+        }
+
+        for (seat, (name, score)) in played.seats.iter().zip(&played.scores).enumerate() {
+            transaction
+                .execute(
+                    "INSERT INTO seats (game_id, seat, player_id, score) VALUES (?1, ?2, ?3, ?4)",
+                    params![game_id, seat as i64, ids[name.as_str()].0, score],
+                )
+                .map_err(db_err)?;
+        }
+        for (ply, recorded) in played.record.moves.iter().enumerate() {
+            let tiles: Vec<String> = recorded.tiles.iter().map(|t| t.to_string()).collect();
+            transaction
+                .execute(
+                    "INSERT INTO moves (game_id, ply, player, tiles) VALUES (?1, ?2, ?3, ?4)",
+                    params![game_id, ply as i64, recorded.player as i64, tiles.join(" ")],
+                )
+                .map_err(db_err)?;
+        }
+
+        let results: Vec<(f64, i32)> = best
+            .iter()
+            .map(|(name, score)| (ids[name].1, *score))
+            .collect();
+        for ((id, rating), change) in ids.values().zip(rating_changes(&results)) {
+            transaction
+                .execute(
+                    "UPDATE players SET rating = ?1, games = games + 1 WHERE id = ?2",
+                    params![rating + change, id],
+                )
+                .map_err(db_err)?;
+        }
+
+        transaction.commit().map_err(db_err)?;
+        Ok(game_id)
+    }
+
+    pub fn player(&self, name: &str) -> Result<Option<PlayerRecord>, String> {
+        self.connection
+            .query_row(
+                "SELECT name, kind, rating, games FROM players WHERE name = ?1",
+                params![name],
+                |row| {
+                    Ok(PlayerRecord {
+                        name: row.get(0)?,
+                        kind: row.get(1)?,
+                        rating: row.get(2)?,
+                        games: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(db_err)
+    }
+
+    /// Highest rated players first
+    pub fn leaderboard(&self, limit: usize) -> Result<Vec<PlayerRecord>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT name, kind, rating, games FROM players
+                 ORDER BY rating DESC, name LIMIT ?1",
+            )
+            .map_err(db_err)?;
+        let players = statement
+            .query_map(params![limit as i64], |row| {
+                Ok(PlayerRecord {
+                    name: row.get(0)?,
+                    kind: row.get(1)?,
+                    rating: row.get(2)?,
+                    games: row.get(3)?,
+                })
+            })
+            .map_err(db_err)?;
+        players.collect::<Result<_, _>>().map_err(db_err)
+    }
+
+    fn stored_game(&self, id: i64, source: String, played_at: i64) -> Result<StoredGame, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT players.name, seats.score FROM seats
+                 JOIN players ON players.id = seats.player_id
+                 WHERE seats.game_id = ?1 ORDER BY seats.seat",
+            )
+            .map_err(db_err)?;
+        let seats: Vec<(String, i32)> = statement
+            .query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_err)?
+            .collect::<Result<_, _>>()
+            .map_err(db_err)?;
+        Ok(StoredGame {
+            id,
+            source,
+            played_at: played_at as u64,
+            seats: seats.iter().map(|(name, _)| name.clone()).collect(),
+            scores: seats.iter().map(|(_, score)| *score).collect(),
+        })
+    }
+
+    pub fn game(&self, id: i64) -> Result<Option<StoredGame>, String> {
+        let found: Option<(String, i64)> = self
+            .connection
+            .query_row(
+                "SELECT source, played_at FROM games WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(db_err)?;
+        match found {
+            Some((source, played_at)) => self.stored_game(id, source, played_at).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Most recent games a player sat in, newest first
+    pub fn games_for(&self, name: &str, limit: usize) -> Result<Vec<StoredGame>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT DISTINCT games.id, games.source, games.played_at FROM games
+                 JOIN seats ON seats.game_id = games.id
+                 JOIN players ON players.id = seats.player_id
+                 WHERE players.name = ?1 ORDER BY games.id DESC LIMIT ?2",
+            )
+            .map_err(db_err)?;
+        let found: Vec<(i64, String, i64)> = statement
+            .query_map(params![name, limit as i64], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(db_err)?
+            .collect::<Result<_, _>>()
+            .map_err(db_err)?;
+        found
+            .into_iter()
+            .map(|(id, source, played_at)| self.stored_game(id, source, played_at))
+            .collect()
+    }
+
+    /// Add one game's wins to the series between these players, starting it if needed
+    /// The wins line up with the players, who can be given in any order.
+    pub fn record_series_game(
+        &mut self,
+        players: &[String],
+        wins: &[f64],
+    ) -> Result<SeriesRecord, String> {
+        if players.len() != wins.len() {
+            return Err("Every player needs a result".to_string());
+        }
+        let (key, order) = series_key(players);
+        let transaction = self.connection.transaction().map_err(db_err)?;
+        transaction
+            .execute(
+                "INSERT OR IGNORE INTO series (players) VALUES (?1)",
+                params![key],
+            )
+            .map_err(db_err)?;
+        let id: i64 = transaction
+            .query_row(
+                "SELECT id FROM series WHERE players = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .map_err(db_err)?;
+        transaction
+            .execute(
+                "UPDATE series SET games = games + 1 WHERE id = ?1",
+                params![id],
+            )
+            .map_err(db_err)?;
+        for (place, i) in order.iter().enumerate() {
+            transaction
+                .execute(
+                    "INSERT INTO series_wins (series_id, place, player, wins) VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT (series_id, place) DO UPDATE SET wins = wins + excluded.wins",
+                    params![id, place as i64, players[*i], wins[*i]],
+                )
+                .map_err(db_err)?;
+        }
+        transaction.commit().map_err(db_err)?;
+
+        self.series_by_id(id)
+    }
+
+    /// The series between exactly these players, if they have played one
+    pub fn series(&self, players: &[String]) -> Result<Option<SeriesRecord>, String> {
+        let (key, _) = series_key(players);
+        let id: Option<i64> = self
+            .connection
+            .query_row(
+                "SELECT id FROM series WHERE players = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db_err)?;
+        id.map(|id| self.series_by_id(id)).transpose()
+    }
+
+    /// Series a player has taken part in, most recently started first
+    pub fn series_for(&self, name: &str, limit: usize) -> Result<Vec<SeriesRecord>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT DISTINCT series_id FROM series_wins WHERE player = ?1
+                 ORDER BY series_id DESC LIMIT ?2",
+            )
+            .map_err(db_err)?;
+        let ids: Vec<i64> = statement
+            .query_map(params![name, limit as i64], |row| row.get(0))
+            .map_err(db_err)?
+            .collect::<Result<_, _>>()
+            .map_err(db_err)?;
+        ids.into_iter().map(|id| self.series_by_id(id)).collect()
+    }
+
+    fn series_by_id(&self, id: i64) -> Result<SeriesRecord, String> {
+        let games: u32 = self
+            .connection
+            .query_row(
+                "SELECT games FROM series WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .map_err(db_err)?;
+        let mut statement = self
+            .connection
+            .prepare("SELECT player, wins FROM series_wins WHERE series_id = ?1 ORDER BY place")
+            .map_err(db_err)?;
+        let rows: Vec<(String, f64)> = statement
+            .query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_err)?
+            .collect::<Result<_, _>>()
+            .map_err(db_err)?;
+        Ok(SeriesRecord {
+            players: rows.iter().map(|(name, _)| name.clone()).collect(),
+            wins: rows.iter().map(|(_, wins)| *wins).collect(),
+            games,
+        })
+    }
+
+    /// Moves of a stored game, which can be checked with `records::replay`
+    pub fn moves(&self, id: i64) -> Result<GameRecord, String> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT player, tiles FROM moves WHERE game_id = ?1 ORDER BY ply")
+            .map_err(db_err)?;
+        let rows: Vec<(i64, String)> = statement
+            .query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db_err)?
+            .collect::<Result<_, _>>()
+            .map_err(db_err)?;
+        let mut moves = Vec::new();
+        for (player, tiles) in rows {
+            let tiles = tiles
+                .split_whitespace()
+                .map(|t| {
+                    t.parse()
+                        .map_err(|_| format!("Invalid stored tiles: {}", tiles))
+                })
+                .collect::<Result<_, String>>()?;
+            moves.push(RecordedMove {
+                player: player as usize,
+                tiles,
+            });
+        }
+        Ok(GameRecord {
+            moves,
+            ..GameRecord::default()
+        })
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use crate::records::replay;
+
+    #[test]
+    fn test_record_and_query() {
+        let mut game = Game::reset();
+        let seats = vec!["greedy", "random", "greedy", "random"];
+        let mut played = PlayedGame::new(seats.iter().map(|s| s.to_string()).collect());
+        for _ in 0..8 {
+            let mut moves: Vec<_> = game.get_legal_moves().into_iter().collect();
+            moves.sort();
+            played.push(&game, moves[0]);
+            game = game
+                .place_piece(moves[0].0, moves[0].1, moves[0].2)
+                .unwrap();
+        }
+
+        let mut db = Database::in_memory().unwrap();
+        assert!(db.record_game("test", &played).is_err());
+        played.finish(&game);
+        let id = db.record_game("test", &played).unwrap();
+
+        let stored = db.game(id).unwrap().unwrap();
+        assert_eq!(stored.seats, seats);
+        assert_eq!(stored.scores, game.get_score());
+        assert_eq!(db.games_for("greedy", 10).unwrap(), vec![stored]);
+        assert!(db.games_for("mcts", 10).unwrap().is_empty());
+        let record = db.moves(id).unwrap();
+        assert_eq!(replay(&record).unwrap().game.history, game.history);
+
+        // Ratings move apart unless the game was drawn, and always sum to the same total
+        let greedy = db.player("greedy").unwrap().unwrap();
+        let random = db.player("random").unwrap().unwrap();
+        assert_eq!(greedy.kind, "agent");
+        assert_eq!(greedy.games, 1);
+        assert!((greedy.rating + random.rating - 2.0 * INITIAL_RATING).abs() < 1e-9);
+        assert_eq!(db.leaderboard(1).unwrap().len(), 1);
+        assert!(db.player("alice").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_series() {
+        let names = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let mut db = Database::in_memory().unwrap();
+        db.record_series_game(&names(&["bob", "alice"]), &[1.0, 0.0])
+            .unwrap();
+        let series = db
+            .record_series_game(&names(&["alice", "bob"]), &[0.5, 0.5])
+            .unwrap();
+        assert_eq!(series.players, names(&["alice", "bob"]));
+        assert_eq!(series.wins, vec![0.5, 1.5]);
+        assert_eq!(series.games, 2);
+        assert_eq!(db.series(&names(&["bob", "alice"])).unwrap(), Some(series));
+
+        assert!(db.series(&names(&["alice", "carol"])).unwrap().is_none());
+        assert!(db.record_series_game(&names(&["alice"]), &[]).is_err());
+        assert_eq!(db.series_for("alice", 10).unwrap().len(), 1);
+        assert!(db.series_for("carol", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rating_changes() {
+        let changes = rating_changes(&[(1500.0, 40), (1500.0, 30), (1500.0, 30)]);
+        assert_eq!(changes, vec![16.0, -8.0, -8.0]);
+        assert_eq!(player_kind("model:weights/latest.pt"), "model");
+        assert_eq!(player_kind("alice"), "human");
+    }
+}
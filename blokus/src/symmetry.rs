@@ -0,0 +1,104 @@
+/*
+Mirror symmetry of positions, used to search one move of each mirrored pair
+The only reflection of the board that keeps the player to move in their corner is the one
+across the diagonal through that corner, and it swaps the two neighbouring colors. A position
+is symmetric when reflecting it and swapping those colors gives the same position back, as on
+the empty board, and then a tile and its mirror image lead to equivalent games.
+*/
+
+use crate::board::BOARD_SIZE as D;
+use crate::game::Game;
+use crate::opening_book::{from_perspective, to_perspective};
+
+const NUM_PLAYERS: usize = 4;
+
+/// Reflect a tile across the diagonal through the player's starting corner
+pub fn mirror(tile: usize, player: usize) -> usize {
+    let tile = to_perspective(tile, player);
+    from_perspective((tile % D) * D + tile / D, player)
+}
+
+/// Whether the position looks the same to the player to move after reflecting it
+/// The neighbouring colors must also hold the same pieces, be in the same state, and have
+/// placed pieces of the same size last, so that the final scores match.
+pub fn is_symmetric(game: &Game) -> bool {
+    let player = game.current_player();
+    let (next, previous) = ((player + 1) % NUM_PLAYERS, (player + 3) % NUM_PLAYERS);
+    let pieces = game.get_piece_state(); // By seat after the player to move
+    if pieces[1] != pieces[3]
+        || game.is_player_active(next) != game.is_player_active(previous)
+        || game.last_piece_len(next) != game.last_piece_len(previous)
+    {
+        return false;
+    }
+
+    let swap = |owner: i32| match owner {
+        o if o == next as i32 => previous as i32,
+        o if o == previous as i32 => next as i32,
+        o => o,
+    };
+    let ownership = game.get_ownership();
+    (0..D * D).all(|tile| ownership[mirror(tile, player)] == swap(ownership[tile]))
+}
+
+/// The tile standing for a mirrored pair, the smaller of the two
+pub fn canonical(tile: usize, player: usize) -> usize {
+    tile.min(mirror(tile, player))
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror() {
+        for player in 0..NUM_PLAYERS {
+            for tile in [0, 1, 21, 57, 399] {
+                assert_eq!(mirror(mirror(tile, player), player), tile);
+            }
+        }
+        assert_eq!(mirror(1, 0), 20);
+        assert_eq!(mirror(19, 1), 19); // Player 1's corner stays put
+    }
+
+    #[test]
+    fn test_is_symmetric() {
+        let game = Game::reset();
+        assert!(is_symmetric(&game));
+
+        // A tile on the diagonal keeps the symmetry, one beside it breaks it
+        let mut started = game.clone();
+        started.apply(0, None).unwrap();
+        assert!(is_symmetric(&started));
+        started.apply(1, None).unwrap();
+        assert!(!is_symmetric(&started));
+
+        // The next player sees the first player's pieces against the full set across the board
+        let next = game.place_piece(9, 0, 0).unwrap();
+        assert_eq!(next.current_player(), 1);
+        assert!(!is_symmetric(&next));
+
+        // With red and green out, blue's position is symmetric when blue and yellow keep to
+        // the diagonal, even though the two of them hold different pieces
+        let place = |game: &Game, tiles: &[usize]| {
+            let player = game.current_player();
+            let (piece, variant, offset) = *game
+                .get_legal_moves()
+                .iter()
+                .find(|(piece, variant, offset)| {
+                    let shape = game.get_piece(player, *piece, *variant);
+                    let mut placed: Vec<usize> = shape.offsets.iter().map(|t| offset + t).collect();
+                    placed.sort();
+                    placed == tiles
+                })
+                .unwrap();
+            game.place_piece(piece, variant, offset).unwrap()
+        };
+        let mut game = Game::reset().pass().unwrap();
+        game = place(&game, &[18, 19, 39]);
+        game = place(&game.pass().unwrap(), &[380]);
+        assert_eq!(game.current_player(), 1);
+        assert!(is_symmetric(&game));
+    }
+}
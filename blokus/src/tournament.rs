@@ -0,0 +1,293 @@
+/*
+Round-robin tournaments between agents
+Each pairing is played by two agents holding two seats each, alternating around
+the table, and the seats are swapped every other game so neither agent always
+moves first. A game is won by the agent holding the highest scoring seat, and
+drawn when both agents share the top score. Every game is kept so it can be stored.
+New checkpoints are also played against the scripted baselines, whose win rates are
+kept over time, since beating the previous model says nothing about the rest.
+*/
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::agents::{self, Agent};
+use crate::game::Game;
+use crate::records::PlayedGame;
+
+/// Win and draw counts between two agents, from the first agent's side
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PairingResult {
+    pub first: String,
+    pub second: String,
+    pub games: u32,
+    pub first_wins: u32,
+    pub second_wins: u32,
+    pub draws: u32,
+    #[serde(skip)]
+    pub played: Vec<PlayedGame>, // Seats are named by agent spec
+}
+
+/// Overall record of one agent, a win is worth one point and a draw half
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Standing {
+    pub agent: String,
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub points: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TournamentResult {
+    pub pairings: Vec<PairingResult>,
+    pub standings: Vec<Standing>, // Best first
+}
+
+/// Play one game, `seats` gives the index of the agent sitting in each seat
+/// Returns which agents hold the top score, and the game with seats named by `names`
+fn play_seated(
+    agents: &mut [Box<dyn Agent>],
+    names: &[&str],
+    seats: [usize; 4],
+) -> Result<(Vec<usize>, PlayedGame), String> {
+    let mut game = Game::reset();
+    let mut played = PlayedGame::new(seats.iter().map(|s| names[*s].to_string()).collect());
+    while !game.is_terminal() {
+        let agent = &mut agents[seats[game.current_player()]];
+        let chosen = agent.choose_move(&game)?;
+        played.push(&game, chosen);
+        let (piece, variant, offset) = chosen;
+        game = game.place_piece(piece, variant, offset)?;
+    }
+    played.finish(&game);
+
+    let scores = game.get_score();
+    let best = *scores.iter().max().unwrap();
+    let mut winners: Vec<usize> = (0..4)
+        .filter(|p| scores[*p] == best)
+        .map(|p| seats[p])
+        .collect();
+    winners.sort();
+    winners.dedup();
+    Ok((winners, played))
+}
+
+/// Play `games` games between two agent specs, see `agents::from_spec`
+/// Game `i` seeds its agents with `seed + i`, so results can be reproduced
+pub fn play_pairing(
+    first: &str,
+    second: &str,
+    games: u32,
+    seed: u64,
+) -> Result<PairingResult, String> {
+    let mut result = PairingResult {
+        first: first.to_string(),
+        second: second.to_string(),
+        games,
+        first_wins: 0,
+        second_wins: 0,
+        draws: 0,
+        played: Vec::new(),
+    };
+
+    for i in 0..games {
+        let game_seed = seed + i as u64;
+        let mut agents = vec![
+            agents::from_spec(first, Some(game_seed))?,
+            agents::from_spec(second, Some(game_seed + 1))?,
+        ];
+        let seats = if i % 2 == 0 {
+            [0, 1, 0, 1]
+        } else {
+            [1, 0, 1, 0]
+        };
+        let (winners, played) = play_seated(&mut agents, &[first, second], seats)?;
+        match winners.as_slice() {
+            [0] => result.first_wins += 1,
+            [1] => result.second_wins += 1,
+            _ => result.draws += 1,
+        }
+        result.played.push(played);
+    }
+
+    Ok(result)
+}
+
+impl PairingResult {
+    /// Share of the games won by the first agent, draws count half
+    pub fn win_rate(&self) -> f32 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        (self.first_wins as f32 + 0.5 * self.draws as f32) / self.games as f32
+    }
+}
+
+/// Scripted agents every checkpoint is checked against
+pub const BASELINES: [&str; 3] = ["random", "greedy", "blocking"];
+
+/// Play an agent against each of the baselines, the agent is always first
+pub fn play_baselines(agent: &str, games: u32, seed: u64) -> Result<Vec<PairingResult>, String> {
+    BASELINES
+        .iter()
+        .map(|baseline| play_pairing(agent, baseline, games, seed))
+        .collect()
+}
+
+/// Append one line per baseline for a round to a CSV history, with a header for a new file
+pub fn append_baseline_history(
+    path: &str,
+    round: usize,
+    pairings: &[PairingResult],
+) -> Result<(), String> {
+    let mut output = String::new();
+    if !Path::new(path).exists() {
+        output.push_str("round,agent,baseline,games,wins,draws,losses,win_rate\n");
+    }
+    for p in pairings {
+        output.push_str(&format!(
+            "{},{},{},{},{},{},{},{:.3}\n",
+            round,
+            p.first,
+            p.second,
+            p.games,
+            p.first_wins,
+            p.draws,
+            p.second_wins,
+            p.win_rate()
+        ));
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(output.as_bytes()))
+        .map_err(|e| format!("Failed to write baseline history {}: {}", path, e))
+}
+
+/// Play every pair of agents against each other
+pub fn round_robin(
+    specs: &[String],
+    games_per_pairing: u32,
+    seed: u64,
+) -> Result<TournamentResult, String> {
+    let mut pairings = Vec::new();
+    for i in 0..specs.len() {
+        for j in i + 1..specs.len() {
+            pairings.push(play_pairing(&specs[i], &specs[j], games_per_pairing, seed)?);
+        }
+    }
+
+    let mut standings: Vec<Standing> = specs
+        .iter()
+        .map(|spec| {
+            let mut standing = Standing {
+                agent: spec.clone(),
+                games: 0,
+                wins: 0,
+                draws: 0,
+                losses: 0,
+                points: 0.0,
+            };
+            for pairing in &pairings {
+                let (wins, losses) = if &pairing.first == spec {
+                    (pairing.first_wins, pairing.second_wins)
+                } else if &pairing.second == spec {
+                    (pairing.second_wins, pairing.first_wins)
+                } else {
+                    continue;
+                };
+                standing.games += pairing.games;
+                standing.wins += wins;
+                standing.losses += losses;
+                standing.draws += pairing.draws;
+            }
+            standing.points = standing.wins as f32 + 0.5 * standing.draws as f32;
+            standing
+        })
+        .collect();
+    standings.sort_by(|a, b| b.points.total_cmp(&a.points));
+
+    Ok(TournamentResult {
+        pairings,
+        standings,
+    })
+}
+
+impl TournamentResult {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// One line per pairing with a header
+    pub fn pairings_csv(&self) -> String {
+        let mut output = String::from("first,second,games,first_wins,second_wins,draws\n");
+        for p in &self.pairings {
+            output.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                p.first, p.second, p.games, p.first_wins, p.second_wins, p.draws
+            ));
+        }
+        output
+    }
+
+    /// One line per agent with a header, best first
+    pub fn standings_csv(&self) -> String {
+        let mut output = String::from("agent,games,wins,draws,losses,points\n");
+        for s in &self.standings {
+            output.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                s.agent, s.games, s.wins, s.draws, s.losses, s.points
+            ));
+        }
+        output
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_robin() {
+        let specs = vec!["random".to_string(), "random".to_string()];
+        let result = round_robin(&specs, 2, 7).unwrap();
+        assert_eq!(result.pairings.len(), 1);
+        let pairing = &result.pairings[0];
+        assert_eq!(pairing.first_wins + pairing.second_wins + pairing.draws, 2);
+        assert_eq!(pairing.played.len(), 2);
+        assert!(pairing.played.iter().all(|p| p.is_finished()));
+
+        // Seeded games are reproducible
+        assert_eq!(round_robin(&specs, 2, 7).unwrap(), result);
+        assert_eq!(result.pairings_csv().lines().count(), 2);
+        assert!(result.to_json().contains("\"standings\""));
+    }
+
+    #[test]
+    fn test_baseline_history() {
+        let pairings = play_baselines("random", 1, 3).unwrap();
+        assert_eq!(pairings.len(), BASELINES.len());
+        assert!(pairings.iter().all(|p| (0.0..=1.0).contains(&p.win_rate())));
+
+        // The header is only written once
+        let path = std::env::temp_dir().join(format!("baselines-{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        append_baseline_history(path, 0, &pairings).unwrap();
+        append_baseline_history(path, 1, &pairings).unwrap();
+        let history = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(history.lines().count(), 1 + 2 * BASELINES.len());
+        assert!(history
+            .lines()
+            .nth(4)
+            .unwrap()
+            .starts_with("1,random,random,1,"));
+    }
+}
@@ -0,0 +1,358 @@
+/*
+Self-play and training loop in Rust with tch, enabled by the `train` feature
+Mirrors model/resnet.py and the loop in model/training.py so the whole pipeline
+can run as one binary. Variables are named like the PyTorch module's state dict,
+so checkpoints load in Python for ONNX export with model/export_onnx.py.
+*/
+
+use tch::nn::{self, OptimizerConfig};
+use tch::{Device, Kind, Tensor};
+
+use crate::board::BOARD_SIZE;
+use crate::evaluator::Evaluator;
+use crate::game::{Game, StateOptions, Variant};
+use crate::opening_book::from_perspective;
+use crate::replay_buffer::{encode_state, Example, ReplayBuffer};
+use crate::search::{self, SearchConfig};
+use crate::tournament;
+
+const D: i64 = BOARD_SIZE as i64;
+
+/// Same padding for the 3x3 convolutions
+fn padded() -> nn::ConvConfig {
+    nn::ConvConfig {
+        padding: 1,
+        ..Default::default()
+    }
+}
+
+/// Parameters for the Rust training loop, defaults follow the Python test config
+#[derive(Clone, Debug)]
+pub struct TrainConfig {
+    pub training_rounds: usize,
+    pub games_per_round: usize,
+    pub training_steps: usize,
+    pub batch_size: usize,
+    pub learning_rate: f64,
+    pub buffer_capacity: usize,
+    pub nn_depth: usize,
+    pub nn_width: i64,
+    pub search: SearchConfig,
+    pub variant: Variant,    // Rules the self-play games follow
+    pub state: StateOptions, // Network input layout, team-relative for cooperative agents
+    pub baseline_games: u32, // Games per round against each scripted baseline, 0 to skip them
+}
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        TrainConfig {
+            training_rounds: 2,
+            games_per_round: 4,
+            training_steps: 10,
+            batch_size: 64,
+            learning_rate: 0.01,
+            buffer_capacity: 500000,
+            nn_depth: 2,
+            nn_width: 16,
+            search: SearchConfig {
+                sims_per_move: 10,
+                ..SearchConfig::default()
+            },
+            variant: Variant::Classic,
+            state: StateOptions::default(),
+            baseline_games: 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ResidualBlock {
+    conv1: nn::Conv2D,
+    conv2: nn::Conv2D,
+    bn1: nn::BatchNorm,
+    bn2: nn::BatchNorm,
+}
+
+impl ResidualBlock {
+    fn new(p: &nn::Path, width: i64) -> ResidualBlock {
+        ResidualBlock {
+            conv1: nn::conv2d(p / "conv1", width, width, 3, padded()),
+            conv2: nn::conv2d(p / "conv2", width, width, 3, padded()),
+            bn1: nn::batch_norm2d(p / "bn1", width, Default::default()),
+            bn2: nn::batch_norm2d(p / "bn2", width, Default::default()),
+        }
+    }
+
+    fn forward_t(&self, x: &Tensor, train: bool) -> Tensor {
+        let y = x
+            .apply(&self.conv1)
+            .apply_t(&self.bn1, train)
+            .relu()
+            .apply(&self.conv2)
+            .apply_t(&self.bn2, train);
+        (y + x).relu()
+    }
+}
+
+/// Policy and value network, see model/resnet.py
+#[derive(Debug)]
+pub struct ResNet {
+    input: nn::Conv2D,
+    res_blocks: Vec<ResidualBlock>,
+    policy_conv: nn::Conv2D,
+    policy_bn: nn::BatchNorm,
+    value_conv: nn::Conv2D,
+    value_bn: nn::BatchNorm,
+    value_linear: nn::Linear,
+}
+
+impl ResNet {
+    /// A network taking positions with the given number of planes, see `StateOptions::planes`
+    pub fn new(p: &nn::Path, planes: i64, blocks: usize, width: i64) -> ResNet {
+        let res_path = p / "res_blocks";
+        let policy_path = p / "policy_head";
+        let value_path = p / "value_head";
+        ResNet {
+            input: nn::conv2d(p / "input", planes, width, 3, padded()),
+            res_blocks: (0..blocks)
+                .map(|i| ResidualBlock::new(&(&res_path / i), width))
+                .collect(),
+            policy_conv: nn::conv2d(&policy_path / 0, width, 1, 1, Default::default()),
+            policy_bn: nn::batch_norm2d(&policy_path / 1, 1, Default::default()),
+            value_conv: nn::conv2d(&value_path / 0, width, 1, 1, Default::default()),
+            value_bn: nn::batch_norm2d(&value_path / 1, 1, Default::default()),
+            value_linear: nn::linear(&value_path / 4, D * D, 4, Default::default()),
+        }
+    }
+
+    /// Get the policy and value for a batch of board states
+    pub fn forward_t(&self, boards: &Tensor, train: bool) -> (Tensor, Tensor) {
+        // ResNet backbone
+        let mut x = boards.apply(&self.input);
+        for block in &self.res_blocks {
+            x = block.forward_t(&x, train);
+        }
+
+        // Policy head - mask out illegal moves so they are 0 in policy
+        let policy = x
+            .apply(&self.policy_conv)
+            .apply_t(&self.policy_bn, train)
+            .relu()
+            .flatten(1, -1);
+        let mask = boards.select(1, 4).flatten(1, -1);
+        let policy = (&policy * &mask + (&mask - 1.0) * 1e9).softmax(-1, Kind::Float) * &mask;
+
+        // Value head
+        let value = x
+            .apply(&self.value_conv)
+            .apply_t(&self.value_bn, train)
+            .relu()
+            .flatten(1, -1)
+            .apply(&self.value_linear)
+            .tanh()
+            .softmax(-1, Kind::Float);
+
+        (policy, value)
+    }
+}
+
+/// Run the network on one position, returning the policy and values in board order
+fn evaluate_position(
+    net: &ResNet,
+    device: Device,
+    game: &Game,
+    options: &StateOptions,
+) -> Result<(Vec<f32>, Vec<f32>), String> {
+    let planes = options.planes().len() as i64;
+    let input = Tensor::from_slice(&encode_state(game, options))
+        .view([1, planes, D, D])
+        .to_device(device);
+    let (policy, value) = tch::no_grad(|| net.forward_t(&input, false));
+    let policy: Vec<f32> =
+        Vec::try_from(policy.flatten(0, -1).to_device(Device::Cpu)).map_err(|e| e.to_string())?;
+    let mut value: Vec<f32> =
+        Vec::try_from(value.flatten(0, -1).to_device(Device::Cpu)).map_err(|e| e.to_string())?;
+
+    // Rotate back from the player's perspective to board order
+    let player = game.current_player();
+    let turns = options.turns(player);
+    let mut board_policy = vec![0.0; policy.len()];
+    for (tile, prob) in policy.iter().enumerate() {
+        board_policy[from_perspective(tile, turns)] = *prob;
+    }
+    value.rotate_right(player);
+    Ok((board_policy, value))
+}
+
+/// Evaluator that runs the network being trained
+pub struct TchEvaluator<'a> {
+    net: &'a ResNet,
+    device: Device,
+    options: StateOptions,
+}
+
+impl Evaluator for TchEvaluator<'_> {
+    fn evaluate(&mut self, game: &Game) -> Result<(Vec<f32>, Vec<f32>), String> {
+        evaluate_position(self.net, self.device, game, &self.options)
+    }
+}
+
+/// Evaluator that owns a network loaded from a checkpoint
+pub struct ModelEvaluator {
+    vs: nn::VarStore,
+    net: ResNet,
+}
+
+impl ModelEvaluator {
+    /// Load a checkpoint saved by `Trainer::run` with the default network shape
+    pub fn load(path: &str) -> Result<ModelEvaluator, String> {
+        let config = TrainConfig::default();
+        let mut vs = nn::VarStore::new(Device::cuda_if_available());
+        let planes = config.state.planes().len() as i64;
+        let net = ResNet::new(&vs.root(), planes, config.nn_depth, config.nn_width);
+        vs.load(path)
+            .map_err(|e| format!("Failed to load model {}: {}", path, e))?;
+        Ok(ModelEvaluator { vs, net })
+    }
+}
+
+impl Evaluator for ModelEvaluator {
+    fn evaluate(&mut self, game: &Game) -> Result<(Vec<f32>, Vec<f32>), String> {
+        evaluate_position(&self.net, self.vs.device(), game, &StateOptions::default())
+    }
+}
+
+/// Owns the network, optimizer, and replay buffer for a training run
+pub struct Trainer {
+    vs: nn::VarStore,
+    net: ResNet,
+    optimizer: nn::Optimizer,
+    buffer: ReplayBuffer,
+    config: TrainConfig,
+}
+
+impl Trainer {
+    pub fn new(config: TrainConfig) -> Result<Trainer, String> {
+        let vs = nn::VarStore::new(Device::cuda_if_available());
+        let planes = config.state.planes().len() as i64;
+        let net = ResNet::new(&vs.root(), planes, config.nn_depth, config.nn_width);
+        let optimizer = nn::Adam::default()
+            .build(&vs, config.learning_rate)
+            .map_err(|e| e.to_string())?;
+        Ok(Trainer {
+            vs,
+            net,
+            optimizer,
+            buffer: ReplayBuffer::new(config.buffer_capacity),
+            config,
+        })
+    }
+
+    /// Play one game with MCTS and the current network
+    fn self_play_game(&self) -> Result<(Vec<Example>, Vec<f32>), String> {
+        let mut game = Game::new(self.config.variant);
+        let mut evaluator = TchEvaluator {
+            net: &self.net,
+            device: self.vs.device(),
+            options: self.config.state,
+        };
+        let mut examples = Vec::new();
+        while !game.is_terminal() {
+            let root = search::search(&game, &self.config.search, &mut evaluator)?;
+            let policy = search::visit_policy(&root, &game, &self.config.search);
+            examples.push(Example::new(&game, &policy, &self.config.state));
+            let action = search::choose_action(&game, &root, examples.len(), &self.config.search)?;
+            game.apply(action, None)?;
+        }
+        Ok((examples, self.config.search.payoff.of(&game)))
+    }
+
+    /// Take one optimizer step on a batch, returns the policy and value losses
+    fn train_step(&mut self, batch: &[Example]) -> (f64, f64) {
+        let n = batch.len() as i64;
+        let device = self.vs.device();
+        let states: Vec<f32> = batch.iter().flat_map(|e| e.state.clone()).collect();
+        let policies: Vec<f32> = batch.iter().flat_map(|e| e.policy.clone()).collect();
+        let values: Vec<f32> = batch.iter().flat_map(|e| e.values.clone()).collect();
+        let planes = self.config.state.planes().len() as i64;
+        let states = Tensor::from_slice(&states)
+            .view([n, planes, D, D])
+            .to_device(device);
+        let policies = Tensor::from_slice(&policies)
+            .view([n, D * D])
+            .to_device(device);
+        let values = Tensor::from_slice(&values).view([n, 4]).to_device(device);
+
+        let (policy, value) = self.net.forward_t(&states, true);
+        let policy_loss = -(policies * (policy + 1e-8).log()).sum(Kind::Float) / n as f64;
+        let value_loss = value.mse_loss(&values, tch::Reduction::Mean);
+        self.optimizer.backward_step(&(&policy_loss + &value_loss));
+        (policy_loss.double_value(&[]), value_loss.double_value(&[]))
+    }
+
+    /// Alternate rounds of self-play and training, saving a checkpoint after each round
+    /// A .safetensors path keeps the PyTorch parameter names
+    /// Each checkpoint then plays the scripted baselines, and the win rates are appended to
+    /// `<checkpoint>.baselines.csv` so regressions against simple opponents show up over time
+    pub fn run(&mut self, checkpoint: &str) -> Result<(), String> {
+        let mut rng = rand::thread_rng();
+        let search = &self.config.search;
+        tracing::info!(
+            sims = search.sims_per_move,
+            c_init = search.c_init,
+            fpu_reduction = ?search.fpu_reduction,
+            prior_temperature = search.prior_temperature,
+            "search config"
+        );
+        for round in 0..self.config.training_rounds {
+            let span = tracing::info_span!("round", round);
+            let _round = span.enter();
+            for _ in 0..self.config.games_per_round {
+                let (examples, payoff) = self.self_play_game()?;
+                self.buffer.add_game(examples, &payoff);
+            }
+
+            let mut losses = (0.0, 0.0);
+            for _ in 0..self.config.training_steps {
+                let batch: Vec<Example> = self
+                    .buffer
+                    .sample(self.config.batch_size, &mut rng)
+                    .into_iter()
+                    .cloned()
+                    .collect();
+                losses = self.train_step(&batch);
+            }
+            tracing::info!(
+                examples = self.buffer.len(),
+                policy_loss = losses.0,
+                value_loss = losses.1,
+                "trained"
+            );
+
+            self.vs.save(checkpoint).map_err(|e| e.to_string())?;
+            self.play_baselines(checkpoint, round)?;
+        }
+
+        Ok(())
+    }
+
+    /// Play the saved checkpoint against the scripted baselines and record the win rates
+    /// Checkpoints are loaded as `model:` agents, which only know the default input layout
+    fn play_baselines(&self, checkpoint: &str, round: usize) -> Result<(), String> {
+        if self.config.baseline_games == 0 || self.config.state != StateOptions::default() {
+            return Ok(());
+        }
+        let agent = format!("model:{}", checkpoint);
+        let pairings =
+            tournament::play_baselines(&agent, self.config.baseline_games, round as u64)?;
+        for pairing in &pairings {
+            tracing::info!(
+                win_rate = pairing.win_rate(),
+                against = %pairing.second,
+                "baseline games"
+            );
+        }
+        let history = format!("{}.baselines.csv", checkpoint);
+        tournament::append_baseline_history(&history, round, &pairings)
+    }
+}
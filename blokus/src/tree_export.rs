@@ -0,0 +1,242 @@
+/*
+Export of MCTS trees for visualization and debugging
+Saved trees keep the position they were searched from, so a later session can load one to
+look over a blunder, or carry on searching from it, without running the search again.
+*/
+
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::Game;
+use crate::node::Node;
+use crate::position::{from_position, to_position, Editor};
+
+/// Limits on which parts of a tree are exported
+/// Search trees get large quickly, so only well visited, shallow nodes are kept
+#[derive(Clone, Copy, Debug)]
+pub struct Pruning {
+    pub max_depth: usize,
+    pub min_visits: u32,
+}
+
+impl Default for Pruning {
+    fn default() -> Self {
+        Pruning {
+            max_depth: 3,
+            min_visits: 1,
+        }
+    }
+}
+
+/// Plain copy of a search node that can be written to JSON
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportedNode {
+    pub tile: Option<usize>, // Action leading to this node, None for the root
+    pub to_play: usize,
+    pub visits: u32,
+    pub prior: f32,
+    pub value: f32,
+    pub children: Vec<ExportedNode>,
+}
+
+impl ExportedNode {
+    pub fn from_node(node: &Node, pruning: Pruning) -> ExportedNode {
+        ExportedNode::build(None, node, pruning, 0)
+    }
+
+    fn build(tile: Option<usize>, node: &Node, pruning: Pruning, depth: usize) -> ExportedNode {
+        let mut children = Vec::new();
+        if depth < pruning.max_depth {
+            let mut kept: Vec<(&usize, &Node)> = node
+                .children
+                .iter()
+                .filter(|(_, child)| child.visits >= pruning.min_visits)
+                .collect();
+            kept.sort_by(|a, b| b.1.visits.cmp(&a.1.visits).then(a.0.cmp(b.0)));
+            for (child_tile, child) in kept {
+                children.push(ExportedNode::build(
+                    Some(*child_tile),
+                    child,
+                    pruning,
+                    depth + 1,
+                ));
+            }
+        }
+
+        ExportedNode {
+            tile,
+            to_play: node.to_play,
+            visits: node.visits,
+            prior: node.prior,
+            value: node.value(),
+            children,
+        }
+    }
+
+    /// Rebuild a search node, values are kept as averages so the sums come back rounded
+    pub fn to_node(&self) -> Node {
+        let mut node = Node::new(self.prior);
+        node.to_play = self.to_play;
+        node.visits = self.visits;
+        node.value_sum = self.value * self.visits as f32;
+        for child in &self.children {
+            if let Some(tile) = child.tile {
+                node.children.insert(tile, child.to_node());
+            }
+        }
+        node
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Render the tree as a Graphviz digraph
+    pub fn to_dot(&self) -> String {
+        let mut output = String::from("digraph mcts {\n    node [shape=box];\n");
+        let mut next_id = 0;
+        self.write_dot(&mut output, &mut next_id);
+        output.push_str("}\n");
+        output
+    }
+
+    fn write_dot(&self, output: &mut String, next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+        let name = match self.tile {
+            Some(tile) => format!("tile {}", tile),
+            None => "root".to_string(),
+        };
+        output.push_str(&format!(
+            "    n{} [label=\"{}\\nplayer {}\\nN={} Q={:.3} P={:.3}\"];\n",
+            id, name, self.to_play, self.visits, self.value, self.prior
+        ));
+
+        for child in &self.children {
+            let child_id = child.write_dot(output, next_id);
+            output.push_str(&format!("    n{} -> n{};\n", id, child_id));
+        }
+        id
+    }
+}
+
+/// A pruned search tree with the position it was searched from
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedTree {
+    pub position: String, // Position at the start of the turn, see position::to_position
+    pub turn_tiles: Vec<usize>, // Tiles already placed this turn, in order
+    pub root: ExportedNode,
+}
+
+impl SavedTree {
+    pub fn new(game: &Game, root: &Node, pruning: Pruning) -> Result<SavedTree, String> {
+        let mut editor = Editor::from_game(game);
+        for tile in game.turn_tiles() {
+            editor.set_square(*tile, None);
+        }
+        Ok(SavedTree {
+            position: to_position(&editor.to_game()?),
+            turn_tiles: game.turn_tiles().to_vec(),
+            root: ExportedNode::from_node(root, pruning),
+        })
+    }
+
+    /// The game the tree was searched from
+    pub fn game(&self) -> Result<Game, String> {
+        let mut game = from_position(&self.position)?;
+        for tile in &self.turn_tiles {
+            game.apply(*tile, None)?;
+        }
+        Ok(game)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("Error writing search tree to {}: {}", path, e))
+    }
+
+    pub fn load(path: &str) -> Result<SavedTree, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading search tree from {}: {}", path, e))?;
+        serde_json::from_str(&text).map_err(|e| format!("Invalid search tree in {}: {}", path, e))
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::HeuristicEvaluator;
+    use crate::search::{search, SearchConfig};
+
+    fn sample_tree() -> Node {
+        let mut root = Node::new(0.0);
+        root.visits = 3;
+        let mut visited = Node::new(0.6);
+        visited.visits = 2;
+        visited.value_sum = 1.0;
+        visited.children.insert(5, Node::new(1.0));
+        root.children.insert(1, visited);
+        root.children.insert(2, Node::new(0.4));
+        root
+    }
+
+    #[test]
+    fn test_pruning() {
+        let exported = ExportedNode::from_node(&sample_tree(), Pruning::default());
+        assert_eq!(exported.visits, 3);
+        assert_eq!(exported.children.len(), 1);
+        assert_eq!(exported.children[0].tile, Some(1));
+        assert_eq!(exported.children[0].value, 0.5);
+        assert!(exported.children[0].children.is_empty());
+
+        let everything = Pruning {
+            max_depth: 10,
+            min_visits: 0,
+        };
+        let exported = ExportedNode::from_node(&sample_tree(), everything);
+        assert_eq!(exported.children.len(), 2);
+        assert_eq!(exported.children[0].children.len(), 1);
+    }
+
+    #[test]
+    fn test_dot_output() {
+        let dot = ExportedNode::from_node(&sample_tree(), Pruning::default()).to_dot();
+        assert!(dot.starts_with("digraph mcts {"));
+        assert!(dot.contains("n0 -> n1;"));
+        assert!(dot.contains("tile 1"));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        // Part way through a piece, so the tiles placed this turn have to come back too
+        let mut game = Game::reset();
+        game.apply(0, None).unwrap();
+        let config = SearchConfig {
+            sims_per_move: 20,
+            ..SearchConfig::default()
+        };
+        let root = search(&game, &config, &mut HeuristicEvaluator::default()).unwrap();
+        let everything = Pruning {
+            max_depth: 100,
+            min_visits: 0,
+        };
+        let saved = SavedTree::new(&game, &root, everything).unwrap();
+
+        let path = std::env::temp_dir().join(format!("tree-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        saved.save(path).unwrap();
+        let loaded = SavedTree::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded, saved);
+
+        let reloaded = loaded.game().unwrap();
+        assert_eq!(reloaded.get_board(), game.get_board());
+        assert_eq!(reloaded.turn_tiles(), &[0]);
+        let node = loaded.root.to_node();
+        assert_eq!(node.visits, root.visits);
+        assert_eq!(node.children.len(), root.children.len());
+        assert_eq!(ExportedNode::from_node(&node, everything), saved.root);
+    }
+}
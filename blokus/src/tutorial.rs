@@ -0,0 +1,220 @@
+/*
+Rules tutorial: scripted positions that each teach one rule by asking for a placement
+A wrong placement is answered with the rule it breaks, from why_illegal, so the lessons
+and any other board that wants to explain a refused move give the same messages.
+*/
+
+use crate::board::BOARD_SIZE as D;
+use crate::game::{Game, Move, Variant};
+use crate::position::Editor;
+
+/// Why the player to move cannot make the move, or None if it is legal
+/// The rules are checked in the order a new player tends to run into them.
+pub fn why_illegal(game: &Game, (piece, variant, offset): Move) -> Option<String> {
+    let player = game.current_player();
+    let pieces = game.get_current_player_pieces();
+    let Some(shape) = pieces.get(piece).and_then(|p| p.variants.get(variant)) else {
+        return Some("That piece has already been played".to_string());
+    };
+    if offset + shape.variant.len() > D * D || offset % D + shape.width > D {
+        return Some("The piece has to fit on the board".to_string());
+    }
+
+    let squares: Vec<usize> = shape.offsets.iter().map(|t| offset + t).collect();
+    let board = game.get_board();
+    if squares.iter().any(|s| board[*s] & 0b1111 != 0) {
+        return Some("Pieces cannot overlap".to_string());
+    }
+    let restricted = 1 << (player + 4);
+    if squares.iter().any(|s| board[*s] & restricted != 0) {
+        return Some(
+            "Your pieces may not touch each other along an edge, only at the corners".to_string(),
+        );
+    }
+    let anchors = game.get_current_anchors();
+    if !squares.iter().any(|s| anchors.contains(s)) {
+        let first = !game.get_ownership().contains(&(player as i32));
+        return Some(if first {
+            "Your first piece has to cover your starting corner".to_string()
+        } else {
+            "Each piece has to touch one of your own pieces at a corner".to_string()
+        });
+    }
+    if !game.get_legal_moves().contains(&(piece, variant, offset)) {
+        return Some("The piece has to cover the squares already placed this turn".to_string());
+    }
+    None
+}
+
+/// What a lesson asks for, on top of the move being legal
+#[derive(Clone, Debug, PartialEq)]
+pub enum Goal {
+    Legal,
+    Touch(usize), // Share an edge with one of this player's squares
+}
+
+/// A position set up to practice one rule, always with Red to move
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lesson {
+    pub title: &'static str,
+    pub text: &'static str,
+    pub done: &'static str, // Shown once the goal is met
+    pub goal: Goal,
+    placed: &'static [(usize, usize, &'static [usize])], // Player, piece id, and its squares
+    red_hand: Option<u32>, // Red's pieces in hand as a mask of ids, or all but those placed
+}
+
+impl Lesson {
+    pub fn game(&self) -> Result<Game, String> {
+        let mut editor = Editor::new(Variant::Classic);
+        for (player, piece, squares) in self.placed {
+            squares
+                .iter()
+                .for_each(|s| editor.set_square(*s, Some(*player)));
+            editor.toggle_piece(*player, *piece);
+        }
+        if let Some(hand) = self.red_hand {
+            editor.pieces[0] = hand;
+        }
+        editor.to_game()
+    }
+
+    /// Check a placement in the lesson's position, the error says what is wrong with it
+    pub fn check(&self, game: &Game, placement: Move) -> Result<(), String> {
+        if let Some(reason) = why_illegal(game, placement) {
+            return Err(reason);
+        }
+        match self.goal {
+            Goal::Legal => Ok(()),
+            Goal::Touch(other) => {
+                let (piece, variant, offset) = placement;
+                let shape = game.get_piece(game.current_player(), piece, variant);
+                let ownership = game.get_ownership();
+                let touches = shape.offsets.iter().map(|t| offset + t).any(|s| {
+                    let (row, col) = (s / D, s % D);
+                    (row > 0 && ownership[s - D] == other as i32)
+                        || (row + 1 < D && ownership[s + D] == other as i32)
+                        || (col > 0 && ownership[s - 1] == other as i32)
+                        || (col + 1 < D && ownership[s + 1] == other as i32)
+                });
+                if touches {
+                    Ok(())
+                } else {
+                    Err("That is legal, but it does not touch the other color".to_string())
+                }
+            }
+        }
+    }
+}
+
+const SQUARE: usize = 7;
+const FOUR: usize = 4;
+const RED_SQUARE: (usize, usize, &[usize]) = (0, SQUARE, &[0, 1, 20, 21]);
+
+/// The lessons in order: starting, corners, other colors, and scoring
+pub fn lessons() -> Vec<Lesson> {
+    vec![
+        Lesson {
+            title: "Start in your corner",
+            text: "Each color starts from its own corner of the board. Red's first piece has \
+                   to cover the top left square, a1. Place any piece there.",
+            done: "Every color opens the same way, from its corner.",
+            goal: Goal::Legal,
+            placed: &[],
+            red_hand: None,
+        },
+        Lesson {
+            title: "Corners only",
+            text: "Every new piece has to touch one of your own pieces corner to corner, \
+                   and may never share an edge with them. Place a piece touching the \
+                   corner of your square.",
+            done: "The squares where your next piece can touch a corner are your anchors.",
+            goal: Goal::Legal,
+            placed: &[RED_SQUARE],
+            red_hand: None,
+        },
+        Lesson {
+            title: "Other colors",
+            text: "Only your own pieces are kept apart. Your pieces may lie right up against \
+                   other colors, which is how you block them. Place a piece that touches \
+                   Blue along an edge.",
+            done: "Squares you take from another color are squares they can no longer use.",
+            goal: Goal::Touch(1),
+            placed: &[RED_SQUARE, (1, FOUR, &[44, 64, 84, 104])],
+            red_hand: None,
+        },
+        Lesson {
+            title: "Scoring",
+            text: "When nobody can move, each color scores a point for every square on the \
+                   board and loses one for every square left in hand. Playing every piece \
+                   earns 15 more, and 5 on top when the last one is the single square. Red \
+                   only has the single square left, finish the set with it.",
+            done: "All pieces played for 15, and 5 more for ending on the single square.",
+            goal: Goal::Legal,
+            placed: &[RED_SQUARE],
+            red_hand: Some(1), // The single square is piece 0
+        },
+    ]
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_why_illegal() {
+        let game = Game::reset();
+        let legal = *game.get_legal_moves().iter().next().unwrap();
+        assert_eq!(why_illegal(&game, legal), None);
+        assert!(why_illegal(&game, (0, 0, 210))
+            .unwrap()
+            .contains("starting corner"));
+        assert!(why_illegal(&game, (4, 0, 399))
+            .unwrap()
+            .contains("fit on the board"));
+        assert!(why_illegal(&game, (30, 0, 0)).unwrap().contains("already"));
+
+        let game = lessons()[1].game().unwrap();
+        assert!(why_illegal(&game, (0, 0, 0)).unwrap().contains("overlap"));
+        assert!(why_illegal(&game, (0, 0, 2))
+            .unwrap()
+            .contains("along an edge"));
+        assert!(why_illegal(&game, (0, 0, 43))
+            .unwrap()
+            .contains("at a corner"));
+    }
+
+    #[test]
+    fn test_lessons() {
+        for lesson in lessons() {
+            let game = lesson.game().unwrap();
+            assert_eq!(game.current_player(), 0);
+            assert!(game
+                .get_legal_moves()
+                .iter()
+                .any(|m| lesson.check(&game, *m).is_ok()));
+        }
+
+        // Down the side misses Blue, across reaches it
+        let lesson = &lessons()[2];
+        let game = lesson.game().unwrap();
+        let two = game
+            .get_current_player_pieces()
+            .iter()
+            .position(|p| p.points == 2)
+            .unwrap();
+        let variant = |width| {
+            (0..2)
+                .find(|v| game.get_piece(0, two, *v).width == width)
+                .unwrap()
+        };
+        assert!(lesson.check(&game, (two, variant(1), 42)).is_err());
+        assert_eq!(lesson.check(&game, (two, variant(2), 42)), Ok(()));
+
+        let scoring = lessons()[3].game().unwrap();
+        let last = scoring.place_piece(0, 0, 42).unwrap();
+        assert_eq!(last.get_score_breakdown()[0].all_played, 15);
+        assert_eq!(last.get_score_breakdown()[0].monomino_last, 5);
+    }
+}
@@ -0,0 +1,161 @@
+/*
+Shallow minimax check of MCTS candidates
+*/
+
+use crate::evaluator::{Evaluator, HeuristicEvaluator};
+use crate::game::{Game, Move};
+use crate::node::Node;
+
+/// Settings for verifying the search's favourite moves before playing them
+#[derive(Clone, Debug)]
+pub struct Verification {
+    pub top_k: usize,               // Number of most visited root tiles to check
+    pub placements_per_tile: usize, // Largest placements tried through each tile
+    pub depth: usize,               // Opponent turns to look ahead
+    pub margin: f32,                // Allowed drop in worst case value to keep the search's choice
+}
+
+impl Default for Verification {
+    fn default() -> Self {
+        Verification {
+            top_k: 3,
+            placements_per_tile: 4,
+            depth: 1,
+            margin: 0.05,
+        }
+    }
+}
+
+/// Value of a position for a player when the next opponents try to block them
+/// Opponents only consider replies that occupy one of the player's anchors, one
+/// per anchor, or playing elsewhere which leaves the position as it is.
+pub fn worst_case_value(
+    game: &Game,
+    player: usize,
+    depth: usize,
+    evaluator: &mut HeuristicEvaluator,
+) -> Result<f32, String> {
+    let (_, values) = evaluator.evaluate(game)?;
+    let mut worst = values[player];
+    if depth == 0 || game.is_terminal() || game.current_player() == player {
+        return Ok(worst);
+    }
+
+    let opponent = game.current_player();
+    let mut moves: Vec<Move> = game.get_legal_moves().into_iter().collect();
+    moves.sort();
+    for anchor in game.board.get_anchors(player) {
+        // Smallest piece that takes the anchor is the cheapest way to block it
+        let block = moves
+            .iter()
+            .filter(|(p, v, o)| {
+                let piece = game.get_piece(opponent, *p, *v);
+                piece.offsets.iter().any(|offset| o + offset == anchor)
+            })
+            .min_by_key(|(p, v, _)| game.get_piece(opponent, *p, *v).offsets.len());
+
+        if let Some((p, v, o)) = block {
+            let next = game.place_piece(*p, *v, *o)?;
+            worst = worst.min(worst_case_value(&next, player, depth - 1, evaluator)?);
+        }
+    }
+
+    Ok(worst)
+}
+
+/// Best worst case value among the largest placements that use a tile
+fn tile_value(
+    game: &Game,
+    tile: usize,
+    verification: &Verification,
+    evaluator: &mut HeuristicEvaluator,
+) -> Result<f32, String> {
+    let player = game.current_player();
+    let mut placements: Vec<(usize, Move)> = game
+        .get_legal_moves()
+        .into_iter()
+        .filter_map(|(p, v, o)| {
+            let piece = game.get_piece(player, p, v);
+            if piece.offsets.iter().any(|offset| o + offset == tile) {
+                Some((piece.offsets.len(), (p, v, o)))
+            } else {
+                None
+            }
+        })
+        .collect();
+    placements.sort_by(|a, b| b.cmp(a));
+
+    let mut best = f32::MIN;
+    for (_, (p, v, o)) in placements.iter().take(verification.placements_per_tile) {
+        let next = game.place_piece(*p, *v, *o)?;
+        best = best.max(worst_case_value(
+            &next,
+            player,
+            verification.depth,
+            evaluator,
+        )?);
+    }
+    Ok(best)
+}
+
+/// Pick the most visited root tile that survives verification
+/// Candidates whose worst case is more than the margin below the best candidate's are skipped
+pub fn select_verified_action(
+    game: &Game,
+    root: &Node,
+    verification: &Verification,
+) -> Result<Option<usize>, String> {
+    let mut candidates: Vec<(&usize, &Node)> = root.children.iter().collect();
+    candidates.sort_by(|a, b| b.1.visits.cmp(&a.1.visits).then(a.0.cmp(b.0)));
+    candidates.truncate(verification.top_k);
+
+    let mut evaluator = HeuristicEvaluator::default();
+    let mut scored = Vec::new();
+    for (tile, _) in candidates {
+        scored.push((
+            *tile,
+            tile_value(game, *tile, verification, &mut evaluator)?,
+        ));
+    }
+
+    let best = scored.iter().map(|(_, v)| *v).fold(f32::MIN, f32::max);
+    Ok(scored
+        .into_iter()
+        .find(|(_, value)| *value >= best - verification.margin)
+        .map(|(tile, _)| tile))
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{search, SearchConfig};
+
+    #[test]
+    fn test_blocking_lowers_value() {
+        let game = Game::reset().place_piece(0, 0, 0).unwrap();
+        let mut evaluator = HeuristicEvaluator::default();
+        let worst = worst_case_value(&game, 0, 0, &mut evaluator).unwrap();
+        let (_, values) = evaluator.evaluate(&game).unwrap();
+        assert_eq!(worst, values[0]);
+
+        // Player 1 is far away and can't block anything yet
+        let blocked = worst_case_value(&game, 0, 1, &mut evaluator).unwrap();
+        assert_eq!(blocked, values[0]);
+    }
+
+    #[test]
+    fn test_verified_action_is_candidate() {
+        let game = Game::reset();
+        let config = SearchConfig {
+            sims_per_move: 20,
+            ..SearchConfig::default()
+        };
+        let root = search(&game, &config, &mut HeuristicEvaluator::default()).unwrap();
+        let verification = Verification::default();
+        let action = select_verified_action(&game, &root, &verification)
+            .unwrap()
+            .unwrap();
+        assert!(game.get_legal_tiles().contains(&action));
+    }
+}
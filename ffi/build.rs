@@ -0,0 +1,17 @@
+// Generate the C header into OUT_DIR whenever the bindings change
+// With BLOKUS_FFI_HEADER set, also refresh the checked in copy at include/blokus.h
+use std::env;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=BLOKUS_FFI_HEADER");
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let bindings = cbindgen::generate(&crate_dir).expect("Failed to generate the C header");
+    let out_dir = env::var("OUT_DIR").unwrap();
+    bindings.write_to_file(Path::new(&out_dir).join("blokus.h"));
+    if env::var_os("BLOKUS_FFI_HEADER").is_some() {
+        bindings.write_to_file("include/blokus.h");
+    }
+}
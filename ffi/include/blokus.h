@@ -0,0 +1,66 @@
+#ifndef BLOKUS_H
+#define BLOKUS_H
+
+/* Generated by cbindgen from ffi/src/lib.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// A game in progress, made by blokus_new and freed by blokus_free
+typedef struct BlokusGame BlokusGame;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Start a game of "classic", "two-player" or "team", a null variant is classic
+// Returns null for an unknown variant, see blokus_last_error
+//
+// # Safety
+// `variant` is null or a nul-terminated string
+struct BlokusGame *blokus_new(const char *variant);
+
+// Free a game, null and games already freed are ignored
+//
+// # Safety
+// `game` is null or came from blokus_new
+void blokus_free(struct BlokusGame *game);
+
+// JSON array of the whole piece placements the player to move can make, with the tiles
+// each covers and its square notation, null without a game
+//
+// # Safety
+// `game` is null or a live game from blokus_new
+char *blokus_legal_moves(const struct BlokusGame *game);
+
+// Play a move in square notation like "a1-a2-b2", "swap" to swap sides under the swap rule,
+// or "pass" to drop out. Returns false and leaves the game as it was for an illegal move,
+// see blokus_last_error
+//
+// # Safety
+// `game` is null or a live game from blokus_new, and `text` is null or a nul-terminated string
+bool blokus_apply(struct BlokusGame *game, const char *text);
+
+// JSON snapshot of the game, null without a game
+//
+// # Safety
+// `game` is null or a live game from blokus_new
+char *blokus_state(const struct BlokusGame *game);
+
+// Why the last call that failed on this thread did, null if none has
+// The engine keeps the string, which lasts until the next failure
+const char *blokus_last_error(void);
+
+// Free a string returned by the engine, null and strings already freed are ignored
+//
+// # Safety
+// `text` is null or came from the engine
+void blokus_free_string(char *text);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* BLOKUS_H */
@@ -0,0 +1,252 @@
+// C bindings for the rules engine, for clients like Unity or Godot that cannot link Rust
+// Structured values cross the boundary as JSON strings, see blokus::state for their layout
+// Strings the engine returns belong to the caller and go back through blokus_free_string
+// Games and strings handed out are remembered until freed, so freeing one twice is ignored
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+use std::sync::Mutex;
+
+use blokus::game::{Game, Variant};
+use blokus::notation::parse_move;
+use blokus::state::{legal_moves, GameState};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+// Addresses of the games and strings the caller holds
+static GAMES: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
+static STRINGS: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
+
+/// Whether the pointer was handed out and not yet freed, forgetting it if so
+fn release<T>(live: &Mutex<BTreeSet<usize>>, pointer: *mut T) -> bool {
+    live.lock().unwrap().remove(&(pointer as usize))
+}
+
+/// A game in progress, made by blokus_new and freed by blokus_free
+pub struct BlokusGame {
+    game: Game,
+}
+
+fn set_error(error: String) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(error).ok());
+}
+
+fn to_c_string(text: String) -> *mut c_char {
+    let text = CString::new(text).map_or(ptr::null_mut(), CString::into_raw);
+    if !text.is_null() {
+        STRINGS.lock().unwrap().insert(text as usize);
+    }
+    text
+}
+
+/// Copy a C string, None for a null pointer
+unsafe fn read(text: *const c_char) -> Result<Option<String>, String> {
+    if text.is_null() {
+        return Ok(None);
+    }
+    let text = CStr::from_ptr(text)
+        .to_str()
+        .map_err(|_| "Strings must be UTF-8".to_string())?;
+    Ok(Some(text.to_string()))
+}
+
+/// Start a game of "classic", "two-player" or "team", a null variant is classic
+/// Returns null for an unknown variant, see blokus_last_error
+///
+/// # Safety
+/// `variant` is null or a nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn blokus_new(variant: *const c_char) -> *mut BlokusGame {
+    let variant = read(variant).and_then(|name| match name {
+        Some(name) => Variant::from_name(&name),
+        None => Ok(Variant::Classic),
+    });
+    match variant {
+        Ok(variant) => {
+            let game = Box::into_raw(Box::new(BlokusGame {
+                game: Game::new(variant),
+            }));
+            GAMES.lock().unwrap().insert(game as usize);
+            game
+        }
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a game, null and games already freed are ignored
+///
+/// # Safety
+/// `game` is null or came from blokus_new
+#[no_mangle]
+pub unsafe extern "C" fn blokus_free(game: *mut BlokusGame) {
+    if release(&GAMES, game) {
+        drop(Box::from_raw(game));
+    }
+}
+
+/// JSON array of the whole piece placements the player to move can make, with the tiles
+/// each covers and its square notation, null without a game
+///
+/// # Safety
+/// `game` is null or a live game from blokus_new
+#[no_mangle]
+pub unsafe extern "C" fn blokus_legal_moves(game: *const BlokusGame) -> *mut c_char {
+    match game.as_ref() {
+        Some(game) => to_c_string(serde_json::to_string(&legal_moves(&game.game)).unwrap()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Play a move in square notation like "a1-a2-b2", "swap" to swap sides under the swap rule,
+/// or "pass" to drop out. Returns false and leaves the game as it was for an illegal move,
+/// see blokus_last_error
+///
+/// # Safety
+/// `game` is null or a live game from blokus_new, and `text` is null or a nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn blokus_apply(game: *mut BlokusGame, text: *const c_char) -> bool {
+    let Some(game) = game.as_mut() else {
+        set_error("No game".to_string());
+        return false;
+    };
+    let next = read(text).and_then(|text| match text.as_deref() {
+        None => Err("No move".to_string()),
+        Some("swap") => game.game.swap(),
+        Some("pass") => game.game.pass(),
+        Some(text) => parse_move(&game.game, text)
+            .and_then(|(piece, variant, offset)| game.game.place_piece(piece, variant, offset)),
+    });
+    match next {
+        Ok(next) => {
+            game.game = next;
+            true
+        }
+        Err(e) => {
+            set_error(e);
+            false
+        }
+    }
+}
+
+/// JSON snapshot of the game, null without a game
+///
+/// # Safety
+/// `game` is null or a live game from blokus_new
+#[no_mangle]
+pub unsafe extern "C" fn blokus_state(game: *const BlokusGame) -> *mut c_char {
+    match game.as_ref() {
+        Some(game) => to_c_string(GameState::new(&game.game).to_json()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Why the last call that failed on this thread did, null if none has
+/// The engine keeps the string, which lasts until the next failure
+#[no_mangle]
+pub extern "C" fn blokus_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Free a string returned by the engine, null and strings already freed are ignored
+///
+/// # Safety
+/// `text` is null or came from the engine
+#[no_mangle]
+pub unsafe extern "C" fn blokus_free_string(text: *mut c_char) {
+    if release(&STRINGS, text) {
+        drop(CString::from_raw(text));
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(text: &str) -> CString {
+        CString::new(text).unwrap()
+    }
+
+    /// Take a string from the engine and free it
+    unsafe fn take(text: *mut c_char) -> String {
+        assert!(!text.is_null());
+        let copy = CStr::from_ptr(text).to_str().unwrap().to_string();
+        blokus_free_string(text);
+        copy
+    }
+
+    unsafe fn last_error() -> String {
+        CStr::from_ptr(blokus_last_error())
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_new() {
+        unsafe {
+            let game = blokus_new(ptr::null());
+            assert!(take(blokus_state(game)).contains(r#""variant":"classic""#));
+            blokus_free(game);
+
+            let game = blokus_new(c("team").as_ptr());
+            assert!(take(blokus_state(game)).contains(r#""variant":"team""#));
+            blokus_free(game);
+
+            assert!(blokus_new(c("duo").as_ptr()).is_null());
+            assert!(last_error().contains("duo"));
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        unsafe {
+            let game = blokus_new(ptr::null());
+            let moves: serde_json::Value =
+                serde_json::from_str(&take(blokus_legal_moves(game))).unwrap();
+            let first = moves[0]["notation"].as_str().unwrap();
+            assert!(blokus_apply(game, c(first).as_ptr()));
+            assert!(take(blokus_state(game)).contains(r#""current_player":1"#));
+
+            // Illegal moves leave the game as it was
+            let before = take(blokus_state(game));
+            assert!(!blokus_apply(game, c(first).as_ptr()));
+            assert!(!last_error().is_empty());
+            assert!(!blokus_apply(game, c("not a move").as_ptr()));
+            assert!(!blokus_apply(game, ptr::null()));
+            assert_eq!(last_error(), "No move");
+            assert_eq!(take(blokus_state(game)), before);
+
+            assert!(blokus_apply(game, c("pass").as_ptr()));
+            blokus_free(game);
+
+            // No game
+            assert!(!blokus_apply(ptr::null_mut(), c("pass").as_ptr()));
+            assert_eq!(last_error(), "No game");
+            assert!(blokus_state(ptr::null()).is_null());
+            assert!(blokus_legal_moves(ptr::null()).is_null());
+        }
+    }
+
+    #[test]
+    fn test_free() {
+        unsafe {
+            let game = blokus_new(ptr::null());
+            let state = blokus_state(game);
+            blokus_free_string(state);
+            blokus_free_string(state);
+            blokus_free_string(ptr::null_mut());
+            blokus_free(game);
+            blokus_free(game);
+            blokus_free(ptr::null_mut());
+            assert!(!GAMES.lock().unwrap().contains(&(game as usize)));
+            assert!(!STRINGS.lock().unwrap().contains(&(state as usize)));
+        }
+    }
+}
@@ -0,0 +1,178 @@
+/*
+Analysis overlay for seeing what the AI thinks of a position
+The position on screen is searched with the in-browser model, or the heuristic evaluator
+when there is none, and blokus::search::SearchAnalysis gives the prior, visits, and value
+of every tile the player to move could start a piece on. The board colors those tiles.
+*/
+
+use std::collections::HashMap;
+
+use wasm_bindgen::JsCast;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use crate::i18n::{tr, trf, use_language};
+
+use blokus::evaluator::HeuristicEvaluator;
+use blokus::game::Game;
+use blokus::notation::format_square;
+use blokus::onnx::OnnxEvaluator;
+use blokus::search::{self, SearchAnalysis, SearchConfig};
+
+const ANALYSIS_SIMS: usize = 100;
+const TOP_CANDIDATES: usize = 5;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Overlay {
+    #[default]
+    Off,
+    Prior,  // The network's policy before any search
+    Visits, // Where the search spent its simulations
+    Value,  // Win rate for the player to move after each tile
+}
+
+impl Overlay {
+    pub const ALL: [Overlay; 4] = [
+        Overlay::Off,
+        Overlay::Prior,
+        Overlay::Visits,
+        Overlay::Value,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Overlay::Off => "Off",
+            Overlay::Prior => "Policy",
+            Overlay::Visits => "Search Visits",
+            Overlay::Value => "Value",
+        }
+    }
+}
+
+/// How one board square is shaded, and the numbers behind it for its tooltip
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heat {
+    pub color: String,
+    pub label: String,
+}
+
+/// Search the position for the player to move, None if the game is over
+pub fn analyze(
+    game: &Game,
+    model: Option<&OnnxEvaluator>,
+) -> Result<Option<SearchAnalysis>, String> {
+    if game.is_terminal() {
+        return Ok(None);
+    }
+    let config = SearchConfig {
+        sims_per_move: ANALYSIS_SIMS,
+        exploration_fraction: 0.0,
+        ..SearchConfig::default()
+    };
+    let root = match model {
+        Some(evaluator) => search::search(game, &config, &mut evaluator.clone())?,
+        None => search::search(game, &config, &mut HeuristicEvaluator::default())?,
+    };
+    Ok(Some(SearchAnalysis::from_root(&root)))
+}
+
+/// Shade each candidate tile, the strongest by the overlay's measure darkest
+/// Policy and visits are scaled to the largest share, values run from red for a loss to green for a win
+pub fn heatmap(analysis: &SearchAnalysis, overlay: Overlay) -> HashMap<usize, Heat> {
+    let total_visits = analysis
+        .children
+        .iter()
+        .map(|c| c.visits)
+        .sum::<u32>()
+        .max(1) as f32;
+    let max_prior = analysis
+        .children
+        .iter()
+        .map(|c| c.prior)
+        .fold(f32::EPSILON, f32::max);
+    let max_visits = analysis
+        .children
+        .iter()
+        .map(|c| c.visits)
+        .max()
+        .unwrap_or(0)
+        .max(1) as f32;
+
+    let mut heat = HashMap::new();
+    for child in &analysis.children {
+        let color = match overlay {
+            Overlay::Off => continue,
+            Overlay::Prior => format!("rgba(255, 120, 0, {:.2})", child.prior / max_prior * 0.9),
+            Overlay::Visits => format!(
+                "rgba(128, 0, 255, {:.2})",
+                child.visits as f32 / max_visits * 0.9
+            ),
+            Overlay::Value if child.visits == 0 => continue,
+            Overlay::Value => format!(
+                "hsla({:.0}, 85%, 45%, 0.75)",
+                child.value.clamp(0.0, 1.0) * 120.0
+            ),
+        };
+        let label = format!(
+            "{}: policy {:.1}%, visits {} ({:.1}%), value {:.1}%",
+            format_square(child.tile),
+            child.prior * 100.0,
+            child.visits,
+            child.visits as f32 / total_visits * 100.0,
+            child.value * 100.0
+        );
+        heat.insert(child.tile, Heat { color, label });
+    }
+    heat
+}
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub overlay: Overlay,
+    pub analysis: Option<SearchAnalysis>,
+    pub with_model: bool, // Whether the in-browser model did the evaluating
+    pub on_change: Callback<Overlay>,
+}
+
+/// Overlay picker and the search's top candidates
+#[function_component]
+pub fn AnalysisPanel(props: &Props) -> Html {
+    let language = use_language();
+    let onchange = {
+        let on_change = props.on_change.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+            if let Some(overlay) = select
+                .value()
+                .parse()
+                .ok()
+                .and_then(|i: usize| Overlay::ALL.get(i))
+            {
+                on_change.emit(*overlay);
+            }
+        })
+    };
+
+    html! {
+        <div class="analysis">
+            <select {onchange}>
+                { for Overlay::ALL.iter().enumerate().map(|(i, overlay)| html! {
+                    <option value={i.to_string()} selected={*overlay == props.overlay}>{ tr(language, overlay.name()) }</option>
+                }) }
+            </select>
+            if props.overlay != Overlay::Off {
+                <p>{ tr(language, if props.with_model { "Evaluated by the model" } else { "Evaluated by the heuristic, no model loaded" }) }</p>
+                if let Some(analysis) = &props.analysis {
+                    <p>{ trf(language, "{} simulations, best line {}", &[&analysis.visits,
+                        &analysis.principal_variation.iter().map(|t| format_square(*t)).collect::<Vec<_>>().join(" ")]) }</p>
+                    <ol class="candidates">
+                        { for analysis.children.iter().take(TOP_CANDIDATES).map(|child| html! {
+                            <li>{ format!("{} visits {} value {:.1}% policy {:.1}%", format_square(child.tile),
+                                child.visits, child.value * 100.0, child.prior * 100.0) }</li>
+                        }) }
+                    </ol>
+                }
+            }
+        </div>
+    }
+}
@@ -1,17 +1,59 @@
-use gloo_console as console;
-use gloo_dialogs::alert;
+use std::collections::{HashMap, HashSet};
+
+use gloo_dialogs::confirm;
+use gloo_timers::callback::Timeout;
 use reqwasm::http::Request;
 use serde::{Deserialize, Serialize};
 use serde_json;
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 
+use crate::analysis::{analyze, heatmap, AnalysisPanel, Overlay};
 use crate::board::BlokusBoard;
+use crate::clock::{self, Clock};
+use crate::editor::{Editing, PositionEditor};
+use crate::history::MoveList;
+use crate::i18n::{tr, trf, Language};
+use crate::online::{
+    ClientMessage, Connection, Lobby, OnlineAction, OnlineState, RoomPanel, Status,
+};
 use crate::pieces::PieceTray;
-use blokus::game::Game;
+use crate::placement::{flipped, footprint, nudged, rotated, targets, Grab};
+use crate::puzzles::{OpenPuzzles, PuzzleAction, PuzzleControls, PuzzleSet};
+use crate::replay::{OpenRecord, Replay, ReplayAction, ReplayControls};
+use crate::saves::{self, SavePanel, SavedGame};
+use crate::scores::{Props as ScoreProps, ScorePanel};
+use crate::settings::{self, Settings, SettingsPanel};
+use crate::setup::{self, time_control_name, variant_name, Setup, SetupScreen};
+use crate::sounds::{self, Sound};
+use crate::state::{Action, GameState};
+use crate::summary::GameSummary;
+use crate::tutorial::{Tutorial, TutorialAction, TutorialControls};
+use crate::win_graph::{self, WinGraph};
+use blokus::agents::{
+    should_swap, Agent, BlockingAgent, Difficulty, HandicappedAgent, HandicappedEvaluator,
+    MctsAgent,
+};
+use blokus::board::BOARD_SIZE as D;
+use blokus::evaluator::HeuristicEvaluator;
+use blokus::game::{Game, Move, PASS, SWAP};
+use blokus::onnx::OnnxEvaluator;
+use blokus::opening_book::from_perspective;
+use blokus::position::to_position;
+use blokus::records::from_history_at;
+use blokus::search::{SearchAnalysis, SearchConfig};
 
 const SERVER_ADDRESS: &str = "http://127.0.0.1:8000/process_request";
-const D: usize = 20;
+const MODEL_PATH: &str = "models/model.onnx"; // Copied next to the page by trunk if present
+const MODEL_SIMS: usize = 25; // Simulations per tile, kept low since search blocks the page
+const PLAYER_NAMES: [&str; 4] = ["Red", "Blue", "Green", "Yellow"];
+const CONTROLS: &str = "Place Piece: Drag by any square
+Select Piece: Click
+Rotate Selected: r or click it again
+Flip Selected: f
+Aim Selected: Arrow keys or click a square
+Place Selected: Enter or click again";
+const NOTICE_MS: u32 = 4000; // How long the note about a player dropping out stays up
 
 #[derive(Serialize, Deserialize, Debug)]
 struct GameStateRequest {
@@ -41,7 +83,7 @@ fn print_rep(rep: [[[bool; D]; D]; 5]) {
         }
         str_rep.push_str("\n");
     }
-    console::log!(str_rep);
+    tracing::debug!("position sent to the model server\n{}", str_rep);
 }
 
 fn get_state_rep(game: &Game) -> GameStateRequest {
@@ -68,7 +110,10 @@ async fn get_ai_move(state: &Game) -> Result<usize, String> {
             let json_value = response.json().await.unwrap();
             let response: GameStateResponse = serde_json::from_value(json_value).unwrap();
             if response.status != 200 {
-                console::error!("AI failed to find a move");
+                tracing::error!(
+                    status = response.status,
+                    "the model server failed to find a move"
+                );
             }
             let tile = response
                 .policy
@@ -79,117 +124,892 @@ async fn get_ai_move(state: &Game) -> Result<usize, String> {
                 .expect("No policy found");
 
             // Return tile to right perspective
-            let row = tile / D;
-            let col = tile % D;
-            let (new_row, new_col) = match state.current_player() {
-                0 => (row, col),
-                1 => (col, D - row - 1),
-                2 => (D - row - 1, D - col - 1),
-                3 => (D - col - 1, row),
-                _ => panic!("Invalid player number"),
-            };
-
-            Ok(new_row * D + new_col)
+            Ok(from_perspective(tile, state.current_player()))
         }
         Err(e) => Err(format!("Failed to get AI move: {:?}", e)),
     }
 }
 
-/// Applies AI moves to state after player has gone
-async fn handle_ai_moves(state: Game) -> Game {
+/// Plays a whole piece for the current player with a local agent
+/// Lets the AI keep playing when the model server is not running
+fn play_local_move(agent: &mut dyn Agent, state: &Game) -> Result<Game, String> {
+    let (piece, variant, offset) = agent.choose_move(state)?;
+    state.place_piece(piece, variant, offset)
+}
+
+/// Fetch the exported network so the AI can search in the browser without a backend
+async fn load_model() -> Result<OnnxEvaluator, String> {
+    let response = Request::get(MODEL_PATH)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {:?}", MODEL_PATH, e))?;
+    if !response.ok() {
+        return Err(format!("No model at {}", MODEL_PATH));
+    }
+    let bytes = response
+        .binary()
+        .await
+        .map_err(|e| format!("Failed to read {}: {:?}", MODEL_PATH, e))?;
+    OnnxEvaluator::from_bytes(&bytes)
+}
+
+/// Applies AI moves to state until it is a person's turn
+/// Searches with the network in the browser when it loaded, otherwise asks the model server
+/// The difficulty holds back the in-browser search and the heuristic fallback
+async fn handle_ai_moves(
+    state: Game,
+    ai_seats: [bool; 4],
+    model: Option<OnnxEvaluator>,
+    difficulty: Difficulty,
+) -> Game {
     let mut next_state = state.clone();
     let mut current_ai = next_state.current_player();
-    while current_ai != 0 && !next_state.is_terminal() {
-        // THIS IS THE CONDITION, DOESN'T WORK WHEN HUMAN IS ELIMINATED
-        let tile = get_ai_move(&next_state).await.unwrap();
-        if let Err(e) = next_state.apply(tile, None) {
-            console::error!("Failed to apply AI move:m", e);
-            break;
+    // Seats are kept by the color they start with, see Game::seat_of
+    while ai_seats[next_state.variant().colors(next_state.seat_of(current_ai))[0]]
+        && !next_state.is_terminal()
+    {
+        // Under the swap rule, take over the opening when it looks better than the reply
+        if next_state.can_swap() {
+            match should_swap(&next_state, &mut HeuristicEvaluator::default()) {
+                Ok(true) => {
+                    next_state = next_state.swap().unwrap_or(next_state);
+                    tracing::info!("the AI swapped sides");
+                    continue;
+                }
+                Ok(false) => {}
+                Err(e) => tracing::warn!(error = %e, "failed to decide on swapping"),
+            }
+        }
+        if let Some(evaluator) = &model {
+            let config = SearchConfig {
+                sims_per_move: MODEL_SIMS.min(difficulty.max_sims),
+                exploration_fraction: 0.0,
+                ..SearchConfig::default()
+            };
+            let evaluator = HandicappedEvaluator::new(evaluator.clone(), &difficulty, None);
+            let agent = MctsAgent::new(evaluator, config);
+            let mut agent = HandicappedAgent::new(Box::new(agent), &difficulty, None);
+            match play_local_move(&mut agent, &next_state) {
+                Ok(state) => next_state = state,
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to apply the AI move");
+                    break;
+                }
+            }
+            current_ai = next_state.current_player();
+            continue;
+        }
+
+        match get_ai_move(&next_state).await {
+            Ok(tile) => {
+                if let Err(e) = next_state.apply(tile, None) {
+                    tracing::error!(error = %e, "failed to apply the AI move");
+                    break;
+                }
+                tracing::info!(tile, "the AI placed a piece");
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "falling back to the heuristic AI");
+                let agent = Box::new(BlockingAgent::default());
+                let mut agent = HandicappedAgent::new(agent, &difficulty, None);
+                match play_local_move(&mut agent, &next_state) {
+                    Ok(state) => next_state = state,
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to apply the AI move");
+                        break;
+                    }
+                }
+            }
         }
 
         current_ai = next_state.current_player();
-        console::log!("AI placed piece at: {:?}", tile);
-        console::log!("Current player: ", current_ai);
+        tracing::info!(player = current_ai, "next to move");
     }
 
     next_state
 }
 
-fn alert_game_over(game: &Game) {
-    let scores = game.get_score();
-    let best_score = scores.iter().max().unwrap();
-    let winners = scores
-        .iter()
-        .enumerate()
-        .filter_map(|(i, s)| if s == best_score { Some(i) } else { None })
-        .collect::<Vec<_>>();
-
-    let mut message = if winners.len() == 1 {
-        format!("Player {} wins!", winners[0] + 1)
+#[function_component]
+pub fn App() -> Html {
+    let state = use_reducer(GameState::default);
+    let start = to_position(&state.start); // Tells games set up by hand apart before any moves
+
+    // Network for the AI seats, None until it loads or if there is none
+    let model = use_state(|| None::<OnnxEvaluator>);
+    {
+        let model = model.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                match load_model().await {
+                    Ok(evaluator) => model.set(Some(evaluator)),
+                    Err(e) => tracing::warn!(error = %e, "running without the in-browser model"),
+                }
+            });
+        });
+    }
+
+    // A room on the multiplayer server, when playing online
+    let online = use_reducer(OnlineState::default);
+    let connection = use_state(|| None::<Connection>);
+
+    // Take our seat back whenever the socket reopens
+    {
+        let connection = connection.clone();
+        let token = online.token.clone();
+        use_effect_with(online.status, move |status| {
+            if let (Status::Connected, Some(connection), Some(token)) =
+                (status, &*connection, token)
+            {
+                connection.send(&ClientMessage::Reconnect { token });
+            }
+        });
+    }
+
+    let on_join = {
+        let dispatcher = online.dispatcher();
+        let connection = connection.clone();
+        Callback::from(move |room| {
+            dispatcher.dispatch(OnlineAction::Join(room));
+            let events = dispatcher.clone();
+            let events = Callback::from(move |action| events.dispatch(action));
+            connection.set(Some(Connection::open(room, events)));
+        })
+    };
+
+    let on_leave = {
+        let dispatcher = online.dispatcher();
+        let connection = connection.clone();
+        Callback::from(move |_| {
+            if let Some(connection) = &*connection {
+                connection.close();
+            }
+            connection.set(None);
+            dispatcher.dispatch(OnlineAction::Leave);
+        })
+    };
+
+    let on_send = {
+        let connection = connection.clone();
+        Callback::from(move |message: ClientMessage| {
+            if let Some(connection) = &*connection {
+                connection.send(&message);
+            }
+        })
+    };
+
+    // A recorded game being stepped through
+    let replay = use_reducer(Replay::default);
+    let on_open = {
+        let dispatcher = replay.dispatcher();
+        Callback::from(move |opened| dispatcher.dispatch(ReplayAction::Open(Box::new(opened))))
+    };
+
+    // A puzzle file being worked through
+    let puzzles = use_reducer(PuzzleSet::default);
+    let on_open_puzzles = {
+        let dispatcher = puzzles.dispatcher();
+        Callback::from(move |opened| dispatcher.dispatch(PuzzleAction::Open(Box::new(opened))))
+    };
+
+    // The rules tutorial's lessons
+    let tutorial = use_reducer(Tutorial::default);
+    let on_open_tutorial = {
+        let dispatcher = tutorial.dispatcher();
+        Callback::from(move |_| dispatcher.dispatch(TutorialAction::Open))
+    };
+
+    // A position being set up by hand, shown on the board in place of the game until played from
+    let editing = use_state(|| None::<Editing>);
+    let on_edit = {
+        let editing = editing.clone();
+        let game = state.game.clone();
+        Callback::from(move |_| editing.set(Some(Editing::new(&game))))
+    };
+    let on_edit_change = {
+        let editing = editing.clone();
+        Callback::from(move |changed| editing.set(Some(changed)))
+    };
+    let on_play_from = {
+        let dispatcher = state.dispatcher();
+        let editing = editing.clone();
+        Callback::from(move |game| {
+            dispatcher.dispatch(Action::SetUp(Box::new(game)));
+            editing.set(None);
+        })
+    };
+    let on_cancel_edit = {
+        let editing = editing.clone();
+        Callback::from(move |_| editing.set(None))
+    };
+
+    // The game on screen, the replay's, the puzzle's, the lesson's, or the room's when one is open
+    let game = if replay.is_open() {
+        replay.game().clone()
+    } else if puzzles.is_open() {
+        puzzles.game().clone()
+    } else if tutorial.is_open() {
+        tutorial.game().clone()
+    } else if online.room.is_some() {
+        online.game.clone()
     } else {
-        format!(
-            "Players {:?} tie!",
-            winners.iter().map(|w| w + 1).collect::<Vec<_>>()
+        state.game.clone()
+    };
+
+    // Where it began, to tell positions apart before any moves
+    let origin = match puzzles.puzzle() {
+        Some(puzzle) if !replay.is_open() => format!("{} {}", puzzles.index, puzzle.position),
+        _ if tutorial.is_open() && !replay.is_open() => format!("lesson {}", tutorial.index),
+        _ => start.clone(),
+    };
+
+    // Its moves, worked out again only when a piece is placed
+    let record = {
+        let from = if replay.is_open() || online.room.is_some() {
+            Game::reset()
+        } else if puzzles.is_open() {
+            puzzles.start.clone()
+        } else if tutorial.is_open() {
+            tutorial.start.clone()
+        } else {
+            state.start.clone()
+        };
+        use_memo(
+            (game.history.clone(), origin.clone()),
+            move |(history, _)| from_history_at(&from, history).unwrap_or_default(),
         )
     };
+    let (moves, step) = match replay.record() {
+        Some(shown) => (shown.moves.clone(), replay.step),
+        None => (record.moves.clone(), record.moves.len()),
+    };
+    let last_move = step
+        .checked_sub(1)
+        .map(|i| moves[i].tiles.clone())
+        .unwrap_or_default();
+    let highlight = if puzzles.solution.is_empty() || replay.is_open() {
+        last_move
+    } else {
+        puzzles.solution.clone() // A puzzle's revealed solution
+    };
 
-    message.push_str("\n\nScores:\n");
-    for (i, score) in scores.iter().enumerate() {
-        message.push_str(&format!("Player {}: {}\n", i + 1, score));
-    }
-    alert(&message);
-}
+    // Picking a move steps the replay there, opening the game on screen in it if need be
+    let on_jump = {
+        let dispatcher = replay.dispatcher();
+        let replaying = replay.is_open();
+        let record = record.clone();
+        Callback::from(move |step| {
+            if !replaying {
+                match Replay::new(vec![(*record).clone()]) {
+                    Ok(opened) => dispatcher.dispatch(ReplayAction::Open(Box::new(opened))),
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to open the replay");
+                        return;
+                    }
+                }
+            }
+            dispatcher.dispatch(ReplayAction::Goto(step));
+        })
+    };
 
-#[function_component]
-pub fn App() -> Html {
-    let state = use_state(|| Game::reset());
+    // Theme, patterns, and sound, saved whenever they change
+    let settings = use_state(settings::load);
+    use_effect_with((*settings).clone(), settings::save);
+    let language = settings.language;
+    use_effect_with(language, |language| {
+        let root = web_sys::window().and_then(|w| w.document()?.document_element());
+        if let Some(root) = root {
+            let _ = root.set_attribute("lang", language.code());
+        }
+    });
+    let on_settings = {
+        let settings = settings.clone();
+        Callback::from(move |changed| settings.set(changed))
+    };
+    let play_sound = {
+        let muted = settings.muted;
+        Callback::from(move |sound| {
+            if !muted {
+                sounds::play(sound);
+            }
+        })
+    };
 
-    let on_board_drop = {
-        let state = state.clone();
-        Callback::from(move |(p, v, offset)| {
-            // Don't do anything if game is over
-            if state.is_terminal() {
+    // Mark each move with a sound, and say so when a player drops out, whether they passed
+    // or had no legal moves left. Anchors the player to move did not have at their last turn flash.
+    // Only moving forward through the same game counts, not undoing or switching to a replay
+    let notice = use_state(|| None::<String>);
+    let summary_open = use_state(|| false); // End of game screen, for games played live
+    let fresh_anchors = use_state(HashSet::<usize>::new);
+    let seen = use_mut_ref(|| (0, 0, [true; 4])); // View, moves, and players still in when last checked
+    let seen_anchors = use_mut_ref(|| vec![HashSet::<usize>::new(); 4]); // At each player's last turn
+    let view = if replay.is_open() {
+        2
+    } else if puzzles.is_open() {
+        3
+    } else if tutorial.is_open() {
+        4
+    } else if online.room.is_some() {
+        1
+    } else {
+        0
+    };
+    {
+        let notice = notice.clone();
+        let summary_open = summary_open.clone();
+        let fresh_anchors = fresh_anchors.clone();
+        let play_sound = play_sound.clone();
+        let game = game.clone();
+        let deps = (view, game.history.len(), origin.clone());
+        use_effect_with(deps, move |&(view, len, _)| {
+            let active = [0, 1, 2, 3].map(|p| game.is_player_active(p));
+            let (seen_view, seen_len, was_active) = *seen.borrow();
+            let forward = view == seen_view && len > seen_len;
+            if forward && game.history.last() == Some(&(1, SWAP)) {
+                play_sound.emit(Sound::Place);
+                notice.set(Some(
+                    tr(language, "Blue swapped sides, the seats trade colors").to_string(),
+                ));
+            } else if forward && game.is_terminal() {
+                play_sound.emit(Sound::GameOver);
+                summary_open.set(view < 2);
+            } else if forward {
+                let out: Vec<String> = (0..4)
+                    .filter(|p| was_active[*p] && !active[*p])
+                    .map(|p| {
+                        if game.history.contains(&(p as i32, PASS)) {
+                            trf(
+                                language,
+                                "{} passed and is out",
+                                &[&tr(language, PLAYER_NAMES[p])],
+                            )
+                        } else {
+                            trf(
+                                language,
+                                "{} has no legal moves and is out",
+                                &[&tr(language, PLAYER_NAMES[p])],
+                            )
+                        }
+                    })
+                    .collect();
+                if out.is_empty() {
+                    play_sound.emit(Sound::Place);
+                } else {
+                    play_sound.emit(Sound::Elimination);
+                    notice.set(Some(out.join(". ")));
+                }
+            }
+            *seen.borrow_mut() = (view, len, active);
+
+            let mut seen_anchors = seen_anchors.borrow_mut();
+            if !forward {
+                seen_anchors.iter_mut().for_each(HashSet::clear);
+            }
+            let anchors = game.get_current_anchors();
+            let known = &seen_anchors[game.current_player()];
+            fresh_anchors.set(if known.is_empty() {
+                HashSet::new()
+            } else {
+                anchors.difference(known).copied().collect()
+            });
+            seen_anchors[game.current_player()] = anchors;
+        });
+    }
+    {
+        let notice = notice.clone();
+        use_effect_with((*notice).clone(), move |shown| {
+            let timeout = shown
+                .is_some()
+                .then(|| Timeout::new(NOTICE_MS, move || notice.set(None)));
+            move || drop(timeout)
+        });
+    }
+
+    // What the AI makes of the position on screen, searched only while the overlay is on
+    let overlay = use_state(Overlay::default);
+    let analysis = use_state(|| None::<SearchAnalysis>);
+    {
+        let analysis = analysis.clone();
+        let game = game.clone();
+        let model = (*model).clone();
+        let deps = (
+            *overlay != Overlay::Off,
+            model.is_some(),
+            view,
+            game.history.len(),
+            origin.clone(),
+        );
+        use_effect_with(deps, move |(on, ..)| {
+            if !*on {
+                analysis.set(None);
                 return;
             }
+            spawn_local(async move {
+                match analyze(&game, model.as_ref()) {
+                    Ok(searched) => analysis.set(searched),
+                    Err(e) => tracing::error!(error = %e, "failed to analyze the position"),
+                }
+            });
+        });
+    }
+    let heat = match &*analysis {
+        Some(searched) => heatmap(searched, *overlay),
+        None => HashMap::new(),
+    };
+    // Each color's chance of winning over the replayed game, rated again when another game is opened
+    let win_chances = use_state(Vec::<Vec<f32>>::new);
+    {
+        let win_chances = win_chances.clone();
+        let positions = replay.positions.clone();
+        let model = (*model).clone();
+        let deps = (
+            replay.is_open(),
+            replay.index,
+            replay.records.len(),
+            model.is_some(),
+        );
+        use_effect_with(deps, move |(open, ..)| {
+            if !*open {
+                win_chances.set(Vec::new());
+                return;
+            }
+            spawn_local(async move {
+                match win_graph::estimate(&positions, model.as_ref()) {
+                    Ok(chances) => win_chances.set(chances),
+                    Err(e) => tracing::error!(error = %e, "failed to rate the replay"),
+                }
+            });
+        });
+    }
+    let on_overlay = {
+        let overlay = overlay.clone();
+        Callback::from(move |picked| overlay.set(picked))
+    };
 
-            // Place piece on board
-            let new_state = match state.place_piece(p, v, offset) {
-                Ok(s) => s,
-                Err(e) => {
-                    console::error!("Failed to place piece: {:?}", e);
-                    return;
+    // Play a move here, or send it to the room when it is our turn there
+    let place = {
+        let dispatcher = state.dispatcher();
+        let connection = connection.clone();
+        let online = online.clone();
+        let replaying = replay.is_open();
+        let setting_up = editing.is_some();
+        let solving = puzzles.dispatcher();
+        let learning = tutorial.dispatcher();
+        let game = game.clone();
+        Callback::from(move |placement: Move| match &*connection {
+            _ if replaying => tracing::warn!("close the replay to play"),
+            _ if setting_up => tracing::warn!("finish setting up the position to play"),
+            _ if view == 3 => solving.dispatch(PuzzleAction::Answer(placement)),
+            _ if view == 4 => learning.dispatch(TutorialAction::Place(placement)),
+            Some(connection) if online.room.is_some() => {
+                if online.my_turn() {
+                    connection.play(&game, placement);
+                } else {
+                    tracing::warn!("wait for your turn");
+                }
+            }
+            _ => dispatcher.dispatch(Action::Place(placement)),
+        })
+    };
+
+    // Let the AI play its seats whenever it is their turn
+    let difficulty = Difficulty::from_name(&state.difficulty).unwrap_or_else(|e| {
+        tracing::warn!(error = %e, "unknown difficulty, playing hard");
+        Difficulty::from_name("hard").unwrap()
+    });
+    {
+        let dispatcher = state.dispatcher();
+        let game = state.game.clone();
+        let ai_seats = state.ai_seats;
+        let ai_to_move = state.ai_to_move();
+        let model = (*model).clone();
+        let difficulty = difficulty.clone();
+        let deps = (state.game.history.len(), ai_seats, start.clone());
+        use_effect_with(deps, move |_| {
+            if ai_to_move {
+                spawn_local(async move {
+                    let next = handle_ai_moves(game.clone(), ai_seats, model, difficulty).await;
+                    dispatcher.dispatch(Action::AiMoved {
+                        from: game.history,
+                        next: Box::new(next),
+                    });
+                });
+            }
+        });
+    }
+
+    // Save after every move so refreshing the tab does not lose the game
+    {
+        let state = state.clone();
+        use_effect_with((state.game.history.len(), state.ai_seats), move |_| {
+            if !state.game.history.is_empty() {
+                match state.to_saved() {
+                    Ok(saved) => saves::save_last(&saved),
+                    Err(e) => tracing::warn!(error = %e, "failed to save the game"),
                 }
+            }
+        });
+    }
+
+    // The piece being dragged and the square it is over
+    let held = use_state(|| None::<Grab>);
+    let hovered = use_state(|| None::<usize>);
+    let on_grab = {
+        let held = held.clone();
+        Callback::from(move |grab| held.set(Some(grab)))
+    };
+
+    let on_release = {
+        let held = held.clone();
+        let hovered = hovered.clone();
+        Callback::from(move |_| {
+            held.set(None);
+            hovered.set(None);
+        })
+    };
+
+    let on_board_hover = {
+        let hovered = hovered.clone();
+        Callback::from(move |cell| {
+            if *hovered != Some(cell) {
+                hovered.set(Some(cell));
+            }
+        })
+    };
+
+    // The piece picked with a click and the square it is aimed at with the arrow keys
+    let orientations = use_state(HashMap::<usize, usize>::new);
+    let selected = use_state(|| None::<Grab>);
+    let cursor = use_state(|| None::<usize>);
+
+    // How many places each of the tray's pieces fits, so the ones that no longer fit can be faded
+    let piece_moves = {
+        let game = game.clone();
+        use_memo((game.history.clone(), origin.clone()), move |_| {
+            game.moves_per_piece()
+        })
+    };
+
+    // Tray indices change once a piece is played
+    {
+        let selected = selected.clone();
+        use_effect_with((game.history.len(), origin.clone()), move |_| {
+            selected.set(None)
+        });
+    }
+
+    let on_select = {
+        let selected = selected.clone();
+        let cursor = cursor.clone();
+        let anchors = game.get_current_anchors();
+        Callback::from(move |grab| {
+            selected.set(Some(grab));
+            if cursor.is_none() {
+                cursor.set(anchors.iter().min().copied());
+            }
+        })
+    };
+
+    // Rotate the selected piece, or flip it when true
+    let turn_selected = {
+        let game = game.clone();
+        let orientations = orientations.clone();
+        let selected = selected.clone();
+        Callback::from(move |flip: bool| {
+            let Some(grab) = *selected else {
+                return;
             };
-            let game = new_state.clone();
-            state.set(new_state);
+            let pieces = game.get_current_player_pieces();
+            let Some(piece) = pieces.get(grab.piece) else {
+                return;
+            };
+            let variant = if flip {
+                flipped(piece.variants.len(), grab.variant)
+            } else {
+                rotated(piece.variants.len(), grab.variant)
+            };
+            let mut updated = (*orientations).clone();
+            updated.insert(piece.id, variant);
+            orientations.set(updated);
+            selected.set(Some(Grab { variant, ..grab }));
+        })
+    };
 
-            // Check if game is over
-            if game.is_terminal() {
-                alert_game_over(&game);
+    let onkeydown = {
+        let place = place.clone();
+        let play_sound = play_sound.clone();
+        let game = game.clone();
+        let turn_selected = turn_selected.clone();
+        let selected = selected.clone();
+        let cursor = cursor.clone();
+        Callback::from(move |event: KeyboardEvent| {
+            let Some(grab) = *selected else {
                 return;
+            };
+            let key = event.key();
+            match key.as_str() {
+                "r" | "f" => turn_selected.emit(key == "f"),
+                "ArrowUp" | "ArrowDown" | "ArrowLeft" | "ArrowRight" => {
+                    event.prevent_default();
+                    cursor.set(Some(nudged(cursor.unwrap_or(0), &key)));
+                }
+                "Enter" => {
+                    let placement = cursor.and_then(|cell| footprint(&game, grab, cell).1);
+                    match placement {
+                        Some(placement) => {
+                            place.emit(placement);
+                            selected.set(None);
+                        }
+                        None => {
+                            tracing::warn!("that piece does not fit there");
+                            play_sound.emit(Sound::Illegal);
+                        }
+                    }
+                }
+                "Escape" => selected.set(None),
+                _ => {}
             }
+        })
+    };
 
-            // Handle AI moves
-            let state = state.clone();
-            spawn_local({
-                async move {
-                    let new_state = handle_ai_moves(game.clone()).await;
-                    state.set(new_state.clone());
-                    if new_state.is_terminal() {
-                        alert_game_over(&game);
+    let on_board_drop = {
+        let place = place.clone();
+        let play_sound = play_sound.clone();
+        let game = game.clone();
+        let held = held.clone();
+        let hovered = hovered.clone();
+        Callback::from(move |cell| {
+            if let Some(grab) = *held {
+                match footprint(&game, grab, cell).1 {
+                    Some(placement) => place.emit(placement),
+                    None => {
+                        tracing::warn!("that piece does not fit there");
+                        play_sound.emit(Sound::Illegal);
                     }
                 }
+            }
+            held.set(None);
+            hovered.set(None);
+        })
+    };
+
+    // Every square the held or selected piece could be placed from, for players who want the help
+    let show_targets = use_state(|| true);
+    let targets = match (*show_targets, (*held).or(*selected)) {
+        (true, Some(grab)) => targets(&game, grab),
+        _ => HashSet::new(),
+    };
+    let on_toggle_targets = {
+        let show_targets = show_targets.clone();
+        Callback::from(move |_| show_targets.set(!*show_targets))
+    };
+
+    // Ghost of the pending placement, from the piece being dragged or else the selected piece
+    let (preview, placement) = match (*held, *hovered, *selected, *cursor) {
+        (Some(grab), Some(cell), _, _) | (None, _, Some(grab), Some(cell)) => {
+            footprint(&game, grab, cell)
+        }
+        _ => (Vec::new(), None),
+    };
+
+    // Clicking a square aims the selected piece there, clicking it again places it
+    // While setting up a position it paints the square instead
+    let on_board_click = {
+        let place = place.clone();
+        let play_sound = play_sound.clone();
+        let selected = selected.clone();
+        let cursor = cursor.clone();
+        let game = game.clone();
+        let editing = editing.clone();
+        Callback::from(move |cell| {
+            if let Some(setting_up) = &*editing {
+                editing.set(Some(setting_up.click(cell)));
+                return;
+            }
+            let Some(grab) = *selected else {
+                return;
+            };
+            if *cursor != Some(cell) {
+                cursor.set(Some(cell));
+                return;
+            }
+            match footprint(&game, grab, cell).1 {
+                Some(placement) => {
+                    place.emit(placement);
+                    selected.set(None);
+                }
+                None => {
+                    tracing::warn!("that piece does not fit there");
+                    play_sound.emit(Sound::Illegal);
+                }
+            }
+        })
+    };
+
+    let on_undo = {
+        let dispatcher = state.dispatcher();
+        Callback::from(move |_| dispatcher.dispatch(Action::Undo))
+    };
+
+    // Giving up the rest of the game cannot be undone by accident, so check first
+    let on_pass = {
+        let dispatcher = state.dispatcher();
+        Callback::from(move |_| {
+            if confirm(
+                "Pass for the rest of the game? You will not be able to place any more pieces.",
+            ) {
+                dispatcher.dispatch(Action::Pass);
+            }
+        })
+    };
+
+    let on_swap = {
+        let dispatcher = state.dispatcher();
+        Callback::from(move |_| dispatcher.dispatch(Action::Swap))
+    };
+
+    let on_toggle_seat = |seat: usize| {
+        let dispatcher = state.dispatcher();
+        Callback::from(move |_| dispatcher.dispatch(Action::ToggleSeat(seat)))
+    };
+
+    // Pick the seats, rules, and options for a new game, straight away on opening the page
+    let setup_open = use_state(|| true);
+    let on_new_game = {
+        let setup_open = setup_open.clone();
+        Callback::from(move |_| setup_open.set(true))
+    };
+    let on_start = {
+        let dispatcher = state.dispatcher();
+        let setup_open = setup_open.clone();
+        let settings = settings.clone();
+        Callback::from(move |setup: Setup| {
+            settings.set(Settings {
+                theme: setup.theme,
+                ..(*settings).clone()
             });
+            dispatcher.dispatch(Action::NewGame(Box::new(setup)));
+            setup_open.set(false);
         })
     };
+    let on_cancel_setup = {
+        let setup_open = setup_open.clone();
+        Callback::from(move |_| setup_open.set(false))
+    };
 
     let on_reset = {
+        let dispatcher = state.dispatcher();
+        Callback::from(move |_| dispatcher.dispatch(Action::Reset))
+    };
+
+    // Time each person's move in a timed game here, starting over whenever the position changes
+    let deadline = use_state(|| None::<f64>);
+    {
+        let deadline = deadline.clone();
+        let timed = state.move_time().filter(|_| {
+            view == 0 && !*setup_open && !state.game.is_terminal() && !state.ai_to_move()
+        });
+        let deps = (state.game.history.len(), timed, start.clone());
+        use_effect_with(deps, move |(_, timed, _)| {
+            deadline.set(timed.map(|seconds| clock::now() + seconds as f64 * 1000.0));
+        });
+    }
+
+    // The AI moves for a person who runs out of time, online the room does this
+    let on_timeout = {
+        let dispatcher = state.dispatcher();
+        let game = state.game.clone();
+        let model = (*model).clone();
+        Callback::from(move |_| {
+            let mut seats = [false; 4];
+            seats[game.current_player()] = true;
+            let dispatcher = dispatcher.clone();
+            let game = game.clone();
+            let model = model.clone();
+            let difficulty = difficulty.clone();
+            spawn_local(async move {
+                let next = handle_ai_moves(game.clone(), seats, model, difficulty).await;
+                dispatcher.dispatch(Action::TimedOut {
+                    from: game.history,
+                    next: Box::new(next),
+                });
+            });
+        })
+    };
+    let on_low_time = play_sound.reform(|_| Sound::LowTime);
+    let (time_control, extra_time, shown_deadline, on_timeout) = match view {
+        0 => (
+            state.time_control,
+            state.handicap.extra_time,
+            *deadline,
+            on_timeout,
+        ),
+        1 => (
+            online.time_control,
+            [0; 4],
+            online.deadline,
+            Callback::noop(),
+        ),
+        _ => (None, [0; 4], None, Callback::noop()),
+    };
+
+    let on_load = {
+        let dispatcher = state.dispatcher();
+        Callback::from(
+            move |saved: SavedGame| match GameState::from_saved(&saved) {
+                Ok(loaded) => dispatcher.dispatch(Action::Load(Box::new(loaded))),
+                Err(e) => tracing::error!(error = %e, "failed to load the game"),
+            },
+        )
+    };
+
+    // Offered on a fresh board when there is an autosave to go back to
+    let has_last = use_state(|| saves::load_last().is_some());
+    let on_resume = {
+        let on_load = on_load.clone();
+        Callback::from(move |_| {
+            if let Some(saved) = saves::load_last() {
+                on_load.emit(saved);
+            }
+        })
+    };
+
+    let on_save = {
         let state = state.clone();
-        Callback::from(move |_| state.set(Game::reset()))
+        Callback::from(move |name: String| match state.to_saved() {
+            Ok(saved) => saves::save_slot(&name, saved),
+            Err(e) => tracing::error!(error = %e, "failed to save the game"),
+        })
     };
 
+    // Online the room starts the rematch, once every player has asked for one
+    let on_rematch = {
+        let dispatcher = state.dispatcher();
+        let summary_open = summary_open.clone();
+        let in_room = online.room.is_some();
+        let on_send = on_send.clone();
+        Callback::from(move |_| {
+            if in_room {
+                on_send.emit(ClientMessage::Rematch);
+            } else {
+                dispatcher.dispatch(Action::Reset);
+            }
+            summary_open.set(false);
+        })
+    };
+    let on_close_summary = {
+        let summary_open = summary_open.clone();
+        Callback::from(move |_| summary_open.set(false))
+    };
+
+    let (board, anchors) = match &*editing {
+        Some(setting_up) => (setting_up.board(), HashSet::new()),
+        None => (*game.get_board(), game.get_current_anchors()),
+    };
+    let variant = state.game.variant();
+    let game = &game;
     html! {
-        <div>
+        <ContextProvider<Language> context={language}>
+        <div class={classes!("app", settings.classes())} {onkeydown} tabindex="0">
             <div class="title">
                 <h1>{ "Blokus Engine" }</h1>
             </div>
@@ -198,36 +1018,141 @@ pub fn App() -> Html {
             <div class="layout ">
 
                 <div class="side-panel">
-                    <h2>{ "Players Remaining" }</h2>
-                    <div class="player-icons">
-
-                        <div class={format!("square red {}", if !state.is_player_active(0) { "eliminated" } else { "" })}></div>
-                        <div class={format!("square blue {}", if !state.is_player_active(1) { "eliminated" } else { "" })}></div>
-                        <div class={format!("square green {}", if !state.is_player_active(2) { "eliminated" } else { "" })}></div>
-                        <div class={format!("square yellow {}", if !state.is_player_active(3) { "eliminated" } else { "" })}></div>
-                    </div>
+                    <h2>{ tr(language, "Scores") }</h2>
+                    <ScorePanel ..ScoreProps::from_game(game) />
+
+                    if let Some(time_control) = time_control {
+                        <h2>{ tr(language, "Clock") }</h2>
+                        <Clock {time_control} {extra_time} deadline={shown_deadline} current={game.current_player()}
+                            active={(0..4).map(|p| game.is_player_active(p)).collect::<Vec<_>>()} {on_low_time} {on_timeout} />
+                    }
+
+                    if let Some(setting_up) = &*editing {
+                        <h2>{ tr(language, "Set Up Position") }</h2>
+                        <PositionEditor editing={setting_up.clone()} on_change={on_edit_change} on_play={on_play_from}
+                            on_cancel={on_cancel_edit} />
+                    } else if replay.is_open() {
+                        <h2>{ tr(language, "Replay") }</h2>
+                        <ReplayControls replay={replay.clone()} />
+                    } else if puzzles.is_open() {
+                        <h2>{ tr(language, "Puzzle") }</h2>
+                        <PuzzleControls puzzles={puzzles.clone()} />
+                    } else if tutorial.is_open() {
+                        <h2>{ tr(language, "Tutorial") }</h2>
+                        <TutorialControls tutorial={tutorial.clone()} />
+                    } else {
+                        <h2>{ tr(language, "Seats") }</h2>
+                        if let Some(room) = online.room {
+                            <RoomPanel {room} variant={online.game.variant()} seats={online.seats.clone()} seat={online.seat} started={online.started}
+                                over={online.game.is_terminal()} series={online.series.clone()} best_of={online.best_of}
+                                status={online.status} error={online.error.clone()} {on_send} {on_leave} />
+                        } else {
+                            <div class="seats">
+                                { for (0..4).filter(|seat| variant.has_color(*seat) && variant.second_color(*seat) >= *seat).map(|seat| {
+                                    // Named by the colors the seat plays, traded by a swap
+                                    let playing = state.game.colors_of(variant.seat(seat))[0];
+                                    let mut name = tr(language, PLAYER_NAMES[playing]).to_string();
+                                    if variant.second_color(playing) != playing {
+                                        name = format!("{} & {}", name, tr(language, PLAYER_NAMES[variant.second_color(playing)]));
+                                    }
+                                    html! {
+                                        <button class="seat" onclick={on_toggle_seat(seat)} disabled={!state.can_toggle(seat)}>
+                                            { format!("{}: {}", name, tr(language, if state.ai_seats[seat] { "AI" } else { "Human" })) }
+                                        </button>
+                                    }
+                                }) }
+                            </div>
+                            <p>{ trf(language, "{} rules, {} AI, {}", &[&variant_name(variant),
+                                &state.difficulty, &time_control_name(state.time_control).to_lowercase()]) }
+                                { if state.handicap.is_none() { "" } else { tr(language, ", with handicaps") } }
+                                { if state.start.has_swap_rule() { tr(language, ", swap rule") } else { "" } }</p>
+                            <p>{ tr(language, if model.is_some() { "AI: in-browser model" } else { "AI: model server or heuristic" }) }</p>
+
+                            <h2>{ tr(language, "Play Online") }</h2>
+                            <Lobby {on_join} />
+                        }
+
+                        <h2>{ tr(language, "Replay") }</h2>
+                        <OpenRecord {on_open} />
+
+                        <h2>{ tr(language, "Puzzles") }</h2>
+                        <OpenPuzzles on_open={on_open_puzzles} />
+
+                        <h2>{ tr(language, "Learn to Play") }</h2>
+                        <button onclick={on_open_tutorial}>{ tr(language, "Rules Tutorial") }</button>
+                    }
                 </div>
 
                 <div class="main-board">
-                    <BlokusBoard board={state.get_board()} on_board_drop={on_board_drop} anchors={state.get_current_anchors()} />
+                    if let Some(text) = &*notice {
+                        <div class="notice">{ text }</div>
+                    }
+                    <BlokusBoard board={board} anchors={anchors}
+                        preview={preview} preview_legal={placement.is_some()} cursor={selected.and(*cursor)}
+                        {highlight} fresh_anchors={(*fresh_anchors).clone()} {targets} {heat}
+                        off_board={(0..D * D).filter(|s| !variant.on_board(*s)).collect::<HashSet<_>>()}
+                        {on_board_click} {on_board_hover} {on_board_drop} />
+                    if replay.is_open() {
+                        <WinGraph probabilities={(*win_chances).clone()} step={replay.step}
+                            on_jump={on_jump.clone()} />
+                    }
                 </div>
 
                 <div class="side-panel">
-                    <h2>{ "Controls" }</h2>
-                    <p style={"white-space: pre-line"}>{"
-                        Select Piece: Click\n
-                        Place Piece: Drag\n
-                        Rotate Piece: r\n
-                        Flip Piece: f\n
-                    "}</p>
-                    <button onclick={on_reset}>{ "Reset Game" }</button>
+                    <h2>{ tr(language, "Controls") }</h2>
+                    <p style={"white-space: pre-line"}>{ tr(language, CONTROLS) }</p>
+                    <button onclick={on_toggle_targets}>
+                        { tr(language, if *show_targets { "Hide Legal Squares" } else { "Show Legal Squares" }) }
+                    </button>
+                    if view == 0 {
+                        <button onclick={on_pass} disabled={state.game.is_terminal() || state.ai_to_move()}>{ tr(language, "Pass") }</button>
+                        if state.game.can_swap() && !state.ai_to_move() {
+                            <p>{ tr(language, "Swap rule: you may take over Red's opening instead of replying to it") }</p>
+                            <button onclick={on_swap}>{ tr(language, "Swap Sides") }</button>
+                        }
+                        <button onclick={on_undo} disabled={!state.can_undo()}>{ tr(language, "Undo") }</button>
+                        <button onclick={on_new_game}>{ tr(language, "New Game") }</button>
+                        <button onclick={on_reset}>{ tr(language, "Reset Game") }</button>
+                        <button onclick={on_edit} disabled={editing.is_some()}>{ tr(language, "Set Up Position") }</button>
+                        if *has_last && state.game.history.is_empty() {
+                            <button onclick={on_resume}>{ tr(language, "Resume Last Game") }</button>
+                        }
+                        <SavePanel {on_save} {on_load} />
+                    }
+
+                    <h2>{ tr(language, "Moves") }</h2>
+                    <MoveList {moves} {step} {on_jump} />
+
+                    <h2>{ tr(language, "Analysis") }</h2>
+                    <AnalysisPanel overlay={*overlay} analysis={(*analysis).clone()} with_model={model.is_some()}
+                        on_change={on_overlay} />
+
+                    <h2>{ tr(language, "Settings") }</h2>
+                    <SettingsPanel settings={(*settings).clone()} on_change={on_settings} />
                 </div>
 
             </div>
             </div>
 
-            <PieceTray pieces={state.get_current_player_pieces()} player_num={state.current_player() as u8 + 1} />
+            <div class="tray-buttons">
+                <button onclick={turn_selected.reform(|_| false)} disabled={selected.is_none()}>{ tr(language, "Rotate") }</button>
+                <button onclick={turn_selected.reform(|_| true)} disabled={selected.is_none()}>{ tr(language, "Flip") }</button>
+            </div>
+            if *setup_open {
+                <SetupScreen initial={Setup { theme: settings.theme, ..setup::load() }} {on_start} on_cancel={on_cancel_setup} />
+            }
+
+            if *summary_open && game.is_terminal() {
+                <GameSummary breakdown={game.get_score_breakdown()} variant={game.variant()} record={(*record).clone()}
+                    can_rematch={view == 0} {on_rematch} on_close={on_close_summary} />
+            }
+
+            <PieceTray pieces={game.get_current_player_pieces()} player_num={game.current_player() as u8 + 1}
+                orientations={(*orientations).clone()} selected={selected.map(|grab| grab.piece)}
+                moves={(*piece_moves).clone()} order={settings.tray_order} grouped={settings.group_by_size}
+                {on_select} on_rotate={turn_selected.reform(|_| false)} {on_grab} {on_release} />
 
         </div>
+        </ContextProvider<Language>>
     }
 }
@@ -1,7 +1,4 @@
-use std::collections::HashSet;
-
-use wasm_bindgen::JsCast;
-use web_sys::HtmlElement;
+use std::collections::{HashMap, HashSet};
 
 use yew::prelude::*;
 use yew::{function_component, html, Properties};
@@ -9,27 +6,86 @@ use yew::events::DragEvent;
 
 use blokus::board::BOARD_SIZE;
 
+use crate::analysis::Heat;
+
+const MAX_ZOOM: f64 = 3.0;
+
+/// Distance between the first two fingers on the screen, if there are two
+fn spread(event: &TouchEvent) -> Option<f64> {
+    let touches = event.touches();
+    let (a, b) = (touches.get(0)?, touches.get(1)?);
+    let dx = (a.client_x() - b.client_x()) as f64;
+    let dy = (a.client_y() - b.client_y()) as f64;
+    Some(dx.hypot(dy)).filter(|d| *d > 0.0)
+}
+
 #[derive(Properties, Clone, PartialEq)]
 pub struct Props {
     pub board: [u8; BOARD_SIZE * BOARD_SIZE],
-    pub on_board_drop: Callback<(usize, usize, usize)>,
     pub anchors: HashSet<usize>,
+    pub preview: Vec<usize>,   // Squares the pending placement would cover
+    pub preview_legal: bool,   // Whether it can be placed there
+    pub cursor: Option<usize>, // Square the selected piece is aimed at with the keyboard
+    pub highlight: Vec<usize>, // Squares of the last piece placed
+    pub fresh_anchors: HashSet<usize>, // Anchors the player to move did not have last turn
+    pub targets: HashSet<usize>, // Squares the held piece can be placed from
+    pub heat: HashMap<usize, Heat>, // Analysis overlay shading
+    pub off_board: HashSet<usize>, // Squares the rules leave out, see Variant::on_board
+    pub on_board_click: Callback<usize>,
+    pub on_board_hover: Callback<usize>,
+    pub on_board_drop: Callback<usize>,
 }
 
 #[function_component]
 pub fn BlokusBoard(props: &Props) -> Html {
-    let Props { board, on_board_drop, anchors } = props.clone();
+    let Props {
+        board,
+        anchors,
+        preview,
+        preview_legal,
+        cursor,
+        highlight,
+        fresh_anchors,
+        targets,
+        heat,
+        off_board,
+        on_board_click,
+        on_board_hover,
+        on_board_drop,
+    } = props.clone();
 
-    let ondragover = {
-        move |event: DragEvent| {
-            event.prevent_default();
+    // Pinching with two fingers zooms in, and the zoomed board scrolls inside its frame
+    let zoom = use_state(|| 1.0);
+    let pinch = use_mut_ref(|| None::<(f64, f64)>); // Finger spread and zoom as the pinch began
+    let ontouchstart = {
+        let zoom = zoom.clone();
+        let pinch = pinch.clone();
+        move |event: TouchEvent| {
+            if let Some(start) = spread(&event) {
+                *pinch.borrow_mut() = Some((start, *zoom));
+            }
+        }
+    };
+    let ontouchmove = {
+        let zoom = zoom.clone();
+        let pinch = pinch.clone();
+        move |event: TouchEvent| {
+            if let (Some((start, from)), Some(now)) = (*pinch.borrow(), spread(&event)) {
+                zoom.set((from * now / start).clamp(1.0, MAX_ZOOM));
+            }
+        }
+    };
+    let ontouchend = move |event: TouchEvent| {
+        if event.touches().length() < 2 {
+            *pinch.borrow_mut() = None;
         }
     };
 
     html! {
-        <div class="board">
+        <div class="board-frame" {ontouchstart} {ontouchmove} {ontouchend}>
+        <div class="board" style={format!("--zoom: {}", *zoom)}>
         {for (0..BOARD_SIZE).map(|i| {
-            
+
             html! {
                 <div class="board-row">
                 {
@@ -43,29 +99,51 @@ pub fn BlokusBoard(props: &Props) -> Html {
                             _ => "square empty".to_string(),
                         };
 
+                        if off_board.contains(&index) {
+                            square_style = format!("{} off-board", square_style);
+                        }
                         if anchors.contains(&index) {
                             square_style = format!("{} anchor", square_style);
                         }
+                        if fresh_anchors.contains(&index) {
+                            square_style = format!("{} fresh-anchor", square_style);
+                        }
+                        if targets.contains(&index) {
+                            square_style = format!("{} target", square_style);
+                        }
+                        if highlight.contains(&index) {
+                            square_style = format!("{} last-move", square_style);
+                        }
+                        if cursor == Some(index) {
+                            square_style = format!("{} cursor", square_style);
+                        }
+                        if preview.contains(&index) {
+                            let validity = if preview_legal { "legal" } else { "illegal" };
+                            square_style = format!("{} preview {}", square_style, validity);
+                        }
 
-                        let ondrop = {
-                            on_board_drop.reform(move |e: DragEvent| {
-                                e.prevent_default();
+                        let (style, title) = match heat.get(&index) {
+                            Some(shade) => {
+                                square_style = format!("{} heat", square_style);
+                                (Some(format!("--heat: {}", shade.color)), Some(shade.label.clone()))
+                            }
+                            None => (None, None),
+                        };
 
-                                let target: HtmlElement = e.target().unwrap().dyn_into().unwrap();
-                                let data = e.data_transfer().expect("Data transfer should exist");
-                                let id = data.get_data("piece_num").expect("Dragged piece should have an id");
-                                let variant = data.get_data("variant").unwrap().parse().unwrap();
-                                let clicked_square: usize = data.get_data("piece_offset").unwrap().parse().unwrap();
+                        let onclick = on_board_click.reform(move |_: MouseEvent| index);
 
-                                let piece: usize = id.parse().unwrap();
-                                let offset: usize = target.id().parse().unwrap();
-                                let offset = offset - clicked_square;
-                                (piece, variant, offset)
-                            })
-                        };
+                        // Snap the held piece to the square under the pointer
+                        let ondragover = on_board_hover.reform(move |e: DragEvent| {
+                            e.prevent_default();
+                            index
+                        });
+                        let ondrop = on_board_drop.reform(move |e: DragEvent| {
+                            e.prevent_default();
+                            index
+                        });
 
                         html! {
-                            <div id={index.to_string()}  class={square_style} {ondrop} {ondragover} ></div>
+                            <div id={index.to_string()}  class={square_style} {style} {title} {onclick} {ondrop} {ondragover} ></div>
                         }
                     })
                 }
@@ -73,5 +151,6 @@ pub fn BlokusBoard(props: &Props) -> Html {
             }
         })}
         </div>
+        </div>
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,103 @@
+/*
+Move clocks for timed games
+Each player gets the same time for every move, plus any extra time given as a handicap, the
+one to move counts down from the deadline and goes red when running low. A local player who runs out has the AI move
+for them, as the room server does online, so the clock only reports it.
+*/
+
+use gloo_timers::callback::Interval;
+use yew::prelude::*;
+
+const PLAYERS: [(&str, &str); 4] = [
+    ("Red", "red"),
+    ("Blue", "blue"),
+    ("Green", "green"),
+    ("Yellow", "yellow"),
+];
+const TICK_MS: u32 = 250;
+const MAX_LOW_TIME_MS: f64 = 10_000.0;
+
+/// Milliseconds since the epoch, the clock every deadline is measured on
+pub fn now() -> f64 {
+    js_sys::Date::now()
+}
+
+/// Warn once a quarter of the move time is left, or ten seconds on longer clocks
+fn low_time_ms(time_control: u64) -> f64 {
+    (time_control as f64 * 250.0).min(MAX_LOW_TIME_MS)
+}
+
+fn format_time(ms: f64) -> String {
+    let seconds = (ms.max(0.0) / 1000.0).ceil() as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub time_control: u64,     // Seconds per move
+    pub deadline: Option<f64>, // When the player to move runs out, None while no one is timed
+    pub current: usize,
+    pub active: Vec<bool>, // Players who can still move
+    pub on_low_time: Callback<()>,
+    pub on_timeout: Callback<()>,
+    #[prop_or_default]
+    pub extra_time: [u64; 4], // Seconds each player gets on top of the time control
+}
+
+/// A clock for every player, ticking for the one to move
+#[function_component]
+pub fn Clock(props: &Props) -> Html {
+    let time = use_state(now);
+
+    // Tick until the deadline passes, reporting low time and the timeout once each
+    {
+        let time = time.clone();
+        let on_low_time = props.on_low_time.clone();
+        let on_timeout = props.on_timeout.clone();
+        let low_time = low_time_ms(props.time_control + props.extra_time[props.current]);
+        use_effect_with(props.deadline, move |deadline| {
+            let interval = deadline.map(|deadline| {
+                let (mut warned, mut timed_out) = (false, false);
+                time.set(now());
+                Interval::new(TICK_MS, move || {
+                    let current = now();
+                    time.set(current);
+                    let left = deadline - current;
+                    if left <= low_time && !std::mem::replace(&mut warned, true) {
+                        on_low_time.emit(());
+                    }
+                    if left <= 0.0 && !std::mem::replace(&mut timed_out, true) {
+                        on_timeout.emit(());
+                    }
+                })
+            });
+            move || drop(interval)
+        });
+    }
+
+    let full = |player: usize| (props.time_control + props.extra_time[player]) as f64 * 1000.0;
+    html! {
+        <div class="clock">
+            { for PLAYERS.iter().enumerate().map(|(player, (name, color))| {
+                let ticking = player == props.current && props.active[player];
+                let left = match props.deadline {
+                    Some(deadline) if ticking => (deadline - *time).min(full(player)),
+                    _ => full(player),
+                };
+                let class = classes!(
+                    "clock-row",
+                    ticking.then_some("to-move"),
+                    (ticking && props.deadline.is_some() && left <= low_time_ms(props.time_control + props.extra_time[player])).then_some("low-time"),
+                    (!props.active[player]).then_some("eliminated"),
+                );
+                html! {
+                    <div {class}>
+                        <div class={classes!("square", *color)}></div>
+                        <span>{ name }</span>
+                        <span class="clock-time">{ format_time(left) }</span>
+                    </div>
+                }
+            }) }
+        </div>
+    }
+}
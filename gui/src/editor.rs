@@ -0,0 +1,214 @@
+/*
+Board editor for setting up a position by hand, to compose a puzzle or reproduce a reported game
+Clicking a square paints it in the picked color, or clears it when it already has that color,
+and each player's pieces are taken out of or put back in their hand by clicking them.
+The position can also be pasted in the one line format of blokus::position and copied out once valid.
+*/
+
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use blokus::board::{Board, BOARD_SIZE as D};
+use blokus::game::{Game, Variant};
+use blokus::position::{from_position, to_position, Editor, TOTAL_TILES};
+
+use crate::setup::variant_name;
+
+const PLAYER_NAMES: [&str; 4] = ["Red", "Blue", "Green", "Yellow"];
+const COLORS: [&str; 4] = ["red", "blue", "green", "yellow"];
+
+/// The position being set up and the color clicked squares are painted, None to clear them
+#[derive(Clone, PartialEq)]
+pub struct Editing {
+    pub editor: Editor,
+    pub brush: Option<usize>,
+}
+
+impl Editing {
+    pub fn new(game: &Game) -> Editing {
+        Editing {
+            editor: Editor::from_game(game),
+            brush: Some(0),
+        }
+    }
+
+    /// Paint a square with the brush, clearing it if it is that color already
+    pub fn click(&self, square: usize) -> Editing {
+        let mut editing = self.clone();
+        let painted = self.editor.squares[square];
+        editing
+            .editor
+            .set_square(square, self.brush.filter(|p| painted != Some(*p)));
+        editing
+    }
+
+    /// The squares in the form the board draws them
+    pub fn board(&self) -> [u8; D * D] {
+        self.editor
+            .squares
+            .map(|player| player.map_or(0, |p| p as u8 + 1))
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub editing: Editing,
+    pub on_change: Callback<Editing>,
+    pub on_play: Callback<Game>,
+    pub on_cancel: Callback<()>,
+}
+
+/// Side panel with the brush, each player's pieces, and the rest of the position
+#[function_component]
+pub fn PositionEditor(props: &Props) -> Html {
+    let editing = &props.editing;
+    let editor = &editing.editor;
+    let game = editor.to_game();
+    let pasted = use_node_ref();
+    let paste_error = use_state(|| None::<String>);
+
+    let update = |change: Box<dyn Fn(&mut Editing)>| {
+        let on_change = props.on_change.clone();
+        let editing = editing.clone();
+        Callback::from(move |_| {
+            let mut updated = editing.clone();
+            change(&mut updated);
+            on_change.emit(updated);
+        })
+    };
+
+    let on_paste = {
+        let on_change = props.on_change.clone();
+        let editing = editing.clone();
+        let pasted = pasted.clone();
+        let paste_error = paste_error.clone();
+        Callback::from(move |_| {
+            let Some(input) = pasted.cast::<HtmlInputElement>() else {
+                return;
+            };
+            match from_position(input.value().trim()) {
+                Ok(game) => {
+                    on_change.emit(Editing {
+                        editor: Editor::from_game(&game),
+                        ..editing.clone()
+                    });
+                    input.set_value("");
+                    paste_error.set(None);
+                }
+                Err(e) => paste_error.set(Some(e)),
+            }
+        })
+    };
+
+    let on_play = {
+        let on_play = props.on_play.clone();
+        let game = game.clone();
+        Callback::from(move |_| {
+            if let Ok(game) = &game {
+                on_play.emit(game.clone());
+            }
+        })
+    };
+
+    // Keep typing in the box from reaching the board's keyboard controls
+    let stop_keys = Callback::from(|event: KeyboardEvent| event.stop_propagation());
+
+    let pieces = Board::new().get_pieces(0);
+    let brush = |player: Option<usize>| {
+        let name = player.map_or("Clear", |p| PLAYER_NAMES[p]);
+        let class = classes!("brush", (editing.brush == player).then_some("picked"));
+        html! {
+            <button {class} onclick={update(Box::new(move |e| e.brush = player))}>{ name }</button>
+        }
+    };
+
+    html! {
+        <div class="editor">
+            <div class="brushes">
+                { for (0..4).map(|p| brush(Some(p))) }
+                { brush(None) }
+                <button onclick={update(Box::new(|e| e.editor = Editor::new(e.editor.variant)))}>{ "Empty Board" }</button>
+            </div>
+
+            <label>
+                { "Rules " }
+                { for Variant::ALL.iter().map(|variant| {
+                    let variant = *variant;
+                    html! {
+                        <button class={classes!((editor.variant == variant).then_some("picked"))}
+                            onclick={update(Box::new(move |e| e.editor.variant = variant))}>
+                            { variant_name(variant) }
+                        </button>
+                    }
+                }) }
+            </label>
+
+            { for (0..4).map(|player| {
+                let placed = editor.placed(player);
+                let in_hand = editor.in_hand(player);
+                let class = classes!("editor-count", (placed + in_hand != TOTAL_TILES).then_some("error"));
+                html! {
+                    <div class="editor-player">
+                        <div class="editor-player-row">
+                            <strong>{ PLAYER_NAMES[player] }</strong>
+                            <span {class}>{ format!("{} placed, {} in hand", placed, in_hand) }</span>
+                        </div>
+                        <div class="editor-pieces">
+                            { for pieces.iter().map(|piece| {
+                                let id = piece.id;
+                                let held = editor.pieces[player] & 1 << id != 0;
+                                let class = classes!("editor-piece", (!held).then_some("used"));
+                                let title = if held { "In hand, click to take it out" } else { "Played, click to put it back" };
+                                html! {
+                                    <div {class} {title} onclick={update(Box::new(move |e| e.editor.toggle_piece(player, id)))}>
+                                        { for piece.shape.iter().map(|row| html! {
+                                            <div class="grid-row">
+                                                { for row.iter().map(|cell| html! {
+                                                    <div class={classes!("square", if *cell { COLORS[player] } else { "blank" })}></div>
+                                                }) }
+                                            </div>
+                                        }) }
+                                    </div>
+                                }
+                            }) }
+                        </div>
+                        <div class="editor-player-row">
+                            <label>
+                                <input type="radio" name="to-move" checked={editor.to_move == player}
+                                    onclick={update(Box::new(move |e| e.editor.to_move = player))} />
+                                { "To move" }
+                            </label>
+                            <label>
+                                <input type="checkbox" checked={editor.out[player]}
+                                    onclick={update(Box::new(move |e| e.editor.out[player] = !e.editor.out[player]))} />
+                                { "Out" }
+                            </label>
+                            <label title="Scores 5 more for playing every piece">
+                                <input type="checkbox" checked={editor.monomino_last[player]}
+                                    onclick={update(Box::new(move |e| e.editor.monomino_last[player] = !e.editor.monomino_last[player]))} />
+                                { "Single square last" }
+                            </label>
+                        </div>
+                    </div>
+                }
+            }) }
+
+            { match &game {
+                Ok(game) => html! { <input class="position" readonly=true value={to_position(game)} onkeydown={stop_keys.clone()} /> },
+                Err(e) => html! { <p class="error">{ e }</p> },
+            } }
+            <div class="editor-paste">
+                <input ref={pasted} placeholder="Paste a position" onkeydown={stop_keys} />
+                <button onclick={on_paste}>{ "Load" }</button>
+            </div>
+            if let Some(e) = &*paste_error {
+                <p class="error">{ e }</p>
+            }
+
+            <div class="modal-buttons">
+                <button onclick={on_play} disabled={game.is_err()}>{ "Play From Here" }</button>
+                <button onclick={props.on_cancel.reform(|_| ())}>{ "Cancel" }</button>
+            </div>
+        </div>
+    }
+}
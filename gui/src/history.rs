@@ -0,0 +1,47 @@
+use yew::prelude::*;
+use yew::{function_component, html, Properties};
+
+use blokus::notation::format_square;
+use blokus::records::RecordedMove;
+
+const COLORS: [&str; 4] = ["red", "blue", "green", "yellow"];
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub moves: Vec<RecordedMove>,
+    pub step: usize, // Moves shown on the board, the rest are greyed out
+    pub on_jump: Callback<usize>,
+}
+
+/// Every move so far in square notation, click one to see the board just after it
+#[function_component]
+pub fn MoveList(props: &Props) -> Html {
+    html! {
+        <ol class="move-list">
+            { for props.moves.iter().enumerate().map(|(i, recorded)| {
+                let notation = if recorded.tiles.is_empty() {
+                    "pass".to_string()
+                } else {
+                    recorded
+                        .tiles
+                        .iter()
+                        .map(|t| format_square(*t))
+                        .collect::<Vec<_>>()
+                        .join("-")
+                };
+                let class = classes!(
+                    "move-entry",
+                    (i + 1 == props.step).then_some("current"),
+                    (i >= props.step).then_some("future"),
+                );
+                let onclick = props.on_jump.reform(move |_| i + 1);
+                html! {
+                    <li {class} {onclick}>
+                        <span class={classes!("move-color", COLORS[recorded.player])}></span>
+                        { notation }
+                    </li>
+                }
+            }) }
+        </ol>
+    }
+}
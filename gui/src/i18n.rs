@@ -0,0 +1,183 @@
+/*
+Translations of the interface text, picked in the settings
+Text is written in English in the components and passed through tr, which looks up the
+picked language's version and falls back to the English when there is none, so a missing
+line shows up untranslated rather than blank. Text with numbers or names in it uses {}
+placeholders, filled in order by trf. The language reaches components through a context
+provided at the root of the app, read with use_language.
+*/
+
+use serde::{Deserialize, Serialize};
+use yew::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Spanish];
+
+    /// The language's name in itself, as the picker shows it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Español",
+        }
+    }
+
+    /// Language tag for the page's lang attribute
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+        }
+    }
+}
+
+/// The language picked in the settings, English outside the app's provider
+#[hook]
+pub fn use_language() -> Language {
+    use_context::<Language>().unwrap_or_default()
+}
+
+/// Text in the given language, the English text is the key
+pub fn tr(language: Language, text: &'static str) -> &'static str {
+    match language {
+        Language::English => text,
+        Language::Spanish => spanish(text).unwrap_or(text),
+    }
+}
+
+/// Translate text with {} placeholders, then fill them in order
+pub fn trf(language: Language, text: &'static str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut filled = String::new();
+    for (i, part) in tr(language, text).split("{}").enumerate() {
+        if let Some(arg) = i.checked_sub(1).and_then(|a| args.get(a)) {
+            filled.push_str(&arg.to_string());
+        }
+        filled.push_str(part);
+    }
+    filled
+}
+
+fn spanish(text: &str) -> Option<&'static str> {
+    Some(match text {
+        // Players
+        "Red" => "Rojo",
+        "Blue" => "Azul",
+        "Green" => "Verde",
+        "Yellow" => "Amarillo",
+        "AI" => "IA",
+        "Human" => "Humano",
+        "Out" => "Fuera",
+
+        // Headings
+        "Scores" => "Puntuación",
+        "Clock" => "Reloj",
+        "Set Up Position" => "Preparar posición",
+        "Replay" => "Repetición",
+        "Puzzle" => "Problema",
+        "Puzzles" => "Problemas",
+        "Tutorial" => "Tutorial",
+        "Seats" => "Asientos",
+        "Play Online" => "Jugar en línea",
+        "Learn to Play" => "Aprender a jugar",
+        "Controls" => "Controles",
+        "Moves" => "Jugadas",
+        "Analysis" => "Análisis",
+        "Settings" => "Ajustes",
+
+        // Side panels
+        "{} rules, {} AI, {}" => "Reglas {}, IA {}, {}",
+        ", with handicaps" => ", con ventajas",
+        ", swap rule" => ", regla de cambio",
+        "AI: in-browser model" => "IA: modelo en el navegador",
+        "AI: model server or heuristic" => "IA: servidor del modelo o heurística",
+        "Rules Tutorial" => "Tutorial de reglas",
+        "Blue swapped sides, the seats trade colors" => {
+            "Azul cambió de lado, los asientos intercambian colores"
+        }
+        "{} passed and is out" => "{} pasó y queda fuera",
+        "{} has no legal moves and is out" => "{} no tiene jugadas legales y queda fuera",
+        "Place Piece: Drag by any square\nSelect Piece: Click\nRotate Selected: r or click it again\nFlip Selected: f\nAim Selected: Arrow keys or click a square\nPlace Selected: Enter or click again" => {
+            "Colocar pieza: arrastrar desde cualquier casilla\nElegir pieza: clic\nGirar la elegida: r o clic de nuevo\nVoltear la elegida: f\nApuntar la elegida: flechas o clic en una casilla\nColocar la elegida: Intro o clic de nuevo"
+        }
+        "Hide Legal Squares" => "Ocultar casillas legales",
+        "Show Legal Squares" => "Mostrar casillas legales",
+        "Pass" => "Pasar",
+        "Swap rule: you may take over Red's opening instead of replying to it" => {
+            "Regla de cambio: puedes quedarte con la apertura de Rojo en lugar de responderla"
+        }
+        "Swap Sides" => "Cambiar de lado",
+        "Undo" => "Deshacer",
+        "New Game" => "Nueva partida",
+        "Reset Game" => "Reiniciar partida",
+        "Resume Last Game" => "Continuar la última partida",
+        "Rotate" => "Girar",
+        "Flip" => "Voltear",
+
+        // Settings
+        "Language " => "Idioma ",
+        "Theme " => "Tema ",
+        " Player patterns" => " Texturas de jugador",
+        " Sound effects" => " Efectos de sonido",
+        "Piece tray " => "Bandeja de piezas ",
+        " Group pieces by size" => " Agrupar piezas por tamaño",
+        "Classic" => "Clásico",
+        "Dark" => "Oscuro",
+        "High Contrast" => "Alto contraste",
+        "Deuteranopia Safe" => "Apto para deuteranopía",
+        "Protanopia Safe" => "Apto para protanopía",
+        "By shape" => "Por forma",
+        "Largest first" => "Primero las grandes",
+        "Smallest first" => "Primero las pequeñas",
+
+        // Replays
+        "Game {}" => "Partida {}",
+        "Move {} of {}" => "Jugada {} de {}",
+        "Play" => "Reproducir",
+        "Pause" => "Pausa",
+        "Close Replay" => "Cerrar repetición",
+
+        // Analysis
+        "Off" => "Desactivado",
+        "Policy" => "Política",
+        "Search Visits" => "Visitas de búsqueda",
+        "Value" => "Valor",
+        "{} simulations, best line {}" => "{} simulaciones, mejor línea {}",
+        "Evaluated by the model" => "Evaluado por el modelo",
+        "Evaluated by the heuristic, no model loaded" => {
+            "Evaluado por la heurística, sin modelo cargado"
+        }
+
+        // Summary
+        "{} wins!" => "¡Gana {}!",
+        "{} tie!" => "¡Empate entre {}!",
+        " and " => " y ",
+        "Place" => "Puesto",
+        "Player" => "Jugador",
+        "Placed" => "Colocadas",
+        "In Hand" => "En mano",
+        "Bonus" => "Bonificación",
+        "Score" => "Puntos",
+        "Side" => "Equipo",
+        "A point off for each square in hand, +15 for playing every piece, and +5 more if the last was the single square" => {
+            "Un punto menos por cada casilla en mano, +15 por jugar todas las piezas y +5 más si la última fue la casilla suelta"
+        }
+        "Rematch" => "Revancha",
+        "Export Game" => "Exportar partida",
+        "Close" => "Cerrar",
+
+        // Tutorial
+        "Lesson {} of {}: {}" => "Lección {} de {}: {}",
+        "Well done! {}" => "¡Muy bien! {}",
+        "Place a piece to try it" => "Coloca una pieza para probarlo",
+        "Retry" => "Reintentar",
+        "{} of {} lessons done" => "{} de {} lecciones hechas",
+        "Close Tutorial" => "Cerrar tutorial",
+        _ => return None,
+    })
+}
@@ -1,10 +1,35 @@
+mod analysis;
 mod app;
 mod board;
+mod clock;
+mod editor;
+mod history;
+mod i18n;
+mod online;
 mod pieces;
+mod placement;
+mod puzzles;
+mod replay;
+mod saves;
+mod scores;
+mod settings;
+mod setup;
+mod sounds;
+mod state;
+mod summary;
+mod tutorial;
+mod win_graph;
 
 use app::App;
 
 fn main() {
+    // Log to the browser console, info and up
+    let config = tracing_wasm::WASMLayerConfigBuilder::new()
+        .set_max_level(tracing::Level::INFO)
+        .set_report_logs_in_timings(false)
+        .build();
+    tracing_wasm::set_as_global_default_with_config(config);
+
     // Run the GUI, will be called by trunk serve
     yew::Renderer::<App>::new().render();
 }
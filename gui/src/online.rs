@@ -0,0 +1,554 @@
+/*
+Networked games in the multiplayer rooms hosted by blokus-server
+The room holds the real game, this keeps a copy in step with its state and move
+messages. The socket is reopened whenever it drops and the seat taken back with
+its token, so a flaky connection does not cost anyone their game. Once a game is over
+the players can ask for a rematch, played with everyone one seat along.
+*/
+
+use std::rc::Rc;
+
+use futures::channel::mpsc::{unbounded, TryRecvError, UnboundedReceiver, UnboundedSender};
+use futures::future::{select, Either};
+use futures::{SinkExt, StreamExt};
+use gloo_timers::future::TimeoutFuture;
+use reqwasm::http::Request;
+use reqwasm::websocket::futures::WebSocket;
+use reqwasm::websocket::Message;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+use yew::Reducible;
+
+use blokus::agents::Difficulty;
+use blokus::game::{Game, Move, Variant};
+use blokus::notation::{format_move, parse_move};
+use blokus::state::GameState;
+
+use crate::clock;
+
+const ROOM_SERVER: &str = "127.0.0.1:3000";
+const RECONNECT_DELAY_MS: u32 = 2000;
+const COLORS: [&str; 4] = ["Red", "Blue", "Green", "Yellow"];
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Sit {
+        seat: Option<usize>,
+        name: Option<String>, // Lets the server keep the series between named players
+    },
+    Reconnect {
+        token: String,
+    },
+    Ready,
+    Start,
+    Move {
+        #[serde(rename = "move")]
+        text: String,
+    },
+    Back,
+    Rematch,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Seated {
+        seat: usize,
+        token: String,
+    },
+    State {
+        state: GameState,
+        seats: Vec<Seat>,
+        #[serde(default)]
+        config: RoomConfig,
+        started: bool,
+        #[serde(default)]
+        series: Series,
+    },
+    Move {
+        player: usize,
+        #[serde(rename = "move")]
+        text: String,
+    },
+    AfkWarning {
+        seat: usize,
+        seconds_left: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// The parts of the room's settings shown here
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RoomConfig {
+    pub time_control: Option<u64>, // Seconds per move
+    pub best_of: Option<u32>,
+}
+
+/// Wins over the room's games, by the players in the order they first sat
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Series {
+    pub players: Vec<String>,
+    pub wins: Vec<f64>,
+    pub games: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Seat {
+    Open,
+    Human {
+        connected: bool,
+        ready: bool,
+        #[serde(default)]
+        away: bool, // The engine plays for them until they come back
+    },
+    Ai {
+        agent: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Offline,
+    Connecting,
+    Connected,
+    Reconnecting,
+}
+
+pub enum OnlineAction {
+    Join(u64),
+    Leave,
+    Connected,
+    Disconnected,
+    Received(ServerMessage),
+}
+
+/// Our copy of a room, `room` is None when playing on this screen only
+#[derive(Clone)]
+pub struct OnlineState {
+    pub room: Option<u64>,
+    pub game: Game,
+    pub seats: Vec<Seat>,
+    pub seat: Option<usize>,
+    pub token: Option<String>,
+    pub started: bool,
+    pub time_control: Option<u64>,
+    pub best_of: Option<u32>,
+    pub series: Series,
+    pub deadline: Option<f64>, // When the player to move runs out, see clock::now
+    pub status: Status,
+    pub error: Option<String>,
+}
+
+impl Default for OnlineState {
+    fn default() -> Self {
+        OnlineState {
+            room: None,
+            game: Game::reset(),
+            seats: vec![Seat::Open; 4],
+            seat: None,
+            token: None,
+            started: false,
+            time_control: None,
+            best_of: None,
+            series: Series::default(),
+            deadline: None,
+            status: Status::Offline,
+            error: None,
+        }
+    }
+}
+
+impl OnlineState {
+    /// The seat playing the color to move, see Game::seat_of
+    pub fn seat_to_move(&self) -> usize {
+        self.game.seat_of(self.game.current_player())
+    }
+
+    /// Whether a move played now would be accepted
+    pub fn my_turn(&self) -> bool {
+        self.status == Status::Connected
+            && self.started
+            && !self.game.is_terminal()
+            && self.seat == Some(self.seat_to_move())
+    }
+}
+
+impl Reducible for OnlineState {
+    type Action = OnlineAction;
+
+    fn reduce(self: Rc<Self>, action: OnlineAction) -> Rc<Self> {
+        // Ignore whatever a socket still had in flight after we left
+        if self.room.is_none() && !matches!(action, OnlineAction::Join(_)) {
+            return self;
+        }
+        let mut state = (*self).clone();
+        match action {
+            OnlineAction::Join(room) => {
+                state = OnlineState {
+                    room: Some(room),
+                    status: Status::Connecting,
+                    ..OnlineState::default()
+                }
+            }
+            OnlineAction::Leave => state = OnlineState::default(),
+            OnlineAction::Connected => {
+                state.status = Status::Connected;
+                state.error = None;
+            }
+            OnlineAction::Disconnected => state.status = Status::Reconnecting,
+            OnlineAction::Received(message) => match message {
+                ServerMessage::Seated { seat, token } => {
+                    state.seat = Some(seat);
+                    state.token = Some(token);
+                }
+                ServerMessage::State {
+                    state: room_state,
+                    seats,
+                    config,
+                    started,
+                    series,
+                } => {
+                    state.seats = seats;
+                    state.started = started;
+                    state.time_control = config.time_control;
+                    state.best_of = config.best_of;
+                    state.series = series;
+                    state.deadline = room_state.time_left.map(|ms| clock::now() + ms as f64);
+                    if state.game.history != room_state.history_pairs() {
+                        match room_state.to_game() {
+                            Ok(rebuilt) => state.game = rebuilt,
+                            Err(e) => {
+                                tracing::error!(error = %e, "could not follow the room's game")
+                            }
+                        }
+                    }
+                }
+                ServerMessage::Move { player, text } => {
+                    if state.seat == Some(player % state.seats.len().max(1)) {
+                        state.error = None; // Any idle warning is over
+                    }
+                    // The state message that follows catches up anything missed here
+                    if state.game.current_player() == player {
+                        let next =
+                            parse_move(&state.game, &text).and_then(|(piece, variant, offset)| {
+                                state.game.place_piece(piece, variant, offset)
+                            });
+                        if let Ok(next) = next {
+                            state.game = next;
+                            state.deadline = state
+                                .time_control
+                                .filter(|_| !state.game.is_terminal())
+                                .map(|seconds| clock::now() + seconds as f64 * 1000.0);
+                        }
+                    }
+                }
+                ServerMessage::AfkWarning { seat, seconds_left } => {
+                    if state.seat == Some(seat) {
+                        state.error = Some(format!(
+                            "Still there? Move within {} seconds to keep your seat",
+                            seconds_left
+                        ));
+                    }
+                }
+                ServerMessage::Error { message } => state.error = Some(message),
+            },
+        }
+        Rc::new(state)
+    }
+}
+
+/// Messages waiting to go out on a room's socket
+/// Clones share the socket, which stays open until one of them is closed.
+#[derive(Clone)]
+pub struct Connection {
+    sender: UnboundedSender<String>,
+}
+
+impl Connection {
+    /// Connect to a room, reporting everything that happens on `on_event`
+    pub fn open(room: u64, on_event: Callback<OnlineAction>) -> Connection {
+        let (sender, receiver) = unbounded();
+        spawn_local(run_socket(room, receiver, on_event));
+        Connection { sender }
+    }
+
+    pub fn send(&self, message: &ClientMessage) {
+        let _ = self
+            .sender
+            .unbounded_send(serde_json::to_string(message).unwrap());
+    }
+
+    /// Play a move for our seat, in the room's square notation
+    pub fn play(&self, game: &Game, chosen: Move) {
+        self.send(&ClientMessage::Move {
+            text: format_move(game, chosen),
+        });
+    }
+
+    pub fn close(&self) {
+        self.sender.close_channel();
+    }
+}
+
+/// Pass messages between the socket and the app, reopening the socket whenever it drops
+async fn run_socket(
+    room: u64,
+    mut outgoing: UnboundedReceiver<String>,
+    on_event: Callback<OnlineAction>,
+) {
+    let url = format!("ws://{}/rooms/{}/ws", ROOM_SERVER, room);
+    loop {
+        // Anything sent while disconnected is stale, and a closed channel means we left
+        loop {
+            match outgoing.try_recv() {
+                Ok(_) => continue,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Closed) => return,
+            }
+        }
+
+        match WebSocket::open(&url) {
+            Ok(socket) => {
+                let (mut sink, mut stream) = socket.split();
+                on_event.emit(OnlineAction::Connected);
+                loop {
+                    match select(stream.next(), outgoing.next()).await {
+                        Either::Left((Some(Ok(Message::Text(text))), _)) => {
+                            match serde_json::from_str(&text) {
+                                Ok(message) => on_event.emit(OnlineAction::Received(message)),
+                                Err(e) => tracing::warn!(error = %e, "unexpected room message"),
+                            }
+                        }
+                        Either::Left((Some(Ok(_)), _)) => {}
+                        Either::Left((_, _)) => break,
+                        Either::Right((Some(text), _)) => {
+                            if sink.send(Message::Text(text)).await.is_err() {
+                                break;
+                            }
+                        }
+                        Either::Right((None, _)) => return,
+                    }
+                }
+                on_event.emit(OnlineAction::Disconnected);
+            }
+            Err(e) => tracing::warn!(error = %e, "failed to reach the room server"),
+        }
+        TimeoutFuture::new(RECONNECT_DELAY_MS).await;
+    }
+}
+
+#[derive(Deserialize)]
+struct CreatedRoom {
+    id: u64,
+}
+
+/// Make a new room where open seats go to the engine at the given difficulty
+async fn create_room(difficulty: &str) -> Result<u64, String> {
+    let config = serde_json::json!({ "difficulty": difficulty });
+    let response = Request::post(&format!("http://{}/rooms", ROOM_SERVER))
+        .header("Content-Type", "application/json")
+        .body(config.to_string())
+        .send()
+        .await
+        .map_err(|e| format!("Failed to create a room: {:?}", e))?;
+    if !response.ok() {
+        return Err(format!("Failed to create a room: {}", response.status()));
+    }
+    let created: CreatedRoom = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to create a room: {:?}", e))?;
+    Ok(created.id)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct LobbyProps {
+    pub on_join: Callback<u64>,
+}
+
+/// Create a room or join one by its id
+#[function_component]
+pub fn Lobby(props: &LobbyProps) -> Html {
+    let room_input = use_node_ref();
+    let difficulty_input = use_node_ref();
+    let error = use_state(|| None::<String>);
+
+    let on_create = {
+        let on_join = props.on_join.clone();
+        let difficulty_input = difficulty_input.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            let difficulty = difficulty_input
+                .cast::<HtmlSelectElement>()
+                .map(|select| select.value())
+                .unwrap_or_else(|| "medium".to_string());
+            let on_join = on_join.clone();
+            let error = error.clone();
+            spawn_local(async move {
+                match create_room(&difficulty).await {
+                    Ok(room) => on_join.emit(room),
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+
+    let on_join_click = {
+        let on_join = props.on_join.clone();
+        let room_input = room_input.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            let room = room_input
+                .cast::<HtmlInputElement>()
+                .and_then(|input| input.value().trim().parse().ok());
+            match room {
+                Some(room) => on_join.emit(room),
+                None => error.set(Some("Enter a room number".to_string())),
+            }
+        })
+    };
+
+    // Keep typing in the box from reaching the board's keyboard controls
+    let stop_keys = Callback::from(|event: KeyboardEvent| event.stop_propagation());
+
+    html! {
+        <div class="lobby">
+            <select ref={difficulty_input}>
+                { for Difficulty::LEVELS.iter().map(|level| html! {
+                    <option value={*level} selected={*level == "medium"}>{ level }</option>
+                }) }
+            </select>
+            <button onclick={on_create}>{ "Create Room" }</button>
+            <input ref={room_input} type="number" placeholder="Room" onkeydown={stop_keys} />
+            <button onclick={on_join_click}>{ "Join Room" }</button>
+            if let Some(e) = &*error {
+                <p class="error">{ e }</p>
+            }
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct RoomProps {
+    pub room: u64,
+    pub variant: Variant,
+    pub seats: Vec<Seat>,
+    pub seat: Option<usize>,
+    pub started: bool,
+    pub over: bool, // The game has ended, so a rematch can be asked for
+    pub series: Series,
+    pub best_of: Option<u32>,
+    pub status: Status,
+    pub error: Option<String>,
+    pub on_send: Callback<ClientMessage>,
+    pub on_leave: Callback<()>,
+}
+
+/// Seats, connection status, and the room's buttons
+#[function_component]
+pub fn RoomPanel(props: &RoomProps) -> Html {
+    let status = match props.status {
+        Status::Offline => "Offline",
+        Status::Connecting => "Connecting...",
+        Status::Connected => "Connected",
+        Status::Reconnecting => "Reconnecting...",
+    };
+    let send = |message: fn() -> ClientMessage| {
+        let on_send = props.on_send.clone();
+        Callback::from(move |_| on_send.emit(message()))
+    };
+    let name_input = use_node_ref();
+    let sit = |seat: usize| {
+        let on_send = props.on_send.clone();
+        let name_input = name_input.clone();
+        Callback::from(move |_| {
+            let name = name_input
+                .cast::<HtmlInputElement>()
+                .map(|input| input.value().trim().to_string())
+                .filter(|name| !name.is_empty());
+            on_send.emit(ClientMessage::Sit {
+                seat: Some(seat),
+                name,
+            })
+        })
+    };
+    let stop_keys = Callback::from(|event: KeyboardEvent| event.stop_propagation());
+    let on_leave = props.on_leave.reform(|_| ());
+    let can_sit = !props.started && props.seat.is_none() && props.status == Status::Connected;
+    let colors = |seat: usize| {
+        let names: Vec<&str> = props
+            .variant
+            .colors(seat)
+            .iter()
+            .map(|c| COLORS[*c])
+            .collect();
+        names.join(" & ")
+    };
+    let away = props
+        .seat
+        .is_some_and(|seat| matches!(props.seats[seat], Seat::Human { away: true, .. }));
+    let series = &props.series;
+    let decided = props
+        .best_of
+        .is_some_and(|n| series.games >= n || series.wins.iter().any(|w| *w > n as f64 / 2.0));
+    let can_rematch = props.started && props.over && props.seat.is_some() && !decided;
+
+    html! {
+        <div class="room">
+            <p>{ format!("Room {}: {}", props.room, status) }</p>
+            <div class="seats">
+                { for props.seats.iter().enumerate().map(|(i, seat)| {
+                    let who = match seat {
+                        Seat::Open => "Open".to_string(),
+                        Seat::Human { away: true, .. } => "Away, engine playing".to_string(),
+                        Seat::Human { connected: false, .. } => "Away".to_string(),
+                        Seat::Human { ready: true, .. } if !props.started => "Ready".to_string(),
+                        Seat::Human { ready: true, .. } if props.over => "Wants a rematch".to_string(),
+                        Seat::Human { .. } => "Player".to_string(),
+                        Seat::Ai { agent } => format!("AI ({})", agent),
+                    };
+                    let you = if props.seat == Some(i) { " (you)" } else { "" };
+                    html! {
+                        <button class="seat" onclick={sit(i)} disabled={!can_sit || *seat != Seat::Open}>
+                            { format!("{}: {}{}", colors(i), who, you) }
+                        </button>
+                    }
+                }) }
+            </div>
+            if can_sit {
+                <input ref={name_input} placeholder="Name (optional)" onkeydown={stop_keys} />
+            }
+            if series.games > 0 {
+                <p>{ match props.best_of {
+                    Some(n) => format!("Series, best of {}, after {} games:", n, series.games),
+                    None => format!("Series after {} games:", series.games),
+                } }</p>
+                <p>{ series.players.iter().zip(&series.wins)
+                    .map(|(player, wins)| format!("{} {}", player, wins))
+                    .collect::<Vec<_>>().join(", ") }</p>
+            }
+            if can_rematch {
+                <button onclick={send(|| ClientMessage::Rematch)}>{ "Rematch" }</button>
+            }
+            if !props.started {
+                <button onclick={send(|| ClientMessage::Ready)} disabled={props.seat.is_none()}>{ "Ready" }</button>
+                <button onclick={send(|| ClientMessage::Start)}>{ "Start Now" }</button>
+            }
+            if away {
+                <button onclick={send(|| ClientMessage::Back)}>{ "I'm Back" }</button>
+            }
+            <button onclick={on_leave}>{ "Leave Room" }</button>
+            if let Some(e) = &props.error {
+                <p class="error">{ e }</p>
+            }
+        </div>
+    }
+}
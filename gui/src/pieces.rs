@@ -1,4 +1,5 @@
-use gloo_console as console;
+use std::collections::HashMap;
+
 use wasm_bindgen::JsCast;
 use web_sys::HtmlElement;
 
@@ -7,13 +8,35 @@ use yew::prelude::*;
 use yew::{function_component, html, Properties};
 
 use blokus::pieces::{Piece, PieceVariant};
-use web_sys::KeyboardEvent;
 use yew::Callback;
 
+use crate::placement::Grab;
+use crate::settings::TrayOrder;
+
+/// Name for the pieces of each size
+fn group_name(size: u32) -> &'static str {
+    match size {
+        1 => "Monomino",
+        2 => "Domino",
+        3 => "Triominoes",
+        4 => "Tetrominoes",
+        _ => "Pentominoes",
+    }
+}
+
 #[derive(Properties, PartialEq)]
 pub struct Props {
     pub pieces: Vec<Piece>,
     pub player_num: u8,
+    pub orientations: HashMap<usize, usize>, // Variant shown for each piece id, 0 if missing
+    pub selected: Option<usize>,             // Index of the selected piece
+    pub moves: Vec<usize>, // Legal placements of each piece, see Game::moves_per_piece
+    pub order: TrayOrder,
+    pub grouped: bool, // Split the tray by piece size
+    pub on_select: Callback<Grab>,
+    pub on_rotate: Callback<()>, // Clicking or tapping the selected piece again turns it
+    pub on_grab: Callback<Grab>,
+    pub on_release: Callback<()>,
 }
 
 #[function_component]
@@ -25,12 +48,47 @@ pub fn PieceTray(props: &Props) -> Html {
         4 => "yellow",
         _ => "empty",
     };
+
+    // Pieces keep their index in the player's set however they are laid out
+    let mut shown: Vec<usize> = (0..props.pieces.len()).collect();
+    let size = |idx: &usize| props.pieces[*idx].points;
+    match props.order {
+        TrayOrder::Shape if props.grouped => shown.sort_by_key(size),
+        TrayOrder::Shape => {}
+        TrayOrder::Largest => shown.sort_by_key(|idx| std::cmp::Reverse(size(idx))),
+        TrayOrder::Smallest => shown.sort_by_key(size),
+    }
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for idx in shown {
+        match groups.last_mut() {
+            Some(group) if !props.grouped || size(&group[0]) == size(&idx) => group.push(idx),
+            _ => groups.push(vec![idx]),
+        }
+    }
+
+    let piece = |idx: &usize| {
+        let piece = &props.pieces[*idx];
+        html! {
+            <GUIPiece key={piece.id} piece={piece.clone()} piece_num={*idx} color={color}
+                variant={props.orientations.get(&piece.id).copied().unwrap_or(0)}
+                selected={props.selected == Some(*idx)} dead={props.moves.get(*idx) == Some(&0)}
+                on_select={props.on_select.clone()} on_rotate={props.on_rotate.clone()}
+                on_grab={props.on_grab.clone()}
+                on_release={props.on_release.clone()} />
+        }
+    };
     html! {
         <div class="piece-tray">
             <div class="piece-tray-inner">
-                { for props.pieces.iter().enumerate().map(|(idx, piece)| html! {
-                    <GUIPiece key={piece.id} piece={piece.clone()} piece_num={idx.to_string()} color={color} />
-                })
+                if props.grouped {
+                    { for groups.iter().map(|group| html! {
+                        <div class="piece-group" key={size(&group[0])}>
+                            <span class="piece-group-name">{ group_name(size(&group[0])) }</span>
+                            <div class="piece-group-pieces">{ for group.iter().map(piece) }</div>
+                        </div>
+                    }) }
+                } else {
+                    { for groups.iter().flatten().map(piece) }
                 }
             </div>
         </div>
@@ -40,117 +98,96 @@ pub fn PieceTray(props: &Props) -> Html {
 #[derive(Properties, PartialEq)]
 pub struct PieceProps {
     pub piece: Piece,
-    pub piece_num: String,
+    pub piece_num: usize,
     pub color: &'static str,
+    pub variant: usize,
+    pub selected: bool,
+    pub dead: bool, // No legal placements left, shown faded
+    pub on_select: Callback<Grab>,
+    pub on_rotate: Callback<()>,
+    pub on_grab: Callback<Grab>,
+    pub on_release: Callback<()>,
 }
 
 #[function_component]
 fn GUIPiece(props: &PieceProps) -> Html {
-    // State
-    let variant = use_state(|| 0);
+    // Which square the piece was picked up by
     let clicked_square = use_state(|| 0);
+    // Whether the piece was already selected when the press began, so the click turns it
+    let was_selected = use_mut_ref(|| false);
+    let grab = Grab {
+        piece: props.piece_num,
+        variant: props.variant,
+        square: *clicked_square,
+    };
 
     let ondragstart = {
-        let variant = variant.clone();
-        let clicked_square = clicked_square.clone();
-        let piece = props.piece.clone();
+        let on_grab = props.on_grab.clone();
         move |event: DragEvent| {
             let target = event.target().unwrap();
             let target: HtmlElement = target.dyn_into().unwrap();
             target.class_list().add_1("dragging").unwrap();
-            let piece_variant = piece.variants.get(*variant).unwrap();
-            let offset = piece_variant
-                .offsets
-                .get(*clicked_square)
-                .unwrap()
-                .to_string();
 
+            // Some browsers only start a drag that carries data
             let data = event.data_transfer().unwrap();
-            let _ = data.set_data(
-                "piece_num",
-                target.get_attribute("data-piece-num").unwrap().as_str(),
-            );
-            let _ = data.set_data("variant", &*variant.to_string().as_str());
-            let _ = data.set_data("piece_offset", offset.as_str());
-            console::log!("Drag start", event);
-            console::log!("Piece offset", offset);
+            let _ = data.set_data("piece_num", &grab.piece.to_string());
+            on_grab.emit(grab);
         }
     };
 
     let squareclicked = {
         let clicked_square = clicked_square.clone();
+        let on_select = props.on_select.clone();
+        let was_selected = was_selected.clone();
+        let selected = props.selected;
         move |event: MouseEvent| {
+            *was_selected.borrow_mut() = selected;
             let target = event.target().unwrap();
             let target: HtmlElement = target.dyn_into().unwrap();
-            let square = target.get_attribute("data-square").unwrap();
-            clicked_square.set(square.clone().parse().unwrap());
-            console::log!("Square clicked", square);
+            let square = target
+                .get_attribute("data-square")
+                .unwrap()
+                .parse()
+                .unwrap();
+            clicked_square.set(square);
+            on_select.emit(Grab { square, ..grab });
+        }
+    };
+
+    // A drag never ends in a click, so this is only a press and release in place
+    let onclick = {
+        let on_rotate = props.on_rotate.clone();
+        move |_: MouseEvent| {
+            if was_selected.replace(false) {
+                on_rotate.emit(());
+            }
         }
     };
 
     let ondragend = {
+        let on_release = props.on_release.clone();
         move |event: DragEvent| {
             let target = event.target().unwrap();
             let target: HtmlElement = target.dyn_into().unwrap();
             target.class_list().remove_1("dragging").unwrap();
-            console::log!("Drag end", event);
+            on_release.emit(());
         }
     };
 
-    let rotate = {
-        let num_variants = props.piece.variants.len();
-        let variant = variant.clone();
-        Callback::from(move |_| {
-            let next = match num_variants {
-                1 => 0,
-                2 => (*variant + 1) % 2,
-                4 => (*variant + 1) % 4,
-                8 => {
-                    if *variant > 3 {
-                        (*variant + 1) % 4 + 4
-                    } else {
-                        (*variant + 1) % 4
-                    }
-                }
-                _ => 0,
-            };
-            variant.set(next);
-            console::log!("ROTATE", *variant)
-        })
-    };
-
-    let flip = {
-        let num_variants = props.piece.variants.len();
-        let variant = variant.clone();
-        Callback::from(move |_| {
-            let next = match num_variants {
-                1 => 0,
-                2 => *variant,
-                4 => (*variant + 2) % 4, // Depends on symmetry of shape but this is okay for now
-                8 => (*variant + 4) % 8,
-                _ => 0,
-            };
-            variant.set(next);
-            console::log!("FLIP", *variant)
-        })
-    };
-
-    let onkeypress = {
-        Callback::from(move |event: KeyboardEvent| match event.key().as_str() {
-            "r" => rotate.emit(event),
-            "f" => flip.emit(event),
-            _ => console::log!("Key pressed", event.key()),
-        })
-    };
-
     let mut square_num = -1;
     let v: &PieceVariant = props
         .piece
         .variants
-        .get(*variant)
-        .expect(format!("Variant {:?} not found", props.piece.variants).as_str());
+        .get(props.variant)
+        .unwrap_or_else(|| panic!("Variant {} not found", props.variant));
+    let class = classes!(
+        "piece",
+        props.selected.then_some("selected"),
+        props.dead.then_some("dead")
+    );
+    let title = props.dead.then_some("No legal placements");
     html! {
-        <div data-piece-num={props.piece_num.clone()} class={classes!("piece")} draggable="true" {ondragstart} {ondragend} {onkeypress} tabindex="0">
+        <div data-piece-num={props.piece_num.to_string()} {class} {title} draggable="true" {ondragstart} {ondragend} {onclick} tabindex="0">
             {for v.get_shape().iter().enumerate().map(|(row_index, row)| html! {
                 <div class="grid-row" key={row_index}>
                     { for row.iter().enumerate().map(|(col_index, &cell)|
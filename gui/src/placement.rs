@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+
+use blokus::board::BOARD_SIZE;
+use blokus::game::{Game, Move};
+
+/// A piece picked up from the tray, held by one of its squares
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grab {
+    pub piece: usize,   // Index into the current player's remaining pieces
+    pub variant: usize, // Index into the piece's variants
+    pub square: usize,  // Which of the variant's squares is held, in reading order
+}
+
+/// Where a held piece would land with its held square on `cell`
+/// Returns the board tiles it would cover, leaving out any off the board,
+/// and the move if the placement is legal
+pub fn footprint(game: &Game, grab: Grab, cell: usize) -> (Vec<usize>, Option<Move>) {
+    let pieces = game.get_current_player_pieces();
+    let variant = match pieces
+        .get(grab.piece)
+        .and_then(|p| p.variants.get(grab.variant))
+    {
+        Some(variant) => variant,
+        None => return (Vec::new(), None),
+    };
+    let held = match variant.offsets.get(grab.square) {
+        Some(held) => *held,
+        None => return (Vec::new(), None),
+    };
+
+    // Work in rows and columns so pieces hanging off one edge do not wrap to the other
+    let size = BOARD_SIZE as i32;
+    let top = (cell / BOARD_SIZE) as i32 - (held / BOARD_SIZE) as i32;
+    let left = (cell % BOARD_SIZE) as i32 - (held % BOARD_SIZE) as i32;
+    let mut tiles = Vec::new();
+    let mut on_board = true;
+    for offset in &variant.offsets {
+        let row = top + (offset / BOARD_SIZE) as i32;
+        let col = left + (offset % BOARD_SIZE) as i32;
+        if (0..size).contains(&row) && (0..size).contains(&col) {
+            tiles.push((row * size + col) as usize);
+        } else {
+            on_board = false;
+        }
+    }
+
+    let placement = (
+        grab.piece,
+        grab.variant,
+        (top * size + left).max(0) as usize,
+    );
+    let legal = on_board && top >= 0 && left >= 0 && game.get_legal_moves().contains(&placement);
+    (tiles, legal.then_some(placement))
+}
+
+/// Cells the held square can be dropped on for a legal placement of the piece as it is turned
+pub fn targets(game: &Game, grab: Grab) -> HashSet<usize> {
+    let pieces = game.get_current_player_pieces();
+    let held = match pieces
+        .get(grab.piece)
+        .and_then(|p| p.variants.get(grab.variant))
+        .and_then(|v| v.offsets.get(grab.square))
+    {
+        Some(held) => *held,
+        None => return HashSet::new(),
+    };
+    game.get_legal_moves()
+        .into_iter()
+        .filter(|(piece, variant, _)| *piece == grab.piece && *variant == grab.variant)
+        .map(|(_, _, offset)| offset + held)
+        .collect()
+}
+
+/// Next variant a quarter turn on, pieces list their rotations before their flipped rotations
+pub fn rotated(num_variants: usize, variant: usize) -> usize {
+    match num_variants {
+        2 => (variant + 1) % 2,
+        4 => (variant + 1) % 4,
+        8 => {
+            if variant > 3 {
+                (variant + 1) % 4 + 4
+            } else {
+                (variant + 1) % 4
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// Variant mirrored left to right
+pub fn flipped(num_variants: usize, variant: usize) -> usize {
+    match num_variants {
+        2 => variant,
+        4 => (variant + 2) % 4, // Depends on symmetry of shape but this is okay for now
+        8 => (variant + 4) % 8,
+        _ => 0,
+    }
+}
+
+/// Move a board square one step in an arrow key's direction, stopping at the edges
+pub fn nudged(cell: usize, key: &str) -> usize {
+    let (row, col) = (cell / BOARD_SIZE, cell % BOARD_SIZE);
+    let (row, col) = match key {
+        "ArrowUp" => (row.saturating_sub(1), col),
+        "ArrowDown" => ((row + 1).min(BOARD_SIZE - 1), col),
+        "ArrowLeft" => (row, col.saturating_sub(1)),
+        "ArrowRight" => (row, (col + 1).min(BOARD_SIZE - 1)),
+        _ => (row, col),
+    };
+    row * BOARD_SIZE + col
+}
@@ -0,0 +1,247 @@
+/*
+Puzzle mode for files in the format of blokus::puzzles, like the ones `puzzles mine` prints
+Each puzzle's position is shown with its objective, the piece placed is checked against it,
+and a wrong answer can be taken back to try again or the solution outlined on the board.
+*/
+
+use std::rc::Rc;
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yew::Reducible;
+
+use blokus::game::{Game, Move};
+use blokus::puzzles::{parse, Objective, Puzzle};
+
+const PLAYER_NAMES: [&str; 4] = ["Red", "Blue", "Green", "Yellow"];
+
+pub enum PuzzleAction {
+    Open(Box<PuzzleSet>),
+    Close,
+    /// Switch to another puzzle in the same file
+    Select(usize),
+    Answer(Move),
+    /// Take back a wrong answer
+    Retry,
+    /// Outline a move that solves the puzzle
+    Reveal,
+}
+
+/// A puzzle file being worked through, empty when no file is open
+#[derive(Clone)]
+pub struct PuzzleSet {
+    pub puzzles: Vec<Puzzle>,
+    pub index: usize,                 // Puzzle being shown
+    pub start: Game,                  // Its position
+    pub answer: Option<(Game, bool)>, // Position after the answer and whether it solved the puzzle
+    pub solution: Vec<usize>,         // Squares of a solution once revealed
+    pub solved: Vec<bool>,
+}
+
+impl Default for PuzzleSet {
+    fn default() -> Self {
+        PuzzleSet {
+            puzzles: Vec::new(),
+            index: 0,
+            start: Game::reset(),
+            answer: None,
+            solution: Vec::new(),
+            solved: Vec::new(),
+        }
+    }
+}
+
+impl PuzzleSet {
+    pub fn new(puzzles: Vec<Puzzle>) -> Result<PuzzleSet, String> {
+        let first = puzzles.first().ok_or("The file has no puzzles")?;
+        Ok(PuzzleSet {
+            start: first.game()?,
+            solved: vec![false; puzzles.len()],
+            puzzles,
+            ..PuzzleSet::default()
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.puzzles.is_empty()
+    }
+
+    pub fn game(&self) -> &Game {
+        match &self.answer {
+            Some((game, _)) => game,
+            None => &self.start,
+        }
+    }
+
+    pub fn puzzle(&self) -> Option<&Puzzle> {
+        self.puzzles.get(self.index)
+    }
+}
+
+// The positions follow from the puzzles and the answer played
+impl PartialEq for PuzzleSet {
+    fn eq(&self, other: &Self) -> bool {
+        let answered = |set: &PuzzleSet| set.answer.as_ref().map(|(g, s)| (g.history.clone(), *s));
+        self.puzzles == other.puzzles
+            && self.index == other.index
+            && answered(self) == answered(other)
+            && self.solution == other.solution
+            && self.solved == other.solved
+    }
+}
+
+impl Reducible for PuzzleSet {
+    type Action = PuzzleAction;
+
+    fn reduce(self: Rc<Self>, action: PuzzleAction) -> Rc<Self> {
+        let mut set = (*self).clone();
+        match action {
+            PuzzleAction::Open(opened) => set = *opened,
+            PuzzleAction::Close => set = PuzzleSet::default(),
+            PuzzleAction::Select(index) => match set.puzzles.get(index).map(Puzzle::game) {
+                Some(Ok(start)) => {
+                    set.index = index;
+                    set.start = start;
+                    set.answer = None;
+                    set.solution.clear();
+                }
+                _ => return self,
+            },
+            PuzzleAction::Answer((piece, variant, offset)) => {
+                let Some(puzzle) = set.puzzle() else {
+                    return self;
+                };
+                if set.answer.is_some() {
+                    return self;
+                }
+                let (Ok(next), Ok(solved)) = (
+                    set.start.place_piece(piece, variant, offset),
+                    puzzle.solves((piece, variant, offset)),
+                ) else {
+                    return self;
+                };
+                set.solved[set.index] |= solved;
+                set.answer = Some((next, solved));
+            }
+            PuzzleAction::Retry => set.answer = None,
+            PuzzleAction::Reveal => {
+                let Some(Ok(solutions)) = set.puzzle().map(Puzzle::solutions) else {
+                    return self;
+                };
+                let Some(&(piece, variant, offset)) = solutions.first() else {
+                    return self;
+                };
+                let player = set.start.current_player();
+                let shape = &set.start.get_piece(player, piece, variant).offsets;
+                set.solution = shape.iter().map(|t| offset + t).collect();
+                set.answer = None;
+            }
+        }
+        Rc::new(set)
+    }
+}
+
+/// What the player to move has to do, in words
+fn goal(puzzle: &Puzzle, player: usize) -> String {
+    let goal = match &puzzle.objective {
+        Objective::Play(moves) if moves.len() == 1 => "find the best move".to_string(),
+        Objective::Play(moves) => format!("find one of the {} best moves", moves.len()),
+        Objective::Anchors(n) => format!("keep {} anchors you can still play from", n),
+        Objective::Block(target) => format!("leave {} with no legal moves", PLAYER_NAMES[*target]),
+    };
+    format!("{} to move, {}", PLAYER_NAMES[player], goal)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct OpenProps {
+    pub on_open: Callback<PuzzleSet>,
+}
+
+/// File picker for a puzzle file
+#[function_component]
+pub fn OpenPuzzles(props: &OpenProps) -> Html {
+    let error = use_state(|| None::<String>);
+
+    let onchange = {
+        let on_open = props.on_open.clone();
+        let error = error.clone();
+        Callback::from(move |event: Event| {
+            let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            input.set_value("");
+            let on_open = on_open.clone();
+            let error = error.clone();
+            spawn_local(async move {
+                let text = JsFuture::from(file.text())
+                    .await
+                    .ok()
+                    .and_then(|text| text.as_string())
+                    .ok_or(format!("Failed to read {}", file.name()));
+                match text.and_then(|text| PuzzleSet::new(parse(&text)?)) {
+                    Ok(set) => {
+                        error.set(None);
+                        on_open.emit(set);
+                    }
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+
+    html! {
+        <div class="replay">
+            <input type="file" accept=".txt" {onchange} />
+            if let Some(e) = &*error {
+                <p class="error">{ e }</p>
+            }
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ControlProps {
+    pub puzzles: UseReducerHandle<PuzzleSet>,
+}
+
+/// The objective, the verdict on the answer, and buttons to move between puzzles
+#[function_component]
+pub fn PuzzleControls(props: &ControlProps) -> Html {
+    let set = &props.puzzles;
+    let Some(puzzle) = set.puzzle() else {
+        return html! {};
+    };
+
+    let button = |label: &str, action: Box<dyn Fn() -> PuzzleAction>, disabled: bool| {
+        let dispatcher = set.dispatcher();
+        let onclick = Callback::from(move |_| dispatcher.dispatch(action()));
+        html! { <button {onclick} {disabled}>{ label.to_string() }</button> }
+    };
+    let (index, count) = (set.index, set.puzzles.len());
+    let solved = set.solved.iter().filter(|s| **s).count();
+
+    html! {
+        <div class="replay">
+            <p><strong>{ format!("Puzzle {} of {}", index + 1, count) }</strong>
+                { if puzzle.title.is_empty() { String::new() } else { format!(": {}", puzzle.title) } }</p>
+            <p>{ goal(puzzle, set.start.current_player()) }</p>
+            { match &set.answer {
+                Some((_, true)) => html! { <p class="puzzle-solved">{ "Solved!" }</p> },
+                Some((_, false)) => html! { <p class="error">{ "Not quite, try again" }</p> },
+                None if !set.solution.is_empty() => html! { <p>{ "A solution is outlined on the board" }</p> },
+                None => html! { <p>{ "Place a piece to answer" }</p> },
+            } }
+            <div class="replay-buttons">
+                { button("<", Box::new(move || PuzzleAction::Select(index.saturating_sub(1))), index == 0) }
+                { button("Retry", Box::new(|| PuzzleAction::Retry), set.answer.is_none()) }
+                { button("Show Solution", Box::new(|| PuzzleAction::Reveal), false) }
+                { button(">", Box::new(move || PuzzleAction::Select(index + 1)), index + 1 == count) }
+            </div>
+            <p>{ format!("{} of {} solved", solved, count) }</p>
+            { button("Close Puzzles", Box::new(|| PuzzleAction::Close), false) }
+        </div>
+    }
+}
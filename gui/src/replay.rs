@@ -0,0 +1,284 @@
+/*
+Replay viewer for recorded games in the formats blokus::records reads, the same
+files the CLI renders. A record file can hold several games, and each is stepped
+through one placement at a time with the last piece placed highlighted.
+*/
+
+use std::rc::Rc;
+
+use gloo_timers::callback::Interval;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+use yew::Reducible;
+
+use crate::i18n::{tr, trf, use_language};
+
+use blokus::bga;
+use blokus::game::Game;
+use blokus::records::{parse_csv, parse_json, GameRecord};
+
+const AUTOPLAY_MS: u32 = 800;
+
+/// Build the position after each move of a record, checking the moves are legal
+fn positions(record: &GameRecord) -> Result<Vec<Game>, String> {
+    let mut positions = vec![Game::reset()];
+    for (i, recorded) in record.moves.iter().enumerate() {
+        let game = positions.last().unwrap();
+        if recorded.player != game.current_player() {
+            return Err(format!(
+                "Move {}: player {} moved but it is player {}'s turn",
+                i + 1,
+                recorded.player,
+                game.current_player()
+            ));
+        }
+        if recorded.tiles.is_empty() {
+            let next = game.pass().map_err(|e| format!("Move {}: {}", i + 1, e))?;
+            positions.push(next);
+            continue;
+        }
+        let (piece, variant, offset) = game
+            .find_move(&recorded.tiles)
+            .ok_or(format!("Move {}: not a legal move", i + 1))?;
+        let next = game.place_piece(piece, variant, offset)?;
+        positions.push(next);
+    }
+    Ok(positions)
+}
+
+pub enum ReplayAction {
+    Open(Box<Replay>),
+    Close,
+    First,
+    Prev,
+    Next,
+    Last,
+    /// Show the board after the given number of moves
+    Goto(usize),
+    TogglePlay,
+    /// Switch to another game in the same file
+    Select(usize),
+}
+
+/// A record file being stepped through, empty when no file is open
+#[derive(Clone)]
+pub struct Replay {
+    pub records: Vec<GameRecord>,
+    pub index: usize,         // Game being shown
+    pub positions: Vec<Game>, // Before the first move and after each one
+    pub step: usize,          // Moves played so far
+    pub playing: bool,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Replay {
+            records: Vec::new(),
+            index: 0,
+            positions: vec![Game::reset()],
+            step: 0,
+            playing: false,
+        }
+    }
+}
+
+impl Replay {
+    pub fn new(records: Vec<GameRecord>) -> Result<Replay, String> {
+        let first = records.first().ok_or("The file has no games")?;
+        let positions = positions(first)?;
+        Ok(Replay {
+            records,
+            index: 0,
+            positions,
+            step: 0,
+            playing: false,
+        })
+    }
+
+    /// Parse a record file by its extension, see blokus::records
+    pub fn from_file(name: &str, text: &str) -> Result<Replay, String> {
+        let records = if name.ends_with(".csv") {
+            parse_csv(text)?
+        } else if name.ends_with(bga::EXTENSION) {
+            bga::parse(text)?
+        } else {
+            parse_json(text)?
+        };
+        Replay::new(records)
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.records.is_empty()
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.positions[self.step]
+    }
+
+    pub fn num_moves(&self) -> usize {
+        self.positions.len() - 1
+    }
+
+    pub fn record(&self) -> Option<&GameRecord> {
+        self.records.get(self.index)
+    }
+}
+
+// The positions follow from the record and the game picked
+impl PartialEq for Replay {
+    fn eq(&self, other: &Self) -> bool {
+        self.records == other.records
+            && self.index == other.index
+            && self.step == other.step
+            && self.playing == other.playing
+    }
+}
+
+impl Reducible for Replay {
+    type Action = ReplayAction;
+
+    fn reduce(self: Rc<Self>, action: ReplayAction) -> Rc<Self> {
+        let mut replay = (*self).clone();
+        match action {
+            ReplayAction::Open(opened) => replay = *opened,
+            ReplayAction::Close => replay = Replay::default(),
+            ReplayAction::First => replay.step = 0,
+            ReplayAction::Prev => replay.step = replay.step.saturating_sub(1),
+            ReplayAction::Next => {
+                if replay.step == replay.num_moves() {
+                    replay.playing = false;
+                } else {
+                    replay.step += 1;
+                }
+            }
+            ReplayAction::Last => replay.step = replay.num_moves(),
+            ReplayAction::Goto(step) => replay.step = step.min(replay.num_moves()),
+            ReplayAction::TogglePlay => {
+                replay.playing = !replay.playing;
+                if replay.playing && replay.step == replay.num_moves() {
+                    replay.step = 0;
+                }
+            }
+            ReplayAction::Select(index) => match replay.records.get(index).map(positions) {
+                Some(Ok(positions)) => {
+                    replay.index = index;
+                    replay.positions = positions;
+                    replay.step = 0;
+                    replay.playing = false;
+                }
+                _ => return self,
+            },
+        }
+        Rc::new(replay)
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct OpenProps {
+    pub on_open: Callback<Replay>,
+}
+
+/// File picker for a .json or .csv record file
+#[function_component]
+pub fn OpenRecord(props: &OpenProps) -> Html {
+    let error = use_state(|| None::<String>);
+
+    let onchange = {
+        let on_open = props.on_open.clone();
+        let error = error.clone();
+        Callback::from(move |event: Event| {
+            let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            input.set_value("");
+            let on_open = on_open.clone();
+            let error = error.clone();
+            spawn_local(async move {
+                let text = JsFuture::from(file.text())
+                    .await
+                    .ok()
+                    .and_then(|text| text.as_string())
+                    .ok_or(format!("Failed to read {}", file.name()));
+                match text.and_then(|text| Replay::from_file(&file.name(), &text)) {
+                    Ok(replay) => {
+                        error.set(None);
+                        on_open.emit(replay);
+                    }
+                    Err(e) => error.set(Some(e)),
+                }
+            });
+        })
+    };
+
+    html! {
+        <div class="replay">
+            <input type="file" accept=".json,.csv" {onchange} />
+            if let Some(e) = &*error {
+                <p class="error">{ e }</p>
+            }
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ControlProps {
+    pub replay: UseReducerHandle<Replay>,
+}
+
+/// Step and autoplay buttons for the open record
+#[function_component]
+pub fn ReplayControls(props: &ControlProps) -> Html {
+    let replay = &props.replay;
+
+    // Step forward on a timer while playing
+    {
+        let dispatcher = replay.dispatcher();
+        use_effect_with(replay.playing, move |playing| {
+            let interval = playing.then(|| {
+                Interval::new(AUTOPLAY_MS, move || dispatcher.dispatch(ReplayAction::Next))
+            });
+            move || drop(interval)
+        });
+    }
+
+    let language = use_language();
+    let button = |label: &'static str, action: fn() -> ReplayAction| {
+        let dispatcher = replay.dispatcher();
+        let onclick = Callback::from(move |_| dispatcher.dispatch(action()));
+        html! { <button {onclick}>{ tr(language, label) }</button> }
+    };
+
+    let onchange = {
+        let dispatcher = replay.dispatcher();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+            if let Ok(index) = select.value().parse() {
+                dispatcher.dispatch(ReplayAction::Select(index));
+            }
+        })
+    };
+
+    html! {
+        <div class="replay">
+            if replay.records.len() > 1 {
+                <select {onchange}>
+                    { for (0..replay.records.len()).map(|i| html! {
+                        <option value={i.to_string()} selected={i == replay.index}>{ trf(language, "Game {}", &[&(i + 1)]) }</option>
+                    }) }
+                </select>
+            }
+            <p>{ trf(language, "Move {} of {}", &[&replay.step, &replay.num_moves()]) }</p>
+            <div class="replay-buttons">
+                { button("|<", || ReplayAction::First) }
+                { button("<", || ReplayAction::Prev) }
+                { button(if replay.playing { "Pause" } else { "Play" }, || ReplayAction::TogglePlay) }
+                { button(">", || ReplayAction::Next) }
+                { button(">|", || ReplayAction::Last) }
+            </div>
+            { button("Close Replay", || ReplayAction::Close) }
+        </div>
+    }
+}
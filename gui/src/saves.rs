@@ -0,0 +1,138 @@
+/*
+Games kept in the browser's localStorage
+The game on screen is saved after every move so refreshing the tab does not lose it,
+and any number of named slots can be saved and loaded from the side panel.
+*/
+
+use std::collections::BTreeMap;
+
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use blokus::game::Variant;
+use blokus::handicap::Handicap;
+use blokus::records::GameRecord;
+
+const LAST_KEY: &str = "blokus:last";
+const SLOTS_KEY: &str = "blokus:slots";
+
+/// Everything needed to pick a game back up
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedGame {
+    pub record: GameRecord,
+    pub undo_points: Vec<usize>, // Tiles placed at each position Undo can go back to
+    pub ai_seats: [bool; 4],
+    #[serde(default)]
+    pub variant: Variant,
+    #[serde(default = "default_difficulty")]
+    pub difficulty: String,
+    #[serde(default)]
+    pub time_control: Option<u64>,
+    #[serde(default)]
+    pub start: Option<String>, // Position the game was set up from, see blokus::position
+    #[serde(default)]
+    pub handicap: Handicap,
+    #[serde(default)]
+    pub swap_rule: bool,
+}
+
+// Games saved before difficulties could be picked had the AI at full strength
+fn default_difficulty() -> String {
+    "hard".to_string()
+}
+
+pub fn save_last(saved: &SavedGame) {
+    if let Err(e) = LocalStorage::set(LAST_KEY, saved) {
+        tracing::warn!(error = %e, "failed to save the game");
+    }
+}
+
+pub fn load_last() -> Option<SavedGame> {
+    LocalStorage::get(LAST_KEY).ok()
+}
+
+/// Named saves, in name order
+pub fn slots() -> BTreeMap<String, SavedGame> {
+    LocalStorage::get(SLOTS_KEY).unwrap_or_default()
+}
+
+pub fn save_slot(name: &str, saved: SavedGame) {
+    let mut all = slots();
+    all.insert(name.to_string(), saved);
+    if let Err(e) = LocalStorage::set(SLOTS_KEY, all) {
+        tracing::warn!(error = %e, "failed to save the game");
+    }
+}
+
+pub fn delete_slot(name: &str) {
+    let mut all = slots();
+    all.remove(name);
+    if let Err(e) = LocalStorage::set(SLOTS_KEY, all) {
+        tracing::warn!(error = %e, "failed to delete the save");
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct SaveProps {
+    pub on_save: Callback<String>,
+    pub on_load: Callback<SavedGame>,
+}
+
+/// Save the game under a name, and load or delete earlier saves
+#[function_component]
+pub fn SavePanel(props: &SaveProps) -> Html {
+    let saved = use_state(slots);
+    let name_input = use_node_ref();
+
+    let on_save = {
+        let on_save = props.on_save.clone();
+        let saved = saved.clone();
+        let name_input = name_input.clone();
+        Callback::from(move |_| {
+            let Some(input) = name_input.cast::<HtmlInputElement>() else {
+                return;
+            };
+            let name = input.value().trim().to_string();
+            if name.is_empty() {
+                return;
+            }
+            on_save.emit(name);
+            input.set_value("");
+            saved.set(slots());
+        })
+    };
+
+    // Keep typing in the box from reaching the board's keyboard controls
+    let stop_keys = Callback::from(|event: KeyboardEvent| event.stop_propagation());
+
+    html! {
+        <div class="saves">
+            <input ref={name_input} placeholder="Save name" onkeydown={stop_keys} />
+            <button onclick={on_save}>{ "Save Game" }</button>
+            { for saved.iter().map(|(name, game)| {
+                let on_load = {
+                    let on_load = props.on_load.clone();
+                    let game = game.clone();
+                    Callback::from(move |_| on_load.emit(game.clone()))
+                };
+                let on_delete = {
+                    let saved = saved.clone();
+                    let name = name.clone();
+                    Callback::from(move |_| {
+                        delete_slot(&name);
+                        saved.set(slots());
+                    })
+                };
+                html! {
+                    <div class="save-slot" key={name.clone()}>
+                        <span>{ format!("{} ({} moves)", name, game.record.moves.len()) }</span>
+                        <button onclick={on_load}>{ "Load" }</button>
+                        <button onclick={on_delete}>{ "Delete" }</button>
+                    </div>
+                }
+            }) }
+        </div>
+    }
+}
@@ -0,0 +1,90 @@
+use yew::prelude::*;
+use yew::{function_component, html, Properties};
+
+use crate::i18n::{tr, use_language};
+
+use blokus::game::Game;
+use blokus::pieces::Piece;
+
+const PLAYERS: [(&str, &str); 4] = [
+    ("Red", "red"),
+    ("Blue", "blue"),
+    ("Green", "green"),
+    ("Yellow", "yellow"),
+];
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub scores: Vec<i32>, // Official scores, remaining squares taken off and bonuses added
+    pub active: Vec<bool>, // Players who can still move
+    pub pieces: Vec<Vec<Piece>>, // Pieces each player has left
+    pub current: usize,
+}
+
+impl Props {
+    pub fn from_game(game: &Game) -> Props {
+        Props {
+            scores: game.get_score(),
+            active: (0..4).map(|p| game.is_player_active(p)).collect(),
+            pieces: (0..4).map(|p| game.get_player_pieces(p)).collect(),
+            current: game.current_player(),
+        }
+    }
+}
+
+/// Every player's score, whether they are still in, and thumbnails of their pieces left
+#[function_component]
+pub fn ScorePanel(props: &Props) -> Html {
+    let language = use_language();
+    html! {
+        <div class="scores">
+            { for PLAYERS.iter().enumerate().map(|(player, (name, color))| {
+                let class = classes!(
+                    "score-row",
+                    (player == props.current && props.active[player]).then_some("to-move"),
+                    (!props.active[player]).then_some("eliminated"),
+                );
+                html! {
+                    <div {class}>
+                        <div class="score-header">
+                            <div class={classes!("square", *color)}></div>
+                            <span>{ tr(language, name) }</span>
+                            <span class="score">{ props.scores[player] }</span>
+                            if !props.active[player] {
+                                <span>{ tr(language, "Out") }</span>
+                            }
+                        </div>
+                        <div class="mini-pieces">
+                            { for props.pieces[player].iter().map(|piece| html! {
+                                <MiniPiece key={piece.id} piece={piece.clone()} color={*color} />
+                            }) }
+                        </div>
+                    </div>
+                }
+            }) }
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct MiniProps {
+    piece: Piece,
+    color: &'static str,
+}
+
+/// A piece drawn small in its first orientation
+#[function_component]
+fn MiniPiece(props: &MiniProps) -> Html {
+    let shape = props.piece.variants[0].get_shape();
+    html! {
+        <div class="mini-piece">
+            { for shape.iter().map(|row| html! {
+                <div class="grid-row">
+                    { for row.iter().map(|&cell| html! {
+                        <div class={classes!("mini-square", if cell { props.color } else { "blank" })}></div>
+                    }) }
+                </div>
+            }) }
+        </div>
+    }
+}
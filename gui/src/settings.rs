@@ -0,0 +1,251 @@
+/*
+Display settings, kept in the browser's localStorage so they survive a refresh
+A theme swaps the palette through the CSS variables in style.css, and the colorblind
+palettes keep the four players apart for deuteranopia and protanopia. Patterns draw a
+different texture on each player's squares so they can be told apart without color.
+*/
+
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use crate::i18n::{tr, use_language, Language};
+
+const SETTINGS_KEY: &str = "blokus:settings";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Classic,
+    Dark,
+    HighContrast,
+    Deuteranopia,
+    Protanopia,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 5] = [
+        Theme::Classic,
+        Theme::Dark,
+        Theme::HighContrast,
+        Theme::Deuteranopia,
+        Theme::Protanopia,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Classic => "Classic",
+            Theme::Dark => "Dark",
+            Theme::HighContrast => "High Contrast",
+            Theme::Deuteranopia => "Deuteranopia Safe",
+            Theme::Protanopia => "Protanopia Safe",
+        }
+    }
+
+    /// Class on the page's root element that sets the theme's colors
+    pub fn class(&self) -> &'static str {
+        match self {
+            Theme::Classic => "theme-classic",
+            Theme::Dark => "theme-dark",
+            Theme::HighContrast => "theme-high-contrast",
+            Theme::Deuteranopia => "theme-deuteranopia",
+            Theme::Protanopia => "theme-protanopia",
+        }
+    }
+}
+
+/// Order of the pieces in the tray
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum TrayOrder {
+    #[default]
+    Shape, // The order of the piece set, similar shapes side by side
+    Largest,
+    Smallest,
+}
+
+impl TrayOrder {
+    pub const ALL: [TrayOrder; 3] = [TrayOrder::Shape, TrayOrder::Largest, TrayOrder::Smallest];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrayOrder::Shape => "By shape",
+            TrayOrder::Largest => "Largest first",
+            TrayOrder::Smallest => "Smallest first",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub language: Language,
+    pub theme: Theme,
+    pub patterns: bool, // Texture each player's squares as well as coloring them
+    pub muted: bool,
+    pub tray_order: TrayOrder,
+    pub group_by_size: bool, // Split the tray into pentominoes, tetrominoes, and so on
+}
+
+impl Settings {
+    /// Classes for the page's root element
+    pub fn classes(&self) -> Classes {
+        classes!(self.theme.class(), self.patterns.then_some("patterns"))
+    }
+}
+
+pub fn load() -> Settings {
+    LocalStorage::get(SETTINGS_KEY).unwrap_or_default()
+}
+
+pub fn save(settings: &Settings) {
+    if let Err(e) = LocalStorage::set(SETTINGS_KEY, settings) {
+        tracing::warn!(error = %e, "failed to save the settings");
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub settings: Settings,
+    pub on_change: Callback<Settings>,
+}
+
+/// Language and theme pickers, the pattern and sound toggles, and how the tray is laid out
+#[function_component]
+pub fn SettingsPanel(props: &Props) -> Html {
+    let language = use_language();
+    let on_language = {
+        let settings = props.settings.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+            if let Some(language) = select
+                .value()
+                .parse()
+                .ok()
+                .and_then(|i: usize| Language::ALL.get(i))
+            {
+                on_change.emit(Settings {
+                    language: *language,
+                    ..settings.clone()
+                });
+            }
+        })
+    };
+
+    let on_theme = {
+        let settings = props.settings.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+            if let Some(theme) = select
+                .value()
+                .parse()
+                .ok()
+                .and_then(|i: usize| Theme::ALL.get(i))
+            {
+                on_change.emit(Settings {
+                    theme: *theme,
+                    ..settings.clone()
+                });
+            }
+        })
+    };
+
+    let on_patterns = {
+        let settings = props.settings.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |event: Event| {
+            let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+            on_change.emit(Settings {
+                patterns: input.checked(),
+                ..settings.clone()
+            });
+        })
+    };
+
+    let on_sound = {
+        let settings = props.settings.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |event: Event| {
+            let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+            on_change.emit(Settings {
+                muted: !input.checked(),
+                ..settings.clone()
+            });
+        })
+    };
+
+    let on_tray_order = {
+        let settings = props.settings.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |event: Event| {
+            let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+            if let Some(order) = select
+                .value()
+                .parse()
+                .ok()
+                .and_then(|i: usize| TrayOrder::ALL.get(i))
+            {
+                on_change.emit(Settings {
+                    tray_order: *order,
+                    ..settings.clone()
+                });
+            }
+        })
+    };
+
+    let on_group = {
+        let settings = props.settings.clone();
+        let on_change = props.on_change.clone();
+        Callback::from(move |event: Event| {
+            let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+            on_change.emit(Settings {
+                group_by_size: input.checked(),
+                ..settings.clone()
+            });
+        })
+    };
+
+    html! {
+        <div class="settings">
+            <label>
+                { tr(language, "Language ") }
+                <select onchange={on_language}>
+                    { for Language::ALL.iter().enumerate().map(|(i, option)| html! {
+                        <option value={i.to_string()} selected={*option == language}>{ option.name() }</option>
+                    }) }
+                </select>
+            </label>
+            <label>
+                { tr(language, "Theme ") }
+                <select onchange={on_theme}>
+                    { for Theme::ALL.iter().enumerate().map(|(i, theme)| html! {
+                        <option value={i.to_string()} selected={*theme == props.settings.theme}>{ tr(language, theme.name()) }</option>
+                    }) }
+                </select>
+            </label>
+            <label>
+                <input type="checkbox" checked={props.settings.patterns} onchange={on_patterns} />
+                { tr(language, " Player patterns") }
+            </label>
+            <label>
+                <input type="checkbox" checked={!props.settings.muted} onchange={on_sound} />
+                { tr(language, " Sound effects") }
+            </label>
+            <label>
+                { tr(language, "Piece tray ") }
+                <select onchange={on_tray_order}>
+                    { for TrayOrder::ALL.iter().enumerate().map(|(i, order)| html! {
+                        <option value={i.to_string()} selected={*order == props.settings.tray_order}>{ tr(language, order.name()) }</option>
+                    }) }
+                </select>
+            </label>
+            <label>
+                <input type="checkbox" checked={props.settings.group_by_size} onchange={on_group} />
+                { tr(language, " Group pieces by size") }
+            </label>
+        </div>
+    }
+}
@@ -0,0 +1,330 @@
+/*
+New game screen for picking who plays, how strong the AI is, the rules, the look, and the clock
+Handicaps for mixed-strength tables are folded away under the menus, see blokus::handicap.
+The last choices are kept in the browser's localStorage and offered again next time.
+*/
+
+use gloo_storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsCast;
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use blokus::agents::Difficulty;
+use blokus::board::Board;
+use blokus::game::Variant;
+use blokus::handicap::Handicap;
+
+use crate::settings::Theme;
+
+const SETUP_KEY: &str = "blokus:setup";
+const PLAYER_NAMES: [&str; 4] = ["Red", "Blue", "Green", "Yellow"];
+const COLORS: [&str; 4] = ["red", "blue", "green", "yellow"];
+const TIME_CONTROLS: [Option<u64>; 5] = [None, Some(10), Some(30), Some(60), Some(120)]; // Seconds per move
+const EXTRA_TIMES: [u64; 4] = [0, 10, 30, 60]; // Seconds added per move as a handicap
+
+/// Everything picked before a game starts
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Setup {
+    pub variant: Variant,
+    pub ai_seats: [bool; 4], // Colors the AI plays, partners always go together
+    pub difficulty: String,  // One of Difficulty::LEVELS
+    pub theme: Theme,
+    pub time_control: Option<u64>, // Seconds per move, None for untimed
+    pub handicap: Handicap,
+    pub swap_rule: bool, // Whether blue may take over red's opening in the two player game
+}
+
+impl Default for Setup {
+    fn default() -> Self {
+        Setup {
+            variant: Variant::Classic,
+            ai_seats: [false, true, true, true],
+            difficulty: "hard".to_string(),
+            theme: Theme::Classic,
+            time_control: None,
+            handicap: Handicap::default(),
+            swap_rule: false,
+        }
+    }
+}
+
+impl Setup {
+    /// Games need someone to play and an AI to play against
+    pub fn is_valid(&self) -> bool {
+        let seats = self.seats();
+        let ai_count = seats
+            .iter()
+            .filter(|colors| self.ai_seats[colors[0]])
+            .count();
+        ai_count > 0 && ai_count < seats.len() && self.handicap.start(self.variant).is_ok()
+    }
+
+    /// The people at the table, each with the colors they play
+    pub fn seats(&self) -> Vec<Vec<usize>> {
+        (0..self.variant.seats())
+            .map(|seat| self.variant.colors(seat))
+            .collect()
+    }
+}
+
+pub fn load() -> Setup {
+    LocalStorage::get(SETUP_KEY).unwrap_or_default()
+}
+
+pub fn save(setup: &Setup) {
+    if let Err(e) = LocalStorage::set(SETUP_KEY, setup) {
+        tracing::warn!(error = %e, "failed to save the game setup");
+    }
+}
+
+/// Short name for the rules, as shown in the side panel
+pub fn variant_name(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Classic => "Classic",
+        Variant::TwoPlayer => "Two player",
+        Variant::Team => "Team",
+        Variant::Junior => "Junior",
+    }
+}
+
+/// What the rules are, for the menu
+fn variant_description(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Classic => "Classic, four players",
+        Variant::TwoPlayer => "Two players, two colors each",
+        Variant::Team => "Teams, partners across the table",
+        Variant::Junior => "Junior, two players with small pieces on a small board",
+    }
+}
+
+pub fn time_control_name(time_control: Option<u64>) -> String {
+    match time_control {
+        Some(seconds) => format!("{} seconds per move", seconds),
+        None => "Untimed".to_string(),
+    }
+}
+
+/// Read the index of the picked option
+fn picked(event: &Event) -> Option<usize> {
+    let select: HtmlSelectElement = event.target()?.dyn_into().ok()?;
+    select.value().parse().ok()
+}
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub initial: Setup,
+    pub on_start: Callback<Setup>,
+    pub on_cancel: Callback<()>,
+}
+
+/// Modal with a menu for each choice and a button to start the game
+#[function_component]
+pub fn SetupScreen(props: &Props) -> Html {
+    let setup = use_state(|| props.initial.clone());
+
+    // Each menu changes one field of the setup
+    let update = |change: fn(&mut Setup, usize)| {
+        let setup = setup.clone();
+        Callback::from(move |event: Event| {
+            if let Some(index) = picked(&event) {
+                let mut updated = (*setup).clone();
+                change(&mut updated, index);
+                setup.set(updated);
+            }
+        })
+    };
+    let on_variant = update(|setup, i| {
+        setup.variant = Variant::ALL[i];
+        // Partners share a seat in the two player game
+        if setup.variant == Variant::TwoPlayer {
+            setup.ai_seats[2] = setup.ai_seats[0];
+            setup.ai_seats[3] = setup.ai_seats[1];
+        }
+    });
+    let on_swap_rule = {
+        let setup = setup.clone();
+        Callback::from(move |_| {
+            let mut updated = (*setup).clone();
+            updated.swap_rule = !updated.swap_rule;
+            setup.set(updated);
+        })
+    };
+    let on_difficulty = update(|setup, i| setup.difficulty = Difficulty::LEVELS[i].to_string());
+    let on_theme = update(|setup, i| setup.theme = Theme::ALL[i]);
+    let on_time = update(|setup, i| setup.time_control = TIME_CONTROLS[i]);
+
+    // Handicap options change a single color's part of the handicap
+    let change = |change: Box<dyn Fn(&mut Handicap)>| {
+        let setup = setup.clone();
+        Callback::from(move |_| {
+            let mut updated = (*setup).clone();
+            change(&mut updated.handicap);
+            setup.set(updated);
+        })
+    };
+    let pieces = Board::new().get_pieces(0);
+    let handicap_options = |player: usize| {
+        let handicap = &setup.handicap;
+        let on_extra_time = {
+            let setup = setup.clone();
+            Callback::from(move |event: Event| {
+                if let Some(index) = picked(&event) {
+                    let mut updated = (*setup).clone();
+                    updated.handicap.extra_time[player] = EXTRA_TIMES[index];
+                    setup.set(updated);
+                }
+            })
+        };
+        html! {
+            <div class="handicap">
+                <div class="editor-player-row">
+                    <strong>{ PLAYER_NAMES[player] }</strong>
+                    <label>
+                        <input type="checkbox" checked={handicap.extra_opening[player]}
+                            onclick={change(Box::new(move |h| h.extra_opening[player] = !h.extra_opening[player]))} />
+                        { "Extra opening placement" }
+                    </label>
+                    if setup.time_control.is_some() {
+                        <select onchange={on_extra_time}>
+                            { for EXTRA_TIMES.iter().enumerate().map(|(i, seconds)| html! {
+                                <option value={i.to_string()} selected={handicap.extra_time[player] == *seconds}>
+                                    { if *seconds == 0 { "No extra time".to_string() } else { format!("{} more seconds", seconds) } }
+                                </option>
+                            }) }
+                        </select>
+                    }
+                </div>
+                <div class="editor-pieces">
+                    { for pieces.iter().map(|piece| {
+                        let id = piece.id;
+                        let removed = handicap.removed[player].contains(&id);
+                        let class = classes!("editor-piece", removed.then_some("used"));
+                        let title = if removed { "Taken out, click to put it back" } else { "Click to take it out of the set" };
+                        let toggle = move |h: &mut Handicap| match h.removed[player].iter().position(|r| *r == id) {
+                            Some(i) => {
+                                h.removed[player].remove(i);
+                            }
+                            None => h.removed[player].push(id),
+                        };
+                        html! {
+                            <div {class} {title} onclick={change(Box::new(toggle))}>
+                                { for piece.shape.iter().map(|row| html! {
+                                    <div class="grid-row">
+                                        { for row.iter().map(|cell| html! {
+                                            <div class={classes!("square", if *cell { COLORS[player] } else { "blank" })}></div>
+                                        }) }
+                                    </div>
+                                }) }
+                            </div>
+                        }
+                    }) }
+                </div>
+            </div>
+        }
+    };
+
+    let seat_menu = |colors: Vec<usize>| {
+        let setup_handle = setup.clone();
+        let onchange = {
+            let colors = colors.clone();
+            Callback::from(move |event: Event| {
+                if let Some(index) = picked(&event) {
+                    let mut updated = (*setup_handle).clone();
+                    for color in &colors {
+                        updated.ai_seats[*color] = index == 1;
+                    }
+                    setup_handle.set(updated);
+                }
+            })
+        };
+        let name = colors
+            .iter()
+            .map(|c| PLAYER_NAMES[*c])
+            .collect::<Vec<_>>()
+            .join(" & ");
+        let ai = setup.ai_seats[colors[0]];
+        html! {
+            <label>
+                { format!("{} ", name) }
+                <select {onchange}>
+                    <option value="0" selected={!ai}>{ "Human" }</option>
+                    <option value="1" selected={ai}>{ "AI" }</option>
+                </select>
+            </label>
+        }
+    };
+
+    let on_start = {
+        let on_start = props.on_start.clone();
+        let setup = setup.clone();
+        Callback::from(move |_| {
+            save(&setup);
+            on_start.emit((*setup).clone());
+        })
+    };
+
+    html! {
+        <div class="modal-backdrop">
+            <div class="modal setup">
+                <h2>{ "New Game" }</h2>
+                <label>
+                    { "Rules " }
+                    <select onchange={on_variant}>
+                        { for Variant::ALL.iter().enumerate().map(|(i, variant)| html! {
+                            <option value={i.to_string()} selected={setup.variant == *variant}>{ variant_description(*variant) }</option>
+                        }) }
+                    </select>
+                </label>
+                if setup.variant == Variant::TwoPlayer {
+                    <label>
+                        <input type="checkbox" checked={setup.swap_rule} onclick={on_swap_rule} />
+                        { "Swap rule: after the opening move, Blue may take over Red's side" }
+                    </label>
+                }
+                { for setup.seats().into_iter().map(seat_menu) }
+                <label>
+                    { "AI difficulty " }
+                    <select onchange={on_difficulty}>
+                        { for Difficulty::LEVELS.iter().enumerate().map(|(i, level)| html! {
+                            <option value={i.to_string()} selected={setup.difficulty == *level}>{ level.to_string() }</option>
+                        }) }
+                    </select>
+                </label>
+                <label>
+                    { "Theme " }
+                    <select onchange={on_theme}>
+                        { for Theme::ALL.iter().enumerate().map(|(i, theme)| html! {
+                            <option value={i.to_string()} selected={setup.theme == *theme}>{ theme.name() }</option>
+                        }) }
+                    </select>
+                </label>
+                <label>
+                    { "Time control " }
+                    <select onchange={on_time}>
+                        { for TIME_CONTROLS.iter().enumerate().map(|(i, time_control)| html! {
+                            <option value={i.to_string()} selected={setup.time_control == *time_control}>
+                                { time_control_name(*time_control) }
+                            </option>
+                        }) }
+                    </select>
+                </label>
+                <details class="handicaps" open={!setup.handicap.is_none()}>
+                    <summary>{ "Handicaps" }</summary>
+                    <p>{ "Take pieces out of a stronger player's set, they count against their score as if left in hand. \
+                        Give a weaker player more time or a second piece on their first turn." }</p>
+                    { for (0..4).map(handicap_options) }
+                    <button onclick={change(Box::new(|h| *h = Handicap::default()))}>{ "No Handicaps" }</button>
+                </details>
+                if !setup.is_valid() {
+                    <p class="error">{ "Seat at least one person and one AI" }</p>
+                }
+                <div class="modal-buttons">
+                    <button onclick={on_start} disabled={!setup.is_valid()}>{ "Start Game" }</button>
+                    <button onclick={props.on_cancel.reform(|_| ())}>{ "Cancel" }</button>
+                </div>
+            </div>
+        </div>
+    }
+}
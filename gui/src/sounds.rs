@@ -0,0 +1,79 @@
+/*
+Short sound effects made with the Web Audio API, so there are no sound files to serve
+Browsers only let a page make sound after the player has interacted with it, which
+is always true by the time the first piece goes down.
+*/
+
+use std::cell::RefCell;
+
+use wasm_bindgen::JsValue;
+use web_sys::{AudioContext, OscillatorType};
+
+const VOLUME: f32 = 0.15;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sound {
+    Place,
+    Illegal,
+    Elimination,
+    GameOver,
+    LowTime,
+}
+
+impl Sound {
+    /// Notes played one after another as (frequency in Hz, length in seconds)
+    fn notes(&self) -> &'static [(f32, f64)] {
+        match self {
+            Sound::Place => &[(660.0, 0.08)],
+            Sound::Illegal => &[(180.0, 0.15)],
+            Sound::Elimination => &[(440.0, 0.15), (330.0, 0.25)],
+            Sound::GameOver => &[(523.0, 0.15), (659.0, 0.15), (784.0, 0.3)],
+            Sound::LowTime => &[(880.0, 0.1)],
+        }
+    }
+
+    fn wave(&self) -> OscillatorType {
+        match self {
+            Sound::Illegal => OscillatorType::Square,
+            _ => OscillatorType::Sine,
+        }
+    }
+}
+
+thread_local! {
+    // Made on the first sound, one is enough for the whole page
+    static CONTEXT: RefCell<Option<AudioContext>> = const { RefCell::new(None) };
+}
+
+pub fn play(sound: Sound) {
+    let played = CONTEXT.with(|context| {
+        let mut context = context.borrow_mut();
+        if context.is_none() {
+            *context = Some(AudioContext::new()?);
+        }
+        schedule(context.as_ref().unwrap(), sound)
+    });
+    if let Err(e) = played {
+        tracing::warn!(error = ?e, "failed to play a sound");
+    }
+}
+
+/// Queue each note with a quick fade out so it does not click
+fn schedule(context: &AudioContext, sound: Sound) -> Result<(), JsValue> {
+    let mut start = context.current_time();
+    for (frequency, length) in sound.notes() {
+        let oscillator = context.create_oscillator()?;
+        oscillator.set_type(sound.wave());
+        oscillator.frequency().set_value(*frequency);
+        let gain = context.create_gain()?;
+        gain.gain().set_value_at_time(VOLUME, start)?;
+        gain.gain()
+            .exponential_ramp_to_value_at_time(0.001, start + length)?;
+        oscillator.connect_with_audio_node(&gain)?;
+        gain.connect_with_audio_node(&context.destination())?;
+        oscillator.start_with_when(start)?;
+        oscillator.stop_with_when(start + length)?;
+        start += length;
+    }
+    Ok(())
+}
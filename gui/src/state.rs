@@ -0,0 +1,275 @@
+use std::rc::Rc;
+
+use yew::Reducible;
+
+use blokus::game::{Game, Move, Variant};
+use blokus::handicap::Handicap;
+use blokus::position::{from_position, to_position};
+use blokus::records::{from_history_at, replay_from, GameRecord};
+
+use crate::saves::SavedGame;
+use crate::setup::Setup;
+
+const NUM_PLAYERS: usize = 4;
+
+pub enum Action {
+    Place(Move),
+    /// The person to move gives up the rest of the game
+    Pass,
+    /// Take over the opening side under the swap rule
+    Swap,
+    /// AI moves searched from the position with the given history
+    AiMoved {
+        from: Vec<(i32, i32)>,
+        next: Box<Game>,
+    },
+    /// The AI's move for a person who ran out of time, searched from the given history
+    TimedOut {
+        from: Vec<(i32, i32)>,
+        next: Box<Game>,
+    },
+    /// Hand a seat between a person and the AI
+    ToggleSeat(usize),
+    Undo,
+    /// Start over with the same seats and options
+    Reset,
+    /// Start a game as picked on the setup screen
+    NewGame(Box<Setup>),
+    /// Play on from a position set up by hand, with the same seats and options
+    SetUp(Box<Game>),
+    /// Pick up a saved game, see GameState::from_saved
+    Load(Box<GameState>),
+}
+
+/// The game along with the positions before each of the humans' moves
+/// Undo goes back to the last one, taking back the AI replies too
+#[derive(Clone)]
+pub struct GameState {
+    pub game: Game,
+    pub start: Game, // Where the game began, a position set up by hand or the empty board
+    pub previous: Vec<Game>,
+    pub ai_seats: [bool; NUM_PLAYERS], // Seats the AI plays, always between one and three
+    pub difficulty: String,            // One of blokus::agents::Difficulty::LEVELS
+    pub time_control: Option<u64>,     // Seconds per move, None for untimed
+    pub handicap: Handicap,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        GameState::from_setup(&Setup::default())
+    }
+}
+
+impl GameState {
+    pub fn from_setup(setup: &Setup) -> GameState {
+        let mut start = setup.handicap.start(setup.variant).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "ignoring the handicaps");
+            Game::new(setup.variant)
+        });
+        if setup.swap_rule && setup.variant == Variant::TwoPlayer {
+            start = start.clone().with_swap_rule().unwrap_or(start);
+        }
+        GameState {
+            game: start.clone(),
+            start,
+            previous: Vec::new(),
+            ai_seats: setup.ai_seats,
+            difficulty: setup.difficulty.clone(),
+            time_control: setup.time_control,
+            handicap: setup.handicap.clone(),
+        }
+    }
+
+    /// Seconds the player to move has for it, None for untimed
+    pub fn move_time(&self) -> Option<u64> {
+        self.time_control
+            .map(|seconds| self.handicap.move_time(seconds, self.game.current_player()))
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.previous.is_empty()
+    }
+
+    /// Whether the AI should move next, seats keep their hands when the sides are swapped
+    pub fn ai_to_move(&self) -> bool {
+        let seat = self.game.seat_of(self.game.current_player());
+        !self.game.is_terminal() && self.ai_seats[self.game.variant().colors(seat)[0]]
+    }
+
+    /// Whether a seat can change hands without leaving all humans or no AI
+    /// Partner colors in the two player game change hands together
+    pub fn can_toggle(&self, seat: usize) -> bool {
+        let mut toggled = self.ai_seats;
+        self.toggle(&mut toggled, seat);
+        let ai_count = toggled.iter().filter(|ai| **ai).count();
+        ai_count > 0 && ai_count < NUM_PLAYERS
+    }
+
+    fn toggle(&self, ai_seats: &mut [bool; NUM_PLAYERS], seat: usize) {
+        let ai = !ai_seats[seat];
+        ai_seats[seat] = ai;
+        ai_seats[self.game.variant().second_color(seat)] = ai;
+    }
+
+    pub fn to_saved(&self) -> Result<SavedGame, String> {
+        let start = to_position(&self.start);
+        let set_up = match self.handicap.start(self.start.variant()) {
+            Ok(handicapped) => start != to_position(&handicapped),
+            Err(_) => true,
+        };
+        Ok(SavedGame {
+            record: from_history_at(&self.start, &self.game.history)?,
+            undo_points: self.previous.iter().map(|g| g.history.len()).collect(),
+            ai_seats: self.ai_seats,
+            variant: self.game.variant(),
+            difficulty: self.difficulty.clone(),
+            time_control: self.time_control,
+            start: set_up.then_some(start),
+            handicap: self.handicap.clone(),
+            swap_rule: self.start.has_swap_rule(),
+        })
+    }
+
+    /// Replay a saved game, checking every move is still legal
+    pub fn from_saved(saved: &SavedGame) -> Result<GameState, String> {
+        let mut start = match &saved.start {
+            Some(position) => from_position(position)?,
+            None => saved.handicap.start(saved.variant)?,
+        };
+        if saved.swap_rule {
+            start = start.with_swap_rule()?;
+        }
+        let game = replay_from(start.clone(), &saved.record)?.game;
+
+        // Positions Undo goes back to are earlier points in the same game
+        let mut previous = Vec::new();
+        for point in &saved.undo_points {
+            // The swap is the one history entry right after the first move
+            let swap_at = saved
+                .record
+                .moves
+                .first()
+                .map_or(0, |m| m.tiles.len().max(1));
+            let swapped = saved.record.swapped && *point > swap_at;
+            let mut tiles = usize::from(swapped);
+            let moves = saved
+                .record
+                .moves
+                .iter()
+                .take_while(|m| {
+                    tiles += m.tiles.len().max(1); // A pass is one history entry
+                    tiles <= *point
+                })
+                .cloned()
+                .collect();
+            previous.push(replay_from(start.clone(), &GameRecord { moves, swapped })?.game);
+        }
+
+        let ai_count = saved.ai_seats.iter().filter(|ai| **ai).count();
+        if ai_count == 0 || ai_count == NUM_PLAYERS {
+            return Err("A saved game needs between one and three AI seats".to_string());
+        }
+        Ok(GameState {
+            game,
+            start,
+            previous,
+            ai_seats: saved.ai_seats,
+            difficulty: saved.difficulty.clone(),
+            time_control: saved.time_control,
+            handicap: saved.handicap.clone(),
+        })
+    }
+}
+
+impl Reducible for GameState {
+    type Action = Action;
+
+    fn reduce(self: Rc<Self>, action: Action) -> Rc<Self> {
+        let mut state = (*self).clone();
+        match action {
+            Action::Place((piece, variant, offset)) => {
+                if state.game.is_terminal() || state.ai_to_move() {
+                    return self;
+                }
+                match state.game.place_piece(piece, variant, offset) {
+                    Ok(next) => {
+                        let previous = std::mem::replace(&mut state.game, next);
+                        state.previous.push(previous);
+                    }
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to place piece");
+                        return self;
+                    }
+                }
+            }
+            Action::Pass => {
+                if state.game.is_terminal() || state.ai_to_move() {
+                    return self;
+                }
+                match state.game.pass() {
+                    Ok(next) => {
+                        let previous = std::mem::replace(&mut state.game, next);
+                        state.previous.push(previous);
+                    }
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to pass");
+                        return self;
+                    }
+                }
+            }
+            Action::Swap => {
+                if !state.game.can_swap() || state.ai_to_move() {
+                    return self;
+                }
+                match state.game.swap() {
+                    Ok(next) => {
+                        let previous = std::mem::replace(&mut state.game, next);
+                        state.previous.push(previous);
+                    }
+                    Err(e) => {
+                        tracing::error!(error = %e, "failed to swap");
+                        return self;
+                    }
+                }
+            }
+            Action::AiMoved { from, next } => {
+                // Ignore searches that finished after an undo, a reset, or the seat changing hands
+                if state.game.history != from || !state.ai_to_move() {
+                    return self;
+                }
+                state.game = *next;
+            }
+            Action::TimedOut { from, next } => {
+                if state.game.history != from || state.ai_to_move() {
+                    return self;
+                }
+                let previous = std::mem::replace(&mut state.game, *next);
+                state.previous.push(previous);
+            }
+            Action::ToggleSeat(seat) => {
+                if seat >= NUM_PLAYERS || !state.can_toggle(seat) {
+                    return self;
+                }
+                let mut ai_seats = state.ai_seats;
+                state.toggle(&mut ai_seats, seat);
+                state.ai_seats = ai_seats;
+            }
+            Action::Undo => match state.previous.pop() {
+                Some(game) => state.game = game,
+                None => return self,
+            },
+            Action::Reset => {
+                state.game = state.start.clone();
+                state.previous.clear();
+            }
+            Action::NewGame(setup) => state = GameState::from_setup(&setup),
+            Action::SetUp(game) => {
+                state.start = (*game).clone();
+                state.game = *game;
+                state.previous.clear();
+            }
+            Action::Load(loaded) => state = *loaded,
+        }
+        Rc::new(state)
+    }
+}
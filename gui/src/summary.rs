@@ -0,0 +1,142 @@
+/*
+End of game screen with the final standings and how each score was made up
+The game record can be downloaded in the JSON format blokus::records reads, so it
+opens again under Replay or with the CLI.
+*/
+
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+use yew::prelude::*;
+
+use crate::i18n::{tr, trf, use_language};
+
+use blokus::game::{ScoreBreakdown, Variant};
+use blokus::records::GameRecord;
+
+const PLAYER_NAMES: [&str; 4] = ["Red", "Blue", "Green", "Yellow"];
+const COLORS: [&str; 4] = ["red", "blue", "green", "yellow"];
+
+/// Save text as a file through the browser's downloads
+fn download(name: &str, text: &str, mime: &str) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&JsValue::from_str(text));
+    let options = BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = Blob::new_with_str_sequence_and_options(&parts, &options)?;
+    let url = Url::create_object_url_with_blob(&blob)?;
+
+    let document = web_sys::window().unwrap().document().unwrap();
+    let link: HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    link.set_href(&url);
+    link.set_download(name);
+    link.click();
+    Url::revoke_object_url(&url)
+}
+
+/// Finishing place of each player by their side's score, tied players share a place
+fn places(scores: &[i32]) -> Vec<usize> {
+    scores
+        .iter()
+        .map(|p| 1 + scores.iter().filter(|q| *q > p).count())
+        .collect()
+}
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub breakdown: Vec<ScoreBreakdown>,
+    pub variant: Variant,
+    pub record: GameRecord,
+    pub can_rematch: bool, // Only games played on this screen can be restarted from here
+    pub on_rematch: Callback<()>,
+    pub on_close: Callback<()>,
+}
+
+/// Modal with the winners, every player's place and score breakdown, and what to do next
+#[function_component]
+pub fn GameSummary(props: &Props) -> Html {
+    let language = use_language();
+    let variant = props.variant;
+    let totals: Vec<i32> = props.breakdown.iter().map(|p| p.total).collect();
+    let team_scores = variant.team_scores(&totals);
+    let places = places(&team_scores);
+    let mut order: Vec<usize> = (0..props.breakdown.len())
+        .filter(|p| variant.has_color(*p))
+        .collect();
+    order.sort_by_key(|p| places[*p]);
+    let sides = matches!(variant, Variant::TwoPlayer | Variant::Team);
+
+    // Partners win together in the two player and team games
+    let winners: Vec<String> = order
+        .iter()
+        .filter(|p| places[**p] == 1 && variant.partner(**p) >= **p)
+        .map(|p| match variant.partner(*p) {
+            partner if partner == *p => tr(language, PLAYER_NAMES[*p]).to_string(),
+            partner => format!(
+                "{} & {}",
+                tr(language, PLAYER_NAMES[*p]),
+                tr(language, PLAYER_NAMES[partner])
+            ),
+        })
+        .collect();
+    let headline = match winners.as_slice() {
+        [winner] => trf(language, "{} wins!", &[winner]),
+        tied => trf(language, "{} tie!", &[&tied.join(tr(language, " and "))]),
+    };
+
+    let on_export = {
+        let record = props.record.clone();
+        Callback::from(move |_| {
+            let text = serde_json::to_string_pretty(&vec![&record]).unwrap();
+            if let Err(e) = download("blokus-game.json", &text, "application/json") {
+                tracing::error!(error = ?e, "failed to export the game");
+            }
+        })
+    };
+
+    html! {
+        <div class="modal-backdrop">
+            <div class="modal">
+                <h2>{ headline }</h2>
+                <table class="summary">
+                    <tr>
+                        <th>{ tr(language, "Place") }</th>
+                        <th>{ tr(language, "Player") }</th>
+                        <th>{ tr(language, "Placed") }</th>
+                        <th>{ tr(language, "In Hand") }</th>
+                        <th>{ tr(language, "Bonus") }</th>
+                        <th>{ tr(language, "Score") }</th>
+                        if sides {
+                            <th>{ tr(language, "Side") }</th>
+                        }
+                    </tr>
+                    { for order.iter().map(|&player| {
+                        let parts = &props.breakdown[player];
+                        html! {
+                            <tr>
+                                <td>{ places[player] }</td>
+                                <td>
+                                    <span class={classes!("move-color", COLORS[player])}></span>
+                                    { tr(language, PLAYER_NAMES[player]) }
+                                </td>
+                                <td>{ parts.placed }</td>
+                                <td>{ -parts.in_hand }</td>
+                                <td>{ format!("+{}", parts.all_played + parts.monomino_last) }</td>
+                                <td class="score">{ parts.total }</td>
+                                if sides {
+                                    <td>{ team_scores[player] }</td>
+                                }
+                            </tr>
+                        }
+                    }) }
+                </table>
+                <p class="summary-note">{ tr(language, "A point off for each square in hand, +15 for playing every piece, and +5 more if the last was the single square") }</p>
+                <div class="modal-buttons">
+                    if props.can_rematch {
+                        <button onclick={props.on_rematch.reform(|_| ())}>{ tr(language, "Rematch") }</button>
+                    }
+                    <button onclick={on_export}>{ tr(language, "Export Game") }</button>
+                    <button onclick={props.on_close.reform(|_| ())}>{ tr(language, "Close") }</button>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,174 @@
+/*
+Rules tutorial for new players, walking through the lessons of blokus::tutorial
+Each lesson sets up its position and asks for a placement, which the engine checks: a
+refused move says which rule it breaks, and a good one is left on the board with a note.
+*/
+
+use std::rc::Rc;
+
+use yew::prelude::*;
+use yew::Reducible;
+
+use crate::i18n::{tr, trf, use_language};
+
+use blokus::game::{Game, Move};
+use blokus::tutorial::{lessons, Lesson};
+
+pub enum TutorialAction {
+    Open,
+    Close,
+    /// Switch to another lesson
+    Select(usize),
+    Place(Move),
+    /// Put the lesson's position back to try again
+    Retry,
+}
+
+/// The lessons being worked through, empty when the tutorial is closed
+#[derive(Clone)]
+pub struct Tutorial {
+    pub lessons: Vec<Lesson>,
+    pub index: usize,                        // Lesson being shown
+    pub start: Game,                         // Its position
+    pub placed: Option<Game>,                // Position after a placement that met the goal
+    pub message: Option<Result<(), String>>, // Verdict on the last placement
+    pub completed: Vec<bool>,
+}
+
+impl Default for Tutorial {
+    fn default() -> Self {
+        Tutorial {
+            lessons: Vec::new(),
+            index: 0,
+            start: Game::reset(),
+            placed: None,
+            message: None,
+            completed: Vec::new(),
+        }
+    }
+}
+
+impl Tutorial {
+    pub fn is_open(&self) -> bool {
+        !self.lessons.is_empty()
+    }
+
+    pub fn game(&self) -> &Game {
+        self.placed.as_ref().unwrap_or(&self.start)
+    }
+
+    pub fn lesson(&self) -> Option<&Lesson> {
+        self.lessons.get(self.index)
+    }
+
+    /// Show a lesson from its starting position
+    fn select(&mut self, index: usize) -> bool {
+        let Some(Ok(start)) = self.lessons.get(index).map(Lesson::game) else {
+            return false;
+        };
+        self.index = index;
+        self.start = start;
+        self.placed = None;
+        self.message = None;
+        true
+    }
+}
+
+// The positions follow from the lesson and the placement
+impl PartialEq for Tutorial {
+    fn eq(&self, other: &Self) -> bool {
+        let placed = |t: &Tutorial| t.placed.as_ref().map(|g| g.history.clone());
+        self.lessons == other.lessons
+            && self.index == other.index
+            && placed(self) == placed(other)
+            && self.message == other.message
+            && self.completed == other.completed
+    }
+}
+
+impl Reducible for Tutorial {
+    type Action = TutorialAction;
+
+    fn reduce(self: Rc<Self>, action: TutorialAction) -> Rc<Self> {
+        let mut tutorial = (*self).clone();
+        match action {
+            TutorialAction::Open => {
+                tutorial.lessons = lessons();
+                tutorial.completed = vec![false; tutorial.lessons.len()];
+                if !tutorial.select(0) {
+                    return self;
+                }
+            }
+            TutorialAction::Close => tutorial = Tutorial::default(),
+            TutorialAction::Select(index) => {
+                if !tutorial.select(index) {
+                    return self;
+                }
+            }
+            TutorialAction::Place((piece, variant, offset)) => {
+                let Some(lesson) = tutorial.lesson() else {
+                    return self;
+                };
+                if tutorial.placed.is_some() {
+                    return self;
+                }
+                let verdict = lesson.check(&tutorial.start, (piece, variant, offset));
+                if verdict.is_ok() {
+                    let Ok(next) = tutorial.start.place_piece(piece, variant, offset) else {
+                        return self;
+                    };
+                    tutorial.placed = Some(next);
+                    tutorial.completed[tutorial.index] = true;
+                }
+                tutorial.message = Some(verdict);
+            }
+            TutorialAction::Retry => {
+                tutorial.placed = None;
+                tutorial.message = None;
+            }
+        }
+        Rc::new(tutorial)
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct TutorialProps {
+    pub tutorial: UseReducerHandle<Tutorial>,
+}
+
+/// The lesson's explanation, the verdict on the last placement, and buttons to move on
+#[function_component]
+pub fn TutorialControls(props: &TutorialProps) -> Html {
+    let language = use_language();
+    let tutorial = &props.tutorial;
+    let Some(lesson) = tutorial.lesson() else {
+        return html! {};
+    };
+
+    let button = |label: &'static str, action: Box<dyn Fn() -> TutorialAction>, disabled: bool| {
+        let dispatcher = tutorial.dispatcher();
+        let onclick = Callback::from(move |_| dispatcher.dispatch(action()));
+        html! { <button {onclick} {disabled}>{ tr(language, label) }</button> }
+    };
+    let (index, count) = (tutorial.index, tutorial.lessons.len());
+    let completed = tutorial.completed.iter().filter(|c| **c).count();
+
+    html! {
+        <div class="replay">
+            <p><strong>{ trf(language, "Lesson {} of {}: {}", &[&(index + 1), &count, &lesson.title]) }</strong></p>
+            <p>{ lesson.text }</p>
+            { match &tutorial.message {
+                Some(Ok(())) => html! { <p class="puzzle-solved">{ trf(language, "Well done! {}", &[&lesson.done]) }</p> },
+                Some(Err(reason)) => html! { <p class="error">{ reason }</p> },
+                None => html! { <p>{ tr(language, "Place a piece to try it") }</p> },
+            } }
+            <div class="replay-buttons">
+                { button("<", Box::new(move || TutorialAction::Select(index.saturating_sub(1))), index == 0) }
+                { button("Retry", Box::new(|| TutorialAction::Retry), tutorial.message.is_none()) }
+                { button(">", Box::new(move || TutorialAction::Select(index + 1)), index + 1 == count) }
+            </div>
+            <p>{ trf(language, "{} of {} lessons done", &[&completed, &count]) }</p>
+            { button("Close Tutorial", Box::new(|| TutorialAction::Close), false) }
+        </div>
+    }
+}
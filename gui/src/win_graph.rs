@@ -0,0 +1,75 @@
+/*
+Win probability graph for replays, one line per color from the first move to the last
+Every position of the game is rated with the in-browser model's value head, or with
+heuristic playouts when there is none, see blokus::evaluator::win_probabilities. A sharp
+drop in a color's line points at the move that lost the game, and clicking jumps there.
+*/
+
+use yew::prelude::*;
+
+use blokus::evaluator::{win_probabilities, RolloutEvaluator};
+use blokus::game::Game;
+use blokus::onnx::OnnxEvaluator;
+
+const PLAYOUTS: usize = 8;
+const WIDTH: f32 = 400.0;
+const HEIGHT: f32 = 100.0;
+const COLORS: [&str; 4] = ["red", "blue", "green", "yellow"];
+
+/// Each color's chance of winning at every position of a replay
+pub fn estimate(
+    positions: &[Game],
+    model: Option<&OnnxEvaluator>,
+) -> Result<Vec<Vec<f32>>, String> {
+    match model {
+        Some(evaluator) => win_probabilities(positions, &mut evaluator.clone()),
+        None => win_probabilities(positions, &mut RolloutEvaluator::new(PLAYOUTS, Some(0))),
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub probabilities: Vec<Vec<f32>>, // Indexed by position, then by color
+    pub step: usize,                  // Position on the board, marked on the graph
+    pub on_jump: Callback<usize>,
+}
+
+#[function_component]
+pub fn WinGraph(props: &Props) -> Html {
+    let positions = props.probabilities.len();
+    if positions == 0 {
+        return html! {};
+    }
+    let column = WIDTH / positions.max(2).saturating_sub(1) as f32;
+    let x = |i: usize| i as f32 * column;
+    let y = |p: f32| HEIGHT * (1.0 - p);
+
+    html! {
+        <div class="win-graph">
+            <svg viewBox={format!("0 0 {} {}", WIDTH, HEIGHT)} preserveAspectRatio="none">
+                <line x1="0" y1={(HEIGHT / 2.0).to_string()} x2={WIDTH.to_string()} y2={(HEIGHT / 2.0).to_string()}
+                    stroke="gray" stroke-dasharray="4" />
+                { for COLORS.iter().enumerate().map(|(player, color)| {
+                    let points: Vec<String> = props.probabilities.iter().enumerate()
+                        .map(|(i, shares)| format!("{:.1},{:.1}", x(i), y(shares[player])))
+                        .collect();
+                    html! {
+                        <polyline points={points.join(" ")} fill="none" stroke={format!("var(--{}-player)", color)}
+                            stroke-width="2" vector-effect="non-scaling-stroke" />
+                    }
+                }) }
+                <line x1={x(props.step).to_string()} y1="0" x2={x(props.step).to_string()} y2={HEIGHT.to_string()}
+                    stroke="gray" vector-effect="non-scaling-stroke" />
+                // A clickable band around each position
+                { for (0..positions).map(|i| {
+                    let on_jump = props.on_jump.clone();
+                    let onclick = Callback::from(move |_| on_jump.emit(i));
+                    html! {
+                        <rect x={(x(i) - column / 2.0).to_string()} y="0" width={column.to_string()}
+                            height={HEIGHT.to_string()} fill="transparent" {onclick} />
+                    }
+                }) }
+            </svg>
+        </div>
+    }
+}
@@ -0,0 +1,33 @@
+"""Export a model checkpoint to ONNX
+
+Accepts PyTorch state dicts (.pt) and checkpoints from the Rust trainer (.safetensors)
+
+Usage: python export_onnx.py <checkpoint> <nn_depth> <nn_width> <output.onnx> [fp32|fp16|int8]
+"""
+
+import sys
+
+import torch
+from safetensors.torch import load_file
+
+from onnx_model import export
+from resnet import ResNet
+
+
+def main():
+    checkpoint, depth, width, output = sys.argv[1], int(sys.argv[2]), int(sys.argv[3]), sys.argv[4]
+    precision = sys.argv[5] if len(sys.argv) > 5 else "fp32"
+    if checkpoint.endswith(".safetensors"):
+        state = load_file(checkpoint)
+    else:
+        state = torch.load(checkpoint, weights_only=True, map_location="cpu")
+
+    # Rust checkpoints have no auxiliary heads or batch norm counters
+    model = ResNet(depth, width)
+    model.load_state_dict(state, strict=False)
+    model.eval()
+    export(model, output, precision)
+
+
+if __name__ == "__main__":
+    main()
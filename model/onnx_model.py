@@ -0,0 +1,51 @@
+"""Reduced precision ONNX inference for CPU-only self-play"""
+
+import onnx
+import onnxruntime as ort
+import torch
+from onnxconverter_common import float16
+from onnxruntime.quantization import QuantType, quantize_dynamic
+
+DIM = 20
+PRECISIONS = ["fp32", "fp16", "int8"]
+
+
+def export(model, output, precision="fp32"):
+    """Export a model to ONNX at the given precision
+
+    fp16 halves the weights but keeps float32 inputs and outputs, int8 quantizes
+    the weights dynamically so activations are quantized on the fly.
+    """
+
+    if precision not in PRECISIONS:
+        raise ValueError(f"Unknown precision {precision}, expected one of {PRECISIONS}")
+
+    fp32_output = output if precision == "fp32" else f"{output}.fp32"
+    torch.onnx.export(
+        model,
+        torch.zeros(1, model.input.in_channels, DIM, DIM, device=next(model.parameters()).device),
+        fp32_output,
+        input_names=["boards"],
+        output_names=["policy", "value"],
+        dynamic_axes={"boards": {0: "batch"}, "policy": {0: "batch"}, "value": {0: "batch"}},
+    )
+
+    if precision == "fp16":
+        converted = float16.convert_float_to_float16(onnx.load(fp32_output), keep_io_types=True)
+        onnx.save(converted, output)
+    elif precision == "int8":
+        quantize_dynamic(fp32_output, output, weight_type=QuantType.QInt8)
+
+
+class OnnxModel:
+    """Runs an exported model with onnxruntime on the CPU
+
+    Called like the PyTorch model so it can stand in for it when handling inference requests.
+    """
+
+    def __init__(self, path):
+        self.session = ort.InferenceSession(path, providers=["CPUExecutionProvider"])
+
+    def __call__(self, boards):
+        policy, value = self.session.run(None, {"boards": boards.cpu().numpy()})
+        return torch.from_numpy(policy), torch.from_numpy(value)
@@ -35,20 +35,21 @@ class ResNet(nn.Module):
     This model uses a resnet backbone with a policy head and a value head.
     The input to the model is a 20x20x5 tensor where the first 4 channels
     are binary boards for each player's pieces on the board. The 5th channel
-    is a binary board with the valid moves for the current player.
+    is a binary board with the valid moves for the current player. Extra
+    input planes from blokus::game::StateOptions come after, see in_channels.
     The policy head outputs a probability distribution over the valid moves
     or the 20x20 spaces. The value head outputs a single value for the expected
     outcome of the game for each player. The value is between 0 and 1.
     """
 
-    def __init__(self, blocks, width, custom_filters=False):
+    def __init__(self, blocks, width, custom_filters=False, in_channels=5):
         super(ResNet, self).__init__()
         self.blocks = blocks
         self.width = width
         self.custom_filters = custom_filters
         self.piece_filters = []
 
-        self.input = nn.Conv2d(5, width, kernel_size=3, padding=1)
+        self.input = nn.Conv2d(in_channels, width, kernel_size=3, padding=1)
         self.res_blocks = nn.ModuleList([ResidualBlock(width, width) for _ in range(blocks)])
         self.policy_head = nn.Sequential(
             nn.Conv2d(width, 1, kernel_size=1),
@@ -65,14 +66,28 @@ class ResNet(nn.Module):
             nn.Tanh(),
         )
 
+        # Auxiliary heads, only used as extra training targets
+        # Ownership predicts which player (or nobody) holds each square at the end of the game
+        # Margin predicts each player's final score minus the best opponent's
+        self.ownership_head = nn.Conv2d(width, 5, kernel_size=1)
+        self.margin_head = nn.Sequential(
+            nn.Conv2d(width, 1, kernel_size=1),
+            nn.BatchNorm2d(1),
+            nn.ReLU(),
+            nn.Flatten(),
+            nn.Linear(DIM * DIM, 4),
+        )
 
-    def forward(self, boards):
+
+    def forward(self, boards, auxiliary=False):
         """Get the policy and value for the given board state
 
         For now, the board is represented by a 20x20x5 tensor where the first 4 channels are
         binary boards for each player's pieces on the board. The 5th channel is a binary board
         with the valid moves for the current player. For now, I'm just going to use the boards.
         It is unclear why the player color is needed in the state.
+
+        With auxiliary set, also returns the ownership logits and score margins.
         """
 
         # ResNet backbone
@@ -91,4 +106,6 @@ class ResNet(nn.Module):
         value = self.value_head(x)
         value = torch.softmax(value, dim=1)
 
+        if auxiliary:
+            return policy, value, self.ownership_head(x), self.margin_head(x)
         return policy, value
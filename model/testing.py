@@ -18,7 +18,7 @@ from tensordict import tensorclass
 
 from resnet import ResNet
 from training import TestConfig, handle_inference_batch
-from blokus_self_play import play_test_game
+from blokus_engine import play_test_game
 
 DIM = 20
 
@@ -9,117 +9,197 @@ from queue import Empty
 
 import wandb
 from tqdm import trange, tqdm
+import numpy as np
 import torch
 from torchrl.data import ReplayBuffer, LazyTensorStorage
 from tensordict import tensorclass
 
-from blokus_self_play import play_training_game
+from blokus_engine import import_games, play_training_games, state_planes, update_opening_book
+from onnx_model import OnnxModel, export
 from resnet import ResNet
 
 DIM = 20
+PROTOCOL_VERSION = 3 # Requests and answers passed to the Rust self-play workers
+PLANES = ["player", "next", "opposite", "previous", "legal"] # Default input planes, see state_planes for the configured ones
+ACTIONS = "tiles-rotated" # Policies are over the board's tiles, turned to the player to move
+TOTAL_TILES = 89 # Margins are scaled by the number of tiles each player has
 MODEL_PATH = "./weights"
+BOOK_PATH = f"{MODEL_PATH}/opening_book.txt"
 
 @tensorclass
 class Data:
     states: torch.Tensor
     policies: torch.Tensor
     scores: torch.Tensor
+    ownership: torch.Tensor
+    margins: torch.Tensor
 
 
-def empty_queue(queue, device):
+def capabilities(planes=PLANES):
+    """What the model was built for, which a worker's engine must match before it sends positions"""
+    return {"protocol": PROTOCOL_VERSION, "board_size": DIM, "planes": planes, "actions": ACTIONS}
+
+
+def unpack_states(shape, bits):
+    """Positions sent by a worker as their shape and the planes packed a bit per square"""
+    count = int(np.prod(shape))
+    squares = np.unpackbits(np.frombuffer(bits, dtype=np.uint8), count=count)
+    return squares.reshape(shape)
+
+
+def predict_batch_states(states, planes=PLANES):
+    """The positions of a predict_batch request, each sent as its number of planes and packed bits"""
+    if len(states) == 0:
+        return np.zeros((0, len(planes), DIM, DIM), dtype=np.uint8)
+    return np.stack([unpack_states((count, DIM, DIM), bits) for count, bits in states])
+
+
+def empty_queue(queue, device, planes=PLANES):
+    """Collect the waiting requests, each is a worker id, a request number, a method, and its payload
+
+    A worker's first request is a handshake with what its engine was built for, these are
+    returned separately as (id, number, capabilities). The rest are predict_batch requests
+    with a list of states, which are stacked together into one batch.
+    """
     ids = []
-    items = []
+    numbers = []
+    counts = []
+    items = [np.zeros((0, len(planes), DIM, DIM), dtype=np.uint8)]
+    handshakes = []
     while True:
         try:
-            id, input = queue.get(block=False)
+            id, number, method, payload = queue.get(block=False)
+            if method == "handshake":
+                handshakes.append((id, number, payload))
+                continue
+            if method != "predict_batch":
+                logging.error(f"Worker {id} called unknown method {method}")
+                continue
+            states = predict_batch_states(payload, planes)
             ids.append(id)
-            items.append(input)
+            numbers.append(number)
+            counts.append(len(states))
+            items.append(states)
         except Empty as e:
             break
 
-    return ids, torch.tensor(items, dtype=torch.float32).view(-1, 5, DIM, DIM).to(device)
+    batch = torch.from_numpy(np.concatenate(items)).to(device, dtype=torch.float32)
+    return ids, numbers, counts, batch, handshakes
 
 
-def handle_inference_batch(model, device, inference_queue, pipes_to_workers):
+def handle_inference_batch(model, device, inference_queue, pipes_to_workers, planes=PLANES):
     """Process batches of inputs from the self-play games
 
     Tries to create a batch of size num_workers // 2 from the inference queue.
     If this runs for too long, there are likely stragglers in the queue and we
     should just empty the queue with what is left. All batches are sent to the
     GPU for processing and the outputs are sent back to the appropriate worker.
+    A worker playing several games at once sends all of their states in one
+    predict_batch request and gets back a list with a (policy, values) pair for
+    each in order, along with the number of the request it answers. Workers check
+    the server is up with empty batches.
+    Handshakes are answered with the model's capabilities, the planes it takes as input
+    among them, and the worker refuses to play if they differ from its own.
     """
 
     time.sleep(.001)
-    ids, batch = empty_queue(inference_queue, device)
-    num_requests = len(ids)
-    if num_requests == 0:
+    ids, numbers, counts, batch, handshakes = empty_queue(inference_queue, device, planes)
+    ours = capabilities(planes)
+    for id, number, worker in handshakes:
+        if worker != ours:
+            logging.error(f"Worker {id} was built for {worker} but the model for {ours}")
+        pipes_to_workers[id].send((number, ours))
+    if len(ids) == 0:
         return 0
 
     # Query the model for the batch of inputs
-    with torch.no_grad():
-        policies, values = model(batch)
+    policies, values = [], []
+    if len(batch) > 0:
+        with torch.no_grad():
+            policies, values = model(batch)
+        policies = policies.cpu().tolist()
+        values = values.cpu().tolist()
 
     # Send the outputs to the appropriate worker
-    for i, id in enumerate(ids):
-        response = (policies[i].cpu().tolist(), values[i].cpu().tolist())
-        pipes_to_workers[id].send(response)
+    start = 0
+    for id, number, count in zip(ids, numbers, counts):
+        response = list(zip(policies[start:start + count], values[start:start + count]))
+        pipes_to_workers[id].send((number, response))
+        start += count
 
-    return num_requests
+    return start
 
 
-def save(game, buffer: ReplayBuffer,):
-    """Save the game data to the replay buffer"""
+def save(game, buffer: ReplayBuffer, planes=PLANES, absolute=False, shaping_weights=(0.0, 0.0, 0.0), shaping_mix=0.0):
+    """Save the game data to the replay buffer
+
+    The states come packed from the engine, laid out as the planes say and already turned to
+    the player to move unless absolute, so only the targets are turned here to match them.
+    With a shaping mix above zero, that fraction of each value target comes from the shaping
+    signals each color collects from the position on, weighted and turned into shares by a softmax
+    """
 
     # Allocate space for the data
-    history, policies, values = game
+    history, policies, values, legal_tiles, ownership, margins, signals, states = game
     num_moves = len(history)
     logging.debug(f"Saving game with {num_moves} moves to the replay buffer")
 
-    state_data = torch.zeros(num_moves, 5, DIM, DIM, dtype=torch.float32)
+    state_data = torch.zeros(num_moves, len(planes), DIM, DIM, dtype=torch.float32)
     policy_data = torch.zeros(num_moves, DIM * DIM, dtype=torch.float32)
     value_data = torch.tensor(values, dtype=torch.float32).repeat(num_moves, 1)
-
-    # For each move from this game, update the state and policy
-    # new_state holds running game state
-    new_state = torch.zeros(5, DIM, DIM, dtype=torch.float32)
-    for i, (move, policy) in enumerate(zip(history, policies)):
-
-        # Shift the state to the correct player's perspective
-        player, tile = move
-        state_data[i] = torch.cat((new_state[player:4], new_state[:player], new_state[4].unsqueeze(0)), dim=0)
+    ownership_data = torch.zeros(num_moves, DIM, DIM, dtype=torch.long)
+    margin_data = torch.zeros(num_moves, 4, dtype=torch.float32)
+
+    # Mix the shaping signals still to come into the value targets, see blokus::shaping
+    if shaping_mix > 0:
+        rewards = torch.zeros(num_moves, 4, dtype=torch.float32)
+        for i, ((player, _), signal) in enumerate(zip(history, signals)):
+            rewards[i, player] = sum(w * s for w, s in zip(shaping_weights, signal))
+        to_come = rewards.flip(0).cumsum(0).flip(0)
+        value_data = (1 - shaping_mix) * value_data + shaping_mix * torch.softmax(to_come, dim=1)
+
+    # Final owner of each square, with 4 marking empty squares
+    final_owners = torch.tensor(ownership, dtype=torch.long).view(DIM, DIM)
+    final_margins = torch.tensor(margins, dtype=torch.float32) / TOTAL_TILES
+
+    # For each move from this game, unpack the state and update the policy
+    for i, (move, policy, state) in enumerate(zip(history, policies, states)):
+        player, _ = move
+        if len(state) > 0:
+            state_data[i] = torch.from_numpy(unpack_states((len(planes), DIM, DIM), bytes(state)).copy())
 
         # Update the policy for this move
         for element in policy:
             action, prob = element
             policy_data[i, action] = prob
 
-            # Update which squares are legal on this move
-            row, col = action // DIM, action % DIM
-            state_data[i, 4, row, col] = 1
-
-        # Rotate state and policy so perspective is the same
-        state_data[i] = torch.rot90(state_data[i], k=player, dims=(1, 2))
-        policy_data[i] = torch.rot90(policy_data[i].reshape(DIM, DIM), k=player).reshape(-1)
+        # Auxiliary targets are relative to the player to move, like the state planes
+        owners = torch.where(final_owners < 0, 4, (final_owners - player) % 4)
+        margin_data[i] = torch.roll(final_margins, -player)
 
-        # Make the move that was made
-        row, col = tile // DIM, tile % DIM
-        new_state[player, row, col] = 1
+        # Rotate policy and ownership as the engine turned the state
+        turns = 0 if absolute else player
+        policy_data[i] = torch.rot90(policy_data[i].reshape(DIM, DIM), k=turns).reshape(-1)
+        ownership_data[i] = torch.rot90(owners, k=turns)
 
-        # if  i < 20:
-        #     print(f"Player {player}")
-        #     print(f"State: {state_data[i]}")
-        #     print(f"Policy: {policy_data[i]}")
+    # Moves played at random to reach a midgame start have no policy to learn from
+    searched = torch.tensor([len(policy) > 0 for policy in policies], dtype=torch.bool)
+    num_searched = int(searched.sum())
+    if num_searched == 0:
+        return
 
     data = Data(
-        states = state_data,
-        policies = policy_data,
-        scores = value_data,
-        batch_size = [num_moves]
+        states = state_data[searched],
+        policies = policy_data[searched],
+        scores = value_data[searched],
+        ownership = ownership_data[searched],
+        margins = margin_data[searched],
+        batch_size = [num_searched]
     )
     buffer.extend(data)
 
 
-def train(step, model, buffer, optimizer, policy_loss, value_loss, device, testing):
+def train(step, model, buffer, optimizer, policy_loss, value_loss, aux_weight, device, testing):
     """Train the model on a batch of data from the replay buffer"""
 
     # Get a batch of data from the replay buffer
@@ -127,19 +207,28 @@ def train(step, model, buffer, optimizer, policy_loss, value_loss, device, testi
     inputs = batch.get("states").to(device)
     policies = batch.get("policies").to(device)
     values = batch.get("scores").to(device)
+    ownership = batch.get("ownership").to(device)
+    margins = batch.get("margins").to(device)
 
     # Train the model
     optimizer.zero_grad()
-    policy, value = model(inputs)
+    policy, value, ownership_logits, margin = model(inputs, auxiliary=True)
     policy_loss = policy_loss(policy, policies)
     value_loss = value_loss(value, values)
-    loss = policy_loss + value_loss
+    ownership_loss = torch.nn.functional.cross_entropy(ownership_logits, ownership)
+    margin_loss = torch.nn.functional.mse_loss(margin, margins)
+    loss = policy_loss + value_loss + aux_weight * (ownership_loss + margin_loss)
     loss.backward()
     optimizer.step()
 
     # Store training statistics
     if not testing:
-        wandb.log({"policy_loss": policy_loss, "value_loss": value_loss}, step=step)
+        wandb.log({
+            "policy_loss": policy_loss,
+            "value_loss": value_loss,
+            "ownership_loss": ownership_loss,
+            "margin_loss": margin_loss,
+        }, step=step)
 
 
 def main():
@@ -166,10 +255,13 @@ def main():
         config = TestConfig(args.cpus)
     else:
         config = Config(args.cpus)
+    logging.info(f"Search: c_init {config.c_init}, fpu reduction {config.fpu_reduction}, prior temperature {config.prior_temperature}")
 
 
-    # Create the model, optimizer, and loss
-    model = ResNet(config.nn_depth, config.nn_width, config.custom_filters).to(device)
+    # Create the model, optimizer, and loss, taking the planes the engine lays out
+    planes = state_planes(config)
+    logging.info(f"Input planes: {len(planes)}")
+    model = ResNet(config.nn_depth, config.nn_width, config.custom_filters, len(planes)).to(device)
     optimizer = torch.optim.Adam(model.parameters(), lr=config.learning_rate)
     policy_loss = torch.nn.CrossEntropyLoss().to(device)
     value_loss = torch.nn.MSELoss().to(device)
@@ -186,42 +278,72 @@ def main():
         batch_size=config.batch_size
     )
 
+    # Seed the replay buffer with recorded human games
+    if config.human_games is not None:
+        human_games = import_games(config.human_games, config)
+        for game in human_games:
+            save(game, buffer, planes, config.state_absolute, config.shaping_weights, config.shaping_mix)
+        logging.info(f"Imported {len(human_games)} human games")
+
     # Train the model
     global_step = 0
     for round in trange(config.training_rounds):
 
         # Create the queues and pipes
         manager = mp.Manager()
-        request_queue = manager.Queue(maxsize=config.cpus * config.games_per_cpu)
+        request_queue = manager.Queue(maxsize=config.workers_per_round())
         pipes_to_model = []
         pipes_to_workers = []
-        for i in range(config.games_per_round()):
+        for i in range(config.workers_per_round()):
             a, b = mp.Pipe()
             pipes_to_model.append(a)
             pipes_to_workers.append(b)
 
-        # Generate spawn asynchronous self-play processes
+        # Serve self-play from a reduced precision copy of the model if configured
+        inference_model = model
+        if config.inference_precision != "fp32":
+            onnx_path = f"{MODEL_PATH}/self_play_{config.inference_precision}.onnx"
+            model.eval()
+            export(model, onnx_path, config.inference_precision)
+            model.train()
+            inference_model = OnnxModel(onnx_path)
+
+        # Generate spawn asynchronous self-play processes, each playing several games at once
         with mp.get_context("spawn").Pool(config.cpus) as pool:
             game_data = pool.starmap_async(
-                play_training_game,
-                [(id, config, request_queue, pipes_to_model[id]) for id in range(config.games_per_round())]
+                play_training_games,
+                [(id, config, request_queue, pipes_to_model[id]) for id in range(config.workers_per_round())]
             )
 
             # Start handling inference requests
             total_requests_ish = config.requests_per_round()
             pbar = tqdm(total=total_requests_ish, desc=f"Self-Play Requests Round {round}")
+            start = time.time()
+            evaluated = 0
             while not game_data.ready():
-                num_requests = handle_inference_batch(model, device, request_queue, pipes_to_workers)
+                num_requests = handle_inference_batch(inference_model, device, request_queue, pipes_to_workers, planes)
+                evaluated += num_requests
                 pbar.update(num_requests)
             pbar.close()
 
+            # Report evaluation throughput
+            throughput = evaluated / (time.time() - start)
+            logging.info(f"Evaluated {evaluated} positions at {throughput:.0f} per second ({config.inference_precision})")
+            if not args.test:
+                wandb.log({"positions_per_second": throughput}, step=global_step)
+
             # Save the game data to the replay buffer
-            for game in game_data.get():
-                save(game, buffer)
+            games = [game for worker_games in game_data.get() for game in worker_games]
+            for game in games:
+                save(game, buffer, planes, config.state_absolute, config.shaping_weights, config.shaping_mix)
+
+            # Aggregate the opening statistics into the book
+            positions = update_opening_book(BOOK_PATH, games, config.book_plies, config.book_min_games)
+            logging.info(f"Opening book has {positions} positions")
 
         # Train the model
         for step in trange(config.training_steps, desc=f"Training round {round}", leave=False):
-            train(global_step, model, buffer, optimizer, policy_loss, value_loss, device, args.test)
+            train(global_step, model, buffer, optimizer, policy_loss, value_loss, config.aux_weight, device, args.test)
             global_step += 1
         torch.save(model.state_dict(), f"{MODEL_PATH}/latest_model.pt")
 
@@ -259,17 +381,49 @@ class Config:
         self.training_steps = 500
         self.cpus = num_cpus
         self.games_per_cpu = 4
+        self.concurrent_games = 4
 
         self.custom_filters = True
+        self.aux_weight = 0.5
         self.nn_width = 256
         self.nn_depth = 10
 
+        self.inference_precision = "fp32"
+
         self.sims_per_move = 50
         self.sample_moves = 30
         self.c_base = 19652
         self.c_init = 1.25
         self.dirichlet_alpha = 0.3
         self.exploration_fraction = 0.25
+        self.payoff = "winner"
+        self.state_team_relative = False # Input planes, see blokus::game::StateOptions
+        self.state_pieces = False
+        self.state_history = 0
+        self.state_absolute = False
+        self.shaping_weights = (0.0, 0.0, 0.0)
+        self.shaping_mix = 0.0
+        self.endgame_threshold = 12
+        self.verify_top_k = 0
+        self.prune_symmetry = True
+        self.fpu_reduction = None
+        self.prior_temperature = 1.0
+        self.widening_base = 0
+        self.widening_exponent = 0.5
+
+        self.human_games = None
+        self.opening_book = None
+        self.book_plies = 20
+        self.book_min_games = 10
+        self.tree_dump_dir = None
+
+        self.random_start_fraction = 0.25
+        self.random_start_moves = 40
+
+        self.eval_timeout = 60
+        self.retry_backoff = 1
+        self.breaker_failures = 3
+        self.max_outage = 600
 
     def to_dict(self):
         return self.__dict__
@@ -277,6 +431,10 @@ class Config:
     def games_per_round(self):
         return self.cpus * self.games_per_cpu
 
+    def workers_per_round(self):
+        """Each worker process interleaves concurrent_games games"""
+        return max(1, self.games_per_round() // self.concurrent_games)
+
     def requests_per_round(self):
         return self.games_per_round() *  DIM**2 * (self.sims_per_move + 2)
 
@@ -293,17 +451,49 @@ class TestConfig(Config):
         self.training_steps = 10
         self.cpus = num_cpus
         self.games_per_cpu = 4
+        self.concurrent_games = 2
 
         self.custom_filters = True
+        self.aux_weight = 0.5
         self.nn_width = 16
         self.nn_depth = 2
 
+        self.inference_precision = "fp32"
+
         self.sims_per_move = 10
         self.sample_moves = 30
         self.c_base = 19652
         self.c_init = 1.25
         self.dirichlet_alpha = 0.3
         self.exploration_fraction = 0.25
+        self.payoff = "winner"
+        self.state_team_relative = False # Input planes, see blokus::game::StateOptions
+        self.state_pieces = False
+        self.state_history = 0
+        self.state_absolute = False
+        self.shaping_weights = (0.0, 0.0, 0.0)
+        self.shaping_mix = 0.0
+        self.endgame_threshold = 12
+        self.verify_top_k = 0
+        self.prune_symmetry = True
+        self.fpu_reduction = None
+        self.prior_temperature = 1.0
+        self.widening_base = 0
+        self.widening_exponent = 0.5
+
+        self.human_games = None
+        self.opening_book = None
+        self.book_plies = 20
+        self.book_min_games = 10
+        self.tree_dump_dir = None
+
+        self.random_start_fraction = 0.25
+        self.random_start_moves = 40
+
+        self.eval_timeout = 10
+        self.retry_backoff = 0.5
+        self.breaker_failures = 3
+        self.max_outage = 60
 
 
 if __name__ == '__main__':
@@ -0,0 +1,334 @@
+/*
+Connection from a self-play worker to the Python inference server
+Requests go on the shared inference queue numbered and naming the method they call, and the
+server answers on the worker's pipe with the same number, so a late answer to a request that
+was given up on is dropped. There are two methods:
+- handshake: before any positions, the worker and server swap what they were built for and
+  the worker refuses to play if they differ, as positions would be read as garbage
+- predict_batch: any number of positions in, a policy and values for each out in the same
+  order, so the server can run the positions of every worker through the network at once
+A request with no answer before the deadline is sent again after a backoff that doubles
+each time. After enough failures in a row the circuit opens: the worker's games pause while
+the server is probed with empty requests, and the worker gives up with an error once the
+server has not answered for too long, rather than stalling the run.
+Each position is sent as its number of planes and the planes packed a bit per square, see
+blokus::game::pack_bits, which model/training.py unpacks with numpy. The planes are laid out by
+the worker's StateOptions, which the handshake names plane by plane.
+*/
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList};
+
+use blokus::board::BOARD_SIZE as D;
+use blokus::game::{Game, StateOptions, POLICY_ENCODING};
+
+/// Version of the requests and answers passed between workers and the inference server
+pub const PROTOCOL_VERSION: u32 = 3;
+
+// Methods a request can call on the inference server
+const HANDSHAKE: &str = "handshake";
+const PREDICT_BATCH: &str = "predict_batch";
+
+// Shared by every client in the process, so answers on a pipe always match one request
+static NEXT_REQUEST: AtomicU64 = AtomicU64::new(0);
+
+/// How long to wait for the inference server and when to give up on it
+#[derive(Clone, Debug)]
+pub struct Resilience {
+    pub timeout: Duration, // Longest wait for an answer before sending again
+    pub backoff: Duration, // Pause after the first failure, doubling with each one after
+    pub max_backoff: Duration,
+    pub breaker_failures: u32, // Failures in a row that open the circuit
+    pub max_outage: Duration,  // How long the circuit stays open before the worker gives up
+}
+
+impl Default for Resilience {
+    fn default() -> Self {
+        Resilience {
+            timeout: Duration::from_secs(60),
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            breaker_failures: 3,
+            max_outage: Duration::from_secs(600),
+        }
+    }
+}
+
+impl Resilience {
+    fn backoff(&self, failures: u32) -> Duration {
+        let doublings = failures.saturating_sub(1).min(16);
+        (self.backoff * 2u32.pow(doublings)).min(self.max_backoff)
+    }
+}
+
+/// What each side of the connection was built for, all of which must match
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capabilities {
+    pub protocol: u32,
+    pub board_size: usize,
+    pub planes: Vec<String>, // What each plane of a position holds, in order
+    pub actions: String,     // How policies map onto moves
+}
+
+impl Capabilities {
+    /// What this engine sends and expects back with positions laid out by `options`
+    pub fn engine(options: &StateOptions) -> Capabilities {
+        Capabilities {
+            protocol: PROTOCOL_VERSION,
+            board_size: D,
+            planes: options.planes(),
+            actions: POLICY_ENCODING.to_string(),
+        }
+    }
+
+    fn to_py<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("protocol", self.protocol)?;
+        dict.set_item("board_size", self.board_size)?;
+        dict.set_item("planes", &self.planes)?;
+        dict.set_item("actions", &self.actions)?;
+        Ok(dict)
+    }
+
+    fn from_py(dict: &Bound<PyAny>) -> PyResult<Capabilities> {
+        Ok(Capabilities {
+            protocol: dict.get_item("protocol")?.extract()?,
+            board_size: dict.get_item("board_size")?.extract()?,
+            planes: dict.get_item("planes")?.extract()?,
+            actions: dict.get_item("actions")?.extract()?,
+        })
+    }
+
+    /// Each way the other side differs, as "field: ours vs theirs"
+    pub fn differences(&self, other: &Capabilities) -> Vec<String> {
+        let mut differences = Vec::new();
+        let mut compare = |field: &str, ours: String, theirs: String| {
+            if ours != theirs {
+                differences.push(format!("{}: {} vs {}", field, ours, theirs));
+            }
+        };
+        compare(
+            "protocol",
+            self.protocol.to_string(),
+            other.protocol.to_string(),
+        );
+        compare(
+            "board_size",
+            self.board_size.to_string(),
+            other.board_size.to_string(),
+        );
+        compare("planes", self.planes.join(","), other.planes.join(","));
+        compare("actions", self.actions.clone(), other.actions.clone());
+        differences
+    }
+}
+
+/// One position as sent to the model, its planes packed a bit per square
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateRepresentation {
+    pub planes: usize,
+    pub bits: Vec<u8>,
+}
+
+impl StateRepresentation {
+    /// The position laid out by `options`
+    pub fn new(game: &Game, options: &StateOptions) -> StateRepresentation {
+        StateRepresentation {
+            planes: options.planes().len(),
+            bits: game.get_packed_state(options),
+        }
+    }
+
+    fn to_py(&self, py: Python) -> PyObject {
+        (self.planes, PyBytes::new_bound(py, &self.bits)).into_py(py)
+    }
+}
+
+/// The model's output for one position, in the perspective of the player to move
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prediction {
+    pub policy: Vec<f32>, // Over the board's tiles
+    pub values: Vec<f32>, // For each player
+}
+
+/// A worker's connection to one inference queue
+pub struct InferenceClient<'a, 'py> {
+    queue: &'a Bound<'py, PyAny>,
+    pipe: &'a Bound<'py, PyAny>,
+    id: i32,
+    options: StateOptions, // How positions are laid out for the model
+    resilience: Resilience,
+    failures: u32, // Requests in a row that got no answer
+}
+
+impl<'a, 'py> InferenceClient<'a, 'py> {
+    pub fn new(
+        queue: &'a Bound<'py, PyAny>,
+        pipe: &'a Bound<'py, PyAny>,
+        id: i32,
+        options: StateOptions,
+        resilience: Resilience,
+    ) -> InferenceClient<'a, 'py> {
+        InferenceClient {
+            queue,
+            pipe,
+            id,
+            options,
+            resilience,
+            failures: 0,
+        }
+    }
+
+    /// Check the server was built for the same positions and policies as this engine
+    pub fn handshake(&mut self) -> Result<(), String> {
+        let ours = Capabilities::engine(&self.options);
+        let request = ours.to_py(self.queue.py()).map_err(|e| e.to_string())?;
+        let answer = self.send(HANDSHAKE, request.as_any())?;
+        let theirs = Capabilities::from_py(&answer)
+            .map_err(|e| format!("Invalid handshake from the inference server: {}", e))?;
+        let differences = ours.differences(&theirs);
+        if differences.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "The inference server was built for other positions than this engine ({}), refusing to play",
+            differences.join(", ")
+        ))
+    }
+
+    /// How positions are laid out for the model, which also says how its policies are turned
+    pub fn options(&self) -> &StateOptions {
+        &self.options
+    }
+
+    /// Evaluate positions in one predict_batch call, giving a prediction for each in order
+    pub fn predict_batch(&mut self, games: &[&Game]) -> Result<Vec<Prediction>, String> {
+        let py = self.queue.py();
+        let states = games
+            .iter()
+            .map(|g| StateRepresentation::new(g, &self.options).to_py(py));
+        let payload = PyList::new_bound(py, states);
+        let answer = self.send(PREDICT_BATCH, payload.as_any())?;
+        let predictions: Vec<(Vec<f32>, Vec<f32>)> = answer
+            .extract()
+            .map_err(|e| format!("Invalid predictions from the inference server: {}", e))?;
+        if predictions.len() != games.len() {
+            return Err(format!(
+                "The inference server predicted {} positions of {}",
+                predictions.len(),
+                games.len()
+            ));
+        }
+        Ok(predictions
+            .into_iter()
+            .map(|(policy, values)| Prediction { policy, values })
+            .collect())
+    }
+
+    /// Send a request until it is answered, pausing while the circuit is open
+    fn send(
+        &mut self,
+        method: &str,
+        payload: &Bound<'py, PyAny>,
+    ) -> Result<Bound<'py, PyAny>, String> {
+        loop {
+            if self.failures >= self.resilience.breaker_failures {
+                self.wait_for_server()?;
+            }
+            if let Some(answer) = self.request(method, payload)? {
+                self.failures = 0;
+                return Ok(answer);
+            }
+
+            self.failures += 1;
+            tracing::warn!(
+                worker = self.id,
+                failures = self.failures,
+                "no answer from the inference server"
+            );
+            self.sleep(self.resilience.backoff(self.failures));
+        }
+    }
+
+    /// Pause until an empty batch is answered, or give up after the longest outage
+    fn wait_for_server(&mut self) -> Result<(), String> {
+        tracing::warn!(
+            worker = self.id,
+            "pausing games until the inference server answers"
+        );
+        let opened = Instant::now();
+        let mut probes = 0;
+        while opened.elapsed() < self.resilience.max_outage {
+            let empty = PyList::empty_bound(self.queue.py());
+            if self.request(PREDICT_BATCH, empty.as_any())?.is_some() {
+                tracing::info!(worker = self.id, probes, "the inference server is back");
+                self.failures = 0;
+                return Ok(());
+            }
+            probes += 1;
+            self.sleep(self.resilience.backoff(probes));
+        }
+        Err(format!(
+            "The inference server has not answered for {} seconds",
+            opened.elapsed().as_secs()
+        ))
+    }
+
+    /// Send one request and wait for its answer, None if the server missed the deadline
+    /// Errors mean the queue or pipe is gone, which no retry fixes
+    fn request(
+        &self,
+        method: &str,
+        payload: &Bound<'py, PyAny>,
+    ) -> Result<Option<Bound<'py, PyAny>>, String> {
+        let py = self.queue.py();
+        let number = NEXT_REQUEST.fetch_add(1, Ordering::Relaxed);
+        let deadline = Instant::now() + self.resilience.timeout;
+        let timeout = self.resilience.timeout.as_secs_f64();
+
+        let sent = self
+            .queue
+            .call_method1("put", ((self.id, number, method, payload), true, timeout));
+        if let Err(e) = sent {
+            let full = py
+                .import_bound("queue")
+                .and_then(|queue| queue.getattr("Full"))
+                .map_err(|e| e.to_string())?;
+            if e.is_instance_bound(py, &full) {
+                return Ok(None);
+            }
+            return Err(format!("Failed to send to the inference server: {}", e));
+        }
+
+        // Drop answers to earlier requests that came in after their deadline
+        let receive_error = |e: PyErr| format!("Lost the inference server's pipe: {}", e);
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let ready: bool = self
+                .pipe
+                .call_method1("poll", (left.as_secs_f64(),))
+                .and_then(|ready| ready.extract())
+                .map_err(receive_error)?;
+            if !ready {
+                return Ok(None);
+            }
+            let answer = self.pipe.call_method0("recv").map_err(receive_error)?;
+            let answered: u64 = answer
+                .get_item(0)
+                .and_then(|n| n.extract())
+                .map_err(|e| format!("Invalid answer from the inference server: {}", e))?;
+            if answered == number {
+                return answer.get_item(1).map(Some).map_err(|e| e.to_string());
+            }
+        }
+    }
+
+    /// Wait without holding the GIL
+    fn sleep(&self, duration: Duration) {
+        self.queue.py().allow_threads(|| thread::sleep(duration));
+    }
+}
@@ -0,0 +1,182 @@
+// Python wrapper around the rules engine, so training code can build and inspect positions
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use blokus::game::{Game, Move, StateOptions};
+use blokus::notation::{format_move, parse_move};
+
+fn to_py_err(e: String) -> PyErr {
+    PyValueError::new_err(e)
+}
+
+/// A game in progress, stepped one tile or one whole piece at a time
+/// Every step can be undone, back to the start of the game
+#[pyclass(name = "Game")]
+#[derive(Clone)]
+pub struct PyGame {
+    game: Game,
+    previous: Vec<Game>, // Positions before each step
+}
+
+#[pymethods]
+impl PyGame {
+    #[new]
+    fn new() -> PyGame {
+        PyGame {
+            game: Game::reset(),
+            previous: Vec::new(),
+        }
+    }
+
+    #[getter]
+    fn current_player(&self) -> usize {
+        self.game.current_player()
+    }
+
+    /// (player, tile) for every tile placed so far
+    #[getter]
+    fn history(&self) -> Vec<(i32, i32)> {
+        self.game.history.clone()
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.game.is_terminal()
+    }
+
+    /// Whether the current player has not placed any tiles of their piece yet
+    fn is_turn_start(&self) -> bool {
+        self.game.is_turn_start()
+    }
+
+    /// Tiles the current player can place next, sorted
+    fn legal_tiles(&self) -> Vec<usize> {
+        let mut tiles = self.game.get_legal_tiles();
+        tiles.sort();
+        tiles
+    }
+
+    /// Whole piece placements as (piece, variant, offset), sorted
+    fn legal_moves(&self) -> Vec<Move> {
+        let mut moves: Vec<Move> = self.game.get_legal_moves().into_iter().collect();
+        moves.sort();
+        moves
+    }
+
+    /// Board tiles a move would cover for the current player
+    fn move_tiles(&self, m: Move) -> Vec<usize> {
+        let (piece, variant, offset) = m;
+        let mut tiles: Vec<usize> = self
+            .game
+            .get_piece(self.game.current_player(), piece, variant)
+            .offsets
+            .iter()
+            .map(|t| offset + t)
+            .collect();
+        tiles.sort();
+        tiles
+    }
+
+    /// Move in square notation like "a1-a2-b2"
+    fn format_move(&self, m: Move) -> String {
+        format_move(&self.game, m)
+    }
+
+    fn parse_move(&self, text: &str) -> PyResult<Move> {
+        parse_move(&self.game, text).map_err(to_py_err)
+    }
+
+    /// Place one tile, optionally finishing the turn with the given piece
+    #[pyo3(signature = (tile, piece=None))]
+    fn step(&mut self, tile: usize, piece: Option<usize>) -> PyResult<()> {
+        if !self.game.get_legal_tiles().contains(&tile) {
+            return Err(to_py_err(format!("Tile {} is not legal", tile)));
+        }
+        let mut next = self.game.clone();
+        next.apply(tile, piece).map_err(to_py_err)?;
+        self.previous.push(std::mem::replace(&mut self.game, next));
+        Ok(())
+    }
+
+    /// Place a whole piece, as given by legal_moves
+    fn play(&mut self, m: Move) -> PyResult<()> {
+        let (piece, variant, offset) = m;
+        let next = self
+            .game
+            .place_piece(piece, variant, offset)
+            .map_err(to_py_err)?;
+        self.previous.push(std::mem::replace(&mut self.game, next));
+        Ok(())
+    }
+
+    /// Take back the last step or play
+    fn undo(&mut self) -> PyResult<()> {
+        match self.previous.pop() {
+            Some(game) => {
+                self.game = game;
+                Ok(())
+            }
+            None => Err(to_py_err("Nothing to undo".to_string())),
+        }
+    }
+
+    /// Network input as a (5, 20, 20) bool numpy array, from the current player's perspective
+    /// Without rotate the board keeps its own coordinates, with four more planes marking who
+    /// is to move, see StateOptions::absolute
+    #[pyo3(signature = (rotate=true))]
+    fn get_board_state(&self, py: Python<'_>, rotate: bool) -> PyResult<PyObject> {
+        let options = StateOptions {
+            absolute: !rotate,
+            ..StateOptions::default()
+        };
+        let state = self.game.get_state(&options);
+        let array = py.import_bound("numpy")?.call_method1("array", (state,))?;
+        Ok(array.unbind())
+    }
+
+    /// Which of the 21 pieces each color holds as a (4, 21) bool numpy array,
+    /// from the current player's perspective like the board state
+    fn get_piece_state(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let held = self.game.get_piece_state();
+        let array = py.import_bound("numpy")?.call_method1("array", (held,))?;
+        Ok(array.unbind())
+    }
+
+    /// Tiles of each of the last n pieces placed as a (n, 20, 20) bool numpy array,
+    /// most recent first and oriented like the board state
+    #[pyo3(signature = (n, rotate=true))]
+    fn get_history_state(&self, py: Python<'_>, n: usize, rotate: bool) -> PyResult<PyObject> {
+        let turns = if rotate {
+            self.game.current_player()
+        } else {
+            0
+        };
+        let planes = self.game.get_history_state(n, turns);
+        let array = py.import_bound("numpy")?.call_method1("array", (planes,))?;
+        Ok(array.unbind())
+    }
+
+    fn score(&self) -> Vec<i32> {
+        self.game.get_score()
+    }
+
+    fn payoff(&self) -> Vec<f32> {
+        self.game.get_payoff()
+    }
+
+    fn copy(&self) -> PyGame {
+        self.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Game(current_player={}, tiles_placed={}, terminal={})",
+            self.game.current_player(),
+            self.game.history.len(),
+            self.game.is_terminal()
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.game.board.to_ansi()
+    }
+}
@@ -1,9 +1,16 @@
-mod node;
+mod client;
+mod engine;
 mod simulation;
 
+use blokus::game::{Game, PASS};
+use blokus::logging::{self, Format};
+use blokus::opening_book::OpeningBook;
+use blokus::records;
+use blokus::shaping::{game_signals, Shaping};
+use engine::PyGame;
 use pyo3::prelude::*;
 use simulation::Config;
-use simulation::{test_game, training_game};
+use simulation::{test_game, training_game, training_games, GameData};
 
 /// Works with Pytorch model to generate self-play data
 #[pyfunction]
@@ -12,7 +19,7 @@ fn play_training_game(
     config: PyObject,
     inference_queue: PyObject,
     pipe: PyObject,
-) -> PyResult<(Vec<(i32, i32)>, Vec<Vec<(i32, f32)>>, Vec<f32>)> {
+) -> PyResult<GameData> {
     Python::with_gil(|py| {
         let config: Config = config.extract::<Config>(py).unwrap();
         let i_queue = inference_queue.bind(py);
@@ -27,7 +34,24 @@ fn play_training_game(
                 )))
             }
         }
+    })
+}
+
+/// Plays config.concurrent_games self-play games in this process, batching their inference
+#[pyfunction]
+fn play_training_games(
+    id: i32,
+    config: PyObject,
+    inference_queue: PyObject,
+    pipe: PyObject,
+) -> PyResult<Vec<GameData>> {
+    Python::with_gil(|py| {
+        let config: Config = config.extract::<Config>(py)?;
+        let i_queue = inference_queue.bind(py);
+        let r_queue = pipe.bind(py);
 
+        training_games(&config, i_queue, r_queue, id, config.concurrent_games)
+            .map_err(PyErr::new::<pyo3::exceptions::PyException, _>)
     })
 }
 
@@ -55,9 +79,94 @@ fn play_test_game(
     })
 }
 
+/// Adds self-play games to the opening book at path, creating it if needed
+#[pyfunction]
+fn update_opening_book(
+    path: String,
+    games: Vec<GameData>,
+    max_ply: usize,
+    min_games: u32,
+) -> PyResult<usize> {
+    let to_py_err = |e: String| PyErr::new::<pyo3::exceptions::PyException, _>(e);
+    let mut book = if std::path::Path::new(&path).exists() {
+        OpeningBook::load(&path).map_err(to_py_err)?
+    } else {
+        OpeningBook::new(max_ply, min_games)
+    };
+
+    for (history, policies, ..) in &games {
+        book.add_game(history, policies).map_err(to_py_err)?;
+    }
+    book.save(&path).map_err(to_py_err)?;
+    Ok(book.len())
+}
+
+/// Converts recorded human games in a .json or .csv file, or Board Game Arena logs in a
+/// .bga.json file, into training data
+/// Each placed tile gets a one-hot policy on the tile that was actually played
+/// and the values are the config's payoff, see blokus::payoff for the payoff specs
+/// The positions are laid out by the config's state options like self-play games
+#[pyfunction]
+fn import_games(py: Python<'_>, path: String, config: PyObject) -> PyResult<Vec<GameData>> {
+    let to_py_err = |e: String| PyErr::new::<pyo3::exceptions::PyException, _>(e);
+    let config: Config = config.extract(py)?;
+    let records = records::load(&path).map_err(to_py_err)?;
+    let payoff = config.payoff().map_err(to_py_err)?;
+    let options = config.state_options();
+
+    let mut games = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        let mut states = Vec::new();
+        let imported = records::replay_visiting(Game::reset(), record, |game| {
+            states.push(game.get_packed_state(&options))
+        })
+        .map_err(|e| to_py_err(format!("Game {}: {}", i + 1, e)))?;
+        let game = imported.game;
+        let policies = game
+            .history
+            .iter()
+            .map(|(_, tile)| match *tile {
+                PASS => Vec::new(),
+                tile => vec![(tile, 1.0)],
+            })
+            .collect();
+        let legal_tiles = imported
+            .legal_tiles
+            .iter()
+            .map(|tiles| tiles.iter().map(|t| *t as i32).collect())
+            .collect();
+        let signals = game_signals(&Game::reset(), &game.history).map_err(to_py_err)?;
+        games.push((
+            game.history.clone(),
+            policies,
+            payoff.of(&game),
+            legal_tiles,
+            game.get_ownership(),
+            game.get_score_margins(),
+            signals.into_iter().map(Shaping::to_tuple).collect(),
+            states,
+        ));
+    }
+    Ok(games)
+}
+
+/// Names of the network input planes the config's state options lay out, see
+/// blokus::game::StateOptions, which the inference server answers handshakes with
+#[pyfunction]
+fn state_planes(py: Python<'_>, config: PyObject) -> PyResult<Vec<String>> {
+    let config: Config = config.extract(py)?;
+    Ok(config.state_options().planes())
+}
+
 #[pymodule]
-fn blokus_self_play(m: &Bound<'_, PyModule>) -> PyResult<()> {
+fn blokus_engine(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    logging::init(Format::Json);
+    m.add_class::<PyGame>()?;
     let _ = m.add_function(wrap_pyfunction!(play_training_game, m)?);
+    _ = m.add_function(wrap_pyfunction!(play_training_games, m)?);
     _ = m.add_function(wrap_pyfunction!(play_test_game, m)?);
+    _ = m.add_function(wrap_pyfunction!(update_opening_book, m)?);
+    _ = m.add_function(wrap_pyfunction!(import_games, m)?);
+    _ = m.add_function(wrap_pyfunction!(state_planes, m)?);
     Ok(())
 }
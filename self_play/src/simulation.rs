@@ -1,16 +1,41 @@
-// One game of self-play using MCTS and a neural network
+// Self-play games using MCTS and a neural network
 use rand::Rng;
-use rand_distr::{Dirichlet, Distribution};
+use std::fs;
+use std::time::Duration;
 use std::vec;
 
 use pyo3::prelude::*;
 
-use crate::node::Node;
 use blokus::board::BOARD_SIZE as D;
-use blokus::game::Game;
+use blokus::evaluator::Evaluator;
+use blokus::game::{Game, StateOptions};
+use blokus::node::Node;
+use blokus::opening_book::OpeningBook;
+use blokus::payoff::Payoff;
+use blokus::search::{self, SearchConfig, SteppedSearch};
+use blokus::shaping::{game_signals, Shaping};
+use blokus::tree_export::{Pruning, SavedTree};
+
+use crate::client::{InferenceClient, Resilience};
 
 const BOARD_SIZE: usize = D * D;
 
+/// History, search policies, payoff, the legal tiles before each move,
+/// the auxiliary targets: final owner of each square and final score margins,
+/// the shaping signals of each history entry, see blokus::shaping,
+/// and the network input before each move packed by Game::get_packed_state, empty where
+/// there is no policy to learn from
+pub type GameData = (
+    Vec<(i32, i32)>,
+    Vec<Vec<(i32, f32)>>,
+    Vec<f32>,
+    Vec<Vec<i32>>,
+    Vec<i32>,
+    Vec<i32>,
+    Vec<(i32, i32, i32)>,
+    Vec<Vec<u8>>,
+);
+
 #[derive(FromPyObject)]
 pub struct Config {
     sims_per_move: usize,
@@ -19,225 +44,132 @@ pub struct Config {
     c_init: f32,
     dirichlet_alpha: f32,
     exploration_fraction: f32,
+    opening_book: Option<String>,
+    tree_dump_dir: Option<String>,
+    endgame_threshold: usize,
+    verify_top_k: usize,
+    prune_symmetry: bool,
+    fpu_reduction: Option<f32>,
+    prior_temperature: f32,
+    widening_base: usize,
+    widening_exponent: f32,
+    pub concurrent_games: usize,
+    random_start_fraction: f32,
+    random_start_moves: usize,
+    eval_timeout: f32,
+    retry_backoff: f32,
+    breaker_failures: u32,
+    max_outage: f32,
+    payoff: String,            // See blokus::payoff
+    state_team_relative: bool, // See blokus::game::StateOptions
+    state_pieces: bool,
+    state_history: usize,
+    state_absolute: bool,
 }
 
-/// Rotates the policy 90 degrees to the right
-fn rotate_policy(state: Vec<f32>) -> Vec<f32> {
-    let mut rotated = vec![0.0; BOARD_SIZE];
-    for i in 0..D {
-        for j in 0..D {
-            rotated[j * D + (D - 1 - i)] = state[i * D + j];
-        }
+impl Config {
+    fn search_config(&self) -> Result<SearchConfig, String> {
+        Ok(SearchConfig {
+            sims_per_move: self.sims_per_move,
+            sample_moves: self.sample_moves,
+            c_base: self.c_base,
+            c_init: self.c_init,
+            dirichlet_alpha: self.dirichlet_alpha,
+            exploration_fraction: self.exploration_fraction,
+            endgame_threshold: self.endgame_threshold,
+            verify_top_k: self.verify_top_k,
+            prune_symmetry: self.prune_symmetry,
+            payoff: self.payoff()?,
+            fpu_reduction: self.fpu_reduction,
+            prior_temperature: self.prior_temperature,
+            widening_base: self.widening_base,
+            widening_exponent: self.widening_exponent,
+            early_stop: false, // The visit counts are training targets, so spend every simulation
+        })
     }
 
-    rotated.to_vec()
-}
-
-/// Evaluate and Expand the Node
-fn evaluate(
-    node: &mut Node,
-    game: &Game,
-    inference_queue: &Bound<PyAny>,
-    pipe: &Bound<PyAny>,
-    id: i32,
-) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
-    // If the game is over, return the payoff
-    if game.is_terminal() {
-        return Ok(game.get_payoff());
+    pub fn payoff(&self) -> Result<Payoff, String> {
+        Payoff::from_spec(&self.payoff)
     }
 
-    // Get the policy and value from the neural network
-    let representation = game.get_board_state(); // Tested
-    let request = (id, representation);
-    inference_queue.call_method1("put", (request,))?;
-
-    // Wait for the result
-    let inference = pipe.call_method0("recv")?;
-    let mut policy: Vec<f32> = inference.get_item(0)?.extract()?;
-    let mut value: Vec<f32> = inference.get_item(1)?.extract()?;
-    let current_player = game.current_player();
-
-    // Rotate the policy so they are in order
-    for _ in 0..(current_player) {
-        policy = rotate_policy(policy);
-    }
-    value.rotate_right(current_player);
-
-    // Normalize policy for node priors, filter out illegal moves
-    let legal_moves = game.get_legal_tiles();
-    let mut exp_policy = vec![];
-    for tile in legal_moves {
-        if policy[tile] > 0.0 {
-            exp_policy.push((tile, policy[tile].exp()));
+    pub fn state_options(&self) -> StateOptions {
+        StateOptions {
+            team_relative: self.state_team_relative,
+            pieces: self.state_pieces,
+            history: self.state_history,
+            absolute: self.state_absolute,
         }
     }
-    let total: f32 = exp_policy.iter().map(|(_, p)| p).sum();
-    // println!("Policy: {:?}\nExp: {:?}", policy, exp_policy);
 
-    // Expand the node with the policy
-    node.to_play = current_player;
-    for (tile, prob) in exp_policy {
-        node.children.insert(tile, Node::new(prob / total));
-    }
-    Ok(value)
-}
-
-/// Get UCB score for a child node
-/// Exploration constant is based on the number of visits to the parent node
-/// so that it will encourage exploration of nodes that have not been visited
-fn ucb_score(parent: &Node, child: &Node, config: &Config) -> f32 {
-    let c_base = config.c_base;
-    let c_init = config.c_init;
-    let parent_visits = parent.visits as f32;
-    let exploration_constant = (((parent_visits + c_base + 1.0) / c_base).ln() + c_init)
-        * parent_visits.sqrt()
-        / (1.0 + child.visits as f32);
-    let prior_score = exploration_constant * child.prior;
-    let value_score = child.value();
-    prior_score + value_score
-}
-
-/// Add noise to the root node to encourage exploration
-fn add_exploration_noise(root: &mut Node, config: &Config) -> () {
-    let num_actions = root.children.len();
-    if num_actions <= 1 {
-        return;
-    }
-
-    let alpha_vec = vec![config.dirichlet_alpha; num_actions];
-    let dirichlet = Dirichlet::new(&alpha_vec).unwrap();
-    let noise = dirichlet.sample(&mut rand::thread_rng());
-    for (i, (_tile, node)) in root.children.iter_mut().enumerate() {
-        node.prior = node.prior * (1.0 - config.exploration_fraction)
-            + noise[i] * config.exploration_fraction;
-    }
-}
-
-/// Sample from a softmax distribution
-/// Used to select actions during the first few moves to encourage exploration
-fn softmax_sample(visit_dist: Vec<(usize, u32)>) -> usize {
-    let total_visits: u32 = visit_dist.iter().fold(0, |acc, (_, visits)| acc + visits);
-    let sample = rand::thread_rng().gen_range(0.0..1.0);
-    let mut sum = 0.0;
-
-    for (tile, visits) in &visit_dist {
-        sum += (*visits as f32) / (total_visits as f32);
-        if sum > sample {
-            return *tile;
+    fn resilience(&self) -> Resilience {
+        Resilience {
+            timeout: Duration::from_secs_f32(self.eval_timeout),
+            backoff: Duration::from_secs_f32(self.retry_backoff),
+            breaker_failures: self.breaker_failures,
+            max_outage: Duration::from_secs_f32(self.max_outage),
+            ..Resilience::default()
         }
     }
-    visit_dist.last().unwrap().0
 }
 
-/// Select child node to explore
-/// Uses UCB formula to balance exploration and exploitation
-/// Returns the action and the child node's key
-fn select_child(node: &Node, config: &Config) -> usize {
-    let mut best_score = 0.0;
-    let mut best_action = 0;
-    for (action, child) in &node.children {
-        let score = ucb_score(node, child, config);
-        // println!("Action: {}, Score: {}", action, score);
-        if score >= best_score {
-            best_score = score;
-            best_action = *action;
+/// Rotates the policy 90 degrees to the right
+fn rotate_policy(state: Vec<f32>) -> Vec<f32> {
+    let mut rotated = vec![0.0; BOARD_SIZE];
+    for i in 0..D {
+        for j in 0..D {
+            rotated[j * D + (D - 1 - i)] = state[i * D + j];
         }
     }
-    best_action
+
+    rotated.to_vec()
 }
 
-/// Select action from policy
-fn select_action(root: &Node, num_moves: usize, config: &Config) -> usize {
-    let visit_dist: Vec<(usize, u32)> = root
-        .children
-        .iter()
-        .map(|(tile, node)| (*tile, node.visits))
-        .collect();
-    if num_moves < config.sample_moves {
-        softmax_sample(visit_dist)
-    } else {
-        visit_dist.iter().max_by(|a, b| a.1.cmp(&b.1)).unwrap().0
+/// Policy over board tiles and value for each player
+type Inference = (Vec<f32>, Vec<f32>);
+
+/// Send positions to the Python inference server in one predict_batch call and wait for the results
+/// Policies are turned back to the board as the client's state options turned the positions,
+/// and values rotated back from each position's player perspective
+fn query_batch(
+    client: &mut InferenceClient,
+    games: &[&Game],
+) -> Result<Vec<Inference>, Box<dyn std::error::Error>> {
+    // Get the policies and values from the neural network
+    let predictions = client.predict_batch(games)?;
+    let mut results = Vec::with_capacity(games.len());
+    for (game, prediction) in games.iter().zip(predictions) {
+        let mut policy = prediction.policy;
+        let mut value = prediction.values;
+        let current_player = game.current_player();
+
+        // Rotate the policy so they are in order
+        for _ in 0..client.options().turns(current_player) {
+            policy = rotate_policy(policy);
+        }
+        value.rotate_right(current_player);
+        results.push((policy, value));
     }
+    Ok(results)
 }
 
-/// Update node when visitied during backpropagation
-fn backpropagate(search_path: Vec<usize>, root: &mut Node, values: Vec<f32>) -> () {
-    let mut node = root;
-    for tile in search_path {
-        node = node.children.get_mut(&tile).unwrap();
-        node.visits += 1;
-        node.value_sum += values[node.to_play];
-    }
+/// Evaluator backed by the Python inference server
+struct QueueEvaluator<'a, 'b, 'py> {
+    client: &'a mut InferenceClient<'b, 'py>,
 }
 
-/// Run MCTS simulations to get policy for root node
-fn mcts(
-    game: &Game,
-    policies: &mut Vec<Vec<(i32, f32)>>,
-    config: &Config,
-    inference_queue: &Bound<PyAny>,
-    pipe: &Bound<PyAny>,
-    id: i32,
-) -> Result<usize, String> {
-    // Initialize root for these sims, evaluate it, and add children
-    let mut root = Node::new(0.0);
-    match evaluate(&mut root, game, inference_queue, pipe, id) {
-        Ok(_) => (),
-        Err(e) => {
-            return Err(format!("Error evaluating root node: {:}", e));
-        }
-    }
-    add_exploration_noise(&mut root, config);
-
-    for _ in 0..config.sims_per_move {
-        // Select a leaf node
-        root.visits += 1;
-        let mut node = &mut root;
-        let mut scratch_game = game.clone();
-        let mut search_path = Vec::new();
-        while node.is_expanded() {
-            let action = select_child(node, config);
-            node = node.children.get_mut(&action).unwrap();
-            let _ = scratch_game.apply(action, None);
-            search_path.push(action);
+impl Evaluator for QueueEvaluator<'_, '_, '_> {
+    fn evaluate(&mut self, game: &Game) -> Result<(Vec<f32>, Vec<f32>), String> {
+        match query_batch(self.client, &[game]) {
+            Ok(mut results) => Ok(results.remove(0)),
+            Err(e) => Err(e.to_string()),
         }
-
-        // Expand and evaluate the leaf node
-        let values = evaluate(node, &scratch_game, inference_queue, pipe, id).unwrap();
-
-        // Backpropagate the value
-        backpropagate(search_path, &mut root, values)
     }
-
-    // Save policy for this state
-    let total_visits: u32 = root
-        .children
-        .iter()
-        .map(|(_tile, child)| child.visits)
-        .sum();
-    let probs = root
-        .children
-        .iter()
-        .map(|(tile, child)| {
-            let p = (child.visits as f32) / (total_visits as f32);
-            (*tile as i32, p)
-        })
-        .collect();
-    policies.push(probs);
-
-    // Pick action to take
-    let action = select_action(&root, policies.len(), config);
-    Ok(action)
 }
 
-fn best_action(
-    game: &Game,
-    id: i32,
-    queue: &Bound<PyAny>,
-    pipe: &Bound<PyAny>,
-) -> Result<usize, String> {
+fn best_action(game: &Game, client: &mut InferenceClient) -> Result<usize, String> {
     let mut root = Node::new(0.0);
-    match evaluate(&mut root, game, queue, pipe, id) {
+    let mut evaluator = QueueEvaluator { client };
+    match search::evaluate(&mut root, game, &SearchConfig::default(), &mut evaluator) {
         Ok(_) => (),
         Err(e) => {
             return Err(format!("Error evaluating root node: {:?}", e));
@@ -264,35 +196,247 @@ fn best_action(
     Ok(best_action)
 }
 
-pub fn training_game(
+/// One self-play game in progress, paused whenever its search needs an evaluation
+struct SelfPlayGame {
+    index: usize,
+    game: Game,
+    policies: Vec<Vec<(i32, f32)>>,
+    legal_tiles: Vec<Vec<i32>>,
+    states: Vec<Vec<u8>>, // Packed network input before each move
+    search: Option<SteppedSearch>,
+    span: tracing::Span, // Entered while the game advances, so its searches are tagged with it
+}
+
+impl SelfPlayGame {
+    fn new(index: usize) -> SelfPlayGame {
+        SelfPlayGame {
+            index,
+            game: Game::reset(),
+            policies: Vec::new(),
+            legal_tiles: Vec::new(),
+            states: Vec::new(),
+            search: None,
+            span: tracing::info_span!("game", index),
+        }
+    }
+
+    /// Start from the empty board, or sometimes from a random midgame position
+    fn start(index: usize, config: &Config) -> Result<SelfPlayGame, String> {
+        let mut game = SelfPlayGame::new(index);
+        let mut rng = rand::thread_rng();
+        if rng.gen::<f32>() < config.random_start_fraction {
+            let moves = rng.gen_range(0..=config.random_start_moves);
+            game.play_random_moves(moves)?;
+        }
+        Ok(game)
+    }
+
+    /// Play whole pieces uniformly at random
+    /// The tiles are recorded without policies or legal tiles so the trainer skips them
+    fn play_random_moves(&mut self, moves: usize) -> Result<(), String> {
+        let mut rng = rand::thread_rng();
+        for _ in 0..moves {
+            if self.game.is_terminal() {
+                break;
+            }
+
+            let mut legal_moves: Vec<_> = self.game.get_legal_moves().into_iter().collect();
+            legal_moves.sort();
+            let (piece, variant, offset) = legal_moves[rng.gen_range(0..legal_moves.len())];
+            let placed = self.game.history.len();
+            self.game = self.game.place_piece(piece, variant, offset)?;
+            for _ in placed..self.game.history.len() {
+                self.policies.push(Vec::new());
+                self.legal_tiles.push(Vec::new());
+                self.states.push(Vec::new());
+            }
+        }
+        Ok(())
+    }
+
+    /// Play moves until the search needs a position evaluated
+    /// Returns None once the game is over
+    fn advance(
+        &mut self,
+        config: &Config,
+        search_config: &SearchConfig,
+        book: Option<&OpeningBook>,
+        id: i32,
+    ) -> Result<Option<Game>, String> {
+        let span = self.span.clone();
+        let _game = span.enter();
+        loop {
+            // Keep searching the current move, or play it once the search is done
+            if let Some(search) = &mut self.search {
+                if let Some(leaf) = search.next_leaf() {
+                    return Ok(Some(leaf.clone()));
+                }
+                let root = self.search.take().unwrap().into_root();
+                self.play_searched(&root, config, search_config, id)?;
+                continue;
+            }
+
+            if self.game.is_terminal() {
+                tracing::info!(
+                    moves = self.game.history.len(),
+                    scores = ?self.game.get_score(),
+                    "game over"
+                );
+                return Ok(None);
+            }
+
+            // Record legality from the engine so the trainer does not have to rebuild it
+            let mut legal: Vec<i32> = self
+                .game
+                .get_legal_tiles()
+                .iter()
+                .map(|t| *t as i32)
+                .collect();
+            legal.sort();
+            self.legal_tiles.push(legal);
+            self.states
+                .push(self.game.get_packed_state(&config.state_options()));
+
+            // Play book moves instantly, using the book's statistics as the policy
+            if let Some(policy) = book.and_then(|b| b.get_policy(&self.game)) {
+                let action = book.unwrap().get_move(&self.game).unwrap();
+                self.policies
+                    .push(policy.iter().map(|(t, p)| (*t as i32, *p)).collect());
+                let _ = self.game.apply(action, None);
+                continue;
+            }
+
+            // Start the MCTS search for the current state
+            self.search = Some(SteppedSearch::new(&self.game, search_config));
+        }
+    }
+
+    /// Record the search policy and play the chosen move
+    fn play_searched(
+        &mut self,
+        root: &Node,
+        config: &Config,
+        search_config: &SearchConfig,
+        id: i32,
+    ) -> Result<(), String> {
+        if let Some(dir) = &config.tree_dump_dir {
+            let path = format!(
+                "{}/game_{}_{}_move_{}.dot",
+                dir,
+                id,
+                self.index,
+                self.policies.len()
+            );
+            let saved = SavedTree::new(&self.game, root, Pruning::default())?;
+            if let Err(e) = fs::write(&path, saved.root.to_dot()) {
+                return Err(format!("Error writing search tree to {}: {}", path, e));
+            }
+            saved.save(&path.replace(".dot", ".json"))?;
+        }
+        self.policies.push(search::visit_policy(root, &self.game, search_config));
+        let action = search::choose_action(&self.game, root, self.policies.len(), search_config)?;
+
+        tracing::debug!(
+            ply = self.game.history.len(),
+            player = self.game.current_player(),
+            tile = action,
+            visits = root.visits,
+            "move"
+        );
+        let _ = self.game.apply(action, None);
+        Ok(())
+    }
+
+    /// Send data to train the model
+    fn into_data(self, payoff: &Payoff) -> Result<GameData, String> {
+        // println!("History: {:?}", self.game.history);
+        let values = payoff.of(&self.game);
+        let ownership = self.game.get_ownership();
+        let margins = self.game.get_score_margins();
+        let signals = game_signals(&Game::reset(), &self.game.history)?;
+        Ok((
+            self.game.history,
+            self.policies,
+            values,
+            self.legal_tiles,
+            ownership,
+            margins,
+            signals.into_iter().map(Shaping::to_tuple).collect(),
+            self.states,
+        ))
+    }
+}
+
+/// Play several self-play games at once in this process
+/// Leaf positions from every game's search are evaluated together in one predict_batch call
+pub fn training_games(
     config: &Config,
     inference_queue: &Bound<PyAny>,
     pipe: &Bound<PyAny>,
     id: i32,
-) -> Result<(Vec<(i32, i32)>, Vec<Vec<(i32, f32)>>, Vec<f32>), String> {
-    // Storage for game data
-    let mut game = Game::reset();
-    let mut policies: Vec<Vec<(i32, f32)>> = Vec::new();
+    num_games: usize,
+) -> Result<Vec<GameData>, String> {
+    let span = tracing::info_span!("worker", id);
+    let _worker = span.enter();
+    let search_config = config.search_config()?;
+    let book = match &config.opening_book {
+        Some(path) => Some(OpeningBook::load(path)?),
+        None => None,
+    };
+    let mut games = (0..num_games)
+        .map(|index| SelfPlayGame::start(index, config))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut client = InferenceClient::new(
+        inference_queue,
+        pipe,
+        id,
+        config.state_options(),
+        config.resilience(),
+    );
+    client.handshake()?;
 
     // Run self-play to generate data
-    while !game.is_terminal() {
-        // Get MCTS policy for current state
-        let action = match mcts(&game, &mut policies, &config, inference_queue, pipe, id) {
-            Ok(a) => a,
+    loop {
+        let mut waiting = Vec::new();
+        let mut leaves = Vec::new();
+        for (i, game) in games.iter_mut().enumerate() {
+            if let Some(leaf) = game.advance(config, &search_config, book.as_ref(), id)? {
+                waiting.push(i);
+                leaves.push(leaf);
+            }
+        }
+        if leaves.is_empty() {
+            break;
+        }
+
+        // Evaluate the leaves of every unfinished game together
+        let leaves: Vec<&Game> = leaves.iter().collect();
+        let results = match query_batch(&mut client, &leaves) {
+            Ok(r) => r,
             Err(e) => {
+                tracing::error!(error = %e, "batched inference failed");
                 return Err(format!("Error running MCTS: {}", e));
             }
         };
-
-        // println!("Player {} --- {}", game.current_player(), action);
-        let _ = game.apply(action, None);
+        for (i, (policy, values)) in waiting.into_iter().zip(results) {
+            games[i].search.as_mut().unwrap().provide(&policy, values)?;
+        }
     }
 
-    // Send data to train the model
-    // println!("History: {:?}", game.history);
-    let values = game.get_payoff();
-    let game_data = (game.history, policies, values.clone());
-    Ok(game_data)
+    games
+        .into_iter()
+        .map(|game| game.into_data(&search_config.payoff))
+        .collect()
+}
+
+pub fn training_game(
+    config: &Config,
+    inference_queue: &Bound<PyAny>,
+    pipe: &Bound<PyAny>,
+    id: i32,
+) -> Result<GameData, String> {
+    let mut games = training_games(config, inference_queue, pipe, id, 1)?;
+    Ok(games.remove(0))
 }
 
 pub fn test_game(
@@ -301,32 +445,45 @@ pub fn test_game(
     baseline_queue: &Bound<PyAny>,
     pipe: &Bound<PyAny>,
 ) -> Result<f32, String> {
+    let span = tracing::info_span!("worker", id);
+    let _worker = span.enter();
     let mut game = Game::reset();
     // let mut policies: Vec<Vec<(i32, f32)>> = Vec::new();
+    let options = StateOptions::default();
+    let mut model = InferenceClient::new(model_queue, pipe, id, options, Resilience::default());
+    let mut baseline =
+        InferenceClient::new(baseline_queue, pipe, id, options, Resilience::default());
+    model.handshake()?;
+    baseline.handshake()?;
 
     // Run self-play to generate data
-    let mut queue;
+    let mut client;
     while !game.is_terminal() {
         // Set queue to query for this action
         if game.current_player() == 0 {
-            queue = model_queue;
+            client = &mut model;
         } else {
-            queue = baseline_queue;
+            client = &mut baseline;
         }
 
         // Get action to take
-        let action = match best_action(&game, id, queue, pipe) {
+        let action = match best_action(&game, client) {
             Ok(a) => a,
             Err(e) => {
-                println!("Error running MCTS: {:?}", e);
+                tracing::error!(error = ?e, "search failed");
                 return Err("Error running MCTS".to_string());
             }
         };
 
-        // println!("Player {} --- {}", game.current_player(), action);
+        tracing::debug!(
+            ply = game.history.len(),
+            player = game.current_player(),
+            tile = action,
+            "move"
+        );
         let _ = game.apply(action, None);
     }
-    println!("Finished Game");
+    tracing::info!(scores = ?game.get_score(), "test game over");
     game.board.print_board();
     Ok(game.get_payoff()[0])
 }
@@ -0,0 +1,6 @@
+// Generate the gRPC game service, using the vendored protoc so no system install is needed
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_prost_build::compile_protos("proto/game.proto")?;
+    Ok(())
+}
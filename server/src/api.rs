@@ -0,0 +1,261 @@
+/*
+REST routes for creating games, reading their state, and playing moves
+
+  POST /games                  Create a game, returns its id and state, optionally with
+                               {"variant": "two-player", "swap_rule": true} (default classic)
+  GET  /games                  List every game, or only finished ones with ?finished=true
+  GET  /games/{id}             Current state
+  GET  /games/{id}/moves       Legal moves for the player to move
+  GET  /games/{id}/hints       Best moves with estimated win rates, ?k=n (default 3)
+  POST /games/{id}/moves       Play {"move": "a1-a2"} in square notation, or {"move": "swap"}
+  POST /games/{id}/ai-move     Let an engine agent move, {"agent": "mcts:200"} (default blocking)
+  GET  /players                Highest rated players and agents, ?limit=n (default 20)
+  GET  /players/{name}/games   Stored games a player sat in, newest first, ?limit=n
+  GET  /players/{name}/series  Rematch series a player took part in, newest first, ?limit=n
+The player routes need the server to be started with a database.
+*/
+
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::task;
+
+use blokus::agents::{from_spec, should_swap};
+use blokus::evaluator::HeuristicEvaluator;
+use blokus::game::{Game, Variant};
+use blokus::hints::{hints, Hint};
+use blokus::state::{legal_moves, GameState, LegalMove};
+use blokus::storage::{Database, PlayerRecord, SeriesRecord, StoredGame};
+
+use crate::store::{GameStore, GameSummary};
+
+pub type SharedStore = Arc<Mutex<GameStore>>;
+
+/// Errors are sent as {"error": message}
+pub struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+fn not_found(e: String) -> ApiError {
+    ApiError(StatusCode::NOT_FOUND, e)
+}
+
+fn bad_request(e: String) -> ApiError {
+    ApiError(StatusCode::BAD_REQUEST, e)
+}
+
+fn internal_error(e: String) -> ApiError {
+    ApiError(StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
+#[derive(Serialize)]
+struct Created {
+    id: u64,
+    state: GameState,
+}
+
+#[derive(Deserialize)]
+struct CreateRequest {
+    variant: Option<String>,
+    #[serde(default)]
+    swap_rule: bool,
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    #[serde(default)]
+    finished: bool,
+}
+
+#[derive(Deserialize)]
+struct LimitQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct HintQuery {
+    k: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct MoveRequest {
+    #[serde(rename = "move")]
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct AiRequest {
+    agent: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AiMove {
+    #[serde(rename = "move")]
+    text: String,
+    state: GameState,
+}
+
+async fn create_game(
+    State(store): State<SharedStore>,
+    request: Option<Json<CreateRequest>>,
+) -> Result<Json<Created>, ApiError> {
+    let mut start = Game::reset();
+    if let Some(Json(request)) = request {
+        if let Some(name) = &request.variant {
+            start = Game::new(Variant::from_name(name).map_err(bad_request)?);
+        }
+        if request.swap_rule {
+            start = start.with_swap_rule().map_err(bad_request)?;
+        }
+    }
+    let mut store = store.lock().unwrap();
+    let id = store.create_from(start);
+    let state = store.state(id).unwrap();
+    Ok(Json(Created { id, state }))
+}
+
+async fn list_games(
+    State(store): State<SharedStore>,
+    Query(query): Query<ListQuery>,
+) -> Json<Vec<GameSummary>> {
+    Json(store.lock().unwrap().summaries(query.finished))
+}
+
+async fn get_game(
+    State(store): State<SharedStore>,
+    Path(id): Path<u64>,
+) -> Result<Json<GameState>, ApiError> {
+    let state = store.lock().unwrap().state(id).map_err(not_found)?;
+    Ok(Json(state))
+}
+
+async fn get_moves(
+    State(store): State<SharedStore>,
+    Path(id): Path<u64>,
+) -> Result<Json<Vec<LegalMove>>, ApiError> {
+    let store = store.lock().unwrap();
+    let game = store.get(id).map_err(not_found)?;
+    Ok(Json(legal_moves(game)))
+}
+
+async fn get_hints(
+    State(store): State<SharedStore>,
+    Path(id): Path<u64>,
+    Query(query): Query<HintQuery>,
+) -> Result<Json<Vec<Hint>>, ApiError> {
+    let game = store.lock().unwrap().get(id).map_err(not_found)?.clone();
+    let k = query.k.unwrap_or(3);
+    let found = task::spawn_blocking(move || hints(&game, &mut HeuristicEvaluator::default(), k))
+        .await
+        .map_err(|e| internal_error(e.to_string()))?
+        .map_err(internal_error)?;
+    Ok(Json(found))
+}
+
+async fn play_move(
+    State(store): State<SharedStore>,
+    Path(id): Path<u64>,
+    Json(request): Json<MoveRequest>,
+) -> Result<Json<GameState>, ApiError> {
+    let mut store = store.lock().unwrap();
+    store.get(id).map_err(not_found)?;
+    let state = store
+        .play_notation(id, &request.text)
+        .map_err(bad_request)?;
+    Ok(Json(state))
+}
+
+/// Search on a blocking thread so slow agents do not hold up other requests
+async fn ai_move(
+    State(store): State<SharedStore>,
+    Path(id): Path<u64>,
+    Json(request): Json<AiRequest>,
+) -> Result<Json<AiMove>, ApiError> {
+    let game = store.lock().unwrap().get(id).map_err(not_found)?.clone();
+    if game.is_terminal() {
+        return Err(bad_request("The game is over".to_string()));
+    }
+    let spec = request.agent.unwrap_or("blocking".to_string());
+
+    let searched = game.clone();
+    let agent_spec = spec.clone();
+    // None when the agent takes over the opening under the swap rule instead of moving
+    let chosen = task::spawn_blocking(move || {
+        let mut agent = from_spec(&agent_spec, None).map_err(bad_request)?;
+        if should_swap(&searched, &mut HeuristicEvaluator::default()).map_err(internal_error)? {
+            return Ok(None);
+        }
+        agent
+            .choose_move(&searched)
+            .map(Some)
+            .map_err(internal_error)
+    })
+    .await
+    .map_err(|e| internal_error(e.to_string()))??;
+
+    let mut store = store.lock().unwrap();
+    let played = match chosen {
+        Some(chosen) => store.play_if_unchanged(id, &game, chosen, &spec),
+        None => store.swap_if_unchanged(id, &game),
+    };
+    let (text, state) = played.map_err(|e| ApiError(StatusCode::CONFLICT, e))?;
+    Ok(Json(AiMove { text, state }))
+}
+
+/// Run a query against the database, if the server has one
+fn with_database<T>(
+    store: &SharedStore,
+    query: impl FnOnce(&Database) -> Result<T, String>,
+) -> Result<Json<T>, ApiError> {
+    let store = store.lock().unwrap();
+    let database = store
+        .database()
+        .ok_or(not_found("The server has no database".to_string()))?;
+    query(database).map(Json).map_err(internal_error)
+}
+
+async fn list_players(
+    State(store): State<SharedStore>,
+    Query(query): Query<LimitQuery>,
+) -> Result<Json<Vec<PlayerRecord>>, ApiError> {
+    with_database(&store, |db| db.leaderboard(query.limit.unwrap_or(20)))
+}
+
+async fn player_games(
+    State(store): State<SharedStore>,
+    Path(name): Path<String>,
+    Query(query): Query<LimitQuery>,
+) -> Result<Json<Vec<StoredGame>>, ApiError> {
+    with_database(&store, |db| db.games_for(&name, query.limit.unwrap_or(20)))
+}
+
+async fn player_series(
+    State(store): State<SharedStore>,
+    Path(name): Path<String>,
+    Query(query): Query<LimitQuery>,
+) -> Result<Json<Vec<SeriesRecord>>, ApiError> {
+    with_database(&store, |db| db.series_for(&name, query.limit.unwrap_or(20)))
+}
+
+pub fn router(store: SharedStore) -> Router {
+    Router::new()
+        .route("/games", post(create_game).get(list_games))
+        .route("/games/{id}", get(get_game))
+        .route("/games/{id}/moves", get(get_moves).post(play_move))
+        .route("/games/{id}/hints", get(get_hints))
+        .route("/games/{id}/ai-move", post(ai_move))
+        .route("/players", get(list_players))
+        .route("/players/{name}/games", get(player_games))
+        .route("/players/{name}/series", get(player_series))
+        .with_state(store)
+}
@@ -0,0 +1,111 @@
+/*
+Server settings from a TOML file given with --config, options on the command line win
+
+  port = 3000
+  grpc_port = 50051
+  db = "games.sqlite"
+
+  [grpc.tls]                  Serve gRPC over TLS
+  cert = "server.crt"         PEM certificate chain
+  key = "server.key"          PEM private key
+
+  [grpc.auth]                 Require "authorization: Bearer <token>" on every gRPC call
+  token_env = "BLOKUS_TOKEN"  Environment variable holding the token, or token = "..."
+*/
+
+use std::env;
+use std::fs;
+
+use serde::Deserialize;
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port: Option<u16>,
+    pub grpc_port: Option<u16>,
+    pub db: Option<String>,
+    pub grpc: GrpcConfig,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GrpcConfig {
+    pub tls: Option<TlsConfig>,
+    pub auth: Option<AuthConfig>,
+}
+
+/// Paths to the PEM files for the server's certificate and key
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: String,
+    pub key: String,
+}
+
+impl TlsConfig {
+    /// Read the certificate chain and key
+    pub fn read(&self) -> Result<(String, String), String> {
+        let read = |path: &str| {
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+        };
+        Ok((read(&self.cert)?, read(&self.key)?))
+    }
+}
+
+/// The token clients share with the server, kept in the file or in the environment
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AuthConfig {
+    pub token: Option<String>,
+    pub token_env: Option<String>,
+}
+
+impl AuthConfig {
+    pub fn token(&self) -> Result<String, String> {
+        let token = match (&self.token, &self.token_env) {
+            (Some(token), None) => token.clone(),
+            (None, Some(name)) => env::var(name)
+                .map_err(|_| format!("The auth token variable {} is not set", name))?,
+            _ => return Err("Give one of token or token_env for grpc.auth".to_string()),
+        };
+        if token.is_empty() {
+            return Err("The auth token is empty".to_string());
+        }
+        Ok(token)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Config, String> {
+    toml::from_str(text).map_err(|e| format!("Invalid config: {}", e))
+}
+
+pub fn load(path: &str) -> Result<Config, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    parse(&text)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("").unwrap(), Config::default());
+
+        let config = parse(
+            "grpc_port = 443\n\n[grpc.tls]\ncert = \"a.crt\"\nkey = \"a.key\"\n\n[grpc.auth]\ntoken = \"secret\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.grpc_port, Some(443));
+        assert_eq!(config.port, None);
+        assert_eq!(config.grpc.tls.unwrap().cert, "a.crt");
+        assert_eq!(config.grpc.auth.unwrap().token(), Ok("secret".to_string()));
+
+        // Typos are caught rather than leaving the server open
+        assert!(parse("[grpc.auth]\ntokn = \"secret\"\n").is_err());
+        let no_token = parse("[grpc.auth]\n").unwrap().grpc.auth.unwrap();
+        assert!(no_token.token().is_err());
+        assert!(parse("port = \"many\"").is_err());
+    }
+}
@@ -0,0 +1,188 @@
+/*
+gRPC game service for remote play, see proto/game.proto
+Shares its games with the REST routes, so a game created over one can be played over the other.
+With a token set in the config, every call must carry it as "authorization: Bearer <token>".
+*/
+
+use std::pin::Pin;
+
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tonic::{Request, Response, Status};
+
+use blokus::state::legal_moves;
+
+use crate::api::SharedStore;
+
+pub mod proto {
+    tonic::include_proto!("blokus.game");
+}
+
+use proto::game_service_server::GameService;
+use proto::{CreateGameRequest, GameId, GameState, MoveRequest, PieceSet};
+
+pub use proto::game_service_server::GameServiceServer;
+
+const NUM_PLAYERS: usize = 4;
+
+pub struct GrpcGames {
+    store: SharedStore,
+}
+
+impl GrpcGames {
+    pub fn new(store: SharedStore) -> GrpcGames {
+        GrpcGames { store }
+    }
+}
+
+/// Let a call through if it carries the shared token, or always when there is no token
+pub fn check_token(token: Option<&str>, request: Request<()>) -> Result<Request<()>, Status> {
+    let Some(token) = token else {
+        return Ok(request);
+    };
+    let given = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match given {
+        Some(given) if same_token(given.as_bytes(), token.as_bytes()) => Ok(request),
+        _ => Err(Status::unauthenticated("Missing or wrong auth token")),
+    }
+}
+
+/// Compare every byte so the time taken does not give away how much of a guess was right
+fn same_token(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Snapshot of a stored game in the protocol's format
+fn game_state(store: &SharedStore, id: u64) -> Result<GameState, Status> {
+    let store = store.lock().unwrap();
+    let game = store.get(id).map_err(Status::not_found)?;
+    Ok(GameState {
+        id,
+        board: game
+            .get_board()
+            .iter()
+            .map(|t| (t & 0b1111) as u32)
+            .collect(),
+        current_player: game.current_player() as u32,
+        is_terminal: game.is_terminal(),
+        scores: game.get_score(),
+        legal_moves: legal_moves(game).into_iter().map(|m| m.notation).collect(),
+        tiles_placed: game.history.len() as u32,
+        remaining_pieces: (0..NUM_PLAYERS)
+            .map(|p| PieceSet {
+                ids: game
+                    .get_player_pieces(p)
+                    .iter()
+                    .map(|piece| piece.id as u32)
+                    .collect(),
+            })
+            .collect(),
+    })
+}
+
+#[tonic::async_trait]
+impl GameService for GrpcGames {
+    async fn create_game(
+        &self,
+        _request: Request<CreateGameRequest>,
+    ) -> Result<Response<GameState>, Status> {
+        let id = self.store.lock().unwrap().create();
+        Ok(Response::new(game_state(&self.store, id)?))
+    }
+
+    async fn get_state(&self, request: Request<GameId>) -> Result<Response<GameState>, Status> {
+        Ok(Response::new(game_state(
+            &self.store,
+            request.get_ref().id,
+        )?))
+    }
+
+    async fn submit_move(
+        &self,
+        request: Request<MoveRequest>,
+    ) -> Result<Response<GameState>, Status> {
+        let MoveRequest { id, r#move } = request.into_inner();
+        {
+            let mut store = self.store.lock().unwrap();
+            store.get(id).map_err(Status::not_found)?;
+            store
+                .play_notation(id, &r#move)
+                .map_err(Status::invalid_argument)?;
+        }
+        Ok(Response::new(game_state(&self.store, id)?))
+    }
+
+    type StreamUpdatesStream = Pin<Box<dyn Stream<Item = Result<GameState, Status>> + Send>>;
+
+    async fn stream_updates(
+        &self,
+        request: Request<GameId>,
+    ) -> Result<Response<Self::StreamUpdatesStream>, Status> {
+        let id = request.get_ref().id;
+        let mut updates = self.store.lock().unwrap().subscribe();
+        let first = game_state(&self.store, id)?;
+        let (sender, receiver) = mpsc::channel(16);
+        let store = self.store.clone();
+
+        tokio::spawn(async move {
+            let mut state = first;
+            loop {
+                let finished = state.is_terminal;
+                if sender.send(Ok(state)).await.is_err() || finished {
+                    return;
+                }
+
+                // Wait for this game to change, catching up if updates were missed
+                loop {
+                    match updates.recv().await {
+                        Ok(changed) if changed == id => break,
+                        Ok(_) => (),
+                        Err(RecvError::Lagged(_)) => break,
+                        Err(RecvError::Closed) => return,
+                    }
+                }
+                state = match game_state(&store, id) {
+                    Ok(state) => state,
+                    Err(e) => {
+                        let _ = sender.send(Err(e)).await;
+                        return;
+                    }
+                };
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver))))
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_auth(value: &str) -> Request<()> {
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", value.parse().unwrap());
+        request
+    }
+
+    #[test]
+    fn test_check_token() {
+        assert!(check_token(None, Request::new(())).is_ok());
+        assert!(check_token(Some("secret"), with_auth("Bearer secret")).is_ok());
+
+        let refused = check_token(Some("secret"), Request::new(())).unwrap_err();
+        assert_eq!(refused.code(), tonic::Code::Unauthenticated);
+        assert!(check_token(Some("secret"), with_auth("Bearer secres")).is_err());
+        assert!(check_token(Some("secret"), with_auth("Bearer secret2")).is_err());
+        assert!(check_token(Some("secret"), with_auth("secret")).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+/*
+Lobby routes for finding a room to join
+
+  GET  /lobby               Rooms that have not started and still have a free seat
+  POST /lobby/quick-match   Find the open room closest to {"rating": 1500}, or create one
+                            with the given "config" when none is close enough
+*/
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::rooms::{RoomConfig, RoomStore};
+use crate::ws::SharedRooms;
+
+/// Largest rating gap between a player and a room's average for quick-match to pair them
+const MATCH_WINDOW: u32 = 200;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RoomListing {
+    pub id: u64,
+    pub config: RoomConfig,
+    pub open_seats: Vec<usize>,
+    pub average_rating: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct QuickMatch {
+    rating: Option<u32>,
+    #[serde(default)]
+    config: RoomConfig,
+}
+
+pub fn listings(rooms: &RoomStore) -> Vec<RoomListing> {
+    rooms
+        .rooms()
+        .filter(|(_, room)| room.is_open())
+        .map(|(id, room)| {
+            let ratings = room.ratings();
+            RoomListing {
+                id,
+                config: room.config().clone(),
+                open_seats: room.open_seats().collect(),
+                average_rating: match ratings.len() {
+                    0 => None,
+                    n => Some(ratings.iter().sum::<u32>() / n as u32),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Pick the open room with the same variant whose players are closest in rating
+/// Rooms without rated players match anyone, and a new room is made if nothing is close
+pub fn quick_match(rooms: &mut RoomStore, rating: Option<u32>, config: RoomConfig) -> u64 {
+    let best = listings(rooms)
+        .into_iter()
+        .filter(|listing| listing.config.variant == config.variant)
+        .filter_map(|listing| {
+            let gap = match (rating, listing.average_rating) {
+                (Some(rating), Some(average)) => rating.abs_diff(average),
+                _ => 0,
+            };
+            (gap <= MATCH_WINDOW).then_some((gap, listing.id))
+        })
+        .min();
+    match best {
+        Some((_, id)) => id,
+        None => rooms.create(config),
+    }
+}
+
+async fn list_rooms(State(rooms): State<SharedRooms>) -> Json<Vec<RoomListing>> {
+    Json(listings(&rooms.lock().unwrap()))
+}
+
+async fn find_match(State(rooms): State<SharedRooms>, Json(request): Json<QuickMatch>) -> Response {
+    if let Err(e) = request.config.validate() {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response();
+    }
+    let id = quick_match(&mut rooms.lock().unwrap(), request.rating, request.config);
+    Json(json!({ "id": id })).into_response()
+}
+
+pub fn router(rooms: SharedRooms) -> Router {
+    Router::new()
+        .route("/lobby", get(list_rooms))
+        .route("/lobby/quick-match", post(find_match))
+        .with_state(rooms)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quick_match_by_rating() {
+        let mut rooms = RoomStore::default();
+        let first = quick_match(&mut rooms, Some(1500), RoomConfig::default());
+        rooms
+            .room_mut(first)
+            .unwrap()
+            .sit(0, None, Some(1500), None)
+            .unwrap();
+        assert_eq!(listings(&rooms)[0].open_seats, vec![1, 2, 3]);
+
+        // Close ratings share a room, distant ones get a new room
+        assert_eq!(
+            quick_match(&mut rooms, Some(1600), RoomConfig::default()),
+            first
+        );
+        let second = quick_match(&mut rooms, Some(2000), RoomConfig::default());
+        assert_ne!(second, first);
+        assert_eq!(listings(&rooms).len(), 2);
+
+        rooms.room_mut(first).unwrap().start().unwrap();
+        assert_eq!(listings(&rooms).len(), 1);
+    }
+}
@@ -0,0 +1,155 @@
+// HTTP and gRPC server for playing games against the engine without the GUI
+// Usage: blokus-server [--config path] [--port n] [--grpc-port n] [--db path], see api.rs, ws.rs, lobby.rs, and proto/game.proto
+// With --db, finished games, player ratings, and room series are kept in a SQLite database
+// The config file can also serve gRPC over TLS and require a shared token, see config.rs
+// Logs go to stderr, filtered with RUST_LOG and in JSON with BLOKUS_LOG_FORMAT=json, see blokus::logging
+use std::env;
+use std::process;
+use std::sync::{Arc, Mutex};
+
+use axum::extract::Request;
+use axum::http::header::{
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+};
+use axum::http::{HeaderValue, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use tokio::net::TcpListener;
+use tonic::transport::{Identity, Server, ServerTlsConfig};
+
+mod api;
+mod config;
+mod grpc;
+mod lobby;
+mod rooms;
+mod store;
+mod ws;
+
+use blokus::logging::{self, Format};
+use blokus::storage::Database;
+use grpc::{GameServiceServer, GrpcGames};
+use rooms::RoomStore;
+use store::GameStore;
+
+const USAGE: &str = "Usage: blokus-server [--config path] [--port n] [--grpc-port n] [--db path]";
+
+/// Get the value of an option, exiting when it is given without one
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a String> {
+    let i = args.iter().position(|a| a == name)?;
+    match args.get(i + 1) {
+        Some(value) => Some(value),
+        None => fail(USAGE),
+    }
+}
+
+/// Get a port option, or the default when it is not given
+fn port_option(args: &[String], name: &str, default: u16) -> u16 {
+    match option(args, name).map(|p| p.parse()) {
+        Some(Ok(port)) => port,
+        Some(Err(_)) => fail(USAGE),
+        None => default,
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/// Let browser front-ends served from another origin, like the GUI under trunk, use the routes
+async fn allow_cross_origin(request: Request, next: Next) -> Response {
+    let mut response = if request.method() == Method::OPTIONS {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        next.run(request).await
+    };
+    let headers = response.headers_mut();
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    headers.insert(
+        ACCESS_CONTROL_ALLOW_METHODS,
+        HeaderValue::from_static("GET, POST, OPTIONS"),
+    );
+    headers.insert(
+        ACCESS_CONTROL_ALLOW_HEADERS,
+        HeaderValue::from_static("content-type"),
+    );
+    response
+}
+
+#[tokio::main]
+async fn main() {
+    logging::init(Format::Text);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let config = match option(&args, "--config") {
+        Some(path) => config::load(path).unwrap_or_else(|e| fail(&e)),
+        None => config::Config::default(),
+    };
+    let port = port_option(&args, "--port", config.port.unwrap_or(3000));
+    let grpc_port = port_option(&args, "--grpc-port", config.grpc_port.unwrap_or(50051));
+
+    // Rooms keep their own connection for the series they add to
+    let db = option(&args, "--db").or(config.db.as_ref());
+    let open = |path| Database::open(path).unwrap_or_else(|e| fail(&e));
+    let (store, rooms) = match db {
+        Some(path) => (
+            GameStore::with_database(open(path)),
+            RoomStore::with_database(open(path)),
+        ),
+        None => (GameStore::default(), RoomStore::default()),
+    };
+    let store = Arc::new(Mutex::new(store));
+    let rooms = Arc::new(Mutex::new(rooms));
+
+    // The gRPC service shares the REST games
+    let grpc_address = format!("0.0.0.0:{}", grpc_port).parse().unwrap();
+    let mut builder = Server::builder();
+    if let Some(tls) = &config.grpc.tls {
+        let (cert, key) = tls.read().unwrap_or_else(|e| fail(&e));
+        let tls = ServerTlsConfig::new().identity(Identity::from_pem(cert, key));
+        builder = builder
+            .tls_config(tls)
+            .unwrap_or_else(|e| fail(&format!("Invalid TLS config: {}", e)));
+    }
+    let token = config
+        .grpc
+        .auth
+        .as_ref()
+        .map(|auth| auth.token().unwrap_or_else(|e| fail(&e)));
+    let games =
+        GameServiceServer::with_interceptor(GrpcGames::new(store.clone()), move |request| {
+            grpc::check_token(token.as_deref(), request)
+        });
+    let grpc = builder.add_service(games).serve(grpc_address);
+    tokio::spawn(async move {
+        if let Err(e) = grpc.await {
+            tracing::error!(error = %e, "gRPC server failed");
+            process::exit(1);
+        }
+    });
+    let secured = match (config.grpc.tls.is_some(), config.grpc.auth.is_some()) {
+        (true, true) => " with TLS and a token",
+        (true, false) => " with TLS",
+        (false, true) => " with a token",
+        (false, false) => "",
+    };
+    tracing::info!("gRPC listening on {}{}", grpc_address, secured);
+
+    tokio::spawn(ws::watch_clocks(rooms.clone()));
+    let app = api::router(store)
+        .merge(ws::router(rooms.clone()))
+        .merge(lobby::router(rooms))
+        .layer(middleware::from_fn(allow_cross_origin));
+    let address = format!("0.0.0.0:{}", port);
+    let listener = match TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to bind {}", address);
+            process::exit(1);
+        }
+    };
+    tracing::info!("listening on {}", address);
+    if let Err(e) = axum::serve(listener, app).await {
+        tracing::error!(error = %e, "server failed");
+        process::exit(1);
+    }
+}
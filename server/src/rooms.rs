@@ -0,0 +1,1146 @@
+/*
+Multiplayer rooms, each holding the authoritative game for up to four connected players
+A room plays any variant of blokus::game::Variant. Two player rooms have two seats, each
+playing both of its colors, and the others have one seat per color. Two player rooms can
+use the swap rule, where the second seat may answer the opening with the move "swap" and
+the seats trade colors, see Game::seat_of.
+Clients sit in a seat and get a token they can use to take the seat back after a
+disconnect. The game starts once every seated player is ready (or someone sends start),
+and any open seats are filled with an engine agent. Every change is broadcast to all
+clients in the room as a state message, and every move as a move message, with the
+engine's search analysis when the room has analysis turned on. Spectators get the
+same messages but cannot sit or play.
+A room can also give players a limit on how long they leave their turn untouched: they
+are warned halfway, and then either pass for good or have the engine play their seat
+until they say they are back.
+Once a game ends the players can ask for a rematch, which starts when all of them have,
+with everyone moved one seat along. The room keeps a series score over its games, and a
+server with a database adds each game to the series between the same named players.
+*/
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use blokus::agents::Difficulty;
+use blokus::cancel::CancellationToken;
+use blokus::game::{Game, Move, Variant};
+use blokus::notation::{format_move, parse_move};
+use blokus::search::SearchAnalysis;
+use blokus::state::GameState;
+use blokus::storage::Database;
+
+pub type ClientId = u64;
+
+/// Settings chosen when a room is created
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomConfig {
+    pub variant: String,           // classic, two-player, or team
+    pub time_control: Option<u64>, // Seconds per move, the engine moves for players who run out
+    pub difficulty: String,        // easy, medium, or hard engine seats
+    pub agent: Option<String>,     // Agent spec for engine seats, overrides the difficulty
+    pub analysis: bool,            // Send the engine's search analysis with its moves
+    pub afk_timeout: Option<u64>,  // Seconds a player may sit on their turn, warned halfway
+    pub afk_action: AfkAction,     // What happens to them after that
+    pub best_of: Option<u32>,      // Length of the series, no more rematches once it is decided
+    pub swap_rule: bool,           // Two player rooms only, see Game::with_swap_rule
+}
+
+impl Default for RoomConfig {
+    fn default() -> Self {
+        RoomConfig {
+            variant: "classic".to_string(),
+            time_control: None,
+            difficulty: "medium".to_string(),
+            agent: None,
+            analysis: false,
+            afk_timeout: None,
+            afk_action: AfkAction::default(),
+            best_of: None,
+            swap_rule: false,
+        }
+    }
+}
+
+/// What happens to a player who leaves their turn untouched for too long
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AfkAction {
+    Pass, // Drop out for the rest of the game
+    #[default]
+    Replace, // The engine plays the seat until they send back
+}
+
+impl RoomConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        self.new_game()?;
+        if self.time_control == Some(0) {
+            return Err("The time control must be at least one second".to_string());
+        }
+        if self.afk_timeout == Some(0) {
+            return Err("The inactivity timeout must be at least one second".to_string());
+        }
+        if self.best_of == Some(0) {
+            return Err("A series needs at least one game".to_string());
+        }
+        self.agent_spec().map(|_| ())
+    }
+
+    /// A game of the room's variant, with the swap rule when it is on
+    fn new_game(&self) -> Result<Game, String> {
+        let game = Game::new(Variant::from_name(&self.variant)?);
+        if self.swap_rule {
+            return game.with_swap_rule();
+        }
+        Ok(game)
+    }
+
+    /// Agent spec for engine seats, see blokus::agents::from_spec
+    pub fn agent_spec(&self) -> Result<String, String> {
+        if let Some(agent) = &self.agent {
+            return Ok(agent.clone());
+        }
+        Difficulty::from_name(&self.difficulty)?;
+        Ok(format!("mcts@{}", self.difficulty))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Sit {
+        seat: Option<usize>,
+        rating: Option<u32>,
+        name: Option<String>, // Needed for the series to be stored
+    },
+    Reconnect {
+        token: String,
+    },
+    Ready,
+    Start,
+    Move {
+        #[serde(rename = "move")]
+        text: String,
+    },
+    Back, // Take the seat back from the engine after being away
+    Rematch,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage<'a> {
+    Seated {
+        seat: usize,
+        token: &'a str,
+    },
+    State {
+        state: GameState,
+        seats: &'a [Seat],
+        config: &'a RoomConfig,
+        started: bool,
+        series: &'a Series,
+    },
+    Move {
+        player: usize,
+        #[serde(rename = "move")]
+        text: &'a str,
+        analysis: Option<&'a SearchAnalysis>,
+    },
+    AfkWarning {
+        seat: usize,
+        seconds_left: u64,
+    },
+    Error {
+        message: &'a str,
+    },
+}
+
+impl ServerMessage<'_> {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Seat {
+    Open,
+    Human {
+        #[serde(skip)]
+        token: String,
+        #[serde(skip)]
+        client: Option<ClientId>,
+        connected: bool,
+        ready: bool,
+        rating: Option<u32>,
+        away: bool, // Timed out on their turn, the engine plays for them
+        name: Option<String>,
+    },
+    Ai {
+        agent: String,
+    },
+}
+
+/// Wins over the room's games, by the players in the order they first sat
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Series {
+    pub players: Vec<String>, // Names, the agent spec for engine seats, or guest
+    pub wins: Vec<f64>,       // A shared win is split, partners both get their side's win
+    pub games: u32,
+}
+
+impl Series {
+    /// Whether a best of n series is over: someone has won more than half, or all n are played
+    pub fn is_decided(&self, best_of: Option<u32>) -> bool {
+        let Some(n) = best_of else {
+            return false;
+        };
+        self.games >= n || self.wins.iter().any(|w| *w > n as f64 / 2.0)
+    }
+}
+
+fn player_name(seat: &Seat) -> String {
+    match seat {
+        Seat::Human {
+            name: Some(name), ..
+        } => name.clone(),
+        Seat::Human { .. } | Seat::Open => "guest".to_string(),
+        Seat::Ai { agent } => agent.clone(),
+    }
+}
+
+pub struct Room {
+    game: Game,
+    variant: Variant,
+    seats: Vec<Seat>,
+    config: RoomConfig,
+    started: bool,
+    turn_started: Instant,
+    thinking: Option<CancellationToken>, // Cancels the engine search running for this room
+    warned: bool,                        // The player to move has been told they are idle
+    series: Series,
+    rotation: usize,        // Seats everyone has moved along since the first game
+    unsaved: Vec<Vec<f64>>, // Wins by player in games the store has yet to save
+    clients: HashMap<ClientId, UnboundedSender<String>>,
+    spectators: HashSet<ClientId>,
+}
+
+impl Room {
+    pub fn new(config: RoomConfig) -> Room {
+        let game = config
+            .new_game()
+            .unwrap_or_else(|_| Game::new(Variant::default()));
+        let variant = game.variant();
+        let seats = variant.seats();
+        Room {
+            game,
+            variant,
+            seats: vec![Seat::Open; seats],
+            config,
+            started: false,
+            turn_started: Instant::now(),
+            thinking: None,
+            warned: false,
+            series: Series::default(),
+            rotation: 0,
+            unsaved: Vec::new(),
+            clients: HashMap::new(),
+            spectators: HashSet::new(),
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn config(&self) -> &RoomConfig {
+        &self.config
+    }
+
+    /// Whether players can still join, before the game starts with a seat free
+    pub fn is_open(&self) -> bool {
+        !self.started && self.open_seats().next().is_some()
+    }
+
+    pub fn open_seats(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.seats.len()).filter(|seat| self.seats[*seat] == Seat::Open)
+    }
+
+    /// Ratings of the seated players who gave one
+    pub fn ratings(&self) -> Vec<u32> {
+        self.seats
+            .iter()
+            .filter_map(|seat| match seat {
+                Seat::Human {
+                    rating: Some(r), ..
+                } => Some(*r),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The seat playing the color to move, see Game::seat_of
+    fn seat_to_move(&self) -> usize {
+        self.game.seat_of(self.game.current_player())
+    }
+
+    fn seat_of(&self, client: ClientId) -> Option<usize> {
+        self.seats
+            .iter()
+            .position(|seat| matches!(seat, Seat::Human { client: Some(c), .. } if *c == client))
+    }
+
+    /// Send a message to one client, ignoring clients that have gone away
+    pub fn send(&self, client: ClientId, message: &ServerMessage) {
+        if let Some(sender) = self.clients.get(&client) {
+            let _ = sender.send(message.to_json());
+        }
+    }
+
+    /// Send a message to every client, players and spectators alike
+    fn broadcast(&self, message: &ServerMessage) {
+        let message = message.to_json();
+        for sender in self.clients.values() {
+            let _ = sender.send(message.clone());
+        }
+    }
+
+    /// Send the current state to every client
+    pub fn broadcast_state(&self) {
+        let time_left = self
+            .time_left(Instant::now())
+            .map(|left| left.as_millis() as u64);
+        self.broadcast(&ServerMessage::State {
+            state: GameState::new(&self.game).with_time_left(time_left),
+            seats: &self.seats,
+            config: &self.config,
+            started: self.started,
+            series: &self.series,
+        });
+    }
+
+    /// Play a move and tell every client about it
+    fn apply(&mut self, chosen: Move, analysis: Option<&SearchAnalysis>) -> Result<(), String> {
+        let text = format_move(&self.game, chosen);
+        let (piece, variant, offset) = chosen;
+        let next = self.game.place_piece(piece, variant, offset)?;
+        self.advance(next, &text, analysis);
+        Ok(())
+    }
+
+    /// Take over the opening under the swap rule
+    fn swap(&mut self) -> Result<(), String> {
+        let next = self.game.swap()?;
+        self.advance(next, "swap", None);
+        Ok(())
+    }
+
+    /// Drop the player to move out of the game
+    fn pass(&mut self) -> Result<(), String> {
+        let next = self.game.pass()?;
+        self.advance(next, "pass", None);
+        Ok(())
+    }
+
+    /// Move on to the next position, starting the next player's clock
+    fn advance(&mut self, next: Game, text: &str, analysis: Option<&SearchAnalysis>) {
+        let player = self.game.current_player();
+        self.game = next;
+        self.turn_started = Instant::now();
+        self.warned = false;
+        self.broadcast(&ServerMessage::Move {
+            player,
+            text,
+            analysis: analysis.filter(|_| self.config.analysis),
+        });
+        if self.game.is_terminal() {
+            self.finish_game();
+            self.broadcast_state();
+        }
+    }
+
+    /// Score the finished game for the series, and let everyone ask for a rematch
+    fn finish_game(&mut self) {
+        // The seat's first color stands for it, partners get their side's result
+        let payoff = self.game.get_payoff();
+        let seats = self.seats.len();
+        let mut wins = vec![0.0; seats];
+        for seat in 0..seats {
+            let won = payoff[self.game.colors_of(seat)[0]];
+            wins[(seat + seats - self.rotation % seats) % seats] = won as f64;
+        }
+        for (total, won) in self.series.wins.iter_mut().zip(&wins) {
+            *total += won;
+        }
+        self.series.games += 1;
+
+        // Guests cannot be told apart from one game to the next
+        let named = !self
+            .seats
+            .iter()
+            .any(|s| matches!(s, Seat::Human { name: None, .. }));
+        if named {
+            self.unsaved.push(wins);
+        }
+        for seat in self.seats.iter_mut() {
+            if let Seat::Human { ready, .. } = seat {
+                *ready = false;
+            }
+        }
+    }
+
+    /// Ask for another game with the same players, which starts once every seated player has
+    pub fn rematch(&mut self, client: ClientId) -> Result<(), String> {
+        if !self.started || !self.game.is_terminal() {
+            return Err("The game is not over".to_string());
+        }
+        if self.series.is_decided(self.config.best_of) {
+            return Err("The series is over".to_string());
+        }
+        let seat = self
+            .seat_of(client)
+            .ok_or("Only players can ask for a rematch")?;
+        if let Seat::Human { ready, .. } = &mut self.seats[seat] {
+            *ready = true;
+        }
+        let all_ready = self
+            .seats
+            .iter()
+            .all(|s| !matches!(s, Seat::Human { ready: false, .. }));
+        if all_ready {
+            self.next_game();
+        }
+        Ok(())
+    }
+
+    /// Start over with everyone one seat along, so colors and the order of play change
+    fn next_game(&mut self) {
+        self.seats.rotate_right(1);
+        self.rotation += 1;
+        self.game = self
+            .config
+            .new_game()
+            .unwrap_or_else(|_| Game::new(self.variant));
+        self.turn_started = Instant::now();
+        self.warned = false;
+        for (seat, s) in self.seats.iter().enumerate() {
+            if let Seat::Human {
+                token,
+                client: Some(client),
+                ..
+            } = s
+            {
+                self.send(*client, &ServerMessage::Seated { seat, token });
+            }
+        }
+    }
+
+    pub fn connect(&mut self, client: ClientId, sender: UnboundedSender<String>) {
+        self.clients.insert(client, sender);
+        self.broadcast_state();
+    }
+
+    /// Add a read-only client
+    pub fn watch(&mut self, client: ClientId, sender: UnboundedSender<String>) {
+        self.spectators.insert(client);
+        self.connect(client, sender);
+    }
+
+    /// Keep the seat for the client's token, but mark it as disconnected
+    pub fn disconnect(&mut self, client: ClientId) {
+        self.clients.remove(&client);
+        self.spectators.remove(&client);
+        if let Some(seat) = self.seat_of(client) {
+            if let Seat::Human {
+                client, connected, ..
+            } = &mut self.seats[seat]
+            {
+                *client = None;
+                *connected = false;
+            }
+        }
+        if self.clients.is_empty() {
+            self.cancel_thinking(); // Nobody is left to see the move
+        }
+        self.broadcast_state();
+    }
+
+    /// Sit in the given seat or the first open one, returns the seat and its token
+    pub fn sit(
+        &mut self,
+        client: ClientId,
+        seat: Option<usize>,
+        rating: Option<u32>,
+        name: Option<String>,
+    ) -> Result<(usize, String), String> {
+        if self.started {
+            return Err("The game has already started".to_string());
+        }
+        if self.seat_of(client).is_some() {
+            return Err("Already seated".to_string());
+        }
+        let seat = match seat {
+            Some(seat) if seat < self.seats.len() => seat,
+            Some(seat) => return Err(format!("No seat {}", seat)),
+            None => self
+                .seats
+                .iter()
+                .position(|s| *s == Seat::Open)
+                .ok_or("The room is full")?,
+        };
+        if self.seats[seat] != Seat::Open {
+            return Err(format!("Seat {} is taken", seat));
+        }
+
+        let token = format!("{:016x}", rand::thread_rng().gen::<u64>());
+        self.seats[seat] = Seat::Human {
+            token: token.clone(),
+            client: Some(client),
+            connected: true,
+            ready: false,
+            rating,
+            away: false,
+            name,
+        };
+        Ok((seat, token))
+    }
+
+    /// Take back a seat after a disconnect
+    pub fn reconnect(&mut self, client: ClientId, token: &str) -> Result<usize, String> {
+        let seat = self
+            .seats
+            .iter()
+            .position(|s| matches!(s, Seat::Human { token: t, .. } if t == token))
+            .ok_or("Unknown token")?;
+        if let Seat::Human {
+            client: c,
+            connected,
+            ..
+        } = &mut self.seats[seat]
+        {
+            *c = Some(client);
+            *connected = true;
+        }
+        Ok(seat)
+    }
+
+    /// Mark the client's seat as ready, starting the game once every seated player is
+    pub fn ready(&mut self, client: ClientId) -> Result<(), String> {
+        let seat = self
+            .seat_of(client)
+            .ok_or("Sit down before getting ready")?;
+        if let Seat::Human { ready, .. } = &mut self.seats[seat] {
+            *ready = true;
+        }
+        let all_ready = self
+            .seats
+            .iter()
+            .all(|s| !matches!(s, Seat::Human { ready: false, .. }));
+        if all_ready && !self.started {
+            self.start()?;
+        }
+        Ok(())
+    }
+
+    /// Fill open seats with the room's agent and allow moves
+    pub fn start(&mut self) -> Result<(), String> {
+        if self.started {
+            return Err("The game has already started".to_string());
+        }
+        let agent = self.config.agent_spec()?;
+        for seat in self.seats.iter_mut() {
+            if *seat == Seat::Open {
+                *seat = Seat::Ai {
+                    agent: agent.clone(),
+                };
+            }
+        }
+        self.series = Series {
+            players: self.seats.iter().map(player_name).collect(),
+            wins: vec![0.0; self.seats.len()],
+            games: 0,
+        };
+        self.started = true;
+        self.turn_started = Instant::now();
+        Ok(())
+    }
+
+    /// Play a move for the client's seat, in square notation, or "swap" to swap sides
+    pub fn play(&mut self, client: ClientId, text: &str) -> Result<(), String> {
+        if !self.started {
+            return Err("The game has not started".to_string());
+        }
+        if self.game.is_terminal() {
+            return Err("The game is over".to_string());
+        }
+        if self.seat_of(client) != Some(self.seat_to_move()) {
+            return Err("It is not your turn".to_string());
+        }
+        let chosen = match text {
+            "swap" => None,
+            _ => Some(parse_move(&self.game, text)?),
+        };
+        self.cancel_thinking();
+        self.set_away(self.seat_to_move(), false);
+        match chosen {
+            Some(chosen) => self.apply(chosen, None),
+            None => self.swap(),
+        }
+    }
+
+    /// Take the client's seat back from the engine
+    pub fn back(&mut self, client: ClientId) -> Result<(), String> {
+        let seat = self.seat_of(client).ok_or("Not seated")?;
+        self.set_away(seat, false);
+        Ok(())
+    }
+
+    fn set_away(&mut self, seat: usize, now_away: bool) {
+        if let Seat::Human { away, .. } = &mut self.seats[seat] {
+            *away = now_away;
+        }
+    }
+
+    /// Warn the player to move once half their inactivity timeout has passed, then act on it
+    /// Players the engine already moves for, because they are away or out of time, are left alone.
+    pub fn check_inactive(&mut self, now: Instant) {
+        let Some(seconds) = self.config.afk_timeout else {
+            return;
+        };
+        if !self.started || self.thinking.is_some() || self.game.is_terminal() {
+            return;
+        }
+        let seat = self.seat_to_move();
+        if !matches!(self.seats[seat], Seat::Human { away: false, .. }) {
+            return;
+        }
+
+        let timeout = Duration::from_secs(seconds);
+        let idle = now.saturating_duration_since(self.turn_started);
+        if idle >= timeout {
+            tracing::info!(seat, action = ?self.config.afk_action, "player timed out");
+            match self.config.afk_action {
+                AfkAction::Pass => {
+                    let _ = self.pass();
+                }
+                AfkAction::Replace => self.set_away(seat, true),
+            }
+            self.broadcast_state();
+        } else if idle >= timeout / 2 && !self.warned {
+            self.warned = true;
+            self.broadcast(&ServerMessage::AfkWarning {
+                seat,
+                seconds_left: (timeout - idle).as_secs(),
+            });
+        }
+    }
+
+    /// The agent spec to run when an engine seat is to move, or a player has run out of time
+    pub fn ai_turn(&self, now: Instant) -> Option<String> {
+        if !self.started || self.thinking.is_some() || self.game.is_terminal() {
+            return None;
+        }
+        let out_of_time = self
+            .config
+            .time_control
+            .is_some_and(|seconds| now >= self.turn_started + Duration::from_secs(seconds));
+        match &self.seats[self.seat_to_move()] {
+            Seat::Ai { agent } => Some(agent.clone()),
+            Seat::Human { away: true, .. } => self.config.agent_spec().ok(),
+            Seat::Human { .. } if out_of_time => self.config.agent_spec().ok(),
+            _ => None,
+        }
+    }
+
+    /// How long the player to move has left, None for untimed games or when no one is moving
+    pub fn time_left(&self, now: Instant) -> Option<Duration> {
+        if !self.started || self.game.is_terminal() {
+            return None;
+        }
+        let seconds = self.config.time_control?;
+        Some((self.turn_started + Duration::from_secs(seconds)).saturating_duration_since(now))
+    }
+
+    /// Keep other callers from starting a second search while one runs
+    /// The token is cancelled if a player moves first, since the search is then wasted
+    pub fn start_thinking(&mut self) -> CancellationToken {
+        let cancel = CancellationToken::new();
+        self.thinking = Some(cancel.clone());
+        cancel
+    }
+
+    pub fn stop_thinking(&mut self) {
+        self.thinking = None;
+    }
+
+    /// Stop the running search early, its move will not be played
+    fn cancel_thinking(&mut self) {
+        if let Some(cancel) = &self.thinking {
+            cancel.cancel();
+        }
+    }
+
+    /// Play an engine move searched on a copy of the game, unless the game has moved on since
+    /// None swaps sides, when the engine would rather take over the opening
+    pub fn play_ai(
+        &mut self,
+        from: &Game,
+        chosen: Option<Move>,
+        analysis: Option<&SearchAnalysis>,
+    ) -> Result<(), String> {
+        if self.game.history != from.history {
+            return Err("The game changed while the engine was thinking".to_string());
+        }
+        match chosen {
+            Some(chosen) => self.apply(chosen, analysis),
+            None => self.swap(),
+        }
+    }
+
+    /// Apply a message from a client, replying to it directly where needed
+    pub fn handle(&mut self, client: ClientId, message: ClientMessage) -> Result<(), String> {
+        if self.spectators.contains(&client) {
+            return Err("Spectators cannot send commands".to_string());
+        }
+        match message {
+            ClientMessage::Sit { seat, rating, name } => {
+                let (seat, token) = self.sit(client, seat, rating, name)?;
+                self.send(
+                    client,
+                    &ServerMessage::Seated {
+                        seat,
+                        token: &token,
+                    },
+                );
+            }
+            ClientMessage::Reconnect { token } => {
+                let seat = self.reconnect(client, &token)?;
+                self.send(
+                    client,
+                    &ServerMessage::Seated {
+                        seat,
+                        token: &token,
+                    },
+                );
+            }
+            ClientMessage::Ready => self.ready(client)?,
+            ClientMessage::Start => self.start()?,
+            ClientMessage::Move { text } => self.play(client, &text)?,
+            ClientMessage::Back => self.back(client)?,
+            ClientMessage::Rematch => self.rematch(client)?,
+        }
+        self.broadcast_state();
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct RoomStore {
+    next_room: u64,
+    next_client: ClientId,
+    rooms: BTreeMap<u64, Room>,
+    database: Option<Database>,
+}
+
+impl RoomStore {
+    /// A store that adds the rooms' games to the series in a database
+    pub fn with_database(database: Database) -> RoomStore {
+        RoomStore {
+            database: Some(database),
+            ..RoomStore::default()
+        }
+    }
+
+    /// Add every game finished since the last call to its players' series
+    pub fn save_series(&mut self) {
+        let Some(database) = &mut self.database else {
+            return;
+        };
+        for (id, room) in self.rooms.iter_mut() {
+            for wins in room.unsaved.drain(..) {
+                if let Err(e) = database.record_series_game(&room.series.players, &wins) {
+                    tracing::error!(room = id, error = %e, "failed to store the series");
+                }
+            }
+        }
+    }
+
+    pub fn create(&mut self, config: RoomConfig) -> u64 {
+        let id = self.next_room;
+        self.next_room += 1;
+        self.rooms.insert(id, Room::new(config));
+        id
+    }
+
+    pub fn rooms(&self) -> impl Iterator<Item = (u64, &Room)> {
+        self.rooms.iter().map(|(id, room)| (*id, room))
+    }
+
+    /// Warn or act on idle players in every room, see Room::check_inactive
+    pub fn check_inactive(&mut self, now: Instant) {
+        for room in self.rooms.values_mut() {
+            room.check_inactive(now);
+        }
+    }
+
+    /// Ids of rooms with an engine seat to move
+    pub fn waiting_for_ai(&self, now: Instant) -> Vec<u64> {
+        self.rooms
+            .iter()
+            .filter(|(_, room)| room.ai_turn(now).is_some())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    pub fn room_mut(&mut self, id: u64) -> Result<&mut Room, String> {
+        self.rooms
+            .get_mut(&id)
+            .ok_or(format!("No room with id {}", id))
+    }
+
+    /// Add a client to a room as a player or a spectator, returning its id
+    pub fn connect(
+        &mut self,
+        id: u64,
+        sender: UnboundedSender<String>,
+        spectator: bool,
+    ) -> Result<ClientId, String> {
+        let client = self.next_client;
+        let room = self.room_mut(id)?;
+        if spectator {
+            room.watch(client, sender);
+        } else {
+            room.connect(client, sender);
+        }
+        self.next_client += 1;
+        Ok(client)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blokus::game::PASS;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    fn random_config() -> RoomConfig {
+        RoomConfig {
+            agent: Some("random".to_string()),
+            ..RoomConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_room_flow() {
+        let mut store = RoomStore::default();
+        let id = store.create(random_config());
+        let (sender, mut received) = unbounded_channel();
+        let client = store.connect(id, sender, false).unwrap();
+        let room = store.room_mut(id).unwrap();
+        assert!(received.try_recv().unwrap().contains("\"type\":\"state\""));
+
+        let (seat, token) = room.sit(client, Some(0), Some(1500), None).unwrap();
+        assert_eq!(seat, 0);
+        assert!(room.sit(client + 1, Some(0), None, None).is_err());
+        assert!(room.play(client, "a1").is_err()); // Not started
+
+        // Everyone seated is ready, so the game starts
+        room.ready(client).unwrap();
+        assert_eq!(
+            room.seats[1],
+            Seat::Ai {
+                agent: "random".to_string()
+            }
+        );
+        assert!(!room.is_open());
+        room.play(client, "a1").unwrap();
+        assert_eq!(room.ai_turn(Instant::now()), Some("random".to_string()));
+        assert!(room.play(client, "b2").is_err()); // Blue's turn
+
+        // The seat is kept through a disconnect
+        room.disconnect(client);
+        assert!(matches!(
+            room.seats[0],
+            Seat::Human {
+                connected: false,
+                ..
+            }
+        ));
+        assert!(room.reconnect(client + 1, "wrong").is_err());
+        assert_eq!(room.reconnect(client + 1, &token).unwrap(), 0);
+        assert_eq!(room.ratings(), vec![1500]);
+    }
+
+    #[test]
+    fn test_spectators_see_moves() {
+        let mut store = RoomStore::default();
+        let id = store.create(RoomConfig {
+            analysis: true,
+            ..random_config()
+        });
+        let (sender, mut received) = unbounded_channel();
+        let spectator = store.connect(id, sender, true).unwrap();
+        let room = store.room_mut(id).unwrap();
+        assert!(room.handle(spectator, ClientMessage::Start).is_err());
+
+        room.start().unwrap();
+        let game = room.game().clone();
+        let chosen = game.find_move(&[0]).unwrap();
+        let analysis = SearchAnalysis {
+            to_play: 0,
+            visits: 1,
+            principal_variation: vec![0],
+            children: Vec::new(),
+        };
+        room.play_ai(&game, Some(chosen), Some(&analysis)).unwrap();
+
+        let messages: Vec<String> = std::iter::from_fn(|| received.try_recv().ok()).collect();
+        let last = messages.last().unwrap();
+        assert!(last.contains(r#""type":"move","player":0,"move":"a1""#));
+        assert!(last.contains(r#""principal_variation":[0]"#));
+    }
+
+    #[test]
+    fn test_time_control() {
+        let mut room = Room::new(RoomConfig {
+            time_control: Some(5),
+            ..random_config()
+        });
+        room.sit(0, Some(0), None, None).unwrap();
+        room.sit(1, Some(1), None, None).unwrap();
+        room.ready(0).unwrap();
+        assert!(room.ai_turn(Instant::now()).is_none()); // Still waiting for player 1
+        assert!(room.time_left(Instant::now()).is_none());
+        room.ready(1).unwrap();
+
+        let later = Instant::now() + Duration::from_secs(6);
+        assert!(room.time_left(Instant::now()).unwrap() <= Duration::from_secs(5));
+        assert_eq!(room.time_left(later), Some(Duration::ZERO));
+        assert!(room.ai_turn(Instant::now()).is_none());
+        assert_eq!(room.ai_turn(later), Some("random".to_string()));
+        let cancel = room.start_thinking();
+        assert!(room.ai_turn(later).is_none());
+
+        // Player 0 moving first cancels the search made for them
+        let legal = *room.game().get_legal_moves().iter().next().unwrap();
+        let text = format_move(room.game(), legal);
+        room.play(0, &text).unwrap();
+        assert!(cancel.is_cancelled());
+        room.stop_thinking();
+
+        assert!(RoomConfig {
+            variant: "duo".to_string(),
+            ..RoomConfig::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_variants() {
+        let mut room = Room::new(RoomConfig {
+            variant: "two-player".to_string(),
+            ..random_config()
+        });
+        assert_eq!(room.open_seats().collect::<Vec<_>>(), vec![0, 1]);
+        assert!(room.sit(0, Some(2), None, None).is_err());
+        room.sit(0, Some(0), None, None).unwrap();
+        room.ready(0).unwrap();
+
+        // The seat plays red and yellow, the engine blue and green
+        let play_first = |room: &mut Room| {
+            let legal = *room.game().get_legal_moves().iter().next().unwrap();
+            let text = format_move(room.game(), legal);
+            room.play(0, &text)
+        };
+        play_first(&mut room).unwrap();
+        assert_eq!(room.ai_turn(Instant::now()), Some("random".to_string()));
+        assert!(play_first(&mut room).is_err());
+        room.pass().unwrap();
+        assert_eq!(room.game().current_player(), 2);
+        assert!(room.ai_turn(Instant::now()).is_none());
+        play_first(&mut room).unwrap();
+
+        let team = RoomConfig {
+            variant: "team".to_string(),
+            ..RoomConfig::default()
+        };
+        assert_eq!(team.validate(), Ok(()));
+        assert_eq!(Room::new(team).game().variant(), Variant::Team);
+
+        // Junior seats play red and yellow, blue and green are never asked to move
+        let mut junior = Room::new(RoomConfig {
+            variant: "junior".to_string(),
+            ..random_config()
+        });
+        assert_eq!(junior.open_seats().collect::<Vec<_>>(), vec![0, 1]);
+        junior.sit(0, Some(1), None, None).unwrap();
+        junior.ready(0).unwrap();
+        assert_eq!(junior.ai_turn(Instant::now()), Some("random".to_string()));
+        junior.pass().unwrap();
+        assert_eq!(junior.game().current_player(), 2);
+        assert!(junior.ai_turn(Instant::now()).is_none());
+        play_first(&mut junior).unwrap();
+    }
+
+    #[test]
+    fn test_swap_rule() {
+        let mut room = Room::new(RoomConfig {
+            variant: "two-player".to_string(),
+            swap_rule: true,
+            ..random_config()
+        });
+        room.sit(0, Some(0), None, None).unwrap();
+        room.sit(1, Some(1), None, None).unwrap();
+        room.ready(0).unwrap();
+        room.ready(1).unwrap();
+        let play_first = |room: &mut Room, client: ClientId| {
+            let legal = *room.game().get_legal_moves().iter().next().unwrap();
+            let text = format_move(room.game(), legal);
+            room.play(client, &text)
+        };
+
+        // The second seat takes over red's opening, and the opener moves on with blue
+        play_first(&mut room, 0).unwrap();
+        assert!(room.play(0, "swap").is_err());
+        room.play(1, "swap").unwrap();
+        assert_eq!(room.game().current_player(), 1);
+        assert!(play_first(&mut room, 1).is_err());
+        play_first(&mut room, 0).unwrap();
+        assert!(room.play(1, "swap").is_err());
+        assert!(play_first(&mut room, 0).is_err());
+        play_first(&mut room, 1).unwrap();
+
+        assert!(RoomConfig {
+            swap_rule: true,
+            ..RoomConfig::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_inactive_players() {
+        let mut room = Room::new(RoomConfig {
+            afk_timeout: Some(10),
+            ..random_config()
+        });
+        let (sender, mut received) = unbounded_channel();
+        room.connect(0, sender);
+        room.sit(0, Some(0), None, None).unwrap();
+        room.ready(0).unwrap();
+        let start = Instant::now();
+
+        // Warned once halfway, then the engine takes over until they are back
+        room.check_inactive(start + Duration::from_secs(6));
+        room.check_inactive(start + Duration::from_secs(7));
+        let messages: Vec<String> = std::iter::from_fn(|| received.try_recv().ok()).collect();
+        let warnings = messages.iter().filter(|m| m.contains("afk_warning"));
+        assert_eq!(warnings.count(), 1);
+        assert!(room.ai_turn(start + Duration::from_secs(7)).is_none());
+
+        room.check_inactive(start + Duration::from_secs(11));
+        assert!(matches!(room.seats[0], Seat::Human { away: true, .. }));
+        assert_eq!(room.ai_turn(start), Some("random".to_string()));
+        room.handle(0, ClientMessage::Back).unwrap();
+        assert!(room.ai_turn(start).is_none());
+
+        // Or they drop out of the game
+        let mut room = Room::new(RoomConfig {
+            afk_timeout: Some(10),
+            afk_action: AfkAction::Pass,
+            ..random_config()
+        });
+        room.sit(0, Some(0), None, None).unwrap();
+        room.ready(0).unwrap();
+        room.check_inactive(Instant::now() + Duration::from_secs(11));
+        assert_eq!(room.game().history, vec![(0, PASS)]);
+        assert!(!room.game().is_player_active(0));
+    }
+
+    #[test]
+    fn test_rematch() {
+        let mut store = RoomStore::with_database(Database::in_memory().unwrap());
+        let id = store.create(RoomConfig {
+            best_of: Some(3),
+            ..random_config()
+        });
+        let (sender, mut received) = unbounded_channel();
+        let room = store.room_mut(id).unwrap();
+        room.connect(0, sender);
+        room.sit(0, Some(0), None, Some("ann".to_string())).unwrap();
+        assert!(room.rematch(0).is_err());
+        room.ready(0).unwrap();
+
+        // Ann places one piece and everyone else passes
+        let win = |room: &mut Room| {
+            let mut played = false;
+            while !room.game.is_terminal() {
+                if !played && room.seat_of(0) == Some(room.seat_to_move()) {
+                    let legal = *room.game.get_legal_moves().iter().next().unwrap();
+                    room.play(0, &format_move(&room.game, legal)).unwrap();
+                    played = true;
+                } else {
+                    room.pass().unwrap();
+                }
+            }
+        };
+        win(room);
+        assert_eq!(room.series.wins, vec![1.0, 0.0, 0.0, 0.0]);
+
+        // Everyone moves one seat along, and the series carries on
+        let _ = std::iter::from_fn(|| received.try_recv().ok()).count();
+        room.handle(0, ClientMessage::Rematch).unwrap();
+        assert_eq!(room.seat_of(0), Some(1));
+        assert!(received.try_recv().unwrap().contains(r#""seat":1"#));
+        assert!(room.game.history.is_empty());
+        win(room);
+        assert_eq!(room.series.wins, vec![2.0, 0.0, 0.0, 0.0]);
+        assert_eq!(room.series.games, 2);
+
+        // Two wins of three decide it
+        assert!(room.series.is_decided(Some(3)));
+        assert!(room.rematch(0).is_err());
+
+        store.save_series();
+        let players = ["ann", "random", "random", "random"].map(String::from);
+        let database = store.database.as_ref().unwrap();
+        let saved = database.series(&players).unwrap().unwrap();
+        assert_eq!(saved.games, 2);
+        assert_eq!(saved.wins, vec![2.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_messages() {
+        let message: ClientMessage =
+            serde_json::from_str(r#"{"type": "move", "move": "a1-a2"}"#).unwrap();
+        assert!(matches!(message, ClientMessage::Move { text } if text == "a1-a2"));
+
+        let seats = vec![Seat::Human {
+            token: "secret".to_string(),
+            client: Some(0),
+            connected: true,
+            ready: false,
+            rating: None,
+            away: false,
+            name: None,
+        }];
+        let json = ServerMessage::State {
+            state: GameState::new(&Game::reset()),
+            seats: &seats,
+            config: &RoomConfig::default(),
+            started: false,
+            series: &Series::default(),
+        }
+        .to_json();
+        assert!(json.contains(
+            r#"{"kind":"human","connected":true,"ready":false,"rating":null,"away":false,"name":null}"#
+        ));
+        assert!(!json.contains("secret"));
+    }
+}
@@ -0,0 +1,251 @@
+/*
+Games held by the server, shared between request handlers behind a mutex
+The id of every game that changes is sent on a broadcast channel for streaming clients.
+With a database, finished games are stored along with who played each seat: the agent
+spec for seats the engine moved in, and guest for the rest.
+*/
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use blokus::game::{Game, Move};
+use blokus::notation::{format_move, parse_move};
+use blokus::records::PlayedGame;
+use blokus::state::GameState;
+use blokus::storage::Database;
+
+const GUEST: &str = "guest";
+
+/// Short description of a game for listings
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GameSummary {
+    pub id: u64,
+    pub is_terminal: bool,
+    pub current_player: usize,
+    pub scores: Vec<i32>,
+    pub moves_played: usize,
+}
+
+pub struct GameStore {
+    next_id: u64,
+    games: BTreeMap<u64, Game>,
+    played: BTreeMap<u64, PlayedGame>,
+    updates: broadcast::Sender<u64>,
+    database: Option<Database>,
+}
+
+impl Default for GameStore {
+    fn default() -> Self {
+        GameStore {
+            next_id: 0,
+            games: BTreeMap::new(),
+            played: BTreeMap::new(),
+            updates: broadcast::channel(64).0,
+            database: None,
+        }
+    }
+}
+
+impl GameStore {
+    /// A store that saves finished games to a database
+    pub fn with_database(database: Database) -> GameStore {
+        GameStore {
+            database: Some(database),
+            ..GameStore::default()
+        }
+    }
+
+    pub fn database(&self) -> Option<&Database> {
+        self.database.as_ref()
+    }
+
+    /// Get the ids of games as they change
+    pub fn subscribe(&self) -> broadcast::Receiver<u64> {
+        self.updates.subscribe()
+    }
+
+    pub fn create(&mut self) -> u64 {
+        self.create_from(Game::reset())
+    }
+
+    /// Create a game starting from a fresh game of some variant, with its rules
+    pub fn create_from(&mut self, start: Game) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.games.insert(id, start);
+        self.played
+            .insert(id, PlayedGame::new(vec![GUEST.to_string(); 4]));
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Result<&Game, String> {
+        self.games.get(&id).ok_or(format!("No game with id {}", id))
+    }
+
+    pub fn state(&self, id: u64) -> Result<GameState, String> {
+        self.get(id).map(GameState::new)
+    }
+
+    /// Play a whole piece for the player to move
+    pub fn play(&mut self, id: u64, chosen: Move) -> Result<GameState, String> {
+        let game = self
+            .games
+            .get(&id)
+            .ok_or(format!("No game with id {}", id))?;
+        if game.is_terminal() {
+            return Err("The game is over".to_string());
+        }
+        let (piece, variant, offset) = chosen;
+        let next = game.place_piece(piece, variant, offset)?;
+        let played = self.played.get_mut(&id).unwrap();
+        played.push(game, chosen);
+        if next.is_terminal() {
+            played.finish(&next);
+            if let Some(database) = &mut self.database {
+                // The move stands even if it cannot be saved
+                if let Err(e) = database.record_game("server", played) {
+                    tracing::error!(game = id, error = %e, "failed to store the game");
+                }
+            }
+        }
+
+        let state = GameState::new(&next);
+        self.games.insert(id, next);
+        let _ = self.updates.send(id);
+        Ok(state)
+    }
+
+    /// Swap sides under the swap rule, the player to move takes over the opening
+    pub fn swap(&mut self, id: u64) -> Result<GameState, String> {
+        let next = self.get(id)?.swap()?;
+        self.played.get_mut(&id).unwrap().swap();
+        let state = GameState::new(&next);
+        self.games.insert(id, next);
+        let _ = self.updates.send(id);
+        Ok(state)
+    }
+
+    /// Play a move given in square notation like "a1-a2-b2", or "swap" to swap sides
+    pub fn play_notation(&mut self, id: u64, text: &str) -> Result<GameState, String> {
+        if text == "swap" {
+            return self.swap(id);
+        }
+        let chosen = parse_move(self.get(id)?, text)?;
+        self.play(id, chosen)
+    }
+
+    /// Play a move the agent chose from a copy of the game, as long as nobody has moved since
+    /// The agent takes over the seat in the stored game, returns the move in square notation
+    pub fn play_if_unchanged(
+        &mut self,
+        id: u64,
+        from: &Game,
+        chosen: Move,
+        agent: &str,
+    ) -> Result<(String, GameState), String> {
+        if self.get(id)?.history != from.history {
+            return Err("The game changed while the engine was thinking".to_string());
+        }
+        let text = format_move(from, chosen);
+        self.played.get_mut(&id).unwrap().seats[from.current_player()] = agent.to_string();
+        Ok((text, self.play(id, chosen)?))
+    }
+
+    /// Swap sides for the agent, as long as nobody has moved since it decided to
+    pub fn swap_if_unchanged(
+        &mut self,
+        id: u64,
+        from: &Game,
+    ) -> Result<(String, GameState), String> {
+        if self.get(id)?.history != from.history {
+            return Err("The game changed while the engine was thinking".to_string());
+        }
+        Ok(("swap".to_string(), self.swap(id)?))
+    }
+
+    pub fn summaries(&self, finished_only: bool) -> Vec<GameSummary> {
+        self.games
+            .iter()
+            .filter(|(_, game)| !finished_only || game.is_terminal())
+            .map(|(id, game)| GameSummary {
+                id: *id,
+                is_terminal: game.is_terminal(),
+                current_player: game.current_player(),
+                scores: game.get_score(),
+                moves_played: game.history.len(),
+            })
+            .collect()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blokus::game::Variant;
+
+    #[test]
+    fn test_store_moves() {
+        let mut store = GameStore::default();
+        let mut updates = store.subscribe();
+        let id = store.create();
+        assert_eq!(store.create(), id + 1);
+        assert!(store.get(id + 2).is_err());
+
+        let state = store.play_notation(id, "a1").unwrap();
+        assert_eq!(state.current_player, 1);
+        assert!(store.play_notation(id, "a1").is_err());
+
+        // A move searched before another player moved is rejected
+        let stale = Game::reset();
+        let first = *stale.get_legal_moves().iter().next().unwrap();
+        assert!(store
+            .play_if_unchanged(id, &stale, first, "random")
+            .is_err());
+
+        assert_eq!(updates.try_recv().unwrap(), id);
+        assert_eq!(store.summaries(false).len(), 2);
+        assert!(store.summaries(true).is_empty());
+    }
+
+    #[test]
+    fn test_swap() {
+        let mut store = GameStore::default();
+        let classic = store.create();
+        let start = Game::new(Variant::TwoPlayer).with_swap_rule().unwrap();
+        let id = store.create_from(start);
+        assert!(store.play_notation(id, "swap").is_err());
+
+        store.play_notation(classic, "a1").unwrap();
+        assert!(store.play_notation(classic, "swap").is_err());
+        let state = store.play_notation(id, "a1").unwrap();
+        assert!(state.can_swap);
+        let state = store.play_notation(id, "swap").unwrap();
+        assert!(!state.can_swap);
+        assert_eq!(state.current_player, 1);
+        assert!(store.get(id).unwrap().is_swapped());
+        assert!(store.played[&id].record.swapped);
+    }
+
+    #[test]
+    fn test_finished_games_are_stored() {
+        let mut store = GameStore::with_database(Database::in_memory().unwrap());
+        let id = store.create();
+        while !store.get(id).unwrap().is_terminal() {
+            let game = store.get(id).unwrap().clone();
+            let mut moves: Vec<Move> = game.get_legal_moves().into_iter().collect();
+            moves.sort();
+            store
+                .play_if_unchanged(id, &game, moves[0], "greedy")
+                .unwrap();
+        }
+
+        let database = store.database().unwrap();
+        let stored = database.games_for("greedy", 10).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].scores, store.get(id).unwrap().get_score());
+        assert_eq!(database.player("greedy").unwrap().unwrap().games, 1);
+    }
+}
@@ -0,0 +1,184 @@
+/*
+WebSocket routes for multiplayer rooms, see rooms.rs for the messages
+
+  POST /rooms            Create a room with a RoomConfig, like {"difficulty": "hard", "time_control": 30}
+  GET  /rooms/{id}/ws    Join a room as a WebSocket client
+  GET  /rooms/{id}/watch Follow a room as a read-only spectator
+*/
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::{task, time};
+
+use blokus::agents::{from_spec, should_swap};
+use blokus::evaluator::HeuristicEvaluator;
+
+use crate::rooms::{ClientId, ClientMessage, RoomConfig, RoomStore, ServerMessage};
+
+pub type SharedRooms = Arc<Mutex<RoomStore>>;
+
+async fn create_room(State(rooms): State<SharedRooms>, Json(config): Json<RoomConfig>) -> Response {
+    if let Err(e) = config.validate() {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response();
+    }
+    let id = rooms.lock().unwrap().create(config);
+    Json(json!({ "id": id })).into_response()
+}
+
+async fn join_room(
+    ws: WebSocketUpgrade,
+    Path(id): Path<u64>,
+    State(rooms): State<SharedRooms>,
+) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, id, rooms, false))
+}
+
+async fn watch_room(
+    ws: WebSocketUpgrade,
+    Path(id): Path<u64>,
+    State(rooms): State<SharedRooms>,
+) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, id, rooms, true))
+}
+
+/// Play engine moves while an engine seat is to move, or a player is out of time
+/// Searches run on a blocking thread without holding the lock
+#[tracing::instrument(name = "room", skip(rooms))]
+pub async fn run_ai(rooms: &SharedRooms, id: u64) {
+    loop {
+        let (game, spec, cancel) = {
+            let mut rooms = rooms.lock().unwrap();
+            let Ok(room) = rooms.room_mut(id) else { return };
+            match room.ai_turn(Instant::now()) {
+                Some(spec) => {
+                    let cancel = room.start_thinking();
+                    (room.game().clone(), spec, cancel)
+                }
+                None => return,
+            }
+        };
+
+        let searched = game.clone();
+        let span = tracing::Span::current();
+        let chosen = task::spawn_blocking(move || {
+            let _room = span.enter();
+            let mut agent = from_spec(&spec, None)?;
+            agent.set_cancellation(cancel);
+            // None when the engine takes over the opening under the swap rule instead of moving
+            if should_swap(&searched, &mut HeuristicEvaluator::default())? {
+                return Ok((None, None));
+            }
+            let chosen = agent.choose_move(&searched)?;
+            Ok::<_, String>((Some(chosen), agent.analysis()))
+        })
+        .await;
+
+        let mut rooms = rooms.lock().unwrap();
+        let Ok(room) = rooms.room_mut(id) else { return };
+        room.stop_thinking();
+        match chosen {
+            Ok(Ok((chosen, analysis))) => {
+                if room.play_ai(&game, chosen, analysis.as_ref()).is_err() {
+                    continue; // Someone else already moved, look again
+                }
+                tracing::debug!(player = game.current_player(), ?chosen, "AI moved");
+                room.broadcast_state();
+            }
+            Ok(Err(e)) => {
+                tracing::warn!(error = %e, "the AI could not move");
+                return;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "the AI's search panicked");
+                return;
+            }
+        }
+    }
+}
+
+fn handle_text(rooms: &SharedRooms, id: u64, client: ClientId, text: &str) {
+    let mut rooms = rooms.lock().unwrap();
+    let Ok(room) = rooms.room_mut(id) else { return };
+    let result = serde_json::from_str::<ClientMessage>(text)
+        .map_err(|e| format!("Invalid message: {}", e))
+        .and_then(|message| room.handle(client, message));
+    if let Err(e) = result {
+        room.send(client, &ServerMessage::Error { message: &e });
+    }
+}
+
+async fn handle_socket(socket: WebSocket, id: u64, rooms: SharedRooms, spectator: bool) {
+    let (mut sink, mut stream) = socket.split();
+    let (sender, mut outgoing) = unbounded_channel::<String>();
+    let connected = rooms.lock().unwrap().connect(id, sender, spectator);
+    let client = match connected {
+        Ok(client) => client,
+        Err(e) => {
+            let message = ServerMessage::Error { message: &e }.to_json();
+            let _ = sink.send(Message::Text(message.into())).await;
+            return;
+        }
+    };
+
+    // Forward broadcasts to this client's socket
+    let writer = tokio::spawn(async move {
+        while let Some(text) = outgoing.recv().await {
+            if sink.send(Message::Text(text.into())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(message)) = stream.next().await {
+        match message {
+            Message::Text(text) => {
+                handle_text(&rooms, id, client, text.as_str());
+                run_ai(&rooms, id).await;
+            }
+            Message::Close(_) => break,
+            _ => (),
+        }
+    }
+
+    if let Ok(room) = rooms.lock().unwrap().room_mut(id) {
+        room.disconnect(client);
+    }
+    writer.abort();
+}
+
+/// Check every room's clock, warning idle players and moving for those out of time or away
+/// Finished games are added to their series in the database here too, off the socket tasks.
+pub async fn watch_clocks(rooms: SharedRooms) {
+    let mut interval = time::interval(Duration::from_millis(500));
+    loop {
+        interval.tick().await;
+        let waiting = {
+            let mut rooms = rooms.lock().unwrap();
+            rooms.check_inactive(Instant::now());
+            rooms.save_series();
+            rooms.waiting_for_ai(Instant::now())
+        };
+        for id in waiting {
+            let rooms = rooms.clone();
+            tokio::spawn(async move { run_ai(&rooms, id).await });
+        }
+    }
+}
+
+pub fn router(rooms: SharedRooms) -> Router {
+    Router::new()
+        .route("/rooms", post(create_room))
+        .route("/rooms/{id}/ws", get(join_room))
+        .route("/rooms/{id}/watch", get(watch_room))
+        .with_state(rooms)
+}
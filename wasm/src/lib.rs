@@ -0,0 +1,138 @@
+// WebAssembly bindings for the rules engine, for JavaScript front-ends other than the Yew GUI
+// Structured values cross the boundary as JSON strings, see blokus::state for their layout
+use wasm_bindgen::prelude::*;
+
+use blokus::agents::from_spec;
+use blokus::evaluator::HeuristicEvaluator;
+use blokus::game::Game;
+use blokus::hints::hints;
+use blokus::notation::{format_move, parse_move};
+use blokus::state::{legal_moves, GameState};
+
+/// A game in progress, every move can be taken back with undo
+#[wasm_bindgen(js_name = Game)]
+pub struct WasmGame {
+    game: Game,
+    previous: Vec<Game>, // Positions before each move
+}
+
+impl WasmGame {
+    fn push(&mut self, next: Game) {
+        self.previous.push(std::mem::replace(&mut self.game, next));
+    }
+}
+
+impl Default for WasmGame {
+    fn default() -> Self {
+        WasmGame::new()
+    }
+}
+
+#[wasm_bindgen(js_class = Game)]
+impl WasmGame {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmGame {
+        WasmGame {
+            game: Game::reset(),
+            previous: Vec::new(),
+        }
+    }
+
+    #[wasm_bindgen(js_name = currentPlayer)]
+    pub fn current_player(&self) -> usize {
+        self.game.current_player()
+    }
+
+    #[wasm_bindgen(js_name = isTerminal)]
+    pub fn is_terminal(&self) -> bool {
+        self.game.is_terminal()
+    }
+
+    /// Tiles the current player can place next, sorted
+    #[wasm_bindgen(js_name = legalTiles)]
+    pub fn legal_tiles(&self) -> Vec<u32> {
+        let mut tiles: Vec<u32> = self
+            .game
+            .get_legal_tiles()
+            .iter()
+            .map(|t| *t as u32)
+            .collect();
+        tiles.sort();
+        tiles
+    }
+
+    /// JSON array of whole piece placements with the tiles they cover
+    #[wasm_bindgen(js_name = legalMoves)]
+    pub fn legal_moves(&self) -> String {
+        serde_json::to_string(&legal_moves(&self.game)).unwrap()
+    }
+
+    /// Place one tile, optionally finishing the turn with the given piece
+    pub fn apply(&mut self, tile: usize, piece: Option<usize>) -> Result<(), JsError> {
+        if !self.game.get_legal_tiles().contains(&tile) {
+            return Err(JsError::new(&format!("Tile {} is not legal", tile)));
+        }
+        let mut next = self.game.clone();
+        next.apply(tile, piece).map_err(|e| JsError::new(&e))?;
+        self.push(next);
+        Ok(())
+    }
+
+    /// Place a whole piece, as given by legalMoves
+    #[wasm_bindgen(js_name = placePiece)]
+    pub fn place_piece(
+        &mut self,
+        piece: usize,
+        variant: usize,
+        offset: usize,
+    ) -> Result<(), JsError> {
+        let next = self
+            .game
+            .place_piece(piece, variant, offset)
+            .map_err(|e| JsError::new(&e))?;
+        self.push(next);
+        Ok(())
+    }
+
+    /// Play a move in square notation like "a1-a2-b2"
+    #[wasm_bindgen(js_name = playMove)]
+    pub fn play_move(&mut self, text: &str) -> Result<(), JsError> {
+        let (piece, variant, offset) =
+            parse_move(&self.game, text).map_err(|e| JsError::new(&e))?;
+        self.place_piece(piece, variant, offset)
+    }
+
+    /// Let an engine agent pick a move, like "blocking" or "mcts:100", returned in square notation
+    #[wasm_bindgen(js_name = suggestMove)]
+    pub fn suggest_move(&self, agent: &str) -> Result<String, JsError> {
+        let mut agent = from_spec(agent, None).map_err(|e| JsError::new(&e))?;
+        let chosen = agent
+            .choose_move(&self.game)
+            .map_err(|e| JsError::new(&e))?;
+        Ok(format_move(&self.game, chosen))
+    }
+
+    /// JSON array of the `k` best moves by the heuristic evaluator, best first
+    /// Each is a legal move with the player's `win_rate` and `score` after it
+    pub fn hints(&self, k: usize) -> Result<String, JsError> {
+        let hints = hints(&self.game, &mut HeuristicEvaluator::default(), k)
+            .map_err(|e| JsError::new(&e))?;
+        Ok(serde_json::to_string(&hints).unwrap())
+    }
+
+    /// Take back the last move, returns false at the start of the game
+    pub fn undo(&mut self) -> bool {
+        match self.previous.pop() {
+            Some(game) => {
+                self.game = game;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// JSON snapshot of the board, scores, and remaining pieces
+    pub fn state(&self) -> String {
+        GameState::new(&self.game).to_json()
+    }
+}